@@ -0,0 +1,589 @@
+//! Scoring every move in a position, for heatmaps, teaching tools, and
+//! finding blunders, and cheaper positional summaries such as influence maps.
+
+pub mod cache;
+
+use std::{cmp::Ordering, fmt};
+
+use self::cache::AnalysisCache;
+use crate::{
+    board::{Board, Coord, Move, Player, Playout},
+    engine::{mcts, Engine, Limits, RandomEngine, SearchEngine},
+    eval::{evaluate, WIN_SCORE},
+};
+
+/// Search scores closer than this to [`WIN_SCORE`] are forced wins or losses.
+const WIN_THRESHOLD: i32 = WIN_SCORE - 1000;
+
+/// How far a stone's influence reaches, in king steps.
+const INFLUENCE_RADIUS: usize = 4;
+
+/// The weight in [`initiative`] of a cell that wins at once.
+const FOUR_WEIGHT: i32 = 4;
+
+/// The weight in [`initiative`] of a cell that makes an open four.
+const THREE_WEIGHT: i32 = 1;
+
+/// How deep [`complexity`] searches to see how the score settles.
+const COMPLEXITY_DEPTH: u8 = 3;
+
+/// The change in score between depths that [`complexity`] counts as
+/// completely volatile: the value of a window missing one stone.
+const VOLATILITY_SCALE: i32 = 512;
+
+/// The number of forcing moves at which [`complexity`] counts the forcing
+/// lines as half branched.
+const HALF_FORCING: f32 = 4.0;
+
+/// The value of a move or position to the player to move.
+///
+/// Scores are ordered from worst to best: a slower loss is better than a
+/// faster one, and a faster win better than a slower one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Score {
+    /// A heuristic evaluation, where positive is good.
+    Eval(i32),
+    /// A forced win in this many plies, counting both players' moves.
+    Win(u32),
+    /// A forced loss in this many plies, counting both players' moves.
+    Loss(u32),
+}
+
+impl Score {
+    /// Converts a score from [`SearchEngine`] or [`evaluate`], counting the
+    /// plies to a win or loss from the position searched.
+    #[must_use]
+    pub const fn from_search(score: i32) -> Self {
+        if score > WIN_THRESHOLD {
+            Self::Win((WIN_SCORE - score).unsigned_abs())
+        } else if score < -WIN_THRESHOLD {
+            Self::Loss((WIN_SCORE + score).unsigned_abs())
+        } else {
+            Self::Eval(score)
+        }
+    }
+
+    /// The score from the opponent's perspective, one ply earlier.
+    #[must_use]
+    pub const fn parent(self) -> Self {
+        match self {
+            Self::Eval(score) => Self::Eval(-score),
+            Self::Win(plies) => Self::Loss(plies + 1),
+            Self::Loss(plies) => Self::Win(plies + 1),
+        }
+    }
+
+    /// A key that sorts scores from worst to best.
+    fn key(self) -> (i8, i64) {
+        match self {
+            Self::Loss(plies) => (-1, i64::from(plies)),
+            Self::Eval(score) => (0, i64::from(score)),
+            Self::Win(plies) => (1, -i64::from(plies)),
+        }
+    }
+}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Eval(score) => write!(f, "{score:+}"),
+            Self::Win(plies) => write!(f, "win in {plies}"),
+            Self::Loss(plies) => write!(f, "loss in {plies}"),
+        }
+    }
+}
+
+/// Scores every legal move in `board`, best first, for the player to move.
+/// There are none if the game is over.
+///
+/// Each move is scored by searching the position after it with `limits`, so
+/// a depth limit of `d` looks `d` plies ahead including the move itself, and
+/// node and time limits apply to each move separately. Without a depth limit,
+/// each move is searched to the [`SearchEngine`]'s default depth.
+#[must_use]
+pub fn score_moves<const SIDE_LENGTH: usize>(
+    board: &Board<SIDE_LENGTH>,
+    limits: &Limits,
+) -> Vec<(Move<SIDE_LENGTH>, Score)> {
+    let mut moves = Vec::new();
+    board.generate_moves(|mv| {
+        moves.push(mv);
+        false
+    });
+    score_listed_moves(board, &moves, limits, None)
+}
+
+/// Scores every legal move in `board` like [`score_moves`], but takes the
+/// score of each position reached from `cache` if it holds one from a search
+/// at least as deep, and adds the scores it searches for.
+#[must_use]
+pub fn score_moves_cached<const SIDE_LENGTH: usize>(
+    board: &Board<SIDE_LENGTH>,
+    limits: &Limits,
+    cache: &mut AnalysisCache,
+) -> Vec<(Move<SIDE_LENGTH>, Score)> {
+    let mut moves = Vec::new();
+    board.generate_moves(|mv| {
+        moves.push(mv);
+        false
+    });
+    score_listed_moves(board, &moves, limits, Some(cache))
+}
+
+/// Scores the moves within two cells of a stone in `board`, best first, like
+/// [`score_moves`]. Far from the stones, moves are rarely worth considering,
+/// so this is much faster on a large board.
+#[must_use]
+pub fn score_candidate_moves<const SIDE_LENGTH: usize>(
+    board: &Board<SIDE_LENGTH>,
+    limits: &Limits,
+) -> Vec<(Move<SIDE_LENGTH>, Score)> {
+    score_listed_moves(board, &board.moves_near(2), limits, None)
+}
+
+/// Scores the moves within two cells of a stone in `board` like
+/// [`score_candidate_moves`], using and filling `cache` like
+/// [`score_moves_cached`].
+#[must_use]
+pub fn score_candidate_moves_cached<const SIDE_LENGTH: usize>(
+    board: &Board<SIDE_LENGTH>,
+    limits: &Limits,
+    cache: &mut AnalysisCache,
+) -> Vec<(Move<SIDE_LENGTH>, Score)> {
+    score_listed_moves(board, &board.moves_near(2), limits, Some(cache))
+}
+
+fn score_listed_moves<const SIDE_LENGTH: usize>(
+    board: &Board<SIDE_LENGTH>,
+    moves: &[Move<SIDE_LENGTH>],
+    limits: &Limits,
+    mut cache: Option<&mut AnalysisCache>,
+) -> Vec<(Move<SIDE_LENGTH>, Score)> {
+    if board.outcome().is_some() {
+        return Vec::new();
+    }
+    let child_limits = Limits {
+        depth: limits.depth.map(|depth| depth.saturating_sub(1)),
+        ..limits.clone()
+    };
+    // one engine for every move, so the searches share their results.
+    let mut engine = SearchEngine::new();
+    let mut scores = Vec::with_capacity(moves.len());
+    for &mv in moves {
+        let child = board.with_move(mv);
+        let cached = cache
+            .as_deref()
+            .and_then(|cache| cache.score(&child, child_limits.depth));
+        let score = match (child.outcome(), cached) {
+            (Some(Player::None), _) => Score::Eval(0),
+            // the move wins.
+            (Some(_), _) => Score::Win(1),
+            (None, Some(score)) => score.parent(),
+            (None, None) => {
+                let (depth, score) = if child_limits.depth == Some(0) {
+                    (0, evaluate(&child))
+                } else {
+                    engine.best_move(&child, &child_limits);
+                    engine
+                        .iterations()
+                        .last()
+                        .map_or_else(|| (0, evaluate(&child)), |info| (info.depth, info.score))
+                };
+                let score = Score::from_search(score);
+                if let Some(cache) = cache.as_deref_mut() {
+                    cache.insert_score(&child, depth, score);
+                }
+                score.parent()
+            }
+        };
+        scores.push((mv, score));
+    }
+    scores.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    scores
+}
+
+/// How strongly each player controls each cell, indexed by row and then
+/// column: positive where X is stronger, negative where O is.
+///
+/// Every stone exerts an influence of 1 on its own cell that halves with
+/// each king step away, out to four steps, and the influences of X's stones
+/// are added and O's subtracted.
+#[must_use]
+pub fn influence<const SIDE_LENGTH: usize>(
+    board: &Board<SIDE_LENGTH>,
+) -> [[f32; SIDE_LENGTH]; SIDE_LENGTH] {
+    let mut map = [[0.0; SIDE_LENGTH]; SIDE_LENGTH];
+    for (player, sign) in [(Player::X, 1.0), (Player::O, -1.0)] {
+        for stone in board.stones(player) {
+            let mut strength = sign;
+            for distance in 0..=INFLUENCE_RADIUS {
+                for mv in Board::ring(stone, distance) {
+                    let Coord { row, col } = mv.coord();
+                    map[row][col] += strength;
+                }
+                strength /= 2.0;
+            }
+        }
+    }
+    map
+}
+
+/// Who is forcing whom in `board`: positive when the side to move has more
+/// outstanding threats than their opponent, negative when they have fewer.
+///
+/// Each side's threats are weighed by the cells that answer them: every
+/// cell where the side could complete a line counts four, and every cell
+/// where it could make an open four counts one. As the side to move acts
+/// first, a positive initiative with a four in it is a win, and a negative
+/// one means the side to move must defend, as [`Board::defensive_moves`]
+/// lists. Like [`evaluate`], this is cheap enough to use as an evaluation
+/// term, and its sign and size suit annotating moves in a game record.
+#[must_use]
+pub fn initiative<const SIDE_LENGTH: usize>(board: &Board<SIDE_LENGTH>) -> i32 {
+    let threats = |player| {
+        let fours = i32::try_from(board.winning_moves(player).len()).unwrap_or(i32::MAX);
+        let threes = i32::try_from(board.open_four_moves(player).len()).unwrap_or(i32::MAX);
+        FOUR_WEIGHT
+            .saturating_mul(fours)
+            .saturating_add(THREE_WEIGHT * threes)
+    };
+    let us = board.turn();
+    threats(us) - threats(-us)
+}
+
+/// How hard `board` is to play, from 0 for a trivial or finished position
+/// towards 1, for choosing positions worth keeping in a data set.
+///
+/// This is the average of three parts, each between 0 and 1:
+///
+/// - the threat density, the cells where either side could win or make an
+///   open four, as a share of the moves within two cells of a stone;
+/// - the branching of forcing lines, from the number of those moves that
+///   make a four for the side to move, which is half its maximum at four
+///   such moves;
+/// - the volatility of the score, the average change in score between the
+///   depths of a search to three plies, where a change of the value of a
+///   four, or between a heuristic score and a forced result, is the maximum.
+///
+/// The search is single-threaded and depth-limited, so the result is
+/// repeatable, but it makes this far costlier than [`initiative`].
+#[must_use]
+pub fn complexity<const SIDE_LENGTH: usize>(board: &Board<SIDE_LENGTH>) -> f32 {
+    #![allow(clippy::cast_precision_loss)]
+    if board.outcome().is_some() {
+        return 0.0;
+    }
+    // a board with no stones near which to move is full, and so finished.
+    let candidates = board.moves_near(2);
+    let us = board.turn();
+    let threats = [us, -us]
+        .into_iter()
+        .map(|player| board.winning_moves(player).len() + board.open_four_moves(player).len())
+        .sum::<usize>();
+    let density = (threats as f32 / candidates.len() as f32).min(1.0);
+    let forcing = candidates
+        .iter()
+        .filter(|&&mv| !board.new_wins(mv, us).is_empty())
+        .count() as f32;
+    let branching = forcing / (forcing + HALF_FORCING);
+    let mut engine = SearchEngine::new();
+    engine.best_move(board, &Limits::depth(COMPLEXITY_DEPTH));
+    let scores = engine
+        .iterations()
+        .iter()
+        .map(|info| info.score.clamp(-VOLATILITY_SCALE, VOLATILITY_SCALE))
+        .collect::<Vec<_>>();
+    let volatility = if scores.len() < 2 {
+        0.0
+    } else {
+        let change = scores
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).abs() as f32)
+            .sum::<f32>();
+        (change / (scores.len() - 1) as f32 / VOLATILITY_SCALE as f32).min(1.0)
+    };
+    (density + branching + volatility) / 3.0
+}
+
+/// How [`winrate`] chooses the moves of its playouts.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PlayoutPolicy {
+    /// Any empty cell, uniformly at random.
+    #[default]
+    Uniform,
+    /// A random empty cell next to a stone, as in the playouts of
+    /// [`MctsEngine`](crate::engine::MctsEngine), which end in more decisive
+    /// games.
+    Nearby,
+}
+
+/// The results of random playouts from a position, for the side to move.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WinRate {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl WinRate {
+    /// The number of playouts.
+    #[must_use]
+    pub const fn playouts(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The percentage of playouts won.
+    #[must_use]
+    pub fn win_percent(&self) -> f64 {
+        self.percent(self.wins)
+    }
+
+    /// The percentage of playouts drawn.
+    #[must_use]
+    pub fn draw_percent(&self) -> f64 {
+        self.percent(self.draws)
+    }
+
+    /// The percentage of playouts lost.
+    #[must_use]
+    pub fn loss_percent(&self) -> f64 {
+        self.percent(self.losses)
+    }
+
+    /// The standard error of each percentage, the largest being that of a
+    /// result seen in half the playouts, or `None` with no playouts.
+    ///
+    /// The standard error of a percentage `p` from `n` playouts is
+    /// `sqrt(p * (100 - p) / n)`.
+    #[must_use]
+    pub fn std_error(&self) -> Option<f64> {
+        (self.playouts() > 0).then(|| {
+            [self.wins, self.draws, self.losses]
+                .into_iter()
+                .map(|count| {
+                    let p = self.percent(count);
+                    (p * (100.0 - p) / f64::from(self.playouts())).sqrt()
+                })
+                .fold(0.0, f64::max)
+        })
+    }
+
+    fn percent(&self, count: u32) -> f64 {
+        if self.playouts() == 0 {
+            return 0.0;
+        }
+        100.0 * f64::from(count) / f64::from(self.playouts())
+    }
+}
+
+impl fmt::Display for WinRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "W {:.1}% D {:.1}% L {:.1}%",
+            self.win_percent(),
+            self.draw_percent(),
+            self.loss_percent()
+        )?;
+        if let Some(error) = self.std_error() {
+            write!(f, " (±{error:.1}%)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts the results of `playouts` random games from `board` for the side
+/// to move.
+///
+/// The moves are chosen by `policy`, and determined by `seed`. This is a
+/// quick, rough signal of who stands better, for display or for labelling
+/// positions.
+#[must_use]
+pub fn winrate<const SIDE_LENGTH: usize>(
+    board: &Board<SIDE_LENGTH>,
+    playouts: u32,
+    policy: PlayoutPolicy,
+    seed: u64,
+) -> WinRate {
+    let mut rng = RandomEngine::new(seed);
+    let us = board.turn();
+    let mut rate = WinRate::default();
+    for _ in 0..playouts {
+        let winner = match policy {
+            PlayoutPolicy::Uniform => Playout::new(*board).play_out(|lo, hi| rng.next_in(lo, hi)),
+            PlayoutPolicy::Nearby => mcts::rollout(*board, &mut rng),
+        };
+        match winner {
+            Player::None => rate.draws += 1,
+            winner if winner == us => rate.wins += 1,
+            _ => rate.losses += 1,
+        }
+    }
+    rate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        complexity, influence, initiative, score_candidate_moves, score_moves, winrate,
+        PlayoutPolicy, Score, WinRate,
+    };
+    use crate::{
+        board::{Board, Move, Player},
+        engine::Limits,
+    };
+
+    #[test]
+    fn scores_are_ordered() {
+        let mut scores = [
+            Score::Eval(5),
+            Score::Win(3),
+            Score::Loss(2),
+            Score::Win(1),
+            Score::Eval(-5),
+            Score::Loss(6),
+        ];
+        scores.sort();
+        assert_eq!(
+            scores,
+            [
+                Score::Loss(2),
+                Score::Loss(6),
+                Score::Eval(-5),
+                Score::Eval(5),
+                Score::Win(3),
+                Score::Win(1),
+            ]
+        );
+        assert_eq!(Score::Win(1).parent(), Score::Loss(2));
+        assert_eq!(Score::from_search(-1_000_000 + 4), Score::Loss(4));
+        assert_eq!(Score::Win(3).to_string(), "win in 3");
+        assert_eq!(Score::Eval(12).to_string(), "+12");
+    }
+
+    #[test]
+    fn scores_every_move() {
+        // X to move has a four on row 5, which O failed to block.
+        let moves = ["b5", "a1", "c5", "a9", "d5", "i1", "e5", "i9"]
+            .map(|mv| mv.parse::<Move<9>>().unwrap());
+        let board = Board::<9>::new().apply_moves(&moves).unwrap();
+        let scores = score_moves(&board, &Limits::depth(2));
+        assert_eq!(scores.len(), 81 - 8);
+        assert_eq!(scores[0], ("a5".parse().unwrap(), Score::Win(1)));
+        assert_eq!(scores[1], ("f5".parse().unwrap(), Score::Win(1)));
+        // any other move lets O block, so it can't be a forced win in one.
+        assert!(scores[2].1 < Score::Win(1));
+
+        let candidates = score_candidate_moves(&board, &Limits::depth(1));
+        assert!(candidates.len() < scores.len());
+        assert_eq!(candidates[0].1, Score::Win(1));
+    }
+
+    #[test]
+    fn finds_blunders() {
+        // O must block X's four at f5; anything else loses at once.
+        let moves =
+            ["b5", "a5", "c5", "a1", "d5", "a9", "e5"].map(|mv| mv.parse::<Move<9>>().unwrap());
+        let board = Board::<9>::new().apply_moves(&moves).unwrap();
+        let scores = score_candidate_moves(&board, &Limits::depth(2));
+        assert_eq!(scores[0].0, "f5".parse().unwrap());
+        assert!(matches!(scores[0].1, Score::Eval(_)));
+        assert!(scores[1..]
+            .iter()
+            .all(|&(_, score)| score == Score::Loss(2)));
+    }
+
+    #[test]
+    fn influence_decays_with_distance() {
+        let board = Board::<9>::new()
+            .apply_moves(&["c5", "g5"].map(|mv| mv.parse().unwrap()))
+            .unwrap();
+        let map = influence(&board);
+        let at = |mv: &str| {
+            let coord = mv.parse::<Move<9>>().unwrap().coord();
+            map[coord.row][coord.col]
+        };
+        // X's stone is on c5, with O's four steps away on g5.
+        assert!((at("c5") - (1.0 - 1.0 / 16.0)).abs() < 1e-6);
+        assert!((at("b4") - 0.5).abs() < 1e-6);
+        assert!(at("e5").abs() < 1e-6);
+        assert!((at("g5") + at("c5")).abs() < 1e-6);
+        assert!(at("a1") > 0.0 && at("i9") < 0.0);
+        assert!(influence(&Board::<9>::new())
+            .iter()
+            .flatten()
+            .all(|value| value.abs() < f32::EPSILON));
+    }
+
+    #[test]
+    fn initiative_follows_threats() {
+        assert_eq!(initiative(&Board::<9>::new()), 0);
+        // X has an open three on row 5, so O, to move, must answer it.
+        let moves = ["c5", "a1", "d5", "a9", "e5"].map(|mv| mv.parse::<Move<9>>().unwrap());
+        let board = Board::<9>::new().apply_moves(&moves).unwrap();
+        assert_eq!(initiative(&board), -2);
+        assert_eq!(initiative(&board.with_turn(Player::X)), 2);
+        // once X extends it to four, the threat is worth more.
+        let board = board
+            .with_move("i1".parse().unwrap())
+            .with_move("f5".parse().unwrap());
+        assert_eq!(initiative(&board), -8);
+    }
+
+    #[test]
+    fn complexity_grows_with_tactics() {
+        let quiet = Board::<9>::new().with_move("e5".parse().unwrap());
+        // both sides have threes on rows 4 and 6, with X to move.
+        let moves = ["c4", "c6", "d4", "d6", "e4", "e6"].map(|mv| mv.parse::<Move<9>>().unwrap());
+        let sharp = Board::<9>::new().apply_moves(&moves).unwrap();
+        let quiet_complexity = complexity(&quiet);
+        let sharp_complexity = complexity(&sharp);
+        assert!((0.0..=1.0).contains(&quiet_complexity));
+        assert!((0.0..=1.0).contains(&sharp_complexity));
+        assert!(sharp_complexity > quiet_complexity);
+        // once X completes a row, there is nothing left to play for.
+        let won = ["f4", "f6", "b4"]
+            .into_iter()
+            .fold(sharp, |board, mv| board.with_move(mv.parse().unwrap()));
+        assert!(complexity(&won).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn winrate_counts_playouts() {
+        // X to move has an open four on row 5, so nearby playouts mostly win.
+        let moves = ["b5", "a1", "c5", "a9", "d5", "i1", "e5", "i9"]
+            .map(|mv| mv.parse::<Move<9>>().unwrap());
+        let board = Board::<9>::new().apply_moves(&moves).unwrap();
+        let rate = winrate(&board, 200, PlayoutPolicy::Nearby, 1);
+        assert_eq!(rate.playouts(), 200);
+        assert!(rate.win_percent() > rate.loss_percent());
+        assert_eq!(rate, winrate(&board, 200, PlayoutPolicy::Nearby, 1));
+        let total = rate.win_percent() + rate.draw_percent() + rate.loss_percent();
+        assert!((total - 100.0).abs() < 1e-9);
+
+        let uniform = winrate(&board, 200, PlayoutPolicy::Uniform, 1);
+        assert_eq!(uniform.playouts(), 200);
+        assert!(uniform.std_error().unwrap() <= 50.0 / 200_f64.sqrt() + 1e-9);
+
+        let rate = WinRate {
+            wins: 3,
+            draws: 0,
+            losses: 1,
+        };
+        assert_eq!(rate.to_string(), "W 75.0% D 0.0% L 25.0% (±21.7%)");
+        assert_eq!(WinRate::default().std_error(), None);
+        assert_eq!(WinRate::default().to_string(), "W 0.0% D 0.0% L 0.0%");
+    }
+}
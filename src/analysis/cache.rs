@@ -0,0 +1,334 @@
+//! Analysis results kept on disk, so that analysing the same positions again,
+//! in this run or a later one, is instant.
+//!
+//! An [`AnalysisCache`] maps positions, up to symmetry, to the [`Score`] a
+//! search gave them and the depth it searched to, or to the [`Value`] a
+//! solver proved. [`score_moves_cached`](super::score_moves_cached) and
+//! [`score_candidate_moves_cached`](super::score_candidate_moves_cached)
+//! consult it before searching and add what they find:
+//!
+//! ```no_run
+//! use gomokugen::{
+//!     analysis::{cache::AnalysisCache, score_moves_cached},
+//!     board::Board,
+//!     engine::Limits,
+//! };
+//!
+//! let mut cache = AnalysisCache::open("analysis.gmkp").unwrap();
+//! let scores = score_moves_cached(&Board::<15>::new(), &Limits::depth(3), &mut cache);
+//! cache.save("analysis.gmkp").unwrap();
+//! ```
+//!
+//! A cache is stored as the magic bytes `GMKA` followed by a [`PositionMap`].
+//! Like the map, a cache holds positions of one board size and win length,
+//! those of the first position put in it, and knows nothing about others.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use super::Score;
+use crate::{
+    board::Board,
+    positions::{invalid_data, FixedBytes, PositionMap},
+    solver::exhaustive::Value,
+};
+
+/// The magic bytes that start a saved cache, before its [`PositionMap`].
+const MAGIC: &[u8; 4] = b"GMKA";
+
+/// What is known about a position.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Entry {
+    /// A search to `depth` plies scored the position.
+    Searched { depth: u8, score: Score },
+    /// A solver proved the position's value.
+    Solved(Value),
+}
+
+impl FixedBytes for Entry {
+    const SIZE: usize = 6;
+
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        #![allow(clippy::cast_possible_wrap)]
+        let (depth, kind, value) = match *self {
+            Self::Searched {
+                depth,
+                score: Score::Eval(score),
+            } => (depth, 0_u8, score),
+            Self::Searched {
+                depth,
+                score: Score::Win(plies),
+            } => (depth, 1, plies as i32),
+            Self::Searched {
+                depth,
+                score: Score::Loss(plies),
+            } => (depth, 2, plies as i32),
+            Self::Solved(Value::Loss) => (0, 3, 0),
+            Self::Solved(Value::Draw) => (0, 4, 0),
+            Self::Solved(Value::Win) => (0, 5, 0),
+        };
+        ((depth, kind), value).write_bytes(out);
+    }
+
+    fn read_bytes(bytes: &[u8]) -> Self {
+        let ((depth, kind), value) = <((u8, u8), i32)>::read_bytes(bytes);
+        // `AnalysisCache::open` rejects files with invalid entries, so this
+        // only meets those written by `write_bytes`.
+        Self::decode(depth, kind, value).unwrap_or(Self::Searched {
+            depth: 0,
+            score: Score::Eval(0),
+        })
+    }
+}
+
+impl Entry {
+    /// The entry written as `depth`, `kind`, and `value` by `write_bytes`,
+    /// or `None` if no entry is written that way.
+    const fn decode(depth: u8, kind: u8, value: i32) -> Option<Self> {
+        #![allow(clippy::cast_sign_loss)]
+        let score = match kind {
+            0 => Score::Eval(value),
+            1 if value >= 0 => Score::Win(value as u32),
+            2 if value >= 0 => Score::Loss(value as u32),
+            3 => return Some(Self::Solved(Value::Loss)),
+            4 => return Some(Self::Solved(Value::Draw)),
+            5 => return Some(Self::Solved(Value::Win)),
+            _ => return None,
+        };
+        Some(Self::Searched { depth, score })
+    }
+}
+
+/// Scores and proven values of positions, as described in the
+/// [module docs](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnalysisCache {
+    entries: PositionMap<Entry>,
+}
+
+impl Default for AnalysisCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnalysisCache {
+    /// Creates an empty cache.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            entries: PositionMap::new(),
+        }
+    }
+
+    /// Loads the cache saved at `path`, or creates an empty one if there is
+    /// no file there yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or is not a
+    /// valid cache.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        match File::open(path) {
+            Ok(file) => Self::read_from(BufReader::new(file)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Reads a cache saved by [`AnalysisCache::save`]. Every entry is
+    /// checked, so that the bytes of another file are never taken for results.
+    fn read_from(mut reader: impl Read) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not an analysis cache"));
+        }
+        let entries = PositionMap::<((u8, u8), i32)>::read_from(reader)?.try_map(
+            |((depth, kind), value)| {
+                Entry::decode(depth, kind, value)
+                    .ok_or_else(|| invalid_data("not an analysis cache"))
+            },
+        )?;
+        Ok(Self { entries })
+    }
+
+    /// Saves the cache to the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        self.entries.write_to(writer)
+    }
+
+    /// The score of `board` for the side to move, if a search to at least
+    /// `depth` plies has scored it, or it is a proven win, loss, or draw.
+    /// With no `depth`, a score from a search to any depth will do.
+    #[must_use]
+    pub fn score<const SIDE_LENGTH: usize>(
+        &self,
+        board: &Board<SIDE_LENGTH>,
+        depth: Option<u8>,
+    ) -> Option<Score> {
+        match *self.entries.get(board)? {
+            Entry::Searched {
+                score: score @ (Score::Win(_) | Score::Loss(_)),
+                ..
+            } => Some(score),
+            Entry::Searched {
+                depth: searched,
+                score,
+            } => (depth.unwrap_or(0) <= searched).then_some(score),
+            Entry::Solved(Value::Draw) => Some(Score::Eval(0)),
+            Entry::Solved(_) => None,
+        }
+    }
+
+    /// The proven value of `board` for the side to move, if a solver has
+    /// proved it or a search found a forced win or loss.
+    #[must_use]
+    pub fn value<const SIDE_LENGTH: usize>(&self, board: &Board<SIDE_LENGTH>) -> Option<Value> {
+        match *self.entries.get(board)? {
+            Entry::Solved(value) => Some(value),
+            Entry::Searched {
+                score: Score::Win(_),
+                ..
+            } => Some(Value::Win),
+            Entry::Searched {
+                score: Score::Loss(_),
+                ..
+            } => Some(Value::Loss),
+            Entry::Searched { .. } => None,
+        }
+    }
+
+    /// Records that a search to `depth` plies gave `board` the score `score`
+    /// for the side to move. Deeper searches and proven results already
+    /// recorded are kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cache holds positions of a different board size or
+    /// win length.
+    pub fn insert_score<const SIDE_LENGTH: usize>(
+        &mut self,
+        board: &Board<SIDE_LENGTH>,
+        depth: u8,
+        score: Score,
+    ) {
+        let entry = self
+            .entries
+            .entry(board)
+            .or_insert(Entry::Searched { depth, score });
+        if let Entry::Searched {
+            depth: old_depth,
+            score: Score::Eval(_),
+        } = *entry
+        {
+            if depth >= old_depth || !matches!(score, Score::Eval(_)) {
+                *entry = Entry::Searched { depth, score };
+            }
+        }
+    }
+
+    /// Records that a solver proved `board` to have the value `value` for
+    /// the side to move.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cache holds positions of a different board size or
+    /// win length.
+    pub fn insert_value<const SIDE_LENGTH: usize>(
+        &mut self,
+        board: &Board<SIDE_LENGTH>,
+        value: Value,
+    ) {
+        self.entries.insert(board, Entry::Solved(value));
+    }
+
+    /// The number of positions in the cache.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnalysisCache;
+    use crate::{
+        analysis::{score_moves, score_moves_cached, Score},
+        board::Board,
+        engine::Limits,
+        solver::exhaustive::Value,
+    };
+
+    #[test]
+    fn keeps_the_best_known_results() {
+        let mut cache = AnalysisCache::new();
+        let board = Board::<9>::new().with_move("c3".parse().unwrap());
+        let image = Board::<9>::new().with_move("g7".parse().unwrap());
+        cache.insert_score(&board, 3, Score::Eval(10));
+        cache.insert_score(&board, 2, Score::Eval(-4));
+        assert_eq!(cache.score(&image, Some(3)), Some(Score::Eval(10)));
+        assert_eq!(cache.score(&image, Some(4)), None);
+        assert_eq!(cache.score(&image, None), Some(Score::Eval(10)));
+        cache.insert_score(&board, 1, Score::Loss(4));
+        assert_eq!(cache.score(&board, Some(9)), Some(Score::Loss(4)));
+        assert_eq!(cache.value(&board), Some(Value::Loss));
+
+        let corner = Board::<9>::new().with_move("a1".parse().unwrap());
+        cache.insert_value(&corner, Value::Draw);
+        let mut bytes = Vec::new();
+        cache.write_to(&mut bytes).unwrap();
+        let read = AnalysisCache::read_from(bytes.as_slice()).unwrap();
+        assert_eq!(read, cache);
+        let mirrored = Board::<9>::new().with_move("i9".parse().unwrap());
+        assert_eq!(read.value(&mirrored), Some(Value::Draw));
+        assert_eq!(read.score(&mirrored, Some(5)), Some(Score::Eval(0)));
+
+        // a container of other six-byte values is not a cache, even if its
+        // values pass for entries.
+        let mut other = crate::positions::PositionMap::<(u32, i16)>::new();
+        other.insert(&corner, (1000, 5));
+        let mut other_bytes = Vec::new();
+        other.write_to(&mut other_bytes).unwrap();
+        assert!(AnalysisCache::read_from(other_bytes.as_slice()).is_err());
+        // nor is a cache with an entry of an unknown kind.
+        let last = bytes.len() - 5;
+        bytes[last] = 9;
+        assert!(AnalysisCache::read_from(bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn analysis_reuses_cached_scores() {
+        let board = Board::<7>::new()
+            .apply_moves(&["d4", "c3", "d5"].map(|mv| mv.parse().unwrap()))
+            .unwrap();
+        let limits = Limits::depth(2);
+        let mut cache = AnalysisCache::new();
+        let scores = score_moves_cached(&board, &limits, &mut cache);
+        assert_eq!(scores, score_moves(&board, &limits));
+        // every reply is now cached, so a second analysis searches nothing.
+        assert_eq!(cache.len(), 49 - 3);
+        assert_eq!(score_moves_cached(&board, &limits, &mut cache), scores);
+        assert_eq!(cache.len(), 49 - 3);
+    }
+}
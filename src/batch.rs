@@ -0,0 +1,159 @@
+//! Packing boards into plane tensors for batched neural network inference.
+//!
+//! A [`Batch`] lays its boards out as one contiguous `N×C×S×S` array of
+//! `f32`s in row-major order: board, then plane, then row, then column. This
+//! is the layout most inference libraries expect, so the whole batch can be
+//! handed over as a single slice.
+
+use crate::board::{Board, Player};
+
+/// The number of planes per board.
+///
+/// Plane 0 marks the stones of the player to move, plane 1 the stones of
+/// their opponent, and plane 2 the last move played, if any.
+pub const PLANES: usize = 3;
+
+/// A batch of boards encoded as planes, remembering which board is where.
+#[derive(Clone, Debug, Default)]
+pub struct Batch<const SIDE_LENGTH: usize> {
+    planes: Vec<f32>,
+    boards: Vec<Board<SIDE_LENGTH>>,
+}
+
+impl<const SIDE_LENGTH: usize> Batch<SIDE_LENGTH> {
+    /// The number of values encoding one board.
+    pub const BOARD_SIZE: usize = PLANES * SIDE_LENGTH * SIDE_LENGTH;
+
+    /// Creates an empty batch.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty batch with room for `boards` boards, so that
+    /// filling it up to that size allocates nothing more.
+    #[must_use]
+    pub fn with_capacity(boards: usize) -> Self {
+        Self {
+            planes: Vec::with_capacity(boards * Self::BOARD_SIZE),
+            boards: Vec::with_capacity(boards),
+        }
+    }
+
+    /// Encodes `board` at the end of the batch, returning its index.
+    pub fn push(&mut self, board: &Board<SIDE_LENGTH>) -> usize {
+        let start = self.planes.len();
+        self.planes.resize(start + Self::BOARD_SIZE, 0.0);
+        let encoded = &mut self.planes[start..];
+        let plane = SIDE_LENGTH * SIDE_LENGTH;
+        board.feature_map_relative(|index, _, _, channel| {
+            encoded[channel * plane + index] = 1.0;
+        });
+        if let Some(mv) = board.last_move() {
+            encoded[2 * plane + mv.index()] = 1.0;
+        }
+        self.boards.push(*board);
+        self.boards.len() - 1
+    }
+
+    /// Removes every board, keeping the allocation.
+    pub fn clear(&mut self) {
+        self.planes.clear();
+        self.boards.clear();
+    }
+
+    /// The number of boards in the batch.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.boards.len()
+    }
+
+    /// Whether the batch has no boards.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.boards.is_empty()
+    }
+
+    /// The shape of the encoded batch: boards, planes, rows, and columns.
+    #[must_use]
+    pub const fn shape(&self) -> [usize; 4] {
+        [self.len(), PLANES, SIDE_LENGTH, SIDE_LENGTH]
+    }
+
+    /// The encoding of the whole batch.
+    #[must_use]
+    pub fn as_slice(&self) -> &[f32] {
+        &self.planes
+    }
+
+    /// The encoding of board `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of range.
+    #[must_use]
+    pub fn planes(&self, index: usize) -> &[f32] {
+        &self.planes[index * Self::BOARD_SIZE..(index + 1) * Self::BOARD_SIZE]
+    }
+
+    /// The board at `index`, or `None` if it is out of range.
+    #[must_use]
+    pub fn board(&self, index: usize) -> Option<&Board<SIDE_LENGTH>> {
+        self.boards.get(index)
+    }
+
+    /// The boards in the batch, in order.
+    #[must_use]
+    pub fn boards(&self) -> &[Board<SIDE_LENGTH>] {
+        &self.boards
+    }
+
+    /// The player to move on board `index`, whose stones are in plane 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of range.
+    #[must_use]
+    pub fn turn(&self, index: usize) -> Player {
+        self.boards[index].turn()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Batch, PLANES};
+    use crate::board::{Board, Player};
+
+    #[test]
+    fn packs_boards_into_planes() {
+        let mut batch = Batch::<3>::with_capacity(2);
+        let first = Board::new().with_move("a1".parse().unwrap());
+        let second = first.with_move("c3".parse().unwrap());
+        assert_eq!(batch.push(&first), 0);
+        assert_eq!(batch.push(&second), 1);
+        assert_eq!(batch.shape(), [2, PLANES, 3, 3]);
+        assert_eq!(batch.as_slice().len(), 2 * PLANES * 9);
+
+        let ones = |index: usize| {
+            batch
+                .planes(index)
+                .iter()
+                .enumerate()
+                .filter(|&(_, &value)| value > 0.5)
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>()
+        };
+        // O is to move after a1, so X's stone is in the opponent's plane.
+        assert_eq!(ones(0), [9, 18]);
+        assert_eq!(batch.turn(0), Player::O);
+        assert_eq!(ones(1), [0, 8 + 9, 8 + 18]);
+        assert_eq!(batch.board(1), Some(&second));
+
+        let allocation = batch.as_slice().as_ptr();
+        batch.clear();
+        assert!(batch.is_empty());
+        batch.push(&second);
+        assert_eq!(batch.as_slice().as_ptr(), allocation);
+        assert_eq!(batch.boards(), [second]);
+    }
+}
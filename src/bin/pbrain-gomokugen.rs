@@ -0,0 +1,67 @@
+//! A Gomocup brain: the crate's [`SearchEngine`] served over the piskvork
+//! protocol on standard input and output, for use with Piskvork or the
+//! Gomocup manager.
+//!
+//! The board size is fixed by the first `START` or `RECTSTART` command the
+//! brain accepts. Commands before it are answered once the size is known,
+//! except `ABOUT` and `END`, which are handled at once.
+
+use std::io::{self, BufRead, Cursor, Read, Write};
+
+use gomokugen::{engine::SearchEngine, protocol::GomocupServer, with_side_length};
+
+/// The side length a `START` or `RECTSTART` command asks for, if the command
+/// is one of those and the board is square.
+fn requested_size(command: &str, args: &str) -> Option<usize> {
+    match command.to_ascii_uppercase().as_str() {
+        "START" => args.trim().parse().ok(),
+        "RECTSTART" => {
+            let (width, height) = args.split_once(',')?;
+            let width = width.trim().parse().ok()?;
+            (height.trim().parse() == Ok(width)).then_some(width)
+        }
+        _ => None,
+    }
+}
+
+fn main() -> io::Result<()> {
+    let server = GomocupServer::default();
+    let stdin = io::stdin();
+    let mut stdout = io::stdout().lock();
+    // the commands to replay to the server once it has started.
+    let mut preamble = String::new();
+    let mut lines = stdin.lock().lines();
+    let size = loop {
+        let Some(line) = lines.next() else {
+            return Ok(());
+        };
+        let line = line?;
+        let (command, args) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        let supported = |size| with_side_length!(size, _SIDE => true, false);
+        if command.eq_ignore_ascii_case("START") || command.eq_ignore_ascii_case("RECTSTART") {
+            match requested_size(command, args) {
+                Some(size) if supported(size) => {
+                    preamble.push_str(&line);
+                    preamble.push('\n');
+                    break size;
+                }
+                _ => writeln!(stdout, "ERROR unsupported board size")?,
+            }
+        } else if command.eq_ignore_ascii_case("ABOUT") {
+            writeln!(stdout, "{}", server.about)?;
+        } else if command.eq_ignore_ascii_case("END") {
+            return Ok(());
+        } else {
+            preamble.push_str(&line);
+            preamble.push('\n');
+        }
+        stdout.flush()?;
+    };
+    drop(lines);
+    let input = Cursor::new(preamble).chain(stdin.lock());
+    with_side_length!(
+        size,
+        SIDE => server.run(&mut SearchEngine::<SIDE>::new(), input, stdout),
+        unreachable!("the size was checked above")
+    )
+}
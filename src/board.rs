@@ -1,19 +1,47 @@
 use std::{
-    fmt::{Debug, Display},
+    fmt::{Debug, Display, Write as _},
     hash::Hash,
     str::FromStr,
 };
 
-use smallvec::SmallVec;
+use crate::{symmetry::Symmetry, zobrist};
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+#[cfg(feature = "rkyv")]
+mod archive;
+mod move_list;
+mod playout;
+#[cfg(feature = "serde")]
+mod schema;
+#[cfg(feature = "serde")]
+mod serialization;
+
+pub use move_list::MoveList;
+pub use playout::Playout;
+#[cfg(feature = "serde")]
+pub use schema::{BoardSchema, Stone};
+pub use zobrist::VERSION as ZOBRIST_VERSION;
+
+/// Up to this many stones, [`Board::generate_moves_near`] visits only the
+/// cells around them, rather than marking the whole board.
+const SPARSE_STONES: usize = 8;
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    rkyv(derive(Copy, Clone, PartialEq, Eq, Debug))
+)]
+#[repr(u8)]
 pub enum Player {
     /// Neither player has a piece on this square.
-    None,
+    None = 0,
     /// The first player.
-    X,
+    X = 1,
     /// The second player.
-    O,
+    O = 2,
 }
 
 impl std::ops::Neg for Player {
@@ -29,6 +57,11 @@ impl std::ops::Neg for Player {
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    rkyv(derive(PartialEq, Eq, Debug))
+)]
 pub struct Move<const SIDE_LENGTH: usize> {
     index: u16,
 }
@@ -39,6 +72,10 @@ impl<const SIDE_LENGTH: usize> Move<SIDE_LENGTH> {
         Self { index: u16::MAX }
     }
 
+    pub(crate) const fn from_index(index: u16) -> Self {
+        Self { index }
+    }
+
     #[must_use]
     pub const fn is_null(&self) -> bool {
         self.index == u16::MAX
@@ -48,6 +85,72 @@ impl<const SIDE_LENGTH: usize> Move<SIDE_LENGTH> {
     pub const fn index(&self) -> usize {
         self.index as usize
     }
+
+    /// The image of this move under `symmetry`. The null move maps to itself.
+    #[must_use]
+    pub const fn transform(self, symmetry: Symmetry) -> Self {
+        #![allow(clippy::cast_possible_truncation)]
+        if self.is_null() {
+            return self;
+        }
+        let Coord { row, col } = symmetry.apply(self.coord(), SIDE_LENGTH);
+        Self {
+            index: (row * SIDE_LENGTH + col) as u16,
+        }
+    }
+
+    /// The coordinate of the cell this move is played on.
+    #[must_use]
+    pub const fn coord(&self) -> Coord {
+        Coord {
+            row: self.index() / SIDE_LENGTH,
+            col: self.index() % SIDE_LENGTH,
+        }
+    }
+
+    /// The number of king steps between the cells of `self` and `other`: the
+    /// larger of the row and column distances.
+    #[must_use]
+    pub const fn chebyshev_distance(&self, other: &Self) -> usize {
+        let (a, b) = (self.coord(), other.coord());
+        let rows = a.row.abs_diff(b.row);
+        let cols = a.col.abs_diff(b.col);
+        if rows > cols {
+            rows
+        } else {
+            cols
+        }
+    }
+
+    /// The number of rook steps of one cell between the cells of `self` and
+    /// `other`: the sum of the row and column distances.
+    #[must_use]
+    pub const fn manhattan_distance(&self, other: &Self) -> usize {
+        let (a, b) = (self.coord(), other.coord());
+        a.row.abs_diff(b.row) + a.col.abs_diff(b.col)
+    }
+
+    /// Whether the cells of `self` and `other` touch, orthogonally or
+    /// diagonally. A cell is not adjacent to itself.
+    #[must_use]
+    pub const fn is_adjacent(&self, other: &Self) -> bool {
+        self.chebyshev_distance(other) == 1
+    }
+}
+
+/// A cell on the board, addressed by row (rank) and column (file).
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Coord {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl Coord {
+    #[must_use]
+    pub const fn new(row: usize, col: usize) -> Self {
+        Self { row, col }
+    }
 }
 
 impl<const SIDE_LENGTH: usize> Display for Move<SIDE_LENGTH> {
@@ -89,14 +192,20 @@ impl<const SIDE_LENGTH: usize> FromStr for Move<SIDE_LENGTH> {
             return Err("Invalid move string, must be 2 or 3 characters");
         }
         let row = bytes[0].to_ascii_uppercase();
-        if row < b'A' || row > b'A' + SIDE_LENGTH as u8 {
+        if row < b'A' || row >= b'A' + SIDE_LENGTH as u8 {
             return Err("Invalid row in move string");
         }
+        if !bytes[1..].iter().all(u8::is_ascii_digit) {
+            return Err("Invalid column in move string");
+        }
         let col = bytes
             .get(2)
             .map_or(bytes[1] - b'0', |&b| b - b'0' + (bytes[1] - b'0') * 10)
             .checked_sub(1)
             .ok_or("Invalid column in move string")?;
+        if usize::from(col) >= SIDE_LENGTH {
+            return Err("Invalid column in move string");
+        }
         let index = u16::from(col) * SIDE_LENGTH as u16 + u16::from(row - b'A');
         if index >= SIDE_LENGTH as u16 * SIDE_LENGTH as u16 {
             return Err("Invalid index in move string");
@@ -105,16 +214,50 @@ impl<const SIDE_LENGTH: usize> FromStr for Move<SIDE_LENGTH> {
     }
 }
 
+impl<const SIDE_LENGTH: usize> TryFrom<u16> for Move<SIDE_LENGTH> {
+    type Error = &'static str;
+
+    /// Makes the move onto the cell with flat index `index`, as returned by
+    /// [`Move::index`].
+    fn try_from(index: u16) -> Result<Self, Self::Error> {
+        if usize::from(index) >= SIDE_LENGTH * SIDE_LENGTH {
+            return Err("Move index out of range");
+        }
+        Ok(Self { index })
+    }
+}
+
+impl<const SIDE_LENGTH: usize> TryFrom<usize> for Move<SIDE_LENGTH> {
+    type Error = &'static str;
+
+    /// Makes the move onto the cell with flat index `index`, as returned by
+    /// [`Move::index`].
+    fn try_from(index: usize) -> Result<Self, Self::Error> {
+        u16::try_from(index)
+            .map_err(|_| "Move index out of range")
+            .and_then(Self::try_from)
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
 pub struct Board<const SIDE_LENGTH: usize> {
     cells: [[Player; SIDE_LENGTH]; SIDE_LENGTH],
     last_move: Option<Move<SIDE_LENGTH>>,
     ply: u16,
+    to_move: Player,
+    key: u64,
+    win_length: u8,
 }
 
 impl<const SIDE_LENGTH: usize> PartialEq for Board<SIDE_LENGTH> {
     fn eq(&self, other: &Self) -> bool {
         self.cells == other.cells
+            && self.to_move == other.to_move
+            && self.win_length == other.win_length
     }
 }
 
@@ -123,9 +266,65 @@ impl<const SIDE_LENGTH: usize> Eq for Board<SIDE_LENGTH> {}
 impl<const SIDE_LENGTH: usize> Hash for Board<SIDE_LENGTH> {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.cells.hash(state);
+        self.to_move.hash(state);
+        self.win_length.hash(state);
     }
 }
 
+/// The reasons one position cannot be reached from another by playing moves.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DiffError<const SIDE_LENGTH: usize> {
+    /// A stone present in the earlier position is missing from the later one.
+    StoneRemoved(Move<SIDE_LENGTH>),
+    /// A stone changed colour between the two positions.
+    StoneChanged(Move<SIDE_LENGTH>),
+    /// The numbers of stones added for each player cannot result from alternating play.
+    WrongStoneCounts {
+        /// Stones added for the player to move in the earlier position.
+        to_move: usize,
+        /// Stones added for their opponent.
+        other: usize,
+    },
+    /// The later position's ply count is not the earlier one's plus the
+    /// number of stones added.
+    WrongPly {
+        /// The ply count that the stones added imply.
+        expected: usize,
+        /// The ply count of the later position.
+        found: usize,
+    },
+    /// The later position's side to move is not the one that the stones
+    /// added imply.
+    WrongTurn {
+        /// The side to move that the stones added imply.
+        expected: Player,
+        /// The side to move in the later position.
+        found: Player,
+    },
+}
+
+impl<const SIDE_LENGTH: usize> Display for DiffError<SIDE_LENGTH> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::StoneRemoved(mv) => write!(f, "stone removed at {mv}"),
+            Self::StoneChanged(mv) => write!(f, "stone changed colour at {mv}"),
+            Self::WrongStoneCounts { to_move, other } => write!(
+                f,
+                "{to_move} stones added for the side to move but {other} for their opponent"
+            ),
+            Self::WrongPly { expected, found } => {
+                write!(f, "ply {found} where the stones added imply {expected}")
+            }
+            Self::WrongTurn { expected, found } => write!(
+                f,
+                "{found:?} to move where the stones added imply {expected:?}"
+            ),
+        }
+    }
+}
+
+impl<const SIDE_LENGTH: usize> std::error::Error for DiffError<SIDE_LENGTH> {}
+
 /// A gomoku board of size `SIDE_LENGTH` by `SIDE_LENGTH`.
 impl<const SIDE_LENGTH: usize> Board<SIDE_LENGTH> {
     #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
@@ -135,20 +334,58 @@ impl<const SIDE_LENGTH: usize> Board<SIDE_LENGTH> {
     ///
     /// # Panics
     ///
-    /// Panics if `SIDE_LENGTH` is greater than 19.
+    /// Panics if `SIDE_LENGTH` is greater than 20, the size used by Gomocup.
     #[must_use]
     pub fn new() -> Self {
         assert!(
-            SIDE_LENGTH <= 19,
-            "Only boards of up to 19x19 are supported."
+            SIDE_LENGTH <= 20,
+            "Only boards of up to 20x20 are supported."
         );
         Self {
             cells: [[Player::None; SIDE_LENGTH]; SIDE_LENGTH],
             last_move: None,
             ply: 0,
+            to_move: Player::X,
+            key: 0,
+            win_length: Self::DEFAULT_WIN_LENGTH,
         }
     }
 
+    /// The win length of a new board: five, or the side length on boards
+    /// smaller than that.
+    #[allow(clippy::cast_possible_truncation)]
+    const DEFAULT_WIN_LENGTH: u8 = if SIDE_LENGTH < 5 {
+        SIDE_LENGTH as u8
+    } else {
+        5
+    };
+
+    /// Sets the number of stones in a row needed to win, which is five by
+    /// default, or the side length on boards smaller than that.
+    ///
+    /// Boards with different win lengths are different positions: they
+    /// compare unequal, and FEN strings record any win length other than
+    /// the default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `win_length` is less than 2 or greater than `SIDE_LENGTH`.
+    #[must_use]
+    pub fn with_win_length(mut self, win_length: usize) -> Self {
+        assert!(
+            (2..=SIDE_LENGTH).contains(&win_length),
+            "Win length must be between 2 and the side length."
+        );
+        self.win_length = u8::try_from(win_length).unwrap();
+        self
+    }
+
+    /// The number of stones in a row needed to win.
+    #[must_use]
+    pub const fn win_length(&self) -> usize {
+        self.win_length as usize
+    }
+
     /// Generates all possible moves on the board and calls `callback` with each one.
     /// Iteration short-circuits if `callback` returns `true`.
     pub fn generate_moves(&self, mut callback: impl FnMut(Move<SIDE_LENGTH>) -> bool) {
@@ -160,6 +397,256 @@ impl<const SIDE_LENGTH: usize> Board<SIDE_LENGTH> {
         }
     }
 
+    /// Generates the moves for which `predicate` returns `true` and calls
+    /// `callback` with each one, in index order. Iteration short-circuits if
+    /// `callback` returns `true`.
+    ///
+    /// `predicate` is only asked about empty cells, so a filter such as a
+    /// region of the board is applied during generation, without collecting
+    /// the moves first.
+    pub fn generate_moves_where(
+        &self,
+        mut predicate: impl FnMut(Move<SIDE_LENGTH>) -> bool,
+        mut callback: impl FnMut(Move<SIDE_LENGTH>) -> bool,
+    ) {
+        self.generate_moves(|mv| predicate(mv) && callback(mv));
+    }
+
+    /// Returns an iterator over the moves for which `predicate` returns
+    /// `true`, in index order, like [`Board::generate_moves_where`].
+    pub fn moves_where<'a>(
+        &'a self,
+        mut predicate: impl FnMut(Move<SIDE_LENGTH>) -> bool + 'a,
+    ) -> impl Iterator<Item = Move<SIDE_LENGTH>> + 'a {
+        self.stones(Player::None).filter(move |&mv| predicate(mv))
+    }
+
+    /// Returns an iterator over the moves at which `player` has a stone, in index order.
+    pub fn stones(&self, player: Player) -> impl Iterator<Item = Move<SIDE_LENGTH>> + '_ {
+        #![allow(clippy::cast_possible_truncation)]
+        self.cells
+            .iter()
+            .flatten()
+            .enumerate()
+            .filter(move |&(_, &c)| c == player)
+            .map(|(i, _)| Move { index: i as u16 })
+    }
+
+    /// Returns an iterator over every legal move paired with the position it
+    /// leads to, which is empty once the game is over.
+    pub fn children(&self) -> impl Iterator<Item = (Move<SIDE_LENGTH>, Self)> + '_ {
+        let legal = if self.outcome().is_some() {
+            0
+        } else {
+            usize::MAX
+        };
+        self.successors().take(legal)
+    }
+
+    /// Returns an iterator over every empty cell paired with the position a
+    /// stone there leads to.
+    ///
+    /// Like [`Board::place`], this carries on past the end of the game, so
+    /// that perft and position generation walk the same tree in every build.
+    pub(crate) fn successors(&self) -> impl Iterator<Item = (Move<SIDE_LENGTH>, Self)> + '_ {
+        #![allow(clippy::cast_possible_truncation)]
+        self.cells
+            .iter()
+            .flatten()
+            .enumerate()
+            .filter(|&(_, &c)| c == Player::None)
+            .map(|(i, _)| {
+                let mv = Move { index: i as u16 };
+                let mut child = *self;
+                child.place(mv);
+                (mv, child)
+            })
+    }
+
+    /// The centre cell of the board, or the cell below and right of the
+    /// centre on boards of even size.
+    #[must_use]
+    pub const fn center() -> Move<SIDE_LENGTH> {
+        #![allow(clippy::cast_possible_truncation)]
+        let center = SIDE_LENGTH / 2;
+        Move {
+            index: (center * SIDE_LENGTH + center) as u16,
+        }
+    }
+
+    /// Returns an iterator over the cells at exactly Chebyshev distance
+    /// `distance` from `around` that are on the board, in index order. The
+    /// ring at distance zero is `around` itself.
+    pub fn ring(
+        around: Move<SIDE_LENGTH>,
+        distance: usize,
+    ) -> impl Iterator<Item = Move<SIDE_LENGTH>> {
+        #![allow(clippy::cast_possible_truncation)]
+        let Coord { row, col } = around.coord();
+        let rows = row.saturating_sub(distance)..=(row + distance).min(SIDE_LENGTH - 1);
+        rows.flat_map(move |r| {
+            let cols = col.saturating_sub(distance)..=(col + distance).min(SIDE_LENGTH - 1);
+            cols.filter(move |&c| r.abs_diff(row).max(c.abs_diff(col)) == distance)
+                .map(move |c| Move {
+                    index: (r * SIDE_LENGTH + c) as u16,
+                })
+        })
+    }
+
+    /// The corners of the smallest rectangle containing every stone: the
+    /// cell with the lowest row and column, then the cell with the highest,
+    /// or `None` if the board is empty.
+    #[must_use]
+    pub fn bounding_box(&self) -> Option<(Coord, Coord)> {
+        self.bounding_box_with_margin(0)
+    }
+
+    /// The corners of the [bounding box](Board::bounding_box) grown by
+    /// `margin` cells on every side, clipped to the edges of the board, or
+    /// `None` if the board is empty.
+    #[must_use]
+    pub fn bounding_box_with_margin(&self, margin: usize) -> Option<(Coord, Coord)> {
+        let mut corners: Option<(Coord, Coord)> = None;
+        for (row, cells) in self.cells.iter().enumerate() {
+            for (col, _) in cells.iter().enumerate().filter(|(_, &c)| c != Player::None) {
+                let (low, high) = corners.get_or_insert((Coord { row, col }, Coord { row, col }));
+                low.col = low.col.min(col);
+                high.row = row;
+                high.col = high.col.max(col);
+            }
+        }
+        corners.map(|(low, high)| {
+            (
+                Coord {
+                    row: low.row.saturating_sub(margin),
+                    col: low.col.saturating_sub(margin),
+                },
+                Coord {
+                    row: (high.row + margin).min(SIDE_LENGTH - 1),
+                    col: (high.col + margin).min(SIDE_LENGTH - 1),
+                },
+            )
+        })
+    }
+
+    /// Generates the empty cells within `radius` rows and columns of a stone,
+    /// or just the centre if the board is empty, and calls `callback` with
+    /// each one, in index order. Iteration short-circuits if `callback`
+    /// returns `true`.
+    ///
+    /// With few stones on the board, only the cells around them are visited,
+    /// so this is much faster than filtering [`Board::generate_moves`] early
+    /// in a game on a large board.
+    pub fn generate_moves_near(
+        &self,
+        radius: usize,
+        mut callback: impl FnMut(Move<SIDE_LENGTH>) -> bool,
+    ) {
+        #![allow(clippy::cast_possible_truncation)]
+        let mut sparse = [Coord::new(0, 0); SPARSE_STONES];
+        let mut count = 0;
+        for (row, cells) in self.cells.iter().enumerate() {
+            for (col, _) in cells.iter().enumerate().filter(|(_, &c)| c != Player::None) {
+                if let Some(slot) = sparse.get_mut(count) {
+                    *slot = Coord { row, col };
+                }
+                count += 1;
+            }
+        }
+        if count == 0 {
+            callback(Self::center());
+            return;
+        }
+        if count > SPARSE_STONES {
+            let near = self.near_mask(radius);
+            self.generate_moves(|mv| {
+                let Coord { row, col } = mv.coord();
+                near[row][col] && callback(mv)
+            });
+            return;
+        }
+        // the stones were found in index order, so the first and last give
+        // the rows of the bounding box.
+        let stones = &sparse[..count];
+        let rows = stones[0].row.saturating_sub(radius)
+            ..=(stones[count - 1].row + radius).min(SIDE_LENGTH - 1);
+        let low_col = stones.iter().map(|stone| stone.col).min().unwrap_or(0);
+        let high_col = stones.iter().map(|stone| stone.col).max().unwrap_or(0);
+        let cols = low_col.saturating_sub(radius)..=(high_col + radius).min(SIDE_LENGTH - 1);
+        for row in rows {
+            for col in cols.clone() {
+                let near = stones
+                    .iter()
+                    .any(|stone| stone.row.abs_diff(row).max(stone.col.abs_diff(col)) <= radius);
+                if near
+                    && self.cells[row][col] == Player::None
+                    && callback(Move {
+                        index: (row * SIDE_LENGTH + col) as u16,
+                    })
+                {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// The empty cells within `radius` rows and columns of a stone, in index
+    /// order, or just the centre if the board is empty, as generated by
+    /// [`Board::generate_moves_near`].
+    #[must_use]
+    pub fn moves_near(&self, radius: usize) -> Vec<Move<SIDE_LENGTH>> {
+        let mut out = Vec::new();
+        self.generate_moves_near(radius, |mv| {
+            out.push(mv);
+            false
+        });
+        out
+    }
+
+    /// Which cells are within `radius` rows and columns of a stone.
+    fn near_mask(&self, radius: usize) -> [[bool; SIDE_LENGTH]; SIDE_LENGTH] {
+        let mut near = [[false; SIDE_LENGTH]; SIDE_LENGTH];
+        for (row, cells) in self.cells.iter().enumerate() {
+            for (col, &c) in cells.iter().enumerate() {
+                if c == Player::None {
+                    continue;
+                }
+                let cols = col.saturating_sub(radius)..=(col + radius).min(SIDE_LENGTH - 1);
+                for near_row in
+                    &mut near[row.saturating_sub(radius)..=(row + radius).min(SIDE_LENGTH - 1)]
+                {
+                    near_row[cols.clone()].fill(true);
+                }
+            }
+        }
+        near
+    }
+
+    /// The moves worth searching. If the player to move can win immediately,
+    /// only the winning move; if the opponent threatens to win, only the
+    /// blocking moves; otherwise every empty cell within `radius` of a stone,
+    /// or every empty cell if `radius` is `None`.
+    pub(crate) fn candidate_moves(&self, radius: Option<usize>) -> Vec<Move<SIDE_LENGTH>> {
+        let us = self.turn();
+        let mut moves = Vec::new();
+        self.generate_moves(|mv| {
+            moves.push(mv);
+            false
+        });
+        if let Some(&win) = moves.iter().find(|&&mv| self.is_winning_move(mv, us)) {
+            return vec![win];
+        }
+        let blocks = moves
+            .iter()
+            .copied()
+            .filter(|&mv| self.is_winning_move(mv, -us))
+            .collect::<Vec<_>>();
+        if !blocks.is_empty() {
+            return blocks;
+        }
+        radius.map_or(moves, |radius| self.moves_near(radius))
+    }
+
     /// Iterates over all filled cells on the board and calls `callback` with each one.
     pub fn feature_map(&self, mut callback: impl FnMut(usize, Player)) {
         for (i, c) in self.cells.iter().flatten().enumerate() {
@@ -169,30 +656,371 @@ impl<const SIDE_LENGTH: usize> Board<SIDE_LENGTH> {
         }
     }
 
+    /// Returns the contents of the cell at `coord`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `coord` is off the board.
+    #[must_use]
+    pub const fn player_at(&self, coord: Coord) -> Player {
+        self.cells[coord.row][coord.col]
+    }
+
+    /// Returns every cell on the board in one slice, in index order, so that
+    /// the cell a move is played on is at [`Move::index`].
+    ///
+    /// [`Player`] is laid out as a single byte: 0 for an empty cell, 1 for X,
+    /// and 2 for O.
+    #[must_use]
+    pub const fn as_flat(&self) -> &[Player] {
+        self.cells.as_flattened()
+    }
+
+    /// Returns an iterator over every cell on the board, empty or not, in index order.
+    pub fn cells(&self) -> impl Iterator<Item = (Coord, Player)> + '_ {
+        self.cells.iter().enumerate().flat_map(|(row, cells)| {
+            cells
+                .iter()
+                .enumerate()
+                .map(move |(col, &c)| (Coord { row, col }, c))
+        })
+    }
+
+    /// Iterates over all filled cells on the board and calls `callback` with
+    /// the flat index, row, column, and a side-to-move-relative channel:
+    /// `0` for stones belonging to the player to move, `1` for the opponent's.
+    pub fn feature_map_relative(&self, mut callback: impl FnMut(usize, usize, usize, usize)) {
+        let us = self.turn();
+        for (row, cells) in self.cells.iter().enumerate() {
+            for (col, c) in cells.iter().enumerate() {
+                if *c != Player::None {
+                    let channel = usize::from(*c != us);
+                    callback(row * SIDE_LENGTH + col, row, col, channel);
+                }
+            }
+        }
+    }
+
     /// Applies a move to the board.
-    pub fn make_move(&mut self, mv @ Move { index }: Move<SIDE_LENGTH>) {
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, or with the `strict-checks` feature, panics if the
+    /// move is off the board or onto a stone. With `strict-checks`, also
+    /// panics if the game is already over; use [`Board::place`] to fill the
+    /// board regardless, as perft does. Otherwise bad moves are not checked,
+    /// and leave the board in an unspecified state.
+    pub fn make_move(&mut self, mv: Move<SIDE_LENGTH>) {
+        if cfg!(feature = "strict-checks") {
+            assert!(
+                self.outcome().is_none(),
+                "Cannot play {mv}: the game is over"
+            );
+        }
+        self.place(mv);
+    }
+
+    /// Places a stone for the side to move and passes the turn, like
+    /// [`Board::make_move`], even if the game is already over.
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, or with the `strict-checks` feature, panics if the
+    /// move is off the board or onto a stone.
+    pub fn place(&mut self, mv @ Move { index }: Move<SIDE_LENGTH>) {
         #![allow(clippy::cast_possible_truncation)]
-        debug_assert!(!mv.is_null(), "Cannot make null move");
+        if cfg!(any(debug_assertions, feature = "strict-checks")) {
+            assert!(!mv.is_null(), "Cannot make null move");
+            assert!(
+                mv.index() < SIDE_LENGTH * SIDE_LENGTH,
+                "Move {index} is off the board"
+            );
+            assert!(
+                self.cells[mv.index() / SIDE_LENGTH][mv.index() % SIDE_LENGTH] == Player::None,
+                "Cannot play {mv} on an occupied cell"
+            );
+        }
         let i = (index / SIDE_LENGTH as u16) as usize;
         let j = (index % SIDE_LENGTH as u16) as usize;
         self.cells[i][j] = self.turn();
+        self.key ^= zobrist::stone(index as usize, self.turn()) ^ zobrist::SIDE;
         self.last_move = Some(mv);
         self.ply += 1;
+        self.to_move = -self.to_move;
+    }
+
+    /// Passes the turn to the opponent without placing a stone.
+    ///
+    /// Gomoku has no passes, so this is only for searches that ask what the
+    /// opponent could do with two moves in a row. The result has no last
+    /// move, so [`Board::outcome`] reports the game as still in progress.
+    pub(crate) fn make_null_move(&mut self) {
+        self.key ^= zobrist::SIDE;
+        self.last_move = None;
+        self.to_move = -self.to_move;
+    }
+
+    /// The Zobrist hash of the position: the stones on the board and the side to move.
+    ///
+    /// Keys are stable across crate versions, so they can be stored on disk;
+    /// [`ZOBRIST_VERSION`] changes if they ever do.
+    #[must_use]
+    pub const fn zobrist_key(&self) -> u64 {
+        self.key
+    }
+
+    /// A 128-bit hash of the position: [`Board::zobrist_key`] in the high
+    /// half, and a second, independent hash of the stones and the side to
+    /// move in the low half.
+    ///
+    /// Two different positions are vanishingly unlikely to share this key,
+    /// so tables that must never confuse positions can check both halves.
+    /// The low half is computed from scratch on every call.
+    #[must_use]
+    pub fn zobrist_key128(&self) -> u128 {
+        let mut check = if self.to_move == Player::O {
+            zobrist::CHECK_SIDE
+        } else {
+            0
+        };
+        for (i, &c) in self.cells.iter().flatten().enumerate() {
+            check ^= zobrist::check_stone(i, c);
+        }
+        u128::from(self.key) << 64 | u128::from(check)
+    }
+
+    fn recompute_key(&mut self) {
+        self.key = if self.to_move == Player::O {
+            zobrist::SIDE
+        } else {
+            0
+        };
+        for (i, &c) in self.cells.iter().flatten().enumerate() {
+            self.key ^= zobrist::stone(i, c);
+        }
+    }
+
+    /// Returns the image of this position under `symmetry`.
+    #[must_use]
+    pub fn transform(&self, symmetry: Symmetry) -> Self {
+        let mut out = *self;
+        for (row, cells) in self.cells.iter().enumerate() {
+            for (col, &c) in cells.iter().enumerate() {
+                let Coord { row, col } = symmetry.apply(Coord { row, col }, SIDE_LENGTH);
+                out.cells[row][col] = c;
+            }
+        }
+        out.last_move = self.last_move.map(|mv| mv.transform(symmetry));
+        out.recompute_key();
+        out
+    }
+
+    /// The Zobrist keys of the eight symmetric images of this position, in
+    /// the order of [`Symmetry::ALL`], as [`Board::transform`] followed by
+    /// [`Board::zobrist_key`] would give them, computed in one pass over the
+    /// board.
+    #[must_use]
+    pub fn symmetric_keys(&self) -> [u64; 8] {
+        let side = if self.to_move == Player::O {
+            zobrist::SIDE
+        } else {
+            0
+        };
+        let mut keys = [side; 8];
+        for (row, cells) in self.cells.iter().enumerate() {
+            for (col, &c) in cells.iter().enumerate() {
+                if c == Player::None {
+                    continue;
+                }
+                for (key, sym) in keys.iter_mut().zip(Symmetry::ALL) {
+                    let Coord { row, col } = sym.apply(Coord { row, col }, SIDE_LENGTH);
+                    *key ^= zobrist::stone(row * SIDE_LENGTH + col, c);
+                }
+            }
+        }
+        keys
+    }
+
+    /// The smallest Zobrist key among the eight symmetric images of this
+    /// position.
+    ///
+    /// Positions that are rotations or reflections of each other share a
+    /// canonical key, so books and deduplication sets keyed by it unify them
+    /// without any transforms by the caller.
+    #[must_use]
+    pub fn canonical_key(&self) -> u64 {
+        self.symmetric_keys().into_iter().min().unwrap_or_default()
+    }
+
+    /// Returns `true` if `mv` can be played in this position: it must be
+    /// on the board, the target cell must be empty, and the game must not be over.
+    #[must_use]
+    pub fn is_legal(&self, mv: Move<SIDE_LENGTH>) -> bool {
+        mv.index() < SIDE_LENGTH * SIDE_LENGTH
+            && self.cells[mv.index() / SIDE_LENGTH][mv.index() % SIDE_LENGTH] == Player::None
+            && self.outcome().is_none()
+    }
+
+    /// Applies a sequence of moves, checking each one with [`Board::is_legal`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the index into `moves` of the first illegal move.
+    pub fn apply_moves(mut self, moves: &[Move<SIDE_LENGTH>]) -> Result<Self, usize> {
+        for (i, &mv) in moves.iter().enumerate() {
+            if !self.is_legal(mv) {
+                return Err(i);
+            }
+            self.make_move(mv);
+        }
+        Ok(self)
+    }
+
+    /// Returns a copy of the board with `mv` applied, leaving `self` untouched.
+    #[must_use]
+    pub fn with_move(&self, mv: Move<SIDE_LENGTH>) -> Self {
+        let mut board = *self;
+        board.make_move(mv);
+        board
     }
 
     /// Returns the player whose turn it is.
     #[must_use]
     pub const fn turn(&self) -> Player {
-        match self.ply % 2 {
-            0 => Player::X,
-            _ => Player::O,
+        self.to_move
+    }
+
+    /// Returns the last move played, or `None` if no move has been played
+    /// since the board was created or set up.
+    #[must_use]
+    pub const fn last_move(&self) -> Option<Move<SIDE_LENGTH>> {
+        self.last_move
+    }
+
+    /// Returns the number of moves played, as recorded in the FEN.
+    #[must_use]
+    pub const fn ply(&self) -> usize {
+        self.ply as usize
+    }
+
+    /// Builds a setup position, such as a handicap or teaching position,
+    /// with `stones` already on the board and `to_move` to play.
+    ///
+    /// The ply count is the number of stones placed, so the side to move need
+    /// not follow from its parity; see [`Board::is_setup`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if two stones share a cell, a stone or the side to
+    /// move is [`Player::None`], or there are more stones than cells.
+    pub fn from_setup(
+        stones: &[(Move<SIDE_LENGTH>, Player)],
+        to_move: Player,
+    ) -> Result<Self, &'static str> {
+        let mut board = Self::new();
+        if to_move == Player::None {
+            return Err("No player to move in setup position");
+        }
+        for &(mv, player) in stones {
+            let coord = mv.coord();
+            let cell = &mut board.cells[coord.row][coord.col];
+            if player == Player::None {
+                return Err("Empty stone in setup position");
+            }
+            if *cell != Player::None {
+                return Err("Two stones on one cell in setup position");
+            }
+            *cell = player;
+        }
+        board.ply = u16::try_from(stones.len()).map_err(|_| "Too many stones in setup position")?;
+        board.to_move = to_move;
+        board.recompute_key();
+        Ok(board)
+    }
+
+    /// Returns `true` if the side to move does not follow from the parity of
+    /// the ply count, as in handicap positions, so that the usual consistency
+    /// checks do not apply. FEN strings for such boards end with `setup`.
+    #[must_use]
+    pub fn is_setup(&self) -> bool {
+        self.ply.is_multiple_of(2) != (self.to_move == Player::X)
+    }
+
+    /// Hands the move to `player`, keeping the ply count, for analysis
+    /// positions where the mover differs from what the ply implies.
+    ///
+    /// If the side to move then disagrees with the parity of the ply, the
+    /// board becomes a [setup position](Board::is_setup) rather than an
+    /// invalid one; use [`Board::set_ply`] to bring the two back in line.
+    ///
+    /// Handing the move over forgets the last move, which would otherwise
+    /// be a stone of the side to move rather than of the side that just moved.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `player` is [`Player::None`].
+    pub fn set_turn(&mut self, player: Player) {
+        assert!(player != Player::None, "No player to move");
+        if player != self.to_move {
+            self.to_move = player;
+            self.key ^= zobrist::SIDE;
+            self.last_move = None;
+        }
+    }
+
+    /// Returns a copy of the board with the move handed to `player`, as for
+    /// [`Board::set_turn`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `player` is [`Player::None`].
+    #[must_use]
+    pub fn with_turn(mut self, player: Player) -> Self {
+        self.set_turn(player);
+        self
+    }
+
+    /// Sets the number of moves played, leaving the side to move alone.
+    ///
+    /// The ply count need not match the number of stones on the board;
+    /// whether the board is full is decided from the stones themselves.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ply` is more than the number of cells on the board.
+    pub fn set_ply(&mut self, ply: usize) {
+        assert!(
+            ply <= SIDE_LENGTH * SIDE_LENGTH,
+            "Ply must be at most the number of cells."
+        );
+        self.ply = u16::try_from(ply).unwrap();
+    }
+
+    /// Swaps every X stone for an O stone and vice versa, and hands the move to
+    /// the other player, so that the side to move owns the same stones as before.
+    ///
+    /// The side to move no longer follows from the parity of the ply count,
+    /// so the result is meant for evaluation and augmentation rather than play
+    /// records, and is a [setup position](Board::is_setup).
+    pub fn swap_colors(&mut self) {
+        for c in self.cells.iter_mut().flatten() {
+            *c = match *c {
+                Player::None => Player::None,
+                p => -p,
+            };
         }
+        self.to_move = -self.to_move;
+        self.recompute_key();
     }
 
-    fn row_along<const D_X: isize, const D_Y: isize>(&self, row: usize, col: usize) -> bool {
+    fn row_along<const D_X: isize, const D_Y: isize>(
+        &self,
+        row: usize,
+        col: usize,
+        last_piece: Player,
+    ) -> bool {
         #![allow(clippy::cast_sign_loss, clippy::cast_possible_wrap)]
         let mut count = 1;
-        let last_piece = -self.turn();
 
         if !(D_X < 0 && row == 0
             || D_Y < 0 && col == 0
@@ -207,7 +1035,7 @@ impl<const SIDE_LENGTH: usize> Board<SIDE_LENGTH> {
                     break;
                 }
                 count += 1;
-                if count == 5 {
+                if count == self.win_length {
                     return true;
                 }
                 if D_X < 0 && row_u == 0
@@ -234,7 +1062,7 @@ impl<const SIDE_LENGTH: usize> Board<SIDE_LENGTH> {
                     break;
                 }
                 count += 1;
-                if count == 5 {
+                if count == self.win_length {
                     return true;
                 }
                 if D_X > 0 && row_d == 0
@@ -252,6 +1080,21 @@ impl<const SIDE_LENGTH: usize> Board<SIDE_LENGTH> {
         false
     }
 
+    fn completes_line(&self, row: usize, col: usize, player: Player) -> bool {
+        self.row_along::<0, 1>(row, col, player)
+            || self.row_along::<1, 0>(row, col, player)
+            || self.row_along::<1, 1>(row, col, player)
+            || self.row_along::<1, -1>(row, col, player)
+    }
+
+    /// Returns `true` if a stone for `player` at `mv` would complete a winning
+    /// line, whether or not it is `player`'s turn.
+    #[must_use]
+    pub fn is_winning_move(&self, mv: Move<SIDE_LENGTH>, player: Player) -> bool {
+        let Coord { row, col } = mv.coord();
+        self.completes_line(row, col, player)
+    }
+
     /// Returns the outcome of the game, if any.
     ///
     /// `None` means the game is still in progress.
@@ -263,22 +1106,68 @@ impl<const SIDE_LENGTH: usize> Board<SIDE_LENGTH> {
         let row = (index / SIDE_LENGTH as u16) as usize;
         let col = (index % SIDE_LENGTH as u16) as usize;
 
-        if self.row_along::<0, 1>(row, col)
-            || self.row_along::<1, 0>(row, col)
-            || self.row_along::<1, 1>(row, col)
-            || self.row_along::<1, -1>(row, col)
-        {
+        if self.completes_line(row, col, -self.turn()) {
             return Some(-self.turn());
         }
 
-        if self.ply as usize == SIDE_LENGTH * SIDE_LENGTH {
+        if self.cells.iter().flatten().all(|&c| c != Player::None) {
             Some(Player::None)
         } else {
             None
         }
     }
 
+    /// Returns the ends of the line completed by the last move, if it won the
+    /// game, with the end of lower index first. The line may be longer than
+    /// the win length.
+    #[must_use]
+    pub fn winning_line(&self) -> Option<(Move<SIDE_LENGTH>, Move<SIDE_LENGTH>)> {
+        #![allow(clippy::cast_possible_truncation)]
+        let last = self.last_move?;
+        let Coord { row, col } = last.coord();
+        let player = self.cells[row][col];
+        if player == Player::None {
+            return None;
+        }
+        let end = |dr: isize, dc: isize| {
+            let (mut r, mut c) = (row, col);
+            while let (Some(next_r), Some(next_c)) =
+                (r.checked_add_signed(dr), c.checked_add_signed(dc))
+            {
+                if next_r >= SIDE_LENGTH
+                    || next_c >= SIDE_LENGTH
+                    || self.cells[next_r][next_c] != player
+                {
+                    break;
+                }
+                (r, c) = (next_r, next_c);
+            }
+            (r, c)
+        };
+        [(0, 1), (1, 0), (1, 1), (1, -1)]
+            .into_iter()
+            .find_map(|(dr, dc)| {
+                let (r0, c0) = end(-dr, -dc);
+                let (r1, c1) = end(dr, dc);
+                let len = r0.abs_diff(r1).max(c0.abs_diff(c1)) + 1;
+                (len >= self.win_length()).then(|| {
+                    (
+                        Move {
+                            index: (r0 * SIDE_LENGTH + c0) as u16,
+                        },
+                        Move {
+                            index: (r1 * SIDE_LENGTH + c1) as u16,
+                        },
+                    )
+                })
+            })
+    }
+
     /// The FEN string for the current board state.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the board is in an invalid state.
     #[must_use]
     pub fn fen(&self) -> String {
         let mut out = String::new();
@@ -304,35 +1193,132 @@ impl<const SIDE_LENGTH: usize> Board<SIDE_LENGTH> {
         });
         out.push(' ');
         out.push_str(&self.ply.to_string());
+        if let Some(mv) = self.last_move {
+            out.push(' ');
+            out.push_str(&mv.to_string());
+        }
+        if self.win_length != Self::DEFAULT_WIN_LENGTH {
+            write!(out, " k={}", self.win_length).unwrap();
+        }
+        if self.is_setup() {
+            out.push_str(" setup");
+        }
         out
     }
 
+    /// Plays a uniformly random move on an empty cell, even if the game is
+    /// already over.
+    ///
+    /// `rng(lo, hi)` must return a value in `lo..hi`.
+    ///
+    /// On a nearly full board this counts the empty cells first; to play many
+    /// random moves in a row, a [`Playout`] chooses each in constant time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the board is full, or if `rng` returns an out-of-range value.
     pub fn make_random_move(&mut self, mut rng: impl FnMut(usize, usize) -> usize) {
-        #![allow(clippy::cast_precision_loss)]
+        #![allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
         let filled_factor = f64::from(self.ply) / (SIDE_LENGTH * SIDE_LENGTH) as f64;
-        // if the board is mostly full, generate moves and then select.
-        // otherwise, just guess moves until we find an empty square.
-        if filled_factor > 0.95 {
-            let mut moves = SmallVec::<[Move<SIDE_LENGTH>; 19 * 19]>::new();
-            self.generate_moves(|mv| {
-                moves.push(mv);
-                false
-            });
-            let index = rng(0, moves.len());
-            self.make_move(moves[index]);
-            return;
+        // if the board is mostly empty, guess moves until we find an empty square.
+        // we expect this to take only a few tries (at most 95% of the board is full,
+        // so we expect to find an empty square in 20 tries), but the ply count can
+        // be set independently of the stones, so give up after a while.
+        if filled_factor <= 0.95 {
+            for _ in 0..64 {
+                let index = rng(0, SIDE_LENGTH * SIDE_LENGTH);
+                if self.cells[index / SIDE_LENGTH][index % SIDE_LENGTH] == Player::None {
+                    self.place(Move {
+                        index: index.try_into().expect("Index out of range"),
+                    });
+                    return;
+                }
+            }
         }
-        // we expect this loop to run only a few times
-        // (at most 95% of the board is full, so we expect to find an empty square in 20 tries)
-        let index = loop {
-            let index = rng(0, SIDE_LENGTH * SIDE_LENGTH);
-            if self.cells[index / SIDE_LENGTH][index % SIDE_LENGTH] == Player::None {
-                break index;
+        // otherwise, count the empty squares and then select.
+        let mut n_moves = 0;
+        self.generate_moves(|_| {
+            n_moves += 1;
+            false
+        });
+        assert!(n_moves > 0, "No empty cells to play on");
+        let mut remaining = rng(0, n_moves);
+        assert!(remaining < n_moves, "Index out of range");
+        let mut chosen = Move::null();
+        self.generate_moves(|mv| {
+            if remaining == 0 {
+                chosen = mv;
+                return true;
             }
-        };
-        self.make_move(Move {
-            index: index.try_into().expect("Index out of range"),
+            remaining -= 1;
+            false
         });
+        self.place(chosen);
+    }
+
+    /// Recovers the stones added between this position and `other`.
+    ///
+    /// The returned moves alternate between the players starting with the side to
+    /// move here, so they can be replayed with [`Board::apply_moves`] whenever the
+    /// intermediate positions are legal.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `other` cannot follow from this position by normal
+    /// play: if a stone was removed or changed colour, or the stones added,
+    /// their number or their colours, do not agree with the ply counts and
+    /// sides to move of the two positions.
+    pub fn diff(
+        &self,
+        other: &Self,
+    ) -> Result<Vec<(Move<SIDE_LENGTH>, Player)>, DiffError<SIDE_LENGTH>> {
+        #![allow(clippy::cast_possible_truncation)]
+        let mut ours = Vec::new();
+        let mut theirs = Vec::new();
+        let us = self.turn();
+        for (i, (&before, &after)) in self
+            .cells
+            .iter()
+            .flatten()
+            .zip(other.cells.iter().flatten())
+            .enumerate()
+        {
+            let mv = Move { index: i as u16 };
+            match (before, after) {
+                _ if before == after => {}
+                (Player::None, p) if p == us => ours.push((mv, p)),
+                (Player::None, p) => theirs.push((mv, p)),
+                (_, Player::None) => return Err(DiffError::StoneRemoved(mv)),
+                _ => return Err(DiffError::StoneChanged(mv)),
+            }
+        }
+        if ours.len() != theirs.len() && ours.len() != theirs.len() + 1 {
+            return Err(DiffError::WrongStoneCounts {
+                to_move: ours.len(),
+                other: theirs.len(),
+            });
+        }
+        let expected = self.ply() + ours.len() + theirs.len();
+        if other.ply() != expected {
+            return Err(DiffError::WrongPly {
+                expected,
+                found: other.ply(),
+            });
+        }
+        let expected = if ours.len() == theirs.len() { us } else { -us };
+        if other.turn() != expected {
+            return Err(DiffError::WrongTurn {
+                expected,
+                found: other.turn(),
+            });
+        }
+        let mut out = Vec::with_capacity(ours.len() + theirs.len());
+        let mut theirs = theirs.into_iter();
+        for pair in ours {
+            out.push(pair);
+            out.extend(theirs.next());
+        }
+        Ok(out)
     }
 }
 
@@ -459,6 +1445,17 @@ impl<const SIDE_LENGTH: usize> FromStr for Board<SIDE_LENGTH> {
     /// `x......o/......../......../......../......../......../o......x x 4`,
     /// meaning that there are four pieces placed (in the corners)
     /// and x is to move next.
+    ///
+    /// The ply count may be followed by the last move played, as in
+    /// `... x 4 G1`, so that a parsed position knows whether the game is
+    /// over; strings without it are still accepted. The last move must be a
+    /// stone of the side that just moved.
+    ///
+    /// A win length other than the default is written next, as in
+    /// `... x 4 G1 k=4`; strings without it get the default win length.
+    ///
+    /// The side to move must follow from the parity of the ply count, unless
+    /// the string ends with `setup`, as written for [`Board::is_setup`] boards.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut out = Self::new();
         let mut parts = s.split_whitespace();
@@ -476,11 +1473,39 @@ impl<const SIDE_LENGTH: usize> FromStr for Board<SIDE_LENGTH> {
         let Some(ply) = parts.next().and_then(|s| s.parse::<u16>().ok()) else {
             return Err("No ply part found in FEN string");
         };
+        if usize::from(ply) > SIDE_LENGTH * SIDE_LENGTH {
+            return Err("Ply part is larger than the board in FEN string");
+        }
         out.ply = ply;
-        if out.turn() != turn {
-            return Err("Turn part does not match ply part in FEN string");
+        out.to_move = turn;
+        let mut parts = parts.peekable();
+        let last_move = match parts.next_if(|&part| part != "setup" && !part.starts_with("k=")) {
+            Some(part) => match part.parse::<Move<SIDE_LENGTH>>() {
+                Ok(mv) => Some(mv),
+                Err(_) => return Err("Invalid last move part found in FEN string"),
+            },
+            None => None,
+        };
+        if let Some(part) = parts.next_if(|&part| part.starts_with("k=")) {
+            match part[2..].parse::<usize>() {
+                Ok(k) if (2..=SIDE_LENGTH).contains(&k) => out = out.with_win_length(k),
+                _ => return Err("Invalid win length part found in FEN string"),
+            }
+        }
+        match parts.next() {
+            Some("setup") => {}
+            Some(_) => return Err("Invalid setup part found in FEN string"),
+            None if out.is_setup() => {
+                return Err("Turn part does not match ply part in FEN string");
+            }
+            None => {}
         }
+        let mut row_count = 0;
         for (i, row) in rows.enumerate() {
+            if i >= SIDE_LENGTH {
+                return Err("Too many rows in FEN string");
+            }
+            row_count += 1;
             let mut col = 0;
             for c in row.chars() {
                 if col >= SIDE_LENGTH {
@@ -498,6 +1523,19 @@ impl<const SIDE_LENGTH: usize> FromStr for Board<SIDE_LENGTH> {
                 return Err("Too few columns in FEN string");
             }
         }
+        if row_count != SIDE_LENGTH {
+            return Err("Too few rows in FEN string");
+        }
+        if let Some(mv) = last_move {
+            let Coord { row, col } = mv.coord();
+            if out.cells[row][col] != -out.to_move {
+                return Err(
+                    "Last move part is not a stone of the side that just moved in FEN string",
+                );
+            }
+            out.last_move = Some(mv);
+        }
+        out.recompute_key();
         Ok(out)
     }
 }
@@ -518,6 +1556,41 @@ mod tests {
         assert_eq!(board.turn(), Player::O);
     }
 
+    #[test]
+    fn fen_rejects_bad_shapes() {
+        use super::*;
+        let full = "...../...../...../...../.....";
+        assert!(Board::<5>::from_str(&format!("{full} x 0")).is_ok());
+        assert!(Board::<5>::from_str("...../...../..... x 0").is_err());
+        assert!(Board::<5>::from_str(&format!("{full}/..... x 0")).is_err());
+        assert!(Board::<5>::from_str(&format!("{full} o 25")).is_ok());
+        assert!(Board::<5>::from_str(&format!("{full} o 900")).is_err());
+    }
+
+    #[test]
+    fn fen_records_win_length() {
+        use super::*;
+        assert_eq!(Board::<3>::new().win_length(), 3);
+        assert_eq!(Board::<15>::new().win_length(), 5);
+        let mut board = Board::<7>::new().with_win_length(3);
+        for index in [0, 48, 1, 47, 2] {
+            board.make_move(Move { index });
+        }
+        let fen = board.fen();
+        assert!(fen.ends_with(" k=3"));
+        let parsed = Board::<7>::from_str(&fen).unwrap();
+        assert_eq!(parsed, board);
+        assert_eq!(parsed.outcome(), Some(Player::X));
+        assert_ne!(
+            parsed,
+            Board::<7>::from_str(&fen.replace(" k=3", "")).unwrap()
+        );
+        assert!(Board::<7>::from_str(
+            "......./......./......./......./......./......./....... x 0 k=8"
+        )
+        .is_err());
+    }
+
     #[test]
     fn fen_string_round_trip_startpos() {
         use super::*;
@@ -549,6 +1622,37 @@ mod tests {
         assert_eq!(board, board2);
     }
 
+    #[test]
+    fn gomocup_20x20() {
+        use super::*;
+        let mut board = Board::<20>::new();
+        for index in 0..20 * 20u16 {
+            let mv = Move::<20> { index };
+            assert_eq!(Move::<20>::from_str(&mv.to_string()).unwrap(), mv);
+        }
+        let corner = Move::<20>::from_str("T20").unwrap();
+        assert_eq!(corner.index(), 399);
+        assert!(Move::<20>::from_str("U1").is_err());
+        assert!(Move::<20>::from_str("A21").is_err());
+        assert!(Move::<20>::from_str("A0").is_err());
+        assert!(Move::<19>::from_str("T1").is_err());
+        assert!(Move::<19>::from_str("A20").is_err());
+        assert!(Move::<20>::from_str("A+").is_err());
+
+        board.make_move(Move::from_str("a1").unwrap());
+        board.make_move(corner);
+        let fen = board.fen();
+        let board2 = Board::<20>::from_str(&fen).unwrap();
+        assert_eq!(board, board2);
+        assert_eq!(board.zobrist_key(), board2.zobrist_key());
+        assert!(Board::<19>::from_str(&fen).is_err());
+
+        let display = board.to_string();
+        assert!(display.contains(" │ 20\n"));
+        assert!(display.contains("   T\n"));
+        assert_eq!(crate::perft::perft(Board::<20>::new(), 2), 400 * 399);
+    }
+
     #[test]
     fn fen_string_round_trip_alt() {
         use super::*;
@@ -568,4 +1672,501 @@ mod tests {
             assert_eq!(mv, mv2);
         }
     }
+
+    #[test]
+    fn winning_lines() {
+        use super::*;
+        let moves = ["e5", "a1", "d6", "a2", "c7", "a3", "f4", "a4"];
+        let board = Board::<9>::new()
+            .apply_moves(&moves.map(|mv| mv.parse().unwrap()))
+            .unwrap();
+        assert_eq!(board.winning_line(), None);
+        let board = board.with_move("g3".parse().unwrap());
+        assert_eq!(board.outcome(), Some(Player::X));
+        assert_eq!(
+            board.winning_line(),
+            Some(("g3".parse().unwrap(), "c7".parse().unwrap()))
+        );
+        assert_eq!(Board::<9>::new().winning_line(), None);
+
+        // the last move survives a FEN round trip, and with it the result.
+        let fen = board.fen();
+        assert!(fen.ends_with(" o 9 G3"));
+        let parsed = Board::<9>::from_str(&fen).unwrap();
+        assert_eq!(parsed.last_move(), board.last_move());
+        assert_eq!(parsed.outcome(), Some(Player::X));
+        let legacy = Board::<9>::from_str(fen.trim_end_matches(" G3")).unwrap();
+        assert_eq!(legacy.outcome(), None);
+        assert!(Board::<9>::from_str(&fen.replace("G3", "H3")).is_err());
+        // the last move must be a stone of the side that just moved.
+        assert!(Board::<9>::from_str(&fen.replace("G3", "A1")).is_err());
+        assert!(Board::<5>::from_str("xxxxx/oooo./...../...../..... o 9 A2").is_err());
+        assert!(Board::<5>::from_str("xxxxx/oooo./...../...../..... o 9 A1").is_ok());
+        assert!(Board::<9>::from_str(&fen.replace("G3", "G3 setup")).is_ok());
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "strict-checks"))]
+    #[should_panic = "occupied cell"]
+    fn checks_occupancy() {
+        use super::*;
+        let mut board = Board::<7>::new();
+        board.make_move("d4".parse().unwrap());
+        board.make_move("d4".parse().unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "strict-checks")]
+    #[should_panic = "game is over"]
+    fn strict_checks_stop_play_after_a_win() {
+        use super::*;
+        let mut board = Board::<5>::new().with_win_length(3);
+        for mv in ["a1", "a2", "b1", "b2", "c1", "c2"] {
+            board.make_move(mv.parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn filtered_movegen() {
+        use super::*;
+        let board = Board::<7>::new()
+            .apply_moves(&["c3", "d3"].map(|mv| mv.parse().unwrap()))
+            .unwrap();
+        let on_row_three = |mv: Move<7>| mv.coord().row == 2;
+        let mut moves = Vec::new();
+        board.generate_moves_where(on_row_three, |mv| {
+            moves.push(mv);
+            false
+        });
+        let names = moves.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(names, ["A3", "B3", "E3", "F3", "G3"]);
+        assert_eq!(board.moves_where(on_row_three).collect::<Vec<_>>(), moves);
+
+        let mut first = None;
+        board.generate_moves_where(
+            |mv| mv.coord().col == 3,
+            |mv| {
+                first = Some(mv);
+                true
+            },
+        );
+        assert_eq!(first, Some("d1".parse().unwrap()));
+    }
+
+    #[test]
+    fn bounding_boxes() {
+        use super::*;
+        assert_eq!(Board::<9>::new().bounding_box(), None);
+        let board = Board::<9>::new()
+            .apply_moves(&["e3", "b5", "g4"].map(|mv| mv.parse().unwrap()))
+            .unwrap();
+        assert_eq!(
+            board.bounding_box(),
+            Some((Coord::new(2, 1), Coord::new(4, 6)))
+        );
+        assert_eq!(
+            board.bounding_box_with_margin(3),
+            Some((Coord::new(0, 0), Coord::new(7, 8)))
+        );
+    }
+
+    #[test]
+    fn sparse_and_dense_moves_near_agree() {
+        use super::*;
+        let mut rng = crate::test_util::test_rng(0x9E37_79B9_7F4A_7C15);
+        assert_eq!(Board::<15>::new().moves_near(2), [Board::<15>::center()]);
+        let mut board = Board::<15>::new();
+        for stones in 1..=SPARSE_STONES + 4 {
+            board.make_random_move(&mut rng);
+            for radius in [0, 1, 2, 3] {
+                let near = board.near_mask(radius);
+                let mut expected = Vec::new();
+                board.generate_moves(|mv| {
+                    let Coord { row, col } = mv.coord();
+                    if near[row][col] {
+                        expected.push(mv);
+                    }
+                    false
+                });
+                assert_eq!(board.moves_near(radius), expected, "{stones} stones");
+            }
+        }
+    }
+
+    #[test]
+    fn random_moves_fill_board() {
+        use super::*;
+        let mut rng = crate::test_util::test_rng(0x2545_F491_4F6C_DD1D);
+        let mut board = Board::<7>::new();
+        for _ in 0..49 {
+            board.make_random_move(&mut rng);
+        }
+        let mut n_moves = 0;
+        board.generate_moves(|_| {
+            n_moves += 1;
+            false
+        });
+        assert_eq!(n_moves, 0);
+    }
+
+    #[test]
+    fn feature_map_relative_channels() {
+        use super::*;
+        let mut board = Board::<7>::new();
+        board.make_move("b3".parse().unwrap());
+        board.make_move("e5".parse().unwrap());
+        board.make_move("g1".parse().unwrap());
+        // O to move, so O's stone is channel 0.
+        let mut features = Vec::new();
+        board.feature_map_relative(|index, row, col, channel| {
+            assert_eq!(index, row * 7 + col);
+            features.push((row, col, channel));
+        });
+        assert_eq!(features, vec![(0, 6, 1), (2, 1, 1), (4, 4, 0)]);
+    }
+
+    #[test]
+    fn cells_cover_board() {
+        use super::*;
+        let mut board = Board::<9>::new();
+        let mv: Move<9> = "c4".parse().unwrap();
+        board.make_move(mv);
+        assert_eq!(board.cells().count(), 81);
+        let filled = board
+            .cells()
+            .filter(|&(_, p)| p != Player::None)
+            .collect::<Vec<_>>();
+        assert_eq!(filled, vec![(mv.coord(), Player::X)]);
+        assert_eq!(mv.coord(), Coord::new(3, 2));
+    }
+
+    #[test]
+    fn stones_per_player() {
+        use super::*;
+        let mut board = Board::<9>::new();
+        for mv in ["e5", "d4", "a1", "i9"] {
+            board.make_move(mv.parse().unwrap());
+        }
+        let xs = board
+            .stones(Player::X)
+            .map(|mv| mv.to_string())
+            .collect::<Vec<_>>();
+        let os = board
+            .stones(Player::O)
+            .map(|mv| mv.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(xs, ["A1", "E5"]);
+        assert_eq!(os, ["D4", "I9"]);
+        assert_eq!(board.stones(Player::None).count(), 77);
+    }
+
+    #[test]
+    fn apply_moves_validates() {
+        use super::*;
+        let moves = ["a1", "b1", "a2", "b2", "a3", "b3", "a4", "b4", "a5", "b5"]
+            .map(|mv| mv.parse::<Move<9>>().unwrap());
+        let board = Board::<9>::new().apply_moves(&moves[..9]).unwrap();
+        assert_eq!(board.outcome(), Some(Player::X));
+        // the game is over before the tenth move.
+        assert_eq!(Board::<9>::new().apply_moves(&moves), Err(9));
+        // a repeated move is illegal.
+        assert_eq!(Board::<9>::new().apply_moves(&[moves[0], moves[0]]), Err(1));
+        assert_eq!(Board::<9>::new().apply_moves(&[Move::null()]), Err(0));
+    }
+
+    #[test]
+    fn diff_recovers_moves() {
+        use super::*;
+        let moves = ["e5", "d4", "e6", "f7"].map(|mv| mv.parse::<Move<9>>().unwrap());
+        let before = Board::<9>::new().apply_moves(&moves[..1]).unwrap();
+        let after = before.apply_moves(&moves[1..]).unwrap();
+        let diff = before.diff(&after).unwrap();
+        assert_eq!(
+            diff,
+            [
+                (moves[1], Player::O),
+                (moves[2], Player::X),
+                (moves[3], Player::O)
+            ]
+        );
+        let replayed = diff.iter().map(|&(mv, _)| mv).collect::<Vec<_>>();
+        assert_eq!(before.apply_moves(&replayed), Ok(after));
+        assert!(before.diff(&before).unwrap().is_empty());
+        assert_eq!(after.diff(&before), Err(DiffError::StoneRemoved(moves[1])));
+    }
+
+    #[test]
+    fn diff_rejects_impossible_transitions() {
+        use super::*;
+        let before = Board::<5>::from_str("x..../...../...../...../..... o 1").unwrap();
+        let changed = Board::<5>::from_str("o..../...../...../...../..... o 1").unwrap();
+        let two_x = Board::<5>::from_str("x.x.x/...../...../...../..... o 1").unwrap();
+        assert_eq!(
+            before.diff(&changed),
+            Err(DiffError::StoneChanged("a1".parse().unwrap()))
+        );
+        assert_eq!(
+            before.diff(&two_x),
+            Err(DiffError::WrongStoneCounts {
+                to_move: 0,
+                other: 2
+            })
+        );
+
+        // the stones added must agree with the ply and the side to move.
+        let one_more = Board::<5>::from_str("x..../o..../...../...../..... x 2").unwrap();
+        assert_eq!(before.diff(&one_more).unwrap().len(), 1);
+        let wrong_ply = Board::<5>::from_str("x..../o..../...../...../..... x 4").unwrap();
+        assert_eq!(
+            before.diff(&wrong_ply),
+            Err(DiffError::WrongPly {
+                expected: 2,
+                found: 4
+            })
+        );
+        let wrong_turn = one_more.with_turn(Player::O);
+        assert_eq!(
+            before.diff(&wrong_turn),
+            Err(DiffError::WrongTurn {
+                expected: Player::X,
+                found: Player::O
+            })
+        );
+    }
+
+    #[test]
+    fn children_match_generate_moves() {
+        use super::*;
+        let board = Board::<7>::new()
+            .apply_moves(&[Move { index: 24 }])
+            .unwrap();
+        let mut moves = Vec::new();
+        board.generate_moves(|mv| {
+            moves.push(mv);
+            false
+        });
+        let children = board.children().collect::<Vec<_>>();
+        assert_eq!(children.len(), 48);
+        for ((mv, child), expected) in children.into_iter().zip(moves) {
+            assert_eq!(mv, expected);
+            assert_eq!(child.turn(), Player::X);
+            assert_eq!(child.player_at(mv.coord()), Player::O);
+        }
+
+        let won = Board::<3>::new()
+            .apply_moves(&[0, 3, 1, 4, 2].map(|index| Move { index }))
+            .unwrap();
+        assert_eq!(won.outcome(), Some(Player::X));
+        assert_eq!(won.children().count(), 0);
+        assert_eq!(won.successors().count(), 4);
+    }
+
+    #[test]
+    fn with_move_copies() {
+        use super::*;
+        let board = Board::<9>::new();
+        let mv = "e5".parse().unwrap();
+        let child = board.with_move(mv);
+        assert_eq!(board.player_at(mv.coord()), Player::None);
+        assert_eq!(child.player_at(mv.coord()), Player::X);
+        assert_eq!(child.turn(), Player::O);
+    }
+
+    #[test]
+    fn swap_colors_round_trip() {
+        use super::*;
+        let board = Board::<9>::new()
+            .apply_moves(&["e5", "d4", "e6"].map(|mv| mv.parse().unwrap()))
+            .unwrap();
+        let mut swapped = board;
+        swapped.swap_colors();
+        assert_eq!(swapped.turn(), Player::X);
+        assert_eq!(swapped.stones(Player::O).count(), 2);
+        assert_eq!(swapped.stones(Player::X).count(), 1);
+        let mut features = Vec::new();
+        board.feature_map_relative(|i, _, _, channel| features.push((i, channel)));
+        let mut swapped_features = Vec::new();
+        swapped.feature_map_relative(|i, _, _, channel| swapped_features.push((i, channel)));
+        assert_eq!(features, swapped_features);
+        assert!(swapped.is_setup());
+        assert_eq!(Board::<9>::from_str(&swapped.fen()).unwrap(), swapped);
+        swapped.swap_colors();
+        assert_eq!(swapped, board);
+        assert_eq!(swapped.fen(), board.fen());
+    }
+
+    #[test]
+    fn ply_need_not_match_stones() {
+        use super::*;
+        let mut board = Board::<3>::new();
+        for index in [0, 1, 2, 4, 3, 5, 7, 6] {
+            board.make_move(Move { index });
+        }
+        board.set_ply(4);
+        assert_eq!(board.outcome(), None);
+        // always guessing the occupied corner must not hang.
+        board.make_random_move(|lo, _| lo);
+        assert_eq!(board.last_move(), Some(Move { index: 8 }));
+        assert_eq!(board.outcome(), Some(Player::None));
+    }
+
+    #[test]
+    fn turn_override() {
+        use super::*;
+        let board = Board::<9>::new().with_move("e5".parse().unwrap());
+        let mut analysis = board.with_turn(Player::X);
+        assert_eq!(analysis.turn(), Player::X);
+        assert!(analysis.is_setup());
+        assert_ne!(analysis, board);
+        assert_eq!(
+            analysis.zobrist_key(),
+            Board::<9>::from_str(&analysis.fen()).unwrap().zobrist_key()
+        );
+        assert_ne!(analysis.zobrist_key(), board.zobrist_key());
+
+        analysis.set_ply(2);
+        assert!(!analysis.is_setup());
+        assert_eq!(analysis.last_move(), None);
+        assert!(analysis.fen().ends_with(" x 2"));
+        analysis.make_move("d4".parse().unwrap());
+        assert_eq!(analysis.stones(Player::X).count(), 2);
+
+        assert_eq!(
+            board.with_turn(Player::O).zobrist_key(),
+            board.zobrist_key()
+        );
+        assert_eq!(
+            board
+                .with_turn(Player::X)
+                .with_turn(Player::O)
+                .zobrist_key(),
+            board.zobrist_key()
+        );
+    }
+
+    #[test]
+    fn handicap_setup() {
+        use super::*;
+        // two handicap stones for X, with X still to move.
+        let stones = ["e5", "c3"].map(|mv| (mv.parse().unwrap(), Player::X));
+        let board = Board::<9>::from_setup(&stones, Player::X).unwrap();
+        assert!(!board.is_setup());
+        assert_eq!(board.ply(), 2);
+        assert_eq!(board.stones(Player::X).count(), 2);
+
+        // with three, the side to move no longer follows from the ply.
+        let stones = ["e5", "c3", "g7"].map(|mv| (mv.parse().unwrap(), Player::X));
+        let board = Board::<9>::from_setup(&stones, Player::X).unwrap();
+        assert!(board.is_setup());
+        let fen = board.fen();
+        assert!(fen.ends_with(" x 3 setup"));
+        let parsed = Board::<9>::from_str(&fen).unwrap();
+        assert_eq!(parsed, board);
+        assert_eq!(parsed.turn(), Player::X);
+        assert_eq!(parsed.zobrist_key(), board.zobrist_key());
+        assert!(Board::<9>::from_str(fen.trim_end_matches(" setup")).is_err());
+        assert!(Board::<9>::from_str(&fen.replace("setup", "extra")).is_err());
+        // regular positions may carry the flag too.
+        assert!(Board::<9>::from_str(&format!("{} setup", Board::<9>::new().fen())).is_ok());
+
+        let next = board.with_move("d4".parse().unwrap());
+        assert_eq!(next.turn(), Player::O);
+        assert!(next.is_setup());
+
+        let doubled = [
+            ("e5".parse().unwrap(), Player::X),
+            ("e5".parse().unwrap(), Player::O),
+        ];
+        assert!(Board::<9>::from_setup(&doubled, Player::X).is_err());
+        assert!(Board::<9>::from_setup(&[], Player::None).is_err());
+    }
+
+    #[test]
+    fn flat_cells_match_moves() {
+        use super::*;
+        let board = Board::<9>::new()
+            .apply_moves(&["e5", "d4"].map(|mv| mv.parse().unwrap()))
+            .unwrap();
+        let flat = board.as_flat();
+        assert_eq!(flat.len(), 81);
+        assert_eq!(flat.iter().filter(|&&p| p != Player::None).count(), 2);
+        for (mv, player) in [("e5", Player::X), ("d4", Player::O)] {
+            assert_eq!(flat[mv.parse::<Move<9>>().unwrap().index()], player);
+        }
+        assert_eq!(Player::O as u8, 2);
+    }
+
+    #[test]
+    fn moves_from_indices() {
+        use super::*;
+        let mv = Move::<9>::try_from(40usize).unwrap();
+        assert_eq!(mv, "e5".parse().unwrap());
+        assert_eq!(Move::<9>::try_from(40u16), Ok(mv));
+        assert_eq!(Move::<9>::try_from(mv.index()), Ok(mv));
+        assert!(Move::<9>::try_from(81usize).is_err());
+        assert!(Move::<9>::try_from(u16::MAX).is_err());
+        assert!(Move::<9>::try_from(usize::MAX).is_err());
+        assert!(Move::<19>::try_from(360u16).is_ok());
+    }
+
+    #[test]
+    fn move_distances() {
+        use super::*;
+        let mv = |s: &str| s.parse::<Move<15>>().unwrap();
+        assert_eq!(mv("h8").chebyshev_distance(&mv("j11")), 3);
+        assert_eq!(mv("h8").manhattan_distance(&mv("j11")), 5);
+        assert_eq!(mv("j11").manhattan_distance(&mv("h8")), 5);
+        assert_eq!(mv("h8").chebyshev_distance(&mv("h8")), 0);
+        assert!(mv("h8").is_adjacent(&mv("g9")));
+        assert!(mv("h8").is_adjacent(&mv("h7")));
+        assert!(!mv("h8").is_adjacent(&mv("h8")));
+        assert!(!mv("h8").is_adjacent(&mv("h10")));
+        // cells at either end of a row are not adjacent across the edge.
+        assert!(!mv("o1").is_adjacent(&mv("a2")));
+    }
+
+    #[test]
+    fn center_and_rings() {
+        use super::*;
+        assert_eq!(Board::<15>::center(), "h8".parse().unwrap());
+        assert_eq!(Board::<9>::center(), "e5".parse().unwrap());
+        let center = Board::<9>::center();
+        assert_eq!(Board::ring(center, 0).collect::<Vec<_>>(), [center]);
+        for distance in 1..=4 {
+            let ring = Board::ring(center, distance).collect::<Vec<_>>();
+            assert_eq!(ring.len(), 8 * distance);
+            assert!(ring
+                .iter()
+                .all(|mv| mv.chebyshev_distance(&center) == distance));
+            assert!(ring.windows(2).all(|w| w[0].index() < w[1].index()));
+        }
+        assert_eq!(Board::ring(center, 5).count(), 0);
+        // rings are clipped at the edges of the board.
+        let corner = "a1".parse::<Move<9>>().unwrap();
+        assert_eq!(Board::ring(corner, 1).count(), 3);
+        assert_eq!(Board::ring(corner, 8).count(), 17);
+    }
+
+    #[test]
+    fn canonical_keys_unify_symmetries() {
+        use super::*;
+        let mut board = Board::<9>::new();
+        for mv in ["e5", "f6", "c5", "d7", "g3"] {
+            board.make_move(mv.parse().unwrap());
+        }
+        let expected = Symmetry::ALL
+            .iter()
+            .map(|&sym| board.transform(sym).zobrist_key())
+            .min()
+            .unwrap();
+        assert_eq!(board.canonical_key(), expected);
+        for sym in Symmetry::ALL {
+            assert_eq!(board.transform(sym).canonical_key(), expected);
+        }
+        let keys = Symmetry::ALL.map(|sym| board.transform(sym).zobrist_key());
+        assert_eq!(board.symmetric_keys(), keys);
+        // the side to move still counts.
+        assert_ne!(board.with_turn(Player::X).canonical_key(), expected);
+        assert_eq!(Board::<9>::new().canonical_key(), 0);
+    }
 }
@@ -0,0 +1,101 @@
+//! Generators for property testing with the `arbitrary` crate.
+//!
+//! An arbitrary [`Board`] is reached by playing legal moves from the empty
+//! board, so it is always a position that could occur in a game, and may be
+//! finished. An arbitrary [`Move`] is any cell on the board; use
+//! [`Board::arbitrary_legal_move`] for one that can be played.
+//!
+//! These plug into fuzzers such as `cargo fuzz` directly, and into
+//! `proptest` through its `arbitrary` interop, for properties such as FEN
+//! round-trips or make/unmake symmetry.
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use super::{Board, Move};
+
+impl<'a, const SIDE_LENGTH: usize> Arbitrary<'a> for Move<SIDE_LENGTH> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        #![allow(clippy::cast_possible_truncation)]
+        let index = u.int_in_range(0..=SIDE_LENGTH * SIDE_LENGTH - 1)?;
+        Ok(Self {
+            index: index as u16,
+        })
+    }
+
+    fn size_hint(_: usize) -> (usize, Option<usize>) {
+        (0, Some(2))
+    }
+}
+
+impl<'a, const SIDE_LENGTH: usize> Arbitrary<'a> for Board<SIDE_LENGTH> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut board = Self::new();
+        let plies = u.int_in_range(0..=SIDE_LENGTH * SIDE_LENGTH)?;
+        for _ in 0..plies {
+            match board.arbitrary_legal_move(u)? {
+                Some(mv) => board.make_move(mv),
+                None => break,
+            }
+        }
+        Ok(board)
+    }
+}
+
+impl<const SIDE_LENGTH: usize> Board<SIDE_LENGTH> {
+    /// Chooses a legal move with `u`, or `None` if the game is over.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `u` runs out of data.
+    pub fn arbitrary_legal_move(
+        &self,
+        u: &mut Unstructured<'_>,
+    ) -> Result<Option<Move<SIDE_LENGTH>>> {
+        if self.outcome().is_some() {
+            return Ok(None);
+        }
+        let mut moves = Vec::new();
+        self.generate_moves(|mv| {
+            moves.push(mv);
+            false
+        });
+        u.choose(&moves).map(|&mv| Some(mv))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    use crate::board::{Board, Move};
+
+    /// Feeds each of a spread of byte strings to `check`.
+    fn for_each_input(mut check: impl FnMut(&mut Unstructured<'_>)) {
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        for len in 0..200 {
+            let bytes = (0..len)
+                .map(|_| {
+                    state = crate::zobrist::splitmix64(state).1;
+                    state.to_le_bytes()[0]
+                })
+                .collect::<Vec<_>>();
+            check(&mut Unstructured::new(&bytes));
+        }
+    }
+
+    #[test]
+    fn arbitrary_boards_round_trip() {
+        for_each_input(|u| {
+            let board = Board::<7>::arbitrary(u).unwrap();
+            let parsed = board.fen().parse::<Board<7>>().unwrap();
+            assert_eq!(parsed, board);
+            assert_eq!(parsed.zobrist_key(), board.zobrist_key());
+            assert_eq!(parsed.outcome(), board.outcome());
+            if let Some(mv) = board.arbitrary_legal_move(u).unwrap() {
+                assert!(board.is_legal(mv));
+            }
+            let mv = Move::<7>::arbitrary(u).unwrap();
+            assert_eq!(mv.to_string().parse::<Move<7>>(), Ok(mv));
+        });
+    }
+}
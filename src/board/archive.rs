@@ -0,0 +1,117 @@
+//! Zero-copy access to boards archived with `rkyv`.
+//!
+//! A slice of boards archived with [`rkyv::to_bytes`] can be written to disk,
+//! memory-mapped, and read in place with [`rkyv::access`], without
+//! deserializing anything. The methods here answer the usual questions about
+//! an archived board directly from the archive, and [`ArchivedBoard::to_board`]
+//! copies one out when a full [`Board`] is needed, for instance to play moves.
+
+use super::{ArchivedBoard, ArchivedMove, ArchivedPlayer, Board, Coord, Move, Player};
+
+impl From<&ArchivedPlayer> for Player {
+    fn from(player: &ArchivedPlayer) -> Self {
+        match player {
+            ArchivedPlayer::None => Self::None,
+            ArchivedPlayer::X => Self::X,
+            ArchivedPlayer::O => Self::O,
+        }
+    }
+}
+
+impl<const SIDE_LENGTH: usize> ArchivedMove<SIDE_LENGTH> {
+    /// The move this archived move represents.
+    #[must_use]
+    pub const fn to_move(&self) -> Move<SIDE_LENGTH> {
+        Move {
+            index: self.index.to_native(),
+        }
+    }
+}
+
+impl<const SIDE_LENGTH: usize> ArchivedBoard<SIDE_LENGTH> {
+    /// Returns the player whose turn it is.
+    #[must_use]
+    pub fn turn(&self) -> Player {
+        (&self.to_move).into()
+    }
+
+    /// Returns the number of moves played.
+    #[must_use]
+    pub fn ply(&self) -> usize {
+        usize::from(self.ply.to_native())
+    }
+
+    /// Returns the last move played, if any.
+    #[must_use]
+    pub fn last_move(&self) -> Option<Move<SIDE_LENGTH>> {
+        self.last_move.as_ref().map(ArchivedMove::to_move)
+    }
+
+    /// Returns the player with a stone at `coord`, if any.
+    #[must_use]
+    pub fn player_at(&self, coord: Coord) -> Player {
+        (&self.cells[coord.row][coord.col]).into()
+    }
+
+    /// The Zobrist hash of the position.
+    #[must_use]
+    pub const fn zobrist_key(&self) -> u64 {
+        self.key.to_native()
+    }
+
+    /// The number of stones in a row needed to win.
+    #[must_use]
+    pub fn win_length(&self) -> usize {
+        usize::from(self.win_length)
+    }
+
+    /// Copies the archived board out into a [`Board`].
+    #[must_use]
+    pub fn to_board(&self) -> Board<SIDE_LENGTH> {
+        let mut board = Board::new();
+        for (cell, archived) in board
+            .cells
+            .iter_mut()
+            .flatten()
+            .zip(self.cells.iter().flatten())
+        {
+            *cell = archived.into();
+        }
+        board.last_move = self.last_move();
+        board.ply = self.ply.to_native();
+        board.to_move = self.turn();
+        board.key = self.zobrist_key();
+        board.win_length = self.win_length;
+        board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rkyv::{rancor::Error, vec::ArchivedVec};
+
+    use crate::board::{ArchivedBoard, Board, Coord, Player};
+
+    #[test]
+    fn reads_boards_in_place() {
+        let mut boards = vec![Board::<9>::new()];
+        for mv in ["e5", "e6", "f5"] {
+            let next = boards.last().unwrap().with_move(mv.parse().unwrap());
+            boards.push(next);
+        }
+        let bytes = rkyv::to_bytes::<Error>(&boards).unwrap();
+        let archived = rkyv::access::<ArchivedVec<ArchivedBoard<9>>, Error>(&bytes).unwrap();
+        assert_eq!(archived.len(), 4);
+        for (archived, board) in archived.iter().zip(&boards) {
+            assert_eq!(archived.turn(), board.turn());
+            assert_eq!(archived.ply(), board.ply());
+            assert_eq!(archived.last_move(), board.last_move());
+            assert_eq!(archived.zobrist_key(), board.zobrist_key());
+            let copy = archived.to_board();
+            assert_eq!(copy, *board);
+            assert_eq!(copy.fen(), board.fen());
+        }
+        assert_eq!(archived[3].player_at(Coord::new(4, 5)), Player::X);
+        assert!(rkyv::access::<ArchivedVec<ArchivedBoard<9>>, Error>(&bytes[1..]).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! A fixed-capacity list of scored moves, for ordering moves in a search
+//! without allocating.
+
+use super::{Board, Move};
+
+/// Up to one move per cell, each with a score, stored inline.
+///
+/// Moves are usually tried best first with [`MoveList::pick_best`], which
+/// does one step of a selection sort at a time, so that a search cut off
+/// after a few moves never sorts the rest.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MoveList<const SIDE_LENGTH: usize> {
+    moves: [[Move<SIDE_LENGTH>; SIDE_LENGTH]; SIDE_LENGTH],
+    scores: [[i32; SIDE_LENGTH]; SIDE_LENGTH],
+    len: usize,
+}
+
+impl<const SIDE_LENGTH: usize> Default for MoveList<SIDE_LENGTH> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIDE_LENGTH: usize> MoveList<SIDE_LENGTH> {
+    /// Creates an empty list.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            moves: [[Move::null(); SIDE_LENGTH]; SIDE_LENGTH],
+            scores: [[0; SIDE_LENGTH]; SIDE_LENGTH],
+            len: 0,
+        }
+    }
+
+    /// Lists the legal moves in `board`, in index order, each scored by
+    /// `score`.
+    #[must_use]
+    pub fn scored(
+        board: &Board<SIDE_LENGTH>,
+        mut score: impl FnMut(Move<SIDE_LENGTH>) -> i32,
+    ) -> Self {
+        let mut list = Self::new();
+        board.generate_moves(|mv| {
+            list.push(mv, score(mv));
+            false
+        });
+        list
+    }
+
+    /// The number of moves in the list.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the list is empty.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds `mv` with the score `score` to the end of the list.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the list already holds a move for every cell.
+    pub const fn push(&mut self, mv: Move<SIDE_LENGTH>, score: i32) {
+        assert!(self.len < SIDE_LENGTH * SIDE_LENGTH, "MoveList is full");
+        let (row, col) = (self.len / SIDE_LENGTH, self.len % SIDE_LENGTH);
+        self.moves[row][col] = mv;
+        self.scores[row][col] = score;
+        self.len += 1;
+    }
+
+    /// Removes every move.
+    pub const fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// The move at `index` and its score, or `None` if the list is shorter.
+    #[must_use]
+    pub const fn get(&self, index: usize) -> Option<(Move<SIDE_LENGTH>, i32)> {
+        if index >= self.len {
+            return None;
+        }
+        let (row, col) = (index / SIDE_LENGTH, index % SIDE_LENGTH);
+        Some((self.moves[row][col], self.scores[row][col]))
+    }
+
+    /// Moves the best-scored of the moves from `index` onwards to `index`,
+    /// and returns it with its score, or `None` if the list is no longer.
+    /// Of equal scores, the earliest is chosen.
+    ///
+    /// Calling this with `0`, `1`, `2`, and so on yields the moves best
+    /// first.
+    pub fn pick_best(&mut self, index: usize) -> Option<(Move<SIDE_LENGTH>, i32)> {
+        let (mut best, mut best_score) = (index, self.get(index)?.1);
+        for i in index + 1..self.len {
+            let score = self.scores[i / SIDE_LENGTH][i % SIDE_LENGTH];
+            if score > best_score {
+                (best, best_score) = (i, score);
+            }
+        }
+        self.swap(index, best);
+        self.get(index)
+    }
+
+    /// Sorts the whole list, best score first.
+    pub fn sort(&mut self) {
+        for index in 0..self.len {
+            self.pick_best(index);
+        }
+    }
+
+    /// Returns an iterator over the moves and their scores, in list order.
+    pub fn iter(&self) -> impl Iterator<Item = (Move<SIDE_LENGTH>, i32)> + '_ {
+        (0..self.len).filter_map(|index| self.get(index))
+    }
+
+    /// Returns an iterator over the moves, in list order.
+    pub fn moves(&self) -> impl Iterator<Item = Move<SIDE_LENGTH>> + '_ {
+        self.iter().map(|(mv, _)| mv)
+    }
+
+    const fn swap(&mut self, a: usize, b: usize) {
+        let (a_row, a_col) = (a / SIDE_LENGTH, a % SIDE_LENGTH);
+        let (b_row, b_col) = (b / SIDE_LENGTH, b % SIDE_LENGTH);
+        let (mv, score) = (self.moves[a_row][a_col], self.scores[a_row][a_col]);
+        self.moves[a_row][a_col] = self.moves[b_row][b_col];
+        self.scores[a_row][a_col] = self.scores[b_row][b_col];
+        self.moves[b_row][b_col] = mv;
+        self.scores[b_row][b_col] = score;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MoveList;
+    use crate::board::{Board, Move};
+
+    #[test]
+    fn picks_moves_best_first() {
+        let board = Board::<3>::new().with_move("b2".parse().unwrap());
+        // score each move by its distance from the top-left corner.
+        let mut list = MoveList::scored(&board, |mv| {
+            let coord = mv.coord();
+            i32::try_from(coord.row + coord.col).unwrap()
+        });
+        assert_eq!(list.len(), 8);
+        assert_eq!(list.pick_best(0), Some(("c3".parse().unwrap(), 4)));
+        assert_eq!(list.pick_best(1).map(|(_, score)| score), Some(3));
+        list.sort();
+        let scores = list.iter().map(|(_, score)| score).collect::<Vec<_>>();
+        assert_eq!(scores, [4, 3, 3, 2, 2, 1, 1, 0]);
+        assert_eq!(list.moves().last(), "a1".parse::<Move<3>>().ok());
+        assert_eq!(list.pick_best(8), None);
+
+        list.clear();
+        assert!(list.is_empty());
+        for _ in 0..9 {
+            list.push(Move::null(), 0);
+        }
+        assert_eq!(list.len(), 9);
+    }
+
+    #[test]
+    #[should_panic = "full"]
+    fn rejects_moves_past_capacity() {
+        let mut list = MoveList::<3>::new();
+        for _ in 0..10 {
+            list.push(Move::null(), 0);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! A board that keeps a list of its empty cells, for random playouts.
+
+use super::{Board, Move, Player};
+
+/// A board together with a list of its empty cells, so that a uniformly
+/// random empty cell can be chosen in constant time however full the board
+/// is.
+///
+/// The list is a permutation of the cells, with the empty ones first, and
+/// its inverse, so that playing a move swaps its cell out of the empty part
+/// in constant time too.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Playout<const SIDE_LENGTH: usize> {
+    board: Board<SIDE_LENGTH>,
+    /// The cells, indexed by slot: the empty cells occupy the first `empty`
+    /// slots.
+    cells: [[u16; SIDE_LENGTH]; SIDE_LENGTH],
+    /// The slot of each cell, indexed by row and column.
+    slots: [[u16; SIDE_LENGTH]; SIDE_LENGTH],
+    empty: usize,
+}
+
+impl<const SIDE_LENGTH: usize> Playout<SIDE_LENGTH> {
+    /// Starts a playout from `board`, listing its empty cells.
+    #[must_use]
+    pub fn new(board: Board<SIDE_LENGTH>) -> Self {
+        #![allow(clippy::cast_possible_truncation)]
+        let mut playout = Self {
+            board,
+            cells: [[0; SIDE_LENGTH]; SIDE_LENGTH],
+            slots: [[0; SIDE_LENGTH]; SIDE_LENGTH],
+            empty: 0,
+        };
+        let mut filled = SIDE_LENGTH * SIDE_LENGTH;
+        for index in 0..SIDE_LENGTH * SIDE_LENGTH {
+            let slot = if board.cells[index / SIDE_LENGTH][index % SIDE_LENGTH] == Player::None {
+                playout.empty += 1;
+                playout.empty - 1
+            } else {
+                filled -= 1;
+                filled
+            };
+            playout.set(slot, index);
+        }
+        playout
+    }
+
+    /// The current position.
+    #[must_use]
+    pub const fn board(&self) -> &Board<SIDE_LENGTH> {
+        &self.board
+    }
+
+    /// The current position, ending the playout.
+    #[must_use]
+    pub const fn into_board(self) -> Board<SIDE_LENGTH> {
+        self.board
+    }
+
+    /// The number of empty cells.
+    #[must_use]
+    pub const fn empty_cells(&self) -> usize {
+        self.empty
+    }
+
+    /// The empty cells, in no particular order.
+    pub fn moves(&self) -> impl Iterator<Item = Move<SIDE_LENGTH>> + '_ {
+        (0..self.empty).map(|slot| Move::from_index(self.cell(slot)))
+    }
+
+    /// Places a stone on `mv` for the side to move, as [`Board::place`]
+    /// does, even if the game is over.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cell is not empty.
+    pub fn make_move(&mut self, mv: Move<SIDE_LENGTH>) {
+        let slot = self.slots[mv.index() / SIDE_LENGTH][mv.index() % SIDE_LENGTH];
+        let slot = usize::from(slot);
+        assert!(slot < self.empty, "Cannot play {mv} on an occupied cell");
+        self.board.place(mv);
+        self.empty -= 1;
+        let last = usize::from(self.cell(self.empty));
+        self.set(slot, last);
+        self.set(self.empty, mv.index());
+    }
+
+    /// Plays a uniformly random move on an empty cell, in constant time, and
+    /// returns it, or `None` if the board is full.
+    ///
+    /// `rng(lo, hi)` must return a value in `lo..hi`, as for
+    /// [`Board::make_random_move`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rng` returns an out-of-range value.
+    pub fn make_random_move(
+        &mut self,
+        mut rng: impl FnMut(usize, usize) -> usize,
+    ) -> Option<Move<SIDE_LENGTH>> {
+        if self.empty == 0 {
+            return None;
+        }
+        let slot = rng(0, self.empty);
+        assert!(slot < self.empty, "Index out of range");
+        let mv = Move::from_index(self.cell(slot));
+        self.make_move(mv);
+        Some(mv)
+    }
+
+    /// Plays random moves until the game ends, returning the winner, or
+    /// [`Player::None`] for a draw.
+    pub fn play_out(&mut self, mut rng: impl FnMut(usize, usize) -> usize) -> Player {
+        loop {
+            if let Some(winner) = self.board.outcome() {
+                return winner;
+            }
+            if self.make_random_move(&mut rng).is_none() {
+                return Player::None;
+            }
+        }
+    }
+
+    const fn cell(&self, slot: usize) -> u16 {
+        self.cells[slot / SIDE_LENGTH][slot % SIDE_LENGTH]
+    }
+
+    /// Puts the cell `index` in `slot`.
+    const fn set(&mut self, slot: usize, index: usize) {
+        #![allow(clippy::cast_possible_truncation)]
+        self.cells[slot / SIDE_LENGTH][slot % SIDE_LENGTH] = index as u16;
+        self.slots[index / SIDE_LENGTH][index % SIDE_LENGTH] = slot as u16;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Playout;
+    use crate::board::{Board, Move, Player};
+
+    #[test]
+    fn tracks_empty_cells() {
+        let start = Board::<5>::new()
+            .apply_moves(&["c3", "a1"].map(|mv| mv.parse().unwrap()))
+            .unwrap();
+        let mut playout = Playout::new(start);
+        assert_eq!(playout.empty_cells(), 23);
+        playout.make_move("e5".parse().unwrap());
+        let mut rng = crate::test_util::test_rng(0x2545_F491_4F6C_DD1D);
+        while let Some(mv) = playout.make_random_move(&mut rng) {
+            let mut expected = Vec::new();
+            playout.board().generate_moves(|mv| {
+                expected.push(mv);
+                false
+            });
+            let mut moves = playout.moves().collect::<Vec<Move<5>>>();
+            moves.sort_by_key(Move::index);
+            assert_eq!(moves, expected, "after {mv}");
+        }
+        assert_eq!(playout.into_board().ply(), 25);
+
+        let mut playout = Playout::new(Board::<9>::new());
+        let winner = playout.play_out(&mut rng);
+        assert_eq!(playout.board().outcome(), Some(winner));
+        assert_ne!(winner, Player::None);
+    }
+}
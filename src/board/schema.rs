@@ -0,0 +1,237 @@
+//! A structured model of a board, for frontends that would rather not parse
+//! FEN strings.
+//!
+//! As JSON, the model of a 15x15 board after two moves looks like this:
+//!
+//! ```json
+//! {
+//!   "size": 15,
+//!   "win_length": 5,
+//!   "turn": "x",
+//!   "ply": 2,
+//!   "stones": [
+//!     {"row": 7, "col": 7, "color": "x"},
+//!     {"row": 7, "col": 8, "color": "o"}
+//!   ],
+//!   "last_move": {"row": 7, "col": 8}
+//! }
+//! ```
+//!
+//! Stones are listed in row-major order.
+
+use serde::{Deserialize, Serialize};
+
+use super::{Board, Coord, Move, Player};
+
+/// A stone on the board.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct Stone {
+    /// The row the stone is in.
+    pub row: usize,
+    /// The column the stone is in.
+    pub col: usize,
+    /// The owner of the stone, `'x'` or `'o'`.
+    pub color: char,
+}
+
+/// A board in the structured form described in the [module docs](self).
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct BoardSchema {
+    /// The side length of the board.
+    pub size: usize,
+    /// The number of stones in a row needed to win, or `None` for the
+    /// [default](Board::with_win_length) of the board size.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub win_length: Option<usize>,
+    /// The side to move, `'x'` or `'o'`.
+    pub turn: char,
+    /// The number of moves played.
+    pub ply: usize,
+    /// The stones on the board.
+    pub stones: Vec<Stone>,
+    /// The cell of the last move played, if known.
+    #[serde(default)]
+    pub last_move: Option<Coord>,
+    /// Whether this is a [setup position](Board::is_setup), in which the
+    /// side to move need not follow from the ply.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub setup: bool,
+}
+
+const fn player_char(player: Player) -> char {
+    match player {
+        Player::X => 'x',
+        Player::O => 'o',
+        Player::None => '.',
+    }
+}
+
+const fn char_player(c: char) -> Result<Player, &'static str> {
+    match c {
+        'x' => Ok(Player::X),
+        'o' => Ok(Player::O),
+        _ => Err("Invalid colour in board schema"),
+    }
+}
+
+impl<const SIDE_LENGTH: usize> Board<SIDE_LENGTH> {
+    /// Describes the board in the structured form of [`BoardSchema`].
+    #[must_use]
+    pub fn to_schema(&self) -> BoardSchema {
+        BoardSchema {
+            size: SIDE_LENGTH,
+            win_length: Some(self.win_length()),
+            turn: player_char(self.turn()),
+            ply: self.ply(),
+            stones: self
+                .cells()
+                .filter(|&(_, player)| player != Player::None)
+                .map(|(Coord { row, col }, player)| Stone {
+                    row,
+                    col,
+                    color: player_char(player),
+                })
+                .collect(),
+            last_move: self.last_move.map(|mv| mv.coord()),
+            setup: self.is_setup(),
+        }
+    }
+
+    /// Builds a board from its structured form.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the schema is for a different board size, places
+    /// two stones on one cell or a stone off the board, has an invalid
+    /// colour or win length, has a last move that is not a stone of the side
+    /// that just moved, or has a side to move that disagrees with the ply
+    /// without being marked as a setup position, as for FEN strings.
+    pub fn from_schema(schema: &BoardSchema) -> Result<Self, &'static str> {
+        #![allow(clippy::cast_possible_truncation)]
+        if schema.size != SIDE_LENGTH {
+            return Err("Board schema is for a different board size");
+        }
+        let win_length = schema
+            .win_length
+            .unwrap_or(Self::DEFAULT_WIN_LENGTH as usize);
+        if !(2..=SIDE_LENGTH).contains(&win_length) {
+            return Err("Invalid win length in board schema");
+        }
+        let turn = char_player(schema.turn)?;
+        let Ok(ply) = u16::try_from(schema.ply) else {
+            return Err("Invalid ply in board schema");
+        };
+        if !schema.setup && (ply % 2 == 0) != (turn == Player::X) {
+            return Err("Turn does not match ply in board schema");
+        }
+        let mut board = Self::new();
+        for stone in &schema.stones {
+            if stone.row >= SIDE_LENGTH || stone.col >= SIDE_LENGTH {
+                return Err("Stone off the board in board schema");
+            }
+            let cell = &mut board.cells[stone.row][stone.col];
+            if *cell != Player::None {
+                return Err("Two stones on one cell in board schema");
+            }
+            *cell = char_player(stone.color)?;
+        }
+        if let Some(Coord { row, col }) = schema.last_move {
+            if row >= SIDE_LENGTH || col >= SIDE_LENGTH || board.cells[row][col] != -turn {
+                return Err("Last move is not a stone of the side that just moved in board schema");
+            }
+            board.last_move = Some(Move {
+                index: (row * SIDE_LENGTH + col) as u16,
+            });
+        }
+        board.ply = ply;
+        board.to_move = turn;
+        board.win_length = win_length as u8;
+        board.recompute_key();
+        Ok(board)
+    }
+
+    /// Describes the board as JSON, in the structured form of [`BoardSchema`].
+    ///
+    /// # Panics
+    ///
+    /// Never panics in practice: the schema always serializes.
+    #[cfg(feature = "json")]
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.to_schema()).expect("board schemas always serialize")
+    }
+
+    /// Builds a board from JSON in the structured form of [`BoardSchema`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON is malformed, or does not describe a
+    /// valid board as for [`Board::from_schema`].
+    #[cfg(feature = "json")]
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        let schema = serde_json::from_str::<BoardSchema>(json)?;
+        Self::from_schema(&schema).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BoardSchema, Stone};
+    use crate::board::{Board, Coord, Move};
+
+    #[test]
+    fn schema_round_trip() {
+        let moves = ["h8", "i8", "h9"].map(|mv| mv.parse::<Move<15>>().unwrap());
+        let board = Board::<15>::new().apply_moves(&moves).unwrap();
+        let schema = board.to_schema();
+        assert_eq!(schema.size, 15);
+        assert_eq!(schema.turn, 'o');
+        assert_eq!(schema.ply, 3);
+        assert_eq!(schema.stones.len(), 3);
+        assert_eq!(schema.last_move, Some(moves[2].coord()));
+        let rebuilt = Board::<15>::from_schema(&schema).unwrap();
+        assert_eq!(rebuilt, board);
+        assert_eq!(rebuilt.fen(), board.fen());
+        assert_eq!(rebuilt.zobrist_key(), board.zobrist_key());
+        assert_eq!(rebuilt.last_move(), board.last_move());
+        assert!(Board::<9>::from_schema(&schema).is_err());
+
+        let mut doubled = schema.clone();
+        doubled.stones.push(schema.stones[0]);
+        assert!(Board::<15>::from_schema(&doubled).is_err());
+        let bad = BoardSchema {
+            stones: vec![Stone {
+                row: 0,
+                col: 0,
+                color: 'z',
+            }],
+            last_move: Some(Coord::new(1, 1)),
+            ..schema
+        };
+        assert!(Board::<15>::from_schema(&bad).is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_round_trip() {
+        let board = Board::<3>::new().with_move("b2".parse().unwrap());
+        let json = board.to_json();
+        assert_eq!(
+            json,
+            r#"{"size":3,"win_length":3,"turn":"o","ply":1,"stones":[{"row":1,"col":1,"color":"x"}],"last_move":{"row":1,"col":1}}"#
+        );
+        assert_eq!(Board::<3>::from_json(&json).unwrap(), board);
+        let bare = Board::<3>::from_json(r#"{"size":3,"turn":"x","ply":0,"stones":[]}"#).unwrap();
+        assert_eq!(bare.win_length(), 3);
+        assert!(Board::<3>::from_json(
+            r#"{"size":3,"win_length":4,"turn":"x","ply":0,"stones":[]}"#
+        )
+        .is_err());
+        assert!(Board::<3>::from_json(r#"{"size":3,"turn":"o","ply":0,"stones":[]}"#).is_err());
+        let setup =
+            Board::<3>::from_json(r#"{"size":3,"turn":"o","ply":0,"stones":[],"setup":true}"#)
+                .unwrap();
+        assert!(setup.is_setup());
+        assert!(setup.to_json().ends_with(r#""setup":true}"#));
+    }
+}
@@ -0,0 +1,227 @@
+//! Serde support for boards and moves.
+//!
+//! Human-readable formats such as JSON get the familiar text forms: a move is
+//! its notation, like `"E5"`, and a board is its [`BoardSchema`]. A bare
+//! FEN string is still read as a board. Binary formats
+//! such as bincode get compact forms instead: a move is its `u16` index, and
+//! a board packs its cells four to a byte, so a 15x15 board takes about 70
+//! bytes rather than the 240 of its FEN string.
+
+use std::fmt;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{Board, BoardSchema, Move, Player};
+
+impl<const SIDE_LENGTH: usize> Serialize for Move<SIDE_LENGTH> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_u16(self.index)
+        }
+    }
+}
+
+impl<'de, const SIDE_LENGTH: usize> Deserialize<'de> for Move<SIDE_LENGTH> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            String::deserialize(deserializer)?
+                .parse()
+                .map_err(de::Error::custom)
+        } else {
+            let index = u16::deserialize(deserializer)?;
+            if usize::from(index) >= SIDE_LENGTH * SIDE_LENGTH {
+                return Err(de::Error::custom("move index out of range"));
+            }
+            Ok(Self { index })
+        }
+    }
+}
+
+/// The binary form of a board.
+#[derive(Serialize, Deserialize)]
+struct PackedBoard {
+    /// Two bits per cell, in row-major order from the low bits of each byte:
+    /// 0 for empty, 1 for X, and 2 for O.
+    cells: Vec<u8>,
+    ply: u16,
+    x_to_move: bool,
+    last_move: Option<u16>,
+    win_length: u8,
+}
+
+impl<const SIDE_LENGTH: usize> Serialize for Board<SIDE_LENGTH> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return self.to_schema().serialize(serializer);
+        }
+        let mut cells = vec![0; (SIDE_LENGTH * SIDE_LENGTH).div_ceil(4)];
+        for (i, &cell) in self.cells.iter().flatten().enumerate() {
+            let bits = match cell {
+                Player::None => 0,
+                Player::X => 1,
+                Player::O => 2,
+            };
+            cells[i / 4] |= bits << (2 * (i % 4));
+        }
+        PackedBoard {
+            cells,
+            ply: self.ply,
+            x_to_move: self.to_move == Player::X,
+            last_move: self.last_move.map(|mv| mv.index),
+            win_length: self.win_length,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, const SIDE_LENGTH: usize> Deserialize<'de> for Board<SIDE_LENGTH> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            return deserializer.deserialize_any(TextBoard);
+        }
+        let packed = PackedBoard::deserialize(deserializer)?;
+        if packed.cells.len() != (SIDE_LENGTH * SIDE_LENGTH).div_ceil(4) {
+            return Err(de::Error::custom("board is for a different board size"));
+        }
+        let mut board = Self::new();
+        for (i, cell) in board.cells.iter_mut().flatten().enumerate() {
+            *cell = match (packed.cells[i / 4] >> (2 * (i % 4))) & 0b11 {
+                0 => Player::None,
+                1 => Player::X,
+                2 => Player::O,
+                _ => return Err(de::Error::custom("invalid cell in board")),
+            };
+        }
+        if !(2..=SIDE_LENGTH).contains(&usize::from(packed.win_length)) {
+            return Err(de::Error::custom("invalid win length for board"));
+        }
+        board.ply = packed.ply;
+        board.to_move = if packed.x_to_move {
+            Player::X
+        } else {
+            Player::O
+        };
+        board.win_length = packed.win_length;
+        if let Some(index) = packed.last_move {
+            if usize::from(index) >= SIDE_LENGTH * SIDE_LENGTH {
+                return Err(de::Error::custom("move index out of range"));
+            }
+            let mv = Move { index };
+            if board.player_at(mv.coord()) != -board.to_move {
+                return Err(de::Error::custom(
+                    "last move is not a stone of the side that just moved",
+                ));
+            }
+            board.last_move = Some(mv);
+        }
+        board.recompute_key();
+        Ok(board)
+    }
+}
+
+/// Reads the human-readable form of a board: its schema, or a FEN string.
+struct TextBoard<const SIDE_LENGTH: usize>;
+
+impl<'de, const SIDE_LENGTH: usize> de::Visitor<'de> for TextBoard<SIDE_LENGTH> {
+    type Value = Board<SIDE_LENGTH>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a board schema or FEN string")
+    }
+
+    fn visit_str<E: de::Error>(self, fen: &str) -> Result<Self::Value, E> {
+        fen.parse().map_err(E::custom)
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        let schema = BoardSchema::deserialize(de::value::MapAccessDeserializer::new(map))?;
+        Board::from_schema(&schema).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::{Board, Move};
+
+    fn board() -> Board<15> {
+        let moves = ["h8", "h9", "i9", "g7", "j10"].map(|mv| mv.parse::<Move<15>>().unwrap());
+        Board::new().apply_moves(&moves).unwrap().with_win_length(6)
+    }
+
+    #[test]
+    fn bincode_round_trip() {
+        let mv = "h8".parse::<Move<15>>().unwrap();
+        let bytes = bincode::serialize(&mv).unwrap();
+        assert_eq!(bytes.len(), 2);
+        assert_eq!(bincode::deserialize::<Move<15>>(&bytes).unwrap(), mv);
+        assert!(bincode::deserialize::<Move<9>>(&bytes).is_err());
+
+        let board = board();
+        let bytes = bincode::serialize(&board).unwrap();
+        // 57 bytes of cells with their length, then the ply, side to move,
+        // last move, and win length.
+        assert_eq!(bytes.len(), 8 + 57 + 2 + 1 + 3 + 1);
+        let decoded = bincode::deserialize::<Board<15>>(&bytes).unwrap();
+        assert_eq!(decoded, board);
+        assert_eq!(decoded.fen(), board.fen());
+        assert_eq!(decoded.zobrist_key(), board.zobrist_key());
+        assert_eq!(decoded.last_move(), board.last_move());
+        assert_eq!(decoded.win_length(), 6);
+        assert!(bincode::deserialize::<Board<9>>(&bytes).is_err());
+
+        let labelled = (board, 0.25);
+        let bytes = bincode::serialize(&labelled).unwrap();
+        assert_eq!(bytes.len(), 72 + 8);
+        let decoded = bincode::deserialize::<(Board<15>, f64)>(&bytes).unwrap();
+        assert_eq!(decoded.0, board);
+        assert!((decoded.1 - 0.25).abs() < f64::EPSILON);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_uses_text_forms() {
+        let board = board();
+        let json = serde_json::to_string(&(board, board.last_move().unwrap())).unwrap();
+        assert_eq!(json, format!(r#"[{},"J10"]"#, board.to_json()));
+        let (decoded, mv) = serde_json::from_str::<(Board<15>, Move<15>)>(&json).unwrap();
+        assert_eq!(decoded, board);
+        assert_eq!(decoded.win_length(), 6);
+        assert_eq!(Some(mv), board.last_move());
+
+        let fen = serde_json::to_string(&board.fen()).unwrap();
+        let decoded = serde_json::from_str::<Board<15>>(&fen).unwrap();
+        assert_eq!(decoded, board);
+        assert_eq!(decoded.win_length(), 6);
+    }
+
+    #[test]
+    fn win_length_survives_every_format() {
+        let board = Board::<9>::new().with_win_length(4);
+        let bytes = bincode::serialize(&board).unwrap();
+        assert_eq!(
+            bincode::deserialize::<Board<9>>(&bytes)
+                .unwrap()
+                .win_length(),
+            4
+        );
+        #[cfg(feature = "json")]
+        {
+            let json = serde_json::to_string(&board).unwrap();
+            assert_eq!(
+                serde_json::from_str::<Board<9>>(&json)
+                    .unwrap()
+                    .win_length(),
+                4
+            );
+        }
+        // the last byte is the win length, which may not exceed the board,
+        // just as for `with_win_length` and board schemas.
+        let mut bytes = bytes;
+        *bytes.last_mut().unwrap() = 10;
+        assert!(bincode::deserialize::<Board<9>>(&bytes).is_err());
+        *bytes.last_mut().unwrap() = 9;
+        assert!(bincode::deserialize::<Board<9>>(&bytes).is_ok());
+    }
+}
@@ -0,0 +1,496 @@
+//! Opening books in a compact binary format.
+//!
+//! A book is a sorted list of `(key, move, weight)` entries, where `key` is the
+//! Zobrist key of a position and `weight` says how strongly the move is
+//! recommended there, in the spirit of Polyglot books for chess.
+//!
+//! On disk, a book is an 8-byte header (the magic bytes `GMKB`, a big-endian
+//! `u16` format version, and a big-endian `u16` side length) followed by
+//! 12-byte entries: a big-endian `u64` key, `u16` move index, and `u16` weight.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    board::{Board, Move, Player},
+    game::Game,
+    symmetry::Symmetry,
+};
+
+pub(crate) const MAGIC: &[u8; 4] = b"GMKB";
+/// Version 2 is the first with stable Zobrist keys; see [`ZOBRIST_VERSION`].
+///
+/// [`ZOBRIST_VERSION`]: crate::board::ZOBRIST_VERSION
+pub(crate) const VERSION: u16 = 2;
+
+/// A single book recommendation.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    rkyv(derive(Debug))
+)]
+pub struct BookEntry {
+    /// The Zobrist key of the position.
+    pub key: u64,
+    /// The flat index of the recommended move.
+    pub mv: u16,
+    /// How strongly the move is recommended, relative to the other moves in the position.
+    pub weight: u16,
+}
+
+impl BookEntry {
+    pub(crate) const SIZE: usize = 12;
+
+    fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut out = [0; Self::SIZE];
+        out[..8].copy_from_slice(&self.key.to_be_bytes());
+        out[8..10].copy_from_slice(&self.mv.to_be_bytes());
+        out[10..].copy_from_slice(&self.weight.to_be_bytes());
+        out
+    }
+
+    pub(crate) const fn from_bytes(bytes: [u8; Self::SIZE]) -> Self {
+        let [k0, k1, k2, k3, k4, k5, k6, k7, m0, m1, w0, w1] = bytes;
+        Self {
+            key: u64::from_be_bytes([k0, k1, k2, k3, k4, k5, k6, k7]),
+            mv: u16::from_be_bytes([m0, m1]),
+            weight: u16::from_be_bytes([w0, w1]),
+        }
+    }
+}
+
+/// An opening book for boards of size `SIDE_LENGTH`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Book<const SIDE_LENGTH: usize> {
+    entries: Vec<BookEntry>,
+}
+
+impl<const SIDE_LENGTH: usize> Book<SIDE_LENGTH> {
+    /// Creates a book from a list of entries, in any order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an entry's move is off the board.
+    #[must_use]
+    pub fn from_entries(mut entries: Vec<BookEntry>) -> Self {
+        assert!(
+            entries
+                .iter()
+                .all(|e| usize::from(e.mv) < SIDE_LENGTH * SIDE_LENGTH),
+            "Book entry move out of range"
+        );
+        entries.sort_unstable_by_key(|e| (e.key, e.mv));
+        Self { entries }
+    }
+
+    /// All entries in the book, sorted by key and then move.
+    #[must_use]
+    pub fn entries(&self) -> &[BookEntry] {
+        &self.entries
+    }
+
+    /// The number of entries in the book.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the book has no entries.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries stored under exactly `key`.
+    #[must_use]
+    pub fn entries_for_key(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|e| e.key < key);
+        let end = self.entries.partition_point(|e| e.key <= key);
+        &self.entries[start..end]
+    }
+
+    /// Looks up the moves recommended in `board`, with their weights.
+    ///
+    /// Lookup is symmetry-aware: entries stored for any rotation or reflection of
+    /// the position are found, and their moves are mapped back onto `board`
+    /// through the inverse transform. Books keyed by plain Zobrist keys, as
+    /// from other tools or from games that only ever reached one image of a
+    /// position, hit every image of it, as do the canonical keys of a
+    /// [`BookBuilder`]. A move found under several images keeps its largest
+    /// weight. Moves are returned in descending order of weight.
+    #[must_use]
+    pub fn probe(&self, board: &Board<SIDE_LENGTH>) -> Vec<(Move<SIDE_LENGTH>, u16)> {
+        probe_with(board, |key| self.entries_for_key(key).iter().copied())
+    }
+
+    /// Picks a book move for `board` at random, in proportion to the entry weights.
+    ///
+    /// `rng(lo, hi)` must return a value in `lo..hi`, as for [`Board::make_random_move`].
+    /// Returns `None` if the position is not in the book or all its weights are zero.
+    pub fn pick(
+        &self,
+        board: &Board<SIDE_LENGTH>,
+        mut rng: impl FnMut(usize, usize) -> usize,
+    ) -> Option<Move<SIDE_LENGTH>> {
+        let moves = self.probe(board);
+        let total = moves.iter().map(|&(_, w)| usize::from(w)).sum::<usize>();
+        if total == 0 {
+            return None;
+        }
+        let mut target = rng(0, total);
+        for (mv, weight) in moves {
+            if target < usize::from(weight) {
+                return Some(mv);
+            }
+            target -= usize::from(weight);
+        }
+        None
+    }
+
+    /// Writes the book in the binary format described in the [module docs](self).
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from the underlying writer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `SIDE_LENGTH` does not fit in a `u16`.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_be_bytes())?;
+        writer.write_all(&u16::try_from(SIDE_LENGTH).unwrap().to_be_bytes())?;
+        for entry in &self.entries {
+            writer.write_all(&entry.to_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads a book in the binary format described in the [module docs](self).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, or if the data is not a book for
+    /// boards of size `SIDE_LENGTH`.
+    pub fn read_from(mut reader: impl Read) -> io::Result<Self> {
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid_data("not a gomokugen book"));
+        }
+        if u16::from_be_bytes([header[4], header[5]]) != VERSION {
+            return Err(invalid_data("unsupported book version"));
+        }
+        if usize::from(u16::from_be_bytes([header[6], header[7]])) != SIDE_LENGTH {
+            return Err(invalid_data("book is for a different board size"));
+        }
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        if data.len() % BookEntry::SIZE != 0 {
+            return Err(invalid_data("truncated book entry"));
+        }
+        let mut entries = Vec::with_capacity(data.len() / BookEntry::SIZE);
+        for chunk in data.chunks_exact(BookEntry::SIZE) {
+            let mut bytes = [0; BookEntry::SIZE];
+            bytes.copy_from_slice(chunk);
+            let entry = BookEntry::from_bytes(bytes);
+            if usize::from(entry.mv) >= SIDE_LENGTH * SIDE_LENGTH {
+                return Err(invalid_data("book entry move out of range"));
+            }
+            entries.push(entry);
+        }
+        entries.sort_unstable_by_key(|e| (e.key, e.mv));
+        Ok(Self { entries })
+    }
+
+    /// Saves the book to the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created or written.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Loads a book from the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a valid book.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+}
+
+/// Looks up the moves recommended in `board` like [`Book::probe`], finding
+/// the entries stored under a key with `lookup`.
+pub(crate) fn probe_with<const SIDE_LENGTH: usize, I: IntoIterator<Item = BookEntry>>(
+    board: &Board<SIDE_LENGTH>,
+    mut lookup: impl FnMut(u64) -> I,
+) -> Vec<(Move<SIDE_LENGTH>, u16)> {
+    let mut out: Vec<(Move<SIDE_LENGTH>, u16)> = Vec::new();
+    for (key, symmetry) in board.symmetric_keys().into_iter().zip(Symmetry::ALL) {
+        for entry in lookup(key) {
+            let mv = Move::<SIDE_LENGTH>::from_index(entry.mv).transform(symmetry.inverse());
+            match out.iter_mut().find(|(m, _)| *m == mv) {
+                Some((_, weight)) => *weight = (*weight).max(entry.weight),
+                None => out.push((mv, entry.weight)),
+            }
+        }
+    }
+    out.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.index().cmp(&b.0.index())));
+    out
+}
+
+fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Builds a [`Book`] from a collection of games, such as those of a
+/// self-play [`Match`](crate::matches::Match) saved as a
+/// [game collection](crate::formats::collection).
+///
+/// Every move played in the first `max_ply` plies of a game is scored from the
+/// point of view of the player who made it: two points for a win, one for a
+/// draw, and none for a loss. Positions are gathered up to symmetry, so a
+/// move's weight counts the games that played it in any rotation or
+/// reflection of the position.
+#[derive(Clone, Debug)]
+pub struct BookBuilder<const SIDE_LENGTH: usize> {
+    max_ply: usize,
+    scores: HashMap<(u64, u16), u64>,
+}
+
+impl<const SIDE_LENGTH: usize> BookBuilder<SIDE_LENGTH> {
+    /// Creates a builder that records moves from the first `max_ply` plies of each game.
+    #[must_use]
+    pub fn new(max_ply: usize) -> Self {
+        Self {
+            max_ply,
+            scores: HashMap::new(),
+        }
+    }
+
+    /// Adds a game played from the empty board.
+    /// `winner` is `Player::None` for a drawn game.
+    pub fn add_game(&mut self, moves: &[Move<SIDE_LENGTH>], winner: Player) {
+        self.add_game_from(&Board::new(), moves, winner);
+    }
+
+    /// Adds a game played from `start`, such as an opening position. Plies
+    /// are counted from the empty board, so only the moves made before ply
+    /// `max_ply` are recorded.
+    pub fn add_game_from(
+        &mut self,
+        start: &Board<SIDE_LENGTH>,
+        moves: &[Move<SIDE_LENGTH>],
+        winner: Player,
+    ) {
+        let mut board = *start;
+        for &mv in moves {
+            if board.ply() >= self.max_ply {
+                break;
+            }
+            let points = match winner {
+                Player::None => 1,
+                p if p == board.turn() => 2,
+                _ => 0,
+            };
+            // store the move in the image of the position with the
+            // canonical key, which is where every image of it is stored. A
+            // position with symmetries of its own has several such images;
+            // taking the least of the move's images in them lets moves that
+            // are equal by symmetry share a score.
+            let keys = board.symmetric_keys();
+            let key = keys
+                .iter()
+                .copied()
+                .min()
+                .unwrap_or_else(|| board.zobrist_key());
+            #[allow(clippy::cast_possible_truncation)]
+            let index = keys
+                .into_iter()
+                .zip(Symmetry::ALL)
+                .filter(|&(image, _)| image == key)
+                .map(|(_, symmetry)| mv.transform(symmetry).index() as u16)
+                .min()
+                .unwrap_or_else(|| mv.index() as u16);
+            *self.scores.entry((key, index)).or_default() += points;
+            board.make_move(mv);
+        }
+    }
+
+    /// Adds a finished game, from its starting position. Unfinished games
+    /// are skipped, as they have no result to score.
+    pub fn add_record(&mut self, game: &Game<SIDE_LENGTH>) {
+        if let Some(winner) = game.winner() {
+            self.add_game_from(game.start(), game.moves(), winner);
+        }
+    }
+
+    /// Adds every game in `games`, each given as its moves and winner.
+    pub fn add_games<'a>(
+        &mut self,
+        games: impl IntoIterator<Item = (&'a [Move<SIDE_LENGTH>], Player)>,
+    ) {
+        for (moves, winner) in games {
+            self.add_game(moves, winner);
+        }
+    }
+
+    /// Produces the book. Weights are scaled down proportionally if any score
+    /// would overflow a `u16`; moves that never scored are left out.
+    #[must_use]
+    pub fn build(&self) -> Book<SIDE_LENGTH> {
+        let max = self.scores.values().copied().max().unwrap_or(0);
+        let scale = max.div_ceil(u64::from(u16::MAX)).max(1);
+        let entries = self
+            .scores
+            .iter()
+            .filter_map(|(&(key, mv), &score)| {
+                let weight = u16::try_from(score / scale).ok()?;
+                (weight > 0).then_some(BookEntry { key, mv, weight })
+            })
+            .collect();
+        Book::from_entries(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Book, BookBuilder, BookEntry};
+    use crate::{
+        board::{Board, Move, Player},
+        game::Game,
+        symmetry::Symmetry,
+    };
+
+    fn moves(list: &[&str]) -> Vec<Move<15>> {
+        list.iter().map(|mv| mv.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn builder_scores_results() {
+        let mut builder = BookBuilder::<15>::new(2);
+        builder.add_game(&moves(&["h8", "h9", "i9"]), Player::X);
+        builder.add_game(&moves(&["h8", "i9"]), Player::None);
+        let book = builder.build();
+        let start = Board::<15>::new();
+        assert_eq!(book.probe(&start), vec![(moves(&["h8"])[0], 3)]);
+        // the loss for O leaves h9 out of the book, and the third move is past max_ply.
+        let after = start.with_move(moves(&["h8"])[0]);
+        let probed = book.probe(&after);
+        assert_eq!(probed.len(), 4, "i9 and its symmetric images: {probed:?}");
+        assert!(probed.iter().all(|&(_, w)| w == 1));
+    }
+
+    #[test]
+    fn probe_is_symmetry_aware() {
+        let mut builder = BookBuilder::<15>::new(4);
+        builder.add_game(&moves(&["c3", "d4", "e5"]), Player::X);
+        let book = builder.build();
+        // the mirror image of the game's first two moves.
+        let mirrored = Board::<15>::new()
+            .apply_moves(&moves(&["m3", "l4"]))
+            .unwrap();
+        assert_eq!(book.probe(&mirrored), vec![(moves(&["k5"])[0], 2)]);
+        let mut always_zero = |_, _| 0;
+        assert_eq!(
+            book.pick(&mirrored, &mut always_zero),
+            Some(moves(&["k5"])[0])
+        );
+        assert_eq!(
+            book.pick(&mirrored.with_move(moves(&["k5"])[0]), always_zero),
+            None
+        );
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let book = Book::<15>::from_entries(vec![
+            BookEntry {
+                key: 42,
+                mv: 112,
+                weight: 7,
+            },
+            BookEntry {
+                key: 1,
+                mv: 0,
+                weight: 65535,
+            },
+        ]);
+        let mut bytes = Vec::new();
+        book.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 8 + 2 * 12);
+        assert_eq!(Book::<15>::read_from(bytes.as_slice()).unwrap(), book);
+        assert!(Book::<19>::read_from(bytes.as_slice()).is_err());
+        assert!(Book::<15>::read_from(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn builder_gathers_symmetric_games() {
+        let mut builder = BookBuilder::<15>::new(3);
+        // the same opening, and its mirror image, from a set-up start.
+        let start = Board::<15>::new().with_move(moves(&["h8"])[0]);
+        let mut game = Game::new(start);
+        for mv in moves(&["g7", "f6"]) {
+            game.play(mv);
+        }
+        game.resign(Player::O);
+        builder.add_record(&game);
+        builder.add_game_from(&start, &moves(&["i7", "j6"]), Player::X);
+        // unfinished games are left out.
+        builder.add_record(&Game::new(start));
+        let book = builder.build();
+        // both games' second moves share one entry.
+        assert_eq!(book.len(), 1);
+        let after = start.with_move(moves(&["g7"])[0]);
+        assert_eq!(book.probe(&after), vec![(moves(&["f6"])[0], 4)]);
+        let mirrored = start.with_move(moves(&["i7"])[0]);
+        assert_eq!(book.probe(&mirrored), vec![(moves(&["j6"])[0], 4)]);
+        // O's replies lost, so they are left out.
+        assert!(book.probe(&start).is_empty());
+    }
+
+    #[test]
+    fn symmetric_moves_share_a_score() {
+        // the empty board is its own image in every symmetry, so these
+        // openings are one move.
+        let mut builder = BookBuilder::<15>::new(1);
+        builder.add_game(&moves(&["a1"]), Player::X);
+        builder.add_game(&moves(&["o15"]), Player::X);
+        let book = builder.build();
+        assert_eq!(book.len(), 1);
+        let probed = book.probe(&Board::new());
+        assert_eq!(probed.len(), 4, "a1 and its images: {probed:?}");
+        assert!(probed.iter().all(|&(_, weight)| weight == 4));
+    }
+
+    #[test]
+    fn raw_keys_hit_every_image() {
+        // an asymmetric position, stored only under its own key.
+        let board = Board::<15>::new()
+            .apply_moves(&moves(&["c3", "d5", "f4"]))
+            .unwrap();
+        let reply = moves(&["e7"])[0];
+        #[allow(clippy::cast_possible_truncation)]
+        let book = Book::<15>::from_entries(vec![BookEntry {
+            key: board.zobrist_key(),
+            mv: reply.index() as u16,
+            weight: 9,
+        }]);
+        for symmetry in Symmetry::ALL {
+            assert_eq!(
+                book.probe(&board.transform(symmetry)),
+                vec![(reply.transform(symmetry), 9)]
+            );
+        }
+    }
+}
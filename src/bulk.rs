@@ -0,0 +1,153 @@
+//! Evaluating large files of positions in parallel, for labelling datasets.
+//!
+//! The input is read as a stream of FEN strings, one per line, in chunks, so
+//! files much larger than memory can be processed. Each chunk is evaluated on
+//! the rayon thread pool, and the results are written in input order as
+//! `fen<TAB>result` lines.
+
+use std::{
+    fmt::Display,
+    io::{self, BufRead, Write},
+};
+
+use rayon::prelude::*;
+
+use crate::{board::Board, engine::Evaluator};
+
+/// The number of lines read and evaluated at a time.
+const CHUNK_LINES: usize = 1 << 12;
+
+/// The number of positions in each batch given to an [`Evaluator`].
+const EVALUATOR_BATCH: usize = 256;
+
+/// Counts of the lines processed by a bulk evaluation.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BulkSummary {
+    /// The number of positions evaluated and written.
+    pub evaluated: u64,
+    /// The number of lines that were not valid FEN strings, which are skipped.
+    /// Blank lines are not counted.
+    pub invalid: u64,
+}
+
+/// Reads FEN strings from `reader`, one per line, and writes each with the
+/// result of `evaluate` to `writer`.
+///
+/// # Errors
+///
+/// Returns any error from reading the input or writing the output.
+pub fn evaluate_fens<const SIDE_LENGTH: usize, T: Display + Send>(
+    reader: impl BufRead,
+    writer: impl Write,
+    evaluate: impl Fn(&Board<SIDE_LENGTH>) -> T + Sync,
+) -> io::Result<BulkSummary> {
+    process(reader, writer, |boards| {
+        boards
+            .par_iter()
+            .map(|board| evaluate(board).to_string())
+            .collect()
+    })
+}
+
+/// Reads FEN strings from `reader` like [`evaluate_fens`], and writes each
+/// with the value `evaluator` gives it, passing the positions to the
+/// evaluator in batches.
+///
+/// # Errors
+///
+/// Returns any error from reading the input or writing the output.
+pub fn evaluate_fens_with<const SIDE_LENGTH: usize>(
+    reader: impl BufRead,
+    writer: impl Write,
+    evaluator: &(impl Evaluator<SIDE_LENGTH> + Sync),
+) -> io::Result<BulkSummary> {
+    process(reader, writer, |boards| {
+        boards
+            .par_chunks(EVALUATOR_BATCH)
+            .flat_map_iter(|batch| {
+                evaluator
+                    .evaluate(batch)
+                    .into_iter()
+                    .map(|(value, _)| value.to_string())
+            })
+            .collect()
+    })
+}
+
+/// Reads the input in chunks, parsing each line, and writes the results of
+/// `evaluate_chunk`, which gives one result per board in order.
+fn process<const SIDE_LENGTH: usize>(
+    reader: impl BufRead,
+    mut writer: impl Write,
+    evaluate_chunk: impl Fn(&[Board<SIDE_LENGTH>]) -> Vec<String>,
+) -> io::Result<BulkSummary> {
+    let mut summary = BulkSummary::default();
+    let mut lines = reader.lines();
+    let mut fens = Vec::with_capacity(CHUNK_LINES);
+    loop {
+        fens.clear();
+        for line in lines.by_ref().take(CHUNK_LINES) {
+            fens.push(line?);
+        }
+        if fens.is_empty() {
+            break;
+        }
+        let parsed = fens
+            .par_iter()
+            .map(|fen| fen.trim())
+            .filter(|fen| !fen.is_empty())
+            .map(|fen| fen.parse::<Board<SIDE_LENGTH>>().map(|board| (fen, board)))
+            .collect::<Vec<_>>();
+        let (valid, invalid): (Vec<_>, Vec<_>) = parsed.into_iter().partition(Result::is_ok);
+        let (fens, boards): (Vec<_>, Vec<_>) = valid.into_iter().flatten().unzip();
+        let results = evaluate_chunk(&boards);
+        for (fen, result) in fens.iter().zip(&results) {
+            writeln!(writer, "{fen}\t{result}")?;
+        }
+        summary.evaluated += results.len() as u64;
+        summary.invalid += invalid.len() as u64;
+    }
+    writer.flush()?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evaluate_fens, evaluate_fens_with, BulkSummary};
+    use crate::{board::Board, engine::UniformEvaluator, eval::evaluate};
+
+    #[test]
+    fn labels_every_position_in_order() {
+        let boards = [
+            Board::<9>::new(),
+            Board::new().with_move("e5".parse().unwrap()),
+            Board::new().with_move("a1".parse().unwrap()),
+        ];
+        let input = format!(
+            "{}\n\nnot a fen\n{}\n{}\n",
+            boards[0].fen(),
+            boards[1].fen(),
+            boards[2].fen()
+        );
+        let mut output = Vec::new();
+        let summary = evaluate_fens(input.as_bytes(), &mut output, evaluate::<9>).unwrap();
+        assert_eq!(
+            summary,
+            BulkSummary {
+                evaluated: 3,
+                invalid: 1
+            }
+        );
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().count(), 3);
+        for (line, board) in output.lines().zip(&boards) {
+            assert_eq!(line, format!("{}\t{}", board.fen(), evaluate(board)));
+        }
+
+        let mut output = Vec::new();
+        evaluate_fens_with::<9>(input.as_bytes(), &mut output, &UniformEvaluator).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().count(), 3);
+        assert!(output.lines().all(|line| line.ends_with("\t0")));
+    }
+}
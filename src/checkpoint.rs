@@ -0,0 +1,410 @@
+//! Checkpointing long perft and generation runs, so that they can resume
+//! after an interruption.
+//!
+//! A run is split up by root move. Whenever a root move is finished and at
+//! least the checkpoint interval has passed since the last save, the
+//! [`Checkpoint`] recording which root moves are done is written to disk,
+//! along with a snapshot of the perft cache or the length of the output
+//! file. Resuming from the checkpoint skips the finished root moves; work on
+//! the root move in progress at the interruption is redone.
+//!
+//! Checkpoint files are replaced atomically, by writing a temporary file next
+//! to the checkpoint and renaming it, so an interruption while saving leaves
+//! the previous checkpoint intact.
+//!
+//! On disk, a checkpoint is the magic bytes `GMKC`, a big-endian `u16` format
+//! version, the depth byte, a big-endian `u16` FEN length and the FEN of the
+//! root, a big-endian `u64` output length, a big-endian `u16` count of
+//! finished root moves and then each as a big-endian `u16` move index and
+//! `u64` result. Perft checkpoints are followed by the cache, in the format
+//! of [`TranspositionTable::write_to`].
+
+use std::{
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    board::{Board, Move},
+    perft::{perft, perft_tt, write_depth_n_fens},
+    positions::{invalid_data, FixedBytes},
+    tt::TranspositionTable,
+};
+
+const MAGIC: &[u8; 4] = b"GMKC";
+const VERSION: u16 = 1;
+
+/// The progress of a run: which root moves are finished, and their results.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint<const SIDE_LENGTH: usize> {
+    root: Board<SIDE_LENGTH>,
+    depth: u8,
+    output_len: u64,
+    completed: Vec<(Move<SIDE_LENGTH>, u64)>,
+}
+
+impl<const SIDE_LENGTH: usize> Checkpoint<SIDE_LENGTH> {
+    /// A checkpoint for a run from `root` to `depth` that has not started.
+    #[must_use]
+    pub const fn new(root: Board<SIDE_LENGTH>, depth: u8) -> Self {
+        Self {
+            root,
+            depth,
+            output_len: 0,
+            completed: Vec::new(),
+        }
+    }
+
+    /// The position the run starts from.
+    #[must_use]
+    pub const fn root(&self) -> &Board<SIDE_LENGTH> {
+        &self.root
+    }
+
+    /// The depth of the run.
+    #[must_use]
+    pub const fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    /// The finished root moves with their results, in the order they finished.
+    #[must_use]
+    pub fn completed(&self) -> &[(Move<SIDE_LENGTH>, u64)] {
+        &self.completed
+    }
+
+    /// Returns `true` if the root move `mv` is finished.
+    #[must_use]
+    pub fn is_completed(&self, mv: Move<SIDE_LENGTH>) -> bool {
+        self.completed.iter().any(|&(done, _)| done == mv)
+    }
+
+    /// The sum of the results of the finished root moves.
+    #[must_use]
+    pub fn total(&self) -> u64 {
+        self.completed.iter().map(|&(_, result)| result).sum()
+    }
+
+    fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        let fen = self.root.fen();
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        VERSION.write_bytes(&mut buf);
+        buf.push(self.depth);
+        u16::try_from(fen.len())
+            .map_err(|_| invalid_data("root FEN is too long"))?
+            .write_bytes(&mut buf);
+        buf.extend_from_slice(fen.as_bytes());
+        self.output_len.write_bytes(&mut buf);
+        u16::try_from(self.completed.len())
+            .map_err(|_| invalid_data("too many root moves"))?
+            .write_bytes(&mut buf);
+        for &(mv, result) in &self.completed {
+            u16::try_from(mv.index()).unwrap().write_bytes(&mut buf);
+            result.write_bytes(&mut buf);
+        }
+        writer.write_all(&buf)
+    }
+
+    fn read_from(mut reader: impl Read) -> io::Result<Self> {
+        let mut header = [0; 9];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid_data("not a gomokugen checkpoint"));
+        }
+        if u16::read_bytes(&header[4..6]) != VERSION {
+            return Err(invalid_data("unsupported checkpoint version"));
+        }
+        let depth = header[6];
+        let mut fen = vec![0; usize::from(u16::read_bytes(&header[7..]))];
+        reader.read_exact(&mut fen)?;
+        let root = std::str::from_utf8(&fen)
+            .ok()
+            .and_then(|fen| fen.parse::<Board<SIDE_LENGTH>>().ok())
+            .ok_or_else(|| invalid_data("checkpoint has an invalid root FEN"))?;
+        let mut counts = [0; 10];
+        reader.read_exact(&mut counts)?;
+        let output_len = u64::read_bytes(&counts[..8]);
+        let mut records = vec![0; usize::from(u16::read_bytes(&counts[8..])) * 10];
+        reader.read_exact(&mut records)?;
+        let completed = records
+            .chunks_exact(10)
+            .map(|record| {
+                let mv = Move::from_index(u16::read_bytes(&record[..2]));
+                if !root.is_legal(mv) {
+                    return Err(invalid_data("checkpoint has an illegal root move"));
+                }
+                Ok((mv, u64::read_bytes(&record[2..])))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Self {
+            root,
+            depth,
+            output_len,
+            completed,
+        })
+    }
+
+    /// Loads the checkpoint at `path`, or starts a new one if there is no
+    /// file there.
+    fn resume(
+        path: &Path,
+        root: Board<SIDE_LENGTH>,
+        depth: u8,
+    ) -> io::Result<(Self, Option<BufReader<File>>)> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok((Self::new(root, depth), None));
+            }
+            Err(e) => return Err(e),
+        };
+        let mut reader = BufReader::new(file);
+        let checkpoint = Self::read_from(&mut reader)?;
+        if checkpoint.root != root
+            || checkpoint.root.turn() != root.turn()
+            || checkpoint.depth != depth
+        {
+            return Err(invalid_data("checkpoint is for a different run"));
+        }
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            done = checkpoint.completed.len(),
+            nodes = checkpoint.total(),
+            "resuming from checkpoint"
+        );
+        Ok((checkpoint, Some(reader)))
+    }
+
+    /// Loads the checkpoint at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a checkpoint.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+}
+
+/// Writes a file at `path` with `write`, replacing any existing file only
+/// once the new one is complete.
+fn save_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut temp = OsString::from(path);
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    #[cfg(feature = "tracing")]
+    tracing::debug!(path = %path.display(), "saving checkpoint");
+    let mut writer = BufWriter::new(File::create(&temp)?);
+    write(&mut writer)?;
+    writer
+        .into_inner()
+        .map_err(io::IntoInnerError::into_error)?
+        .sync_all()?;
+    fs::rename(temp, path)
+}
+
+/// Counts leaf nodes like [`perft_tt`], checkpointing to the file at `path`
+/// and resuming from it if it exists.
+///
+/// Checkpoints are saved at most once every `interval`, and each includes a
+/// snapshot of `table`, which replaces its contents on resuming. The
+/// checkpoint is left in place when the run finishes, so running again
+/// returns the total straight away.
+///
+/// # Errors
+///
+/// Returns any error from reading or writing the checkpoint, including a
+/// checkpoint for a different root or depth.
+pub fn perft_resumable<const SIDE_LENGTH: usize>(
+    board: Board<SIDE_LENGTH>,
+    depth: u8,
+    table: &mut TranspositionTable<u64>,
+    path: impl AsRef<Path>,
+    interval: Duration,
+) -> io::Result<u64> {
+    let path = path.as_ref();
+    if depth == 0 {
+        return Ok(perft(board, depth));
+    }
+    let (mut checkpoint, reader) = Checkpoint::resume(path, board, depth)?;
+    if let Some(reader) = reader {
+        *table = TranspositionTable::read_from(reader)?;
+    }
+    let save = |checkpoint: &Checkpoint<SIDE_LENGTH>, table: &TranspositionTable<u64>| {
+        save_atomically(path, |writer| {
+            checkpoint.write_to(&mut *writer)?;
+            table.write_to(writer)
+        })
+    };
+
+    let mut last_save = Instant::now();
+    let mut pending = false;
+    for (mv, child) in board.successors() {
+        if checkpoint.is_completed(mv) {
+            continue;
+        }
+        let count = perft_tt(child, depth - 1, table);
+        checkpoint.completed.push((mv, count));
+        pending = true;
+        if last_save.elapsed() >= interval {
+            save(&checkpoint, table)?;
+            last_save = Instant::now();
+            pending = false;
+        }
+    }
+    if pending {
+        save(&checkpoint, table)?;
+    }
+    Ok(checkpoint.total())
+}
+
+/// Writes positions to the file at `output` like [`write_depth_n_fens`],
+/// checkpointing to the file at `path` and resuming from it if it exists.
+///
+/// Checkpoints are saved at most once every `interval`. On resuming,
+/// `output` is cut back to its length at the checkpoint before more
+/// positions are appended, so no position is written twice. Without a
+/// checkpoint, `output` is overwritten. Returns the number of positions in
+/// `output`.
+///
+/// # Errors
+///
+/// Returns any error from writing the output or from reading or writing the
+/// checkpoint, including a checkpoint for a different root or depth.
+pub fn write_depth_n_fens_resumable<const SIDE_LENGTH: usize>(
+    board: Board<SIDE_LENGTH>,
+    output: impl AsRef<Path>,
+    depth: u8,
+    path: impl AsRef<Path>,
+    interval: Duration,
+) -> io::Result<u64> {
+    let path = path.as_ref();
+    let (mut checkpoint, _) = Checkpoint::resume(path, board, depth)?;
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(output)?;
+    file.set_len(checkpoint.output_len)?;
+    file.seek(SeekFrom::End(0))?;
+    let mut writer = BufWriter::new(file);
+    if depth == 0 {
+        let count = write_depth_n_fens(board, &mut writer, depth)?;
+        writer.flush()?;
+        return Ok(count);
+    }
+
+    let save = |checkpoint: &mut Checkpoint<SIDE_LENGTH>,
+                writer: &mut BufWriter<File>|
+     -> io::Result<()> {
+        writer.flush()?;
+        let file = writer.get_mut();
+        file.sync_data()?;
+        checkpoint.output_len = file.stream_position()?;
+        save_atomically(path, |out| checkpoint.write_to(out))
+    };
+
+    let mut last_save = Instant::now();
+    let mut pending = false;
+    for (mv, child) in board.successors() {
+        if checkpoint.is_completed(mv) {
+            continue;
+        }
+        let count = write_depth_n_fens(child, &mut writer, depth - 1)?;
+        checkpoint.completed.push((mv, count));
+        pending = true;
+        if last_save.elapsed() >= interval {
+            save(&mut checkpoint, &mut writer)?;
+            last_save = Instant::now();
+            pending = false;
+        }
+    }
+    if pending {
+        save(&mut checkpoint, &mut writer)?;
+    }
+    writer.flush()?;
+    Ok(checkpoint.total())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, time::Duration};
+
+    use super::{perft_resumable, write_depth_n_fens_resumable, Checkpoint};
+    use crate::{
+        board::Board,
+        perft::{perft, write_depth_n_fens},
+        tt::TranspositionTable,
+    };
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("gomokugen-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn perft_resumes_from_a_checkpoint() {
+        let board = Board::<5>::new();
+        let expected = perft(board, 3);
+        let path = temp_path("perft.ckpt");
+        let _ = fs::remove_file(&path);
+
+        let mut table = TranspositionTable::new(1 << 10);
+        let total = perft_resumable(board, 3, &mut table, &path, Duration::ZERO).unwrap();
+        assert_eq!(total, expected);
+        let mut partial = Checkpoint::<5>::load(&path).unwrap();
+        assert_eq!(partial.completed().len(), 25);
+
+        // simulate an interruption after the first ten root moves.
+        partial.completed.truncate(10);
+        super::save_atomically(&path, |writer| {
+            partial.write_to(&mut *writer)?;
+            TranspositionTable::<u64>::new(16).write_to(writer)
+        })
+        .unwrap();
+        let mut table = TranspositionTable::new(1 << 10);
+        let total = perft_resumable(board, 3, &mut table, &path, Duration::ZERO).unwrap();
+        assert_eq!(total, expected);
+        assert_eq!(Checkpoint::<5>::load(&path).unwrap().total(), expected);
+
+        // a checkpoint for another run is rejected.
+        assert!(perft_resumable(board, 2, &mut table, &path, Duration::ZERO).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn generation_resumes_without_duplicates() {
+        let board = Board::<4>::new();
+        let path = temp_path("fens.ckpt");
+        let output = temp_path("fens.txt");
+        let _ = fs::remove_file(&path);
+
+        let mut expected = Vec::new();
+        write_depth_n_fens(board, &mut expected, 2).unwrap();
+        let count = write_depth_n_fens_resumable(board, &output, 2, &path, Duration::ZERO).unwrap();
+        assert_eq!(count, 16 * 15);
+        assert_eq!(fs::read(&output).unwrap(), expected);
+
+        // roll the checkpoint back, and leave junk from the interrupted root
+        // move at the end of the output.
+        let mut partial = Checkpoint::<4>::load(&path).unwrap();
+        partial.completed.truncate(3);
+        partial.output_len = expected
+            .split_inclusive(|&b| b == b'\n')
+            .take(3 * 15)
+            .map(<[u8]>::len)
+            .sum::<usize>() as u64;
+        super::save_atomically(&path, |writer| partial.write_to(writer)).unwrap();
+        let count = write_depth_n_fens_resumable(board, &output, 2, &path, Duration::ZERO).unwrap();
+        assert_eq!(count, 16 * 15);
+        assert_eq!(fs::read(&output).unwrap(), expected);
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&output).unwrap();
+    }
+}
@@ -0,0 +1,196 @@
+//! Game clocks for timed games.
+
+use std::time::Duration;
+
+use crate::{board::Player, engine::Limits};
+
+/// How much time each player has for the game.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TimeControl {
+    /// A fixed amount of time for the whole game.
+    SuddenDeath(Duration),
+    /// A fixed amount of time, plus `increment` added after every move.
+    Increment { base: Duration, increment: Duration },
+    /// A fixed amount of main time, after which every move must be made
+    /// within `period`. Overrunning a period uses it up, and a player who
+    /// runs out of periods loses.
+    ByoYomi {
+        base: Duration,
+        period: Duration,
+        periods: u32,
+    },
+}
+
+/// The clocks of both players in a game.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Clock {
+    control: TimeControl,
+    remaining: [Duration; 2],
+    periods: [u32; 2],
+    flagged: Option<Player>,
+}
+
+const fn side(player: Player) -> usize {
+    match player {
+        Player::X => 0,
+        Player::O => 1,
+        Player::None => panic!("No player"),
+    }
+}
+
+impl Clock {
+    /// Starts both players with the full time of `control`.
+    #[must_use]
+    pub const fn new(control: TimeControl) -> Self {
+        let (base, periods) = match control {
+            TimeControl::SuddenDeath(base) | TimeControl::Increment { base, .. } => (base, 0),
+            TimeControl::ByoYomi { base, periods, .. } => (base, periods),
+        };
+        Self {
+            control,
+            remaining: [base; 2],
+            periods: [periods; 2],
+            flagged: None,
+        }
+    }
+
+    /// The time control the clock was started with.
+    #[must_use]
+    pub const fn control(&self) -> TimeControl {
+        self.control
+    }
+
+    /// The main time `player` has left.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `player` is `Player::None`.
+    #[must_use]
+    pub const fn remaining(&self, player: Player) -> Duration {
+        self.remaining[side(player)]
+    }
+
+    /// The byo-yomi periods `player` has left.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `player` is `Player::None`.
+    #[must_use]
+    pub const fn periods(&self, player: Player) -> u32 {
+        self.periods[side(player)]
+    }
+
+    /// The player who ran out of time, if any.
+    #[must_use]
+    pub const fn flagged(&self) -> Option<Player> {
+        self.flagged
+    }
+
+    /// `limits` with the clock fields filled in for `player`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `player` is `Player::None`.
+    #[must_use]
+    pub fn limits(&self, player: Player, limits: Limits) -> Limits {
+        let (increment, byo_yomi) = match self.control {
+            TimeControl::SuddenDeath(_) => (None, None),
+            TimeControl::Increment { increment, .. } => (Some(increment), None),
+            TimeControl::ByoYomi { period, .. } => (None, Some(period)),
+        };
+        Limits {
+            time_left: Some(self.remaining(player)),
+            increment,
+            byo_yomi,
+            ..limits
+        }
+    }
+
+    /// Charges `player` for a move that took `elapsed`, returning `false` if
+    /// they ran out of time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `player` is `Player::None`.
+    pub fn punch(&mut self, player: Player, elapsed: Duration) -> bool {
+        let i = side(player);
+        if let Some(overrun) = elapsed.checked_sub(self.remaining[i]) {
+            self.remaining[i] = Duration::ZERO;
+            let in_time = match self.control {
+                TimeControl::ByoYomi { period, .. } => {
+                    // each whole period overrun is used up.
+                    let used = overrun.as_nanos() / period.as_nanos().max(1);
+                    let used = u32::try_from(used).unwrap_or(u32::MAX);
+                    let in_time = used < self.periods[i];
+                    self.periods[i] = self.periods[i].saturating_sub(used);
+                    in_time
+                }
+                _ => overrun.is_zero(),
+            };
+            if !in_time {
+                self.flagged = Some(player);
+                return false;
+            }
+        } else {
+            self.remaining[i] -= elapsed;
+        }
+        if let TimeControl::Increment { increment, .. } = self.control {
+            self.remaining[i] += increment;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Clock, TimeControl};
+    use crate::{board::Player, engine::Limits};
+
+    const fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn sudden_death_and_increment() {
+        let mut clock = Clock::new(TimeControl::SuddenDeath(secs(10)));
+        assert!(clock.punch(Player::X, secs(4)));
+        assert_eq!(clock.remaining(Player::X), secs(6));
+        assert_eq!(clock.remaining(Player::O), secs(10));
+        assert!(!clock.punch(Player::X, secs(7)));
+        assert_eq!(clock.flagged(), Some(Player::X));
+
+        let mut clock = Clock::new(TimeControl::Increment {
+            base: secs(10),
+            increment: secs(2),
+        });
+        assert!(clock.punch(Player::O, secs(4)));
+        assert_eq!(clock.remaining(Player::O), secs(8));
+        let limits = clock.limits(Player::O, Limits::depth(3));
+        assert_eq!(limits.depth, Some(3));
+        assert_eq!(limits.time_left, Some(secs(8)));
+        assert_eq!(limits.increment, Some(secs(2)));
+        assert!(limits.time_budget().unwrap() < secs(8));
+    }
+
+    #[test]
+    fn byo_yomi_periods() {
+        let mut clock = Clock::new(TimeControl::ByoYomi {
+            base: secs(5),
+            period: secs(10),
+            periods: 2,
+        });
+        // main time runs out, but the move is within the first period.
+        assert!(clock.punch(Player::X, secs(12)));
+        assert_eq!(clock.remaining(Player::X), Duration::ZERO);
+        assert_eq!(clock.periods(Player::X), 2);
+        // overrunning one period uses it up.
+        assert!(clock.punch(Player::X, secs(15)));
+        assert_eq!(clock.periods(Player::X), 1);
+        let budget = clock.limits(Player::X, Limits::default()).time_budget();
+        assert_eq!(budget, Some(secs(9)));
+        assert!(!clock.punch(Player::X, secs(11)));
+        assert_eq!(clock.flagged(), Some(Player::X));
+    }
+}
@@ -0,0 +1,427 @@
+//! Statistics gathered from databases of games, such as
+//! [game collections](crate::formats::collection).
+//!
+//! [`GameStats`] summarises the results of a database, and [`OpeningStats`]
+//! records how the games through each early position turned out, as the
+//! material for weighted opening books. Games are added one at a time, so
+//! that a database can be streamed through without holding it in memory:
+//!
+//! ```
+//! use gomokugen::{board::Board, database::GameStats, game::Game};
+//!
+//! let mut game = Game::new(Board::<15>::new());
+//! for mv in ["h8", "i9", "h9", "i10", "h10", "i11", "h11", "i12", "h12"] {
+//!     game.play(mv.parse().unwrap());
+//! }
+//! let mut stats = GameStats::default();
+//! stats.add(&game);
+//! assert_eq!(stats.average_length(), Some(9.0));
+//! assert_eq!(stats.first_player_score(15, Default::default()).unwrap().wins, 1);
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+};
+
+use crate::{
+    board::{Board, Coord, Move, Player},
+    game::Game,
+    matches::Score,
+    positions::{FixedBytes, PositionMap},
+    rules::Rule,
+};
+
+/// Aggregate statistics over many games, possibly on boards of different
+/// sizes and under different rules.
+///
+/// Results are counted from the perspective of the first player: the side
+/// to move in each game's starting position. Unfinished games count towards
+/// the number of games but not towards any results or the average length.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameStats {
+    games: u64,
+    finished: u64,
+    total_moves: u64,
+    by_variant: BTreeMap<(usize, Rule), Score>,
+    by_opening: BTreeMap<(usize, Coord), Score>,
+}
+
+impl GameStats {
+    /// Adds `game` to the statistics.
+    pub fn add<const SIDE_LENGTH: usize>(&mut self, game: &Game<SIDE_LENGTH>) {
+        self.games += 1;
+        let Some(winner) = game.winner() else {
+            return;
+        };
+        self.finished += 1;
+        self.total_moves += game.moves().len() as u64;
+        let first = game.start().turn();
+        let record = |score: &mut Score| match winner {
+            Player::None => score.draws += 1,
+            winner if winner == first => score.wins += 1,
+            _ => score.losses += 1,
+        };
+        record(
+            self.by_variant
+                .entry((SIDE_LENGTH, game.rule()))
+                .or_default(),
+        );
+        if let Some(opening) = game.moves().first() {
+            record(
+                self.by_opening
+                    .entry((SIDE_LENGTH, opening.coord()))
+                    .or_default(),
+            );
+        }
+    }
+
+    /// The number of games added, finished or not.
+    #[must_use]
+    pub const fn games(&self) -> u64 {
+        self.games
+    }
+
+    /// The number of finished games added.
+    #[must_use]
+    pub const fn finished(&self) -> u64 {
+        self.finished
+    }
+
+    /// The average number of moves in a finished game, or `None` if no
+    /// games have finished.
+    #[must_use]
+    pub fn average_length(&self) -> Option<f64> {
+        #![allow(clippy::cast_precision_loss)]
+        (self.finished > 0).then(|| self.total_moves as f64 / self.finished as f64)
+    }
+
+    /// The first player's results in finished games on boards of side length
+    /// `side_length` under `rule`, or `None` if there were none.
+    #[must_use]
+    pub fn first_player_score(&self, side_length: usize, rule: Rule) -> Option<Score> {
+        self.by_variant.get(&(side_length, rule)).copied()
+    }
+
+    /// The first player's results for each board size and rule, in order of
+    /// size.
+    pub fn variants(&self) -> impl Iterator<Item = (usize, Rule, Score)> + '_ {
+        self.by_variant
+            .iter()
+            .map(|(&(side_length, rule), &score)| (side_length, rule, score))
+    }
+
+    /// The first player's results for each first move played, with the side
+    /// length of the board it was played on, in order of size and then of
+    /// cell.
+    pub fn openings(&self) -> impl Iterator<Item = (usize, Coord, Score)> + '_ {
+        self.by_opening
+            .iter()
+            .map(|(&(side_length, cell), &score)| (side_length, cell, score))
+    }
+}
+
+/// Writes one line of results, with the first player's share of the points.
+fn write_score(f: &mut fmt::Formatter<'_>, label: &str, score: Score) -> fmt::Result {
+    write!(
+        f,
+        "  {label:<22} {:>7} games  +{} ={} -{}",
+        score.games(),
+        score.wins,
+        score.draws,
+        score.losses
+    )?;
+    match score.points() {
+        Some(points) => writeln!(f, "  {:.1}%", points * 100.0),
+        None => writeln!(f),
+    }
+}
+
+impl Display for GameStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "games: {} ({} unfinished)",
+            self.games,
+            self.games - self.finished
+        )?;
+        if let Some(length) = self.average_length() {
+            writeln!(f, "average length: {length:.1} moves")?;
+        }
+        if !self.by_variant.is_empty() {
+            writeln!(f, "first player results by board size and rule:")?;
+        }
+        for (side_length, rule, score) in self.variants() {
+            let label = format!("{side_length}x{side_length} {rule}");
+            write_score(f, &label, score)?;
+        }
+        if !self.by_opening.is_empty() {
+            writeln!(f, "first player results by opening move:")?;
+        }
+        for (side_length, cell, score) in self.openings() {
+            #[allow(clippy::cast_possible_truncation)]
+            let column = char::from(b'A' + cell.col as u8);
+            let label = format!("{side_length}x{side_length} {column}{}", cell.row + 1);
+            write_score(f, &label, score)?;
+        }
+        Ok(())
+    }
+}
+
+/// How the finished games through a position turned out, from the
+/// perspective of the side to move in it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PositionRecord {
+    /// The number of games that reached the position.
+    pub games: u32,
+    /// The number of those games won by the side to move.
+    pub wins: u32,
+    /// The number of those games drawn.
+    pub draws: u32,
+}
+
+impl PositionRecord {
+    /// The number of games lost by the side to move.
+    #[must_use]
+    pub const fn losses(&self) -> u32 {
+        self.games - self.wins - self.draws
+    }
+
+    /// The results as a [`Score`] for the side to move.
+    #[must_use]
+    pub const fn score(&self) -> Score {
+        Score {
+            wins: self.wins,
+            draws: self.draws,
+            losses: self.losses(),
+        }
+    }
+
+    const fn merge(&mut self, other: Self) {
+        self.games += other.games;
+        self.wins += other.wins;
+        self.draws += other.draws;
+    }
+}
+
+impl FixedBytes for PositionRecord {
+    const SIZE: usize = 12;
+
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        (self.games, self.wins, self.draws).write_bytes(out);
+    }
+
+    fn read_bytes(bytes: &[u8]) -> Self {
+        let (games, wins, draws) = FixedBytes::read_bytes(bytes);
+        Self { games, wins, draws }
+    }
+}
+
+/// The results of the finished games through each position reached in their
+/// first plies, with positions that are rotations or reflections of each
+/// other counted together.
+///
+/// The map of positions can be saved with [`PositionMap::save`], and the
+/// statistics of several databases gathered apart and then merged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpeningStats<const SIDE_LENGTH: usize> {
+    max_ply: usize,
+    positions: PositionMap<PositionRecord>,
+}
+
+impl<const SIDE_LENGTH: usize> OpeningStats<SIDE_LENGTH> {
+    /// Creates empty statistics that record the positions up to and including
+    /// the one after `max_ply` moves of each game.
+    #[must_use]
+    pub fn new(max_ply: usize) -> Self {
+        Self {
+            max_ply,
+            positions: PositionMap::new(),
+        }
+    }
+
+    /// Adds `game`, unless it is unfinished.
+    pub fn add(&mut self, game: &Game<SIDE_LENGTH>) {
+        let Some(winner) = game.winner() else {
+            return;
+        };
+        let mut board = *game.start();
+        let mut moves = game.moves().iter().take(self.max_ply);
+        loop {
+            let record = self.positions.entry(&board).or_default();
+            record.games += 1;
+            match winner {
+                Player::None => record.draws += 1,
+                winner if winner == board.turn() => record.wins += 1,
+                _ => {}
+            }
+            let Some(&mv) = moves.next() else {
+                break;
+            };
+            board.make_move(mv);
+        }
+    }
+
+    /// The results of the games through `board` or any of its symmetric
+    /// images, or `None` if no game reached it.
+    #[must_use]
+    pub fn get(&self, board: &Board<SIDE_LENGTH>) -> Option<PositionRecord> {
+        self.positions.get(board).copied()
+    }
+
+    /// The moves from `board` that games went on with, each with the results
+    /// through the position it leads to, from the perspective of the side to
+    /// move there, most played first.
+    ///
+    /// Of moves that lead to symmetric positions, only the first in index
+    /// order is listed, since the games through them are counted together.
+    #[must_use]
+    pub fn continuations(
+        &self,
+        board: &Board<SIDE_LENGTH>,
+    ) -> Vec<(Move<SIDE_LENGTH>, PositionRecord)> {
+        let mut seen = Vec::new();
+        let mut out = Vec::new();
+        for (mv, child) in board.children() {
+            let key = child.canonical_key();
+            if seen.contains(&key) {
+                continue;
+            }
+            seen.push(key);
+            if let Some(&record) = self.positions.get_key(key) {
+                out.push((mv, record));
+            }
+        }
+        out.sort_by_key(|&(mv, record)| (std::cmp::Reverse(record.games), mv.index()));
+        out
+    }
+
+    /// The number of distinct positions recorded.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Whether no positions have been recorded.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Adds the positions recorded in `other`.
+    pub fn merge(&mut self, other: Self) {
+        self.positions.merge(other.positions, PositionRecord::merge);
+    }
+
+    /// The recorded positions, keyed by [`Board::canonical_key`].
+    #[must_use]
+    pub const fn positions(&self) -> &PositionMap<PositionRecord> {
+        &self.positions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GameStats, OpeningStats, PositionRecord};
+    use crate::{
+        board::{Board, Coord, Player},
+        game::{Game, GameResult, Termination},
+        matches::Score,
+        rules::Rule,
+    };
+
+    #[test]
+    fn counts_results_by_variant_and_opening() {
+        let mut stats = GameStats::default();
+        let mut won = Game::new(Board::<5>::new());
+        for mv in ["c3", "a1", "c2", "a2", "c1", "a3", "c4", "a4", "c5"] {
+            won.play(mv.parse().unwrap());
+        }
+        let mut resigned = Game::new(Board::<5>::new()).with_rule(Rule::Renju);
+        resigned.play("c3".parse().unwrap());
+        resigned.resign(Player::X);
+        let mut unfinished = Game::new(Board::<7>::new());
+        unfinished.play("d4".parse().unwrap());
+        stats.add(&won);
+        stats.add(&resigned);
+        stats.add(&unfinished);
+
+        assert_eq!((stats.games(), stats.finished()), (3, 2));
+        assert_eq!(stats.average_length(), Some(5.0));
+        let win = Score {
+            wins: 1,
+            ..Score::default()
+        };
+        let loss = Score {
+            losses: 1,
+            ..Score::default()
+        };
+        assert_eq!(stats.first_player_score(5, Rule::Freestyle), Some(win));
+        assert_eq!(stats.first_player_score(5, Rule::Renju), Some(loss));
+        assert_eq!(stats.first_player_score(7, Rule::Freestyle), None);
+        assert_eq!(
+            stats.openings().collect::<Vec<_>>(),
+            [(
+                5,
+                Coord { row: 2, col: 2 },
+                Score {
+                    wins: 1,
+                    draws: 0,
+                    losses: 1
+                }
+            )]
+        );
+        let report = stats.to_string();
+        assert!(report.contains("games: 3 (1 unfinished)"));
+        assert!(report.contains("5x5 renju"));
+        assert!(report.contains("5x5 C3"));
+    }
+
+    #[test]
+    fn opening_stats_unify_symmetric_positions() {
+        let mut stats = OpeningStats::<5>::new(2);
+        let mut drawn = Game::new(Board::<5>::new());
+        drawn.play("b2".parse().unwrap());
+        drawn.end(GameResult {
+            winner: Player::None,
+            termination: Termination::Adjudication,
+        });
+        let mut won = Game::new(Board::<5>::new());
+        for mv in ["d4", "a1", "d3", "a2", "d2", "a3", "d1", "a4", "d5"] {
+            won.play(mv.parse().unwrap());
+        }
+        let mut unfinished = Game::new(Board::<5>::new());
+        unfinished.play("c3".parse().unwrap());
+        for game in [&drawn, &won, &unfinished] {
+            stats.add(game);
+        }
+
+        let start = Board::<5>::new();
+        assert_eq!(
+            stats.get(&start),
+            Some(PositionRecord {
+                games: 2,
+                wins: 1,
+                draws: 1
+            })
+        );
+        // b2 and d4 are reflections of each other.
+        let after = PositionRecord {
+            games: 2,
+            wins: 0,
+            draws: 1,
+        };
+        assert_eq!(after.losses(), 1);
+        assert_eq!(
+            stats.continuations(&start),
+            [("b2".parse().unwrap(), after)]
+        );
+        // the start, b2, and d4 a1 and its images; the third move is past max_ply.
+        assert_eq!(stats.len(), 3);
+
+        let mut other = OpeningStats::<5>::new(2);
+        other.add(&won);
+        stats.merge(other);
+        assert_eq!(stats.get(&start).map(|record| record.games), Some(3));
+    }
+}
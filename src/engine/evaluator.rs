@@ -0,0 +1,65 @@
+//! Pluggable position evaluators, such as neural networks, for the
+//! [`MctsEngine`](super::MctsEngine) to use in place of random playouts.
+
+use crate::board::{Board, Move};
+
+/// Evaluates positions in batches, giving each a value and a policy.
+///
+/// The value is the expected result for the side to move, from -1 for a
+/// loss to 1 for a win. The policy has an entry for every cell, indexed by
+/// [`Move::index`], giving how promising each move looks; only the entries
+/// of moves the search considers are used, and they need not sum to one.
+///
+/// Batches let backends that run on accelerators amortise their overhead.
+pub trait Evaluator<const SIDE_LENGTH: usize> {
+    /// Evaluates each of `boards`, returning one `(value, policy)` pair per
+    /// board, in order.
+    fn evaluate(&self, boards: &[Board<SIDE_LENGTH>]) -> Vec<(f32, Vec<f32>)>;
+}
+
+/// An evaluator that knows nothing: every position is even, and every legal
+/// move equally likely. Useful as a placeholder, and for testing.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct UniformEvaluator;
+
+impl<const SIDE_LENGTH: usize> Evaluator<SIDE_LENGTH> for UniformEvaluator {
+    fn evaluate(&self, boards: &[Board<SIDE_LENGTH>]) -> Vec<(f32, Vec<f32>)> {
+        #![allow(clippy::cast_precision_loss)]
+        boards
+            .iter()
+            .map(|board| {
+                let mut moves = Vec::new();
+                board.generate_moves(|mv: Move<SIDE_LENGTH>| {
+                    moves.push(mv);
+                    false
+                });
+                let mut policy = vec![0.0; SIDE_LENGTH * SIDE_LENGTH];
+                for mv in &moves {
+                    policy[mv.index()] = 1.0 / moves.len() as f32;
+                }
+                (0.0, policy)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Evaluator, UniformEvaluator};
+    use crate::board::Board;
+
+    #[test]
+    fn uniform_policy_over_legal_moves() {
+        let board = Board::<3>::new().with_move("b2".parse().unwrap());
+        let evaluations = UniformEvaluator.evaluate(&[Board::new(), board]);
+        assert_eq!(evaluations.len(), 2);
+        assert!(evaluations[0]
+            .1
+            .iter()
+            .all(|&p| (p - 1.0 / 9.0).abs() < 1e-6));
+        let (value, policy) = &evaluations[1];
+        assert!(value.abs() < f32::EPSILON);
+        assert!(policy[4].abs() < f32::EPSILON);
+        assert!((policy.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+    }
+}
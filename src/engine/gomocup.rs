@@ -0,0 +1,388 @@
+//! Driving external engines that speak the Gomocup (piskvork) protocol.
+//!
+//! Each move is requested by sending the whole position with `BOARD`, so the
+//! engine needs no memory of earlier moves. Coordinates are sent as `x,y`
+//! with `x` the column and `y` the row, both counted from zero.
+//!
+//! `BOARD` lists the stones in the order they were played. Boards do not
+//! record that order, so the adapter remembers the stones it sent last time
+//! and the move the engine chose, and appends the stones added since. A
+//! position that does not follow from the previous request, such as the
+//! first position of a game from an opening, is sent in row-major order.
+//!
+//! Replies must arrive within the turn's time budget, plus a margin, or
+//! within the margin alone for commands other than move requests. An engine
+//! that misses a deadline is [flagged](GomocupEngine::missed_deadline) and
+//! asked for nothing more, since its late reply would be taken as the answer
+//! to the next request.
+
+use std::{
+    ffi::OsStr,
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use super::{Engine, Limits};
+use crate::board::{Board, Move, Player};
+
+/// How long past the time budget of a turn to wait for a reply, unless set
+/// with [`GomocupEngine::with_reply_margin`].
+const REPLY_MARGIN: Duration = Duration::from_secs(5);
+
+/// An engine running in another process, spoken to over its standard input and output.
+#[derive(Debug)]
+pub struct GomocupEngine {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<io::Result<String>>,
+    board_size: Option<usize>,
+    history: Vec<(usize, Player)>,
+    margin: Duration,
+    missed_deadline: bool,
+    error: Option<io::Error>,
+}
+
+impl GomocupEngine {
+    /// Starts `program` with `args`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the process can't be started.
+    pub fn spawn<S: AsRef<OsStr>>(
+        program: impl AsRef<OsStr>,
+        args: impl IntoIterator<Item = S>,
+    ) -> io::Result<Self> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(io::Error::other("engine process has no pipes"));
+        };
+        // the engine's output is read on another thread, so that replies
+        // can be waited for with a deadline.
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            child,
+            stdin,
+            lines,
+            board_size: None,
+            history: Vec::new(),
+            margin: REPLY_MARGIN,
+            missed_deadline: false,
+            error: None,
+        })
+    }
+
+    /// Sets how long past the time budget of a turn to wait for a reply,
+    /// which is also the time allowed for replies to other commands. The
+    /// default is five seconds.
+    #[must_use]
+    pub const fn with_reply_margin(mut self, margin: Duration) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// The error that made the most recent move request fail, if any.
+    #[must_use]
+    pub const fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    /// Whether the engine has failed to reply before a deadline, after which
+    /// every request fails.
+    #[must_use]
+    pub const fn missed_deadline(&self) -> bool {
+        self.missed_deadline
+    }
+
+    /// Asks the engine to describe itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if communication with the engine fails.
+    pub fn about(&mut self) -> io::Result<String> {
+        self.send("ABOUT")?;
+        self.reply(Some(self.margin))
+    }
+
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        if self.missed_deadline {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "engine missed an earlier deadline",
+            ));
+        }
+        writeln!(self.stdin, "{line}")?;
+        self.stdin.flush()
+    }
+
+    /// Reads the next line that isn't an informational message, waiting at
+    /// most `timeout` for it, if given.
+    fn reply(&mut self, timeout: Option<Duration>) -> io::Result<String> {
+        let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+        loop {
+            let line = match deadline {
+                Some(deadline) => self
+                    .lines
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now())),
+                None => self
+                    .lines
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let line = match line {
+                Ok(line) => line?,
+                Err(RecvTimeoutError::Timeout) => {
+                    self.missed_deadline = true;
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "engine did not reply in time",
+                    ));
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "engine closed its output",
+                    ));
+                }
+            };
+            let line = line.trim();
+            if line.starts_with("MESSAGE") || line.starts_with("DEBUG") || line.is_empty() {
+                continue;
+            }
+            if line.starts_with("ERROR") || line.starts_with("UNKNOWN") {
+                return Err(io::Error::other(line.to_string()));
+            }
+            return Ok(line.to_string());
+        }
+    }
+
+    fn request_move<const SIDE_LENGTH: usize>(
+        &mut self,
+        board: &Board<SIDE_LENGTH>,
+        limits: &Limits,
+    ) -> io::Result<Move<SIDE_LENGTH>> {
+        if self.board_size != Some(SIDE_LENGTH) {
+            self.send(&format!("START {SIDE_LENGTH}"))?;
+            let reply = self.reply(Some(self.margin))?;
+            if reply != "OK" {
+                return Err(io::Error::other(format!(
+                    "unexpected reply to START: {reply}"
+                )));
+            }
+            self.board_size = Some(SIDE_LENGTH);
+            self.history.clear();
+        }
+        let budget = limits.time_budget();
+        if let Some(time) = budget {
+            self.send(&format!("INFO timeout_turn {}", time.as_millis()))?;
+        }
+        if let Some(time) = limits.time_left {
+            self.send(&format!("INFO time_left {}", time.as_millis()))?;
+        }
+        let stones = self.stones_in_order(board);
+        self.send("BOARD")?;
+        let us = board.turn();
+        for &(mv, player) in &stones {
+            let coord = mv.coord();
+            let who = if player == us { 1 } else { 2 };
+            self.send(&format!("{},{},{who}", coord.col, coord.row))?;
+        }
+        self.send("DONE")?;
+        // without a time budget, as when searching to a fixed depth, the
+        // engine may take as long as it needs.
+        let reply = self.reply(budget.map(|budget| budget + self.margin))?;
+        let mv = parse_move(&reply).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid move from engine: {reply}"),
+            )
+        })?;
+        self.history = stones
+            .into_iter()
+            .chain([(mv, us)])
+            .map(|(mv, player)| (mv.index(), player))
+            .collect();
+        Ok(mv)
+    }
+
+    /// The stones of `board` in the order they were played, as far as the
+    /// stones sent in the previous request and the engine's reply tell it,
+    /// and otherwise in row-major order.
+    fn stones_in_order<const SIDE_LENGTH: usize>(
+        &self,
+        board: &Board<SIDE_LENGTH>,
+    ) -> Vec<(Move<SIDE_LENGTH>, Player)> {
+        let known = self
+            .history
+            .iter()
+            .filter_map(|&(index, player)| {
+                let mv = Move::from_index(u16::try_from(index).ok()?);
+                (board.player_at(mv.coord()) == player).then_some((mv, player))
+            })
+            .collect::<Vec<_>>();
+        if known.len() == self.history.len() {
+            let to_move = known.last().map_or(Player::X, |&(_, player)| -player);
+            let added = Board::from_setup(&known, to_move)
+                .ok()
+                .and_then(|previous| previous.diff(board).ok());
+            if let Some(added) = added {
+                return known.into_iter().chain(added).collect();
+            }
+        }
+        board
+            .cells()
+            .filter(|&(_, player)| player != Player::None)
+            .map(|(coord, player)| {
+                let index = coord.row * SIDE_LENGTH + coord.col;
+                (Move::from_index(u16::try_from(index).unwrap()), player)
+            })
+            .collect()
+    }
+}
+
+/// Parses a Gomocup `x,y` coordinate pair.
+pub(crate) fn parse_move<const SIDE_LENGTH: usize>(reply: &str) -> Option<Move<SIDE_LENGTH>> {
+    let (x, y) = reply.split_once(',')?;
+    let (col, row) = (
+        x.trim().parse::<usize>().ok()?,
+        y.trim().parse::<usize>().ok()?,
+    );
+    if col >= SIDE_LENGTH || row >= SIDE_LENGTH {
+        return None;
+    }
+    Some(Move::from_index(
+        u16::try_from(row * SIDE_LENGTH + col).ok()?,
+    ))
+}
+
+impl<const SIDE_LENGTH: usize> Engine<SIDE_LENGTH> for GomocupEngine {
+    /// Asks the engine for a move. If communication fails, the error is kept
+    /// for [`GomocupEngine::error`] and the null move is returned.
+    fn best_move(&mut self, board: &Board<SIDE_LENGTH>, limits: &Limits) -> Move<SIDE_LENGTH> {
+        match self.request_move(board, limits) {
+            Ok(mv) => {
+                self.error = None;
+                mv
+            }
+            Err(error) => {
+                self.error = Some(error);
+                Move::null()
+            }
+        }
+    }
+}
+
+impl Drop for GomocupEngine {
+    fn drop(&mut self) {
+        // give the engine a moment to exit cleanly before killing it.
+        let _ = self.send("END");
+        let deadline = Instant::now() + Duration::from_millis(100);
+        while Instant::now() < deadline {
+            if matches!(self.child.try_wait(), Ok(Some(_))) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        time::{Duration, Instant},
+    };
+
+    use super::{parse_move, GomocupEngine};
+    use crate::{
+        board::{Board, Move},
+        engine::{Engine, Limits},
+    };
+
+    #[test]
+    fn parses_coordinates() {
+        assert_eq!(parse_move::<15>("7,7"), Some("h8".parse().unwrap()));
+        assert_eq!(parse_move::<15>(" 0 , 14"), Some("a15".parse().unwrap()));
+        assert_eq!(parse_move::<15>("15,0"), None);
+        assert_eq!(parse_move::<15>("OK"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn talks_to_a_process() {
+        // an engine that always plays at 3,1.
+        let script = r#"while read -r line; do
+            case "$line" in
+                START*) echo OK ;;
+                DONE) echo "MESSAGE thinking"; echo 3,1 ;;
+                END) exit 0 ;;
+            esac
+        done"#;
+        let mut engine = GomocupEngine::spawn("sh", ["-c", script]).unwrap();
+        let board = Board::<9>::new().with_move("a1".parse().unwrap());
+        let mv = engine.best_move(&board, &Limits::default());
+        assert_eq!(mv, "d2".parse::<Move<9>>().unwrap());
+        assert!(engine.error().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sends_stones_in_play_order() {
+        // an engine that plays just above the last stone it was sent.
+        let script = r#"while read -r line; do
+            case "$line" in
+                START*) echo OK ;;
+                DONE) x=${last%%,*}; rest=${last#*,}; y=${rest%%,*}; echo "$x,$((y + 1))" ;;
+                *,*) last=$line ;;
+                END) exit 0 ;;
+            esac
+        done"#;
+        let mut engine = GomocupEngine::spawn("sh", ["-c", script]).unwrap();
+        let mut board = Board::<9>::new().with_move("a1".parse().unwrap());
+        let mv = engine.best_move(&board, &Limits::default());
+        assert_eq!(mv, "a2".parse::<Move<9>>().unwrap());
+        board.make_move(mv);
+        // b1 comes before a2 in row-major order, but was played after it.
+        board.make_move("b1".parse().unwrap());
+        let mv = engine.best_move(&board, &Limits::default());
+        assert_eq!(mv, "b2".parse::<Move<9>>().unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn flags_engines_that_miss_deadlines() {
+        // an engine that never answers a move request.
+        let script = r#"while read -r line; do
+            case "$line" in
+                START*) echo OK ;;
+                END) exit 0 ;;
+            esac
+        done"#;
+        let mut engine = GomocupEngine::spawn("sh", ["-c", script])
+            .unwrap()
+            .with_reply_margin(Duration::from_millis(50));
+        let board = Board::<9>::new();
+        let limits = Limits::time(Duration::from_millis(50));
+        assert!(Engine::<9>::best_move(&mut engine, &board, &limits).is_null());
+        assert_eq!(engine.error().unwrap().kind(), io::ErrorKind::TimedOut);
+        assert!(engine.missed_deadline());
+        // the engine is not asked again, so this fails at once.
+        let start = Instant::now();
+        assert!(Engine::<9>::best_move(&mut engine, &board, &Limits::default()).is_null());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
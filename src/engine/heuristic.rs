@@ -0,0 +1,38 @@
+//! An engine that looks one move ahead.
+
+use super::{Engine, Limits};
+use crate::{
+    board::{Board, Move},
+    eval::{evaluate_with, EvalWeights},
+};
+
+/// Wins if it can, blocks if it must, and otherwise plays the move near the
+/// existing stones that leads to the best [static evaluation](crate::eval).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct HeuristicEngine {
+    weights: EvalWeights,
+}
+
+impl HeuristicEngine {
+    /// Creates a new heuristic engine.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self::with_weights(EvalWeights::DEFAULT)
+    }
+
+    /// Creates a heuristic engine that evaluates positions with `weights`.
+    #[must_use]
+    pub const fn with_weights(weights: EvalWeights) -> Self {
+        Self { weights }
+    }
+}
+
+impl<const SIDE_LENGTH: usize> Engine<SIDE_LENGTH> for HeuristicEngine {
+    fn best_move(&mut self, board: &Board<SIDE_LENGTH>, _: &Limits) -> Move<SIDE_LENGTH> {
+        board
+            .candidate_moves(Some(2))
+            .into_iter()
+            .max_by_key(|&mv| -evaluate_with(&board.with_move(mv), &self.weights))
+            .unwrap_or_else(Move::null)
+    }
+}
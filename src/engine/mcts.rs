@@ -0,0 +1,669 @@
+//! A Monte Carlo tree search engine.
+//!
+//! The search tree is kept between moves: when asked about a position that
+//! follows from the previous root by one or two moves, the matching subtree
+//! is reused. The engine can also ponder, growing the tree on a background
+//! thread while the opponent thinks.
+//!
+//! Leaves are scored with random playouts, or with an [`Evaluator`] if one
+//! is given, in which case its policy also guides the selection of moves.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Instant,
+};
+
+use super::{Engine, Evaluator, Limits, RandomEngine};
+use crate::board::{Board, Move, Player};
+
+/// The number of iterations run when no limit is given.
+const DEFAULT_ITERATIONS: u64 = 10_000;
+
+/// The largest tree, in nodes, that a search will grow.
+const MAX_NODES: usize = 4_000_000;
+
+/// The weight of the exploration term in the UCT formula.
+const EXPLORATION: f64 = 1.4;
+
+/// The weight of the exploration term in the PUCT formula, used with an
+/// evaluator's policy.
+const PUCT_EXPLORATION: f64 = 1.5;
+
+/// An evaluator shared with the pondering thread.
+type SharedEvaluator<const SIDE_LENGTH: usize> = Arc<dyn Evaluator<SIDE_LENGTH> + Send + Sync>;
+
+#[derive(Clone, Debug)]
+struct Node<const SIDE_LENGTH: usize> {
+    /// The move leading to this node.
+    mv: Move<SIDE_LENGTH>,
+    /// The index of the first child. The children are stored contiguously.
+    first_child: usize,
+    children: usize,
+    expanded: bool,
+    visits: u32,
+    /// The total result of the playouts through this node, from the
+    /// perspective of the player who made `mv`.
+    value: f64,
+    /// The evaluator's policy for `mv`, normalised over its siblings.
+    prior: f64,
+}
+
+impl<const SIDE_LENGTH: usize> Node<SIDE_LENGTH> {
+    const fn new(mv: Move<SIDE_LENGTH>) -> Self {
+        Self {
+            mv,
+            first_child: 0,
+            children: 0,
+            expanded: false,
+            visits: 0,
+            value: 0.0,
+            prior: 0.0,
+        }
+    }
+}
+
+/// A search tree, with the root at index zero.
+#[derive(Clone, Debug)]
+struct Tree<const SIDE_LENGTH: usize> {
+    root: Board<SIDE_LENGTH>,
+    nodes: Vec<Node<SIDE_LENGTH>>,
+}
+
+impl<const SIDE_LENGTH: usize> Tree<SIDE_LENGTH> {
+    fn new(root: Board<SIDE_LENGTH>) -> Self {
+        Self {
+            root,
+            nodes: vec![Node::new(Move::null())],
+        }
+    }
+
+    fn children(&self, index: usize) -> std::ops::Range<usize> {
+        let node = &self.nodes[index];
+        node.first_child..node.first_child + node.children
+    }
+
+    /// The tree rooted at `board`, reusing this tree's nodes if `board` is
+    /// reached from its root by at most two moves.
+    fn reroot(self, board: &Board<SIDE_LENGTH>) -> Self {
+        if self.root == *board {
+            return self;
+        }
+        for first in self.children(0) {
+            let after_first = self.root.with_move(self.nodes[first].mv);
+            if after_first == *board {
+                return self.subtree(first, after_first);
+            }
+            for second in self.children(first) {
+                let after_second = after_first.with_move(self.nodes[second].mv);
+                if after_second == *board {
+                    return self.subtree(second, after_second);
+                }
+            }
+        }
+        Self::new(*board)
+    }
+
+    /// Copies the subtree under `index`, whose position is `root`, into a new tree.
+    fn subtree(&self, index: usize, root: Board<SIDE_LENGTH>) -> Self {
+        let mut nodes = vec![self.nodes[index].clone()];
+        let mut queue = std::collections::VecDeque::from([(index, 0)]);
+        while let Some((old, new)) = queue.pop_front() {
+            let first_child = nodes.len();
+            for child in self.children(old) {
+                queue.push_back((child, nodes.len()));
+                nodes.push(self.nodes[child].clone());
+            }
+            nodes[new].first_child = first_child;
+        }
+        Self { root, nodes }
+    }
+
+    /// The child of `index` with the best UCT score, or with the best PUCT
+    /// score if the children have priors from an evaluator.
+    fn select(&self, index: usize, priors: bool) -> usize {
+        let parent_visits = f64::from(self.nodes[index].visits.max(1));
+        let ln_visits = parent_visits.ln();
+        let uct = |child: usize| {
+            let node = &self.nodes[child];
+            let visits = f64::from(node.visits);
+            if priors {
+                // unvisited moves are assumed to be even.
+                let q = if node.visits == 0 {
+                    0.5
+                } else {
+                    node.value / visits
+                };
+                return (PUCT_EXPLORATION * node.prior)
+                    .mul_add(parent_visits.sqrt() / (1.0 + visits), q);
+            }
+            if node.visits == 0 {
+                return f64::INFINITY;
+            }
+            EXPLORATION.mul_add((ln_visits / visits).sqrt(), node.value / visits)
+        };
+        self.children(index)
+            .max_by(|&a, &b| uct(a).total_cmp(&uct(b)))
+            .expect("select is only called on nodes with children")
+    }
+
+    /// Runs one playout: selects a leaf, expands it, and records its score
+    /// along the path. Without an evaluator, the leaf is scored by finishing
+    /// the game from one of its children with random moves.
+    fn iterate(
+        &mut self,
+        rng: &mut RandomEngine,
+        evaluator: Option<&(dyn Evaluator<SIDE_LENGTH> + Send + Sync)>,
+    ) {
+        let mut board = self.root;
+        let mut path = vec![0];
+        let mut index = 0;
+        while self.nodes[index].expanded && self.nodes[index].children > 0 {
+            index = self.select(index, evaluator.is_some());
+            board.make_move(self.nodes[index].mv);
+            path.push(index);
+        }
+        // the result for the player who moved into the leaf, from 0 to 1.
+        let score = match (board.outcome(), evaluator) {
+            (Some(winner), _) => Self::score(winner, board.turn()),
+            (None, Some(evaluator)) => {
+                let (value, policy) = evaluator
+                    .evaluate(&[board])
+                    .pop()
+                    .expect("an evaluator returns one evaluation per board");
+                if !self.nodes[index].expanded {
+                    let moves = board.candidate_moves(Some(2));
+                    self.expand(index, &moves);
+                    let total = moves.iter().map(|mv| policy[mv.index()]).sum::<f32>();
+                    for (child, mv) in self.children(index).zip(&moves) {
+                        self.nodes[child].prior = if total > 0.0 {
+                            f64::from(policy[mv.index()] / total)
+                        } else {
+                            1.0 / f64::from(u32::try_from(moves.len()).unwrap_or(u32::MAX))
+                        };
+                    }
+                }
+                (1.0 - f64::from(value)) / 2.0
+            }
+            (None, None) => {
+                if !self.nodes[index].expanded {
+                    let moves = board.candidate_moves(Some(2));
+                    self.expand(index, &moves);
+                    if let Some(&mv) = moves.first() {
+                        index = self.nodes[index].first_child;
+                        board.make_move(mv);
+                        path.push(index);
+                    }
+                }
+                Self::score(rollout(board, rng), board.turn())
+            }
+        };
+        // alternate the score between the players along the path.
+        let mut score = score;
+        for &index in path.iter().rev() {
+            let node = &mut self.nodes[index];
+            node.visits += 1;
+            node.value += score;
+            score = 1.0 - score;
+        }
+    }
+
+    /// The score of a game won by `winner` for the player who made the last
+    /// move, when `to_move` is to move.
+    fn score(winner: Player, to_move: Player) -> f64 {
+        match winner {
+            Player::None => 0.5,
+            winner if winner == to_move => 0.0,
+            _ => 1.0,
+        }
+    }
+
+    /// Adds `moves` as the children of `index`.
+    fn expand(&mut self, index: usize, moves: &[Move<SIDE_LENGTH>]) {
+        let first_child = self.nodes.len();
+        self.nodes.extend(moves.iter().map(|&mv| Node::new(mv)));
+        let node = &mut self.nodes[index];
+        node.first_child = first_child;
+        node.children = moves.len();
+        node.expanded = true;
+    }
+
+    /// The most visited move from the root.
+    fn best_move(&self) -> Move<SIDE_LENGTH> {
+        self.children(0)
+            .max_by_key(|&child| self.nodes[child].visits)
+            .map_or(Move::null(), |child| self.nodes[child].mv)
+    }
+
+    /// The line from `index` that follows the most visited child at each step.
+    fn line(&self, mut index: usize) -> Vec<Move<SIDE_LENGTH>> {
+        let mut line = vec![self.nodes[index].mv];
+        while let Some(child) = self
+            .children(index)
+            .filter(|&child| self.nodes[child].visits > 0)
+            .max_by_key(|&child| self.nodes[child].visits)
+        {
+            line.push(self.nodes[child].mv);
+            index = child;
+        }
+        line
+    }
+}
+
+/// One of the most visited lines of a Monte Carlo search.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MctsLine<const SIDE_LENGTH: usize> {
+    /// The number of playouts through the line's first move.
+    pub visits: u32,
+    /// The average result of those playouts for the side to move, from 0 for
+    /// a loss to 1 for a win.
+    pub value: f64,
+    /// The line, following the most visited move at each step.
+    pub pv: Vec<Move<SIDE_LENGTH>>,
+}
+
+/// Plays random moves near the existing stones until the game ends,
+/// returning the winner.
+pub(crate) fn rollout<const SIDE_LENGTH: usize>(
+    mut board: Board<SIDE_LENGTH>,
+    rng: &mut RandomEngine,
+) -> Player {
+    loop {
+        if let Some(winner) = board.outcome() {
+            return winner;
+        }
+        let moves = board.candidate_moves(Some(1));
+        if moves.is_empty() {
+            return Player::None;
+        }
+        board.make_move(moves[rng.next_in(0, moves.len())]);
+    }
+}
+
+/// Searches with UCT, expanding the moves near existing stones and scoring
+/// leaves with random playouts.
+///
+/// Given an [`Evaluator`] with [`MctsEngine::with_evaluator`], it scores
+/// leaves with the evaluator instead, and selects moves with PUCT, guided by
+/// the evaluator's policy.
+///
+/// [`Limits::nodes`] bounds the number of playouts, and [`Limits::depth`] is
+/// ignored.
+pub struct MctsEngine<const SIDE_LENGTH: usize> {
+    tree: Option<Tree<SIDE_LENGTH>>,
+    rng: RandomEngine,
+    evaluator: Option<SharedEvaluator<SIDE_LENGTH>>,
+    ponder: Option<Ponder<SIDE_LENGTH>>,
+}
+
+/// A search running on a background thread until `stop` is set.
+struct Ponder<const SIDE_LENGTH: usize> {
+    board: Board<SIDE_LENGTH>,
+    stop: Arc<AtomicBool>,
+    worker: JoinHandle<(Tree<SIDE_LENGTH>, RandomEngine)>,
+}
+
+impl<const SIDE_LENGTH: usize> MctsEngine<SIDE_LENGTH> {
+    /// Creates an engine whose playouts are determined by `seed`.
+    #[must_use]
+    pub const fn new(seed: u64) -> Self {
+        Self {
+            tree: None,
+            rng: RandomEngine::new(seed),
+            evaluator: None,
+            ponder: None,
+        }
+    }
+
+    /// Scores leaves with `evaluator` instead of random playouts, and uses
+    /// its policy to decide which moves to explore. The search tree kept
+    /// from earlier searches is discarded.
+    #[must_use]
+    pub fn with_evaluator(
+        mut self,
+        evaluator: impl Evaluator<SIDE_LENGTH> + Send + Sync + 'static,
+    ) -> Self {
+        self.ponder_stop();
+        self.tree = None;
+        self.evaluator = Some(Arc::new(evaluator));
+        self
+    }
+
+    /// The number of playouts through the root of the kept search tree, which
+    /// includes any reused from earlier searches and pondering.
+    #[must_use]
+    pub fn root_visits(&self) -> u32 {
+        self.tree.as_ref().map_or(0, |tree| tree.nodes[0].visits)
+    }
+
+    /// The `count` most visited moves in the kept search tree, most visited
+    /// first, each with the line that follows it.
+    #[must_use]
+    pub fn lines(&self, count: usize) -> Vec<MctsLine<SIDE_LENGTH>> {
+        let Some(tree) = &self.tree else {
+            return Vec::new();
+        };
+        let mut children = tree
+            .children(0)
+            .filter(|&child| tree.nodes[child].visits > 0)
+            .collect::<Vec<_>>();
+        children.sort_by_key(|&child| std::cmp::Reverse(tree.nodes[child].visits));
+        children
+            .into_iter()
+            .take(count)
+            .map(|child| {
+                let node = &tree.nodes[child];
+                MctsLine {
+                    visits: node.visits,
+                    value: node.value / f64::from(node.visits),
+                    pv: tree.line(child),
+                }
+            })
+            .collect()
+    }
+
+    /// The kept search tree in Graphviz DOT format, for seeing why the engine
+    /// prefers a move.
+    ///
+    /// The tree is cut off `depth` plies below the root, and only the `width`
+    /// most visited children of each node are shown. Each node is labelled
+    /// with its move, its number of playouts `N`, and its average result `Q`
+    /// for the player who made the move, from 0 for a loss to 1 for a win.
+    /// Render it with, for example, `dot -Tsvg tree.dot -o tree.svg`.
+    #[must_use]
+    pub fn dot(&self, depth: usize, width: usize) -> String {
+        use std::fmt::Write;
+
+        let mut out =
+            String::from("digraph mcts {\n    node [shape=box, fontname=\"monospace\"];\n");
+        let Some(tree) = &self.tree else {
+            out.push_str("}\n");
+            return out;
+        };
+        let _ = writeln!(out, "    n0 [label=\"root\\nN={}\"];", tree.nodes[0].visits);
+        let mut stack = vec![(0, 0)];
+        while let Some((index, ply)) = stack.pop() {
+            if ply == depth {
+                continue;
+            }
+            let mut children = tree
+                .children(index)
+                .filter(|&child| tree.nodes[child].visits > 0)
+                .collect::<Vec<_>>();
+            children.sort_by_key(|&child| std::cmp::Reverse(tree.nodes[child].visits));
+            children.truncate(width);
+            for &child in &children {
+                let node = &tree.nodes[child];
+                let _ = writeln!(
+                    out,
+                    "    n{child} [label=\"{}\\nN={}\\nQ={:.3}\"];\n    n{index} -> n{child};",
+                    node.mv,
+                    node.visits,
+                    node.value / f64::from(node.visits),
+                );
+            }
+            stack.extend(children.into_iter().rev().map(|child| (child, ply + 1)));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Stops pondering, returning the tree it grew and the position it was
+    /// pondering.
+    fn join_ponder(&mut self) -> Option<(Tree<SIDE_LENGTH>, Board<SIDE_LENGTH>)> {
+        let ponder = self.ponder.take()?;
+        ponder.stop.store(true, Ordering::Relaxed);
+        let (tree, rng) = ponder.worker.join().expect("the ponder thread panicked");
+        self.rng = rng;
+        Some((tree, ponder.board))
+    }
+}
+
+impl<const SIDE_LENGTH: usize> Default for MctsEngine<SIDE_LENGTH> {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl<const SIDE_LENGTH: usize> Engine<SIDE_LENGTH> for MctsEngine<SIDE_LENGTH> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "mcts", skip_all, fields(position = %board.fen()))
+    )]
+    fn best_move(&mut self, board: &Board<SIDE_LENGTH>, limits: &Limits) -> Move<SIDE_LENGTH> {
+        if let Some((tree, _)) = self.join_ponder() {
+            self.tree = Some(tree);
+        }
+        let start = Instant::now();
+        let deadline = limits.time_budget().map(|time| start + time);
+        let iterations = match (limits.nodes, deadline) {
+            (Some(nodes), _) => nodes,
+            (None, Some(_)) => u64::MAX,
+            (None, None) if limits.infinite => u64::MAX,
+            (None, None) => DEFAULT_ITERATIONS,
+        };
+        let mut tree = self
+            .tree
+            .take()
+            .map_or_else(|| Tree::new(*board), |tree| tree.reroot(board));
+        if board.outcome().is_some() {
+            return Move::null();
+        }
+        for i in 0..iterations {
+            // checking the clock is relatively slow, so only do it occasionally.
+            if tree.nodes.len() >= MAX_NODES
+                || (i.is_multiple_of(64)
+                    && (limits.is_stopped() || deadline.is_some_and(|d| Instant::now() >= d)))
+            {
+                break;
+            }
+            tree.iterate(&mut self.rng, self.evaluator.as_deref());
+        }
+        let mv = tree.best_move();
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            %mv,
+            visits = tree.nodes[0].visits,
+            tree_nodes = tree.nodes.len(),
+            time_ms = start.elapsed().as_millis(),
+            "search finished"
+        );
+        self.tree = Some(tree);
+        mv
+    }
+
+    fn ponder_start(&mut self, board: &Board<SIDE_LENGTH>) {
+        self.ponder_stop();
+        if board.outcome().is_some() {
+            return;
+        }
+        let mut tree = self
+            .tree
+            .take()
+            .map_or_else(|| Tree::new(*board), |tree| tree.reroot(board));
+        let mut rng = self.rng.clone();
+        let evaluator = self.evaluator.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker = {
+            let stop = Arc::clone(&stop);
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) && tree.nodes.len() < MAX_NODES {
+                    tree.iterate(&mut rng, evaluator.as_deref());
+                }
+                (tree, rng)
+            })
+        };
+        self.ponder = Some(Ponder {
+            board: *board,
+            stop,
+            worker,
+        });
+    }
+
+    fn ponder_hit(&mut self, mv: Move<SIDE_LENGTH>) {
+        if let Some((tree, board)) = self.join_ponder() {
+            self.tree = Some(tree.reroot(&board.with_move(mv)));
+        }
+    }
+
+    fn ponder_stop(&mut self) {
+        if self.join_ponder().is_some() {
+            self.tree = None;
+        }
+    }
+}
+
+impl<const SIDE_LENGTH: usize> Drop for MctsEngine<SIDE_LENGTH> {
+    fn drop(&mut self) {
+        self.join_ponder();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::MctsEngine;
+    use crate::{
+        board::{Board, Move},
+        engine::{Engine, Evaluator, Limits, StopFlag, UniformEvaluator},
+    };
+
+    #[test]
+    fn finds_wins_and_blocks() {
+        let mut engine = MctsEngine::new(1);
+        // X can win at once.
+        let moves = ["c5", "a1", "d5", "a9", "e5", "i1", "f5", "i9"]
+            .map(|mv| mv.parse::<Move<9>>().unwrap());
+        let board = Board::<9>::new().apply_moves(&moves).unwrap();
+        let mv = engine.best_move(&board, &Limits::nodes(200)).to_string();
+        assert!(mv == "B5" || mv == "G5", "unexpected move {mv}");
+        // O must block the four.
+        let board = Board::<9>::new().apply_moves(&moves[..7]).unwrap();
+        let mv = engine.best_move(&board, &Limits::nodes(200)).to_string();
+        assert!(mv == "B5" || mv == "G5", "unexpected move {mv}");
+    }
+
+    #[test]
+    fn reports_several_lines() {
+        let mut engine = MctsEngine::new(5);
+        let board = Board::<9>::new().with_move("e5".parse().unwrap());
+        let mv = engine.best_move(&board, &Limits::nodes(300));
+        let lines = engine.lines(3);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].pv[0], mv);
+        assert!(lines.windows(2).all(|w| w[0].visits >= w[1].visits));
+        assert!(lines.iter().all(|line| (0.0..=1.0).contains(&line.value)));
+        assert!(lines[0].pv.len() > 1);
+    }
+
+    #[test]
+    fn exports_the_tree_as_dot() {
+        let mut engine = MctsEngine::<9>::new(3);
+        assert_eq!(
+            engine.dot(2, 3),
+            "digraph mcts {\n    node [shape=box, fontname=\"monospace\"];\n}\n"
+        );
+        let board = Board::<9>::new().with_move("e5".parse().unwrap());
+        let mv = engine.best_move(&board, &Limits::nodes(500));
+        let dot = engine.dot(2, 3);
+        assert!(dot.starts_with("digraph mcts {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("n0 [label=\"root\\nN=500\"];"));
+        // three children of the root, and up to three of each of those.
+        let edges = dot.lines().filter(|line| line.contains("->")).count();
+        let from_root = dot
+            .lines()
+            .filter(|line| line.starts_with("    n0 ->"))
+            .count();
+        assert_eq!(from_root, 3);
+        assert!(edges > 3 && edges <= 3 + 9);
+        // the most visited move is shown first.
+        assert!(dot.lines().nth(3).unwrap().contains(&format!("\"{mv}\\n")));
+    }
+
+    #[test]
+    fn reuses_the_tree() {
+        let mut engine = MctsEngine::new(2);
+        let board = Board::<9>::new().with_move("e5".parse().unwrap());
+        let mv = engine.best_move(&board, &Limits::nodes(500));
+        assert!(engine.root_visits() >= 500);
+        // the opponent's reply is already in the tree, so its visits carry over.
+        let board = board.with_move(mv);
+        let reply = engine.best_move(&board, &Limits::nodes(1));
+        let board = board.with_move(reply);
+        engine.best_move(&board, &Limits::nodes(1));
+        assert!(engine.root_visits() > 1);
+        // an unrelated position starts afresh.
+        engine.best_move(&Board::new(), &Limits::nodes(10));
+        assert_eq!(engine.root_visits(), 10);
+    }
+
+    #[test]
+    fn stops_an_infinite_search() {
+        let mut engine = MctsEngine::new(6);
+        let board = Board::<9>::new().with_move("e5".parse().unwrap());
+        let stop = StopFlag::new();
+        let stopper = {
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                stop.stop();
+            })
+        };
+        let mv = engine.best_move(&board, &Limits::infinite(stop));
+        stopper.join().unwrap();
+        assert!(board.is_legal(mv));
+        assert!(engine.root_visits() > 0);
+    }
+
+    /// Evaluates every position as even, and likes one move above all others.
+    struct Favourite(Move<9>);
+
+    impl Evaluator<9> for Favourite {
+        fn evaluate(&self, boards: &[Board<9>]) -> Vec<(f32, Vec<f32>)> {
+            let mut policy = vec![0.01; 81];
+            policy[self.0.index()] = 1.0;
+            vec![(0.0, policy); boards.len()]
+        }
+    }
+
+    #[test]
+    fn searches_with_an_evaluator() {
+        let favourite = "d4".parse().unwrap();
+        let mut engine = MctsEngine::new(0).with_evaluator(Favourite(favourite));
+        let board = Board::<9>::new().with_move("e5".parse().unwrap());
+        assert_eq!(engine.best_move(&board, &Limits::nodes(100)), favourite);
+        assert_eq!(engine.root_visits(), 100);
+
+        // the evaluator's values don't stop it finding a forced win.
+        let mut engine = MctsEngine::new(0).with_evaluator(UniformEvaluator);
+        let moves = ["c5", "a1", "d5", "a9", "e5", "i1", "f5", "i9"]
+            .map(|mv| mv.parse::<Move<9>>().unwrap());
+        let board = Board::<9>::new().apply_moves(&moves).unwrap();
+        let mv = engine.best_move(&board, &Limits::nodes(50)).to_string();
+        assert!(mv == "B5" || mv == "G5", "unexpected move {mv}");
+    }
+
+    #[test]
+    fn ponders_on_the_opponents_time() {
+        let mut engine = MctsEngine::new(3);
+        let board = Board::<9>::new().with_move("e5".parse().unwrap());
+        engine.ponder_start(&board);
+        std::thread::sleep(Duration::from_millis(50));
+        engine.ponder_hit("d4".parse().unwrap());
+        let pondered = engine.root_visits();
+        assert!(pondered > 0);
+        let board = board.with_move("d4".parse().unwrap());
+        let mv = engine.best_move(&board, &Limits::nodes(10));
+        assert!(board.is_legal(mv));
+        assert_eq!(engine.root_visits(), pondered + 10);
+
+        engine.ponder_start(&board.with_move(mv));
+        engine.ponder_stop();
+        assert_eq!(engine.root_visits(), 0);
+    }
+}
@@ -0,0 +1,293 @@
+//! Engines that choose moves, behind a common [`Engine`] trait so that
+//! protocol adapters, match runners, and tools can be generic over them.
+
+use std::{
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::board::{Board, Move};
+
+pub mod evaluator;
+pub mod gomocup;
+pub mod heuristic;
+pub mod mcts;
+pub mod random;
+pub mod search;
+
+pub use evaluator::{Evaluator, UniformEvaluator};
+pub use gomocup::GomocupEngine;
+pub use heuristic::HeuristicEngine;
+pub use mcts::{MctsEngine, MctsLine};
+pub use random::RandomEngine;
+pub use search::{PvLine, SearchEngine, SearchInfo};
+
+/// A flag that asks a search to stop as soon as it can, which can be set
+/// from another thread while the search runs. Clones share the flag.
+#[derive(Clone, Debug, Default)]
+pub struct StopFlag(Arc<AtomicBool>);
+
+impl StopFlag {
+    /// Creates a flag that is not set.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks the searches watching the flag to stop.
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the flag is set.
+    #[must_use]
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Clears the flag, so it can be used for another search.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Flags are equal if they are clones of each other.
+impl PartialEq for StopFlag {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for StopFlag {}
+
+impl Hash for StopFlag {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state);
+    }
+}
+
+/// Bounds on how much work an engine or solver may do.
+///
+/// Every limit is optional, and a search stops at whichever it reaches first,
+/// or when its [`StopFlag`] is set. Engines that don't search ignore the
+/// limits. When playing under a time control, the clock fields describe the
+/// engine's clock, and [`Limits::time_budget`] suggests how long to spend on
+/// the move.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Limits {
+    /// The maximum search depth, in plies.
+    pub depth: Option<u8>,
+    /// The maximum number of positions to visit.
+    pub nodes: Option<u64>,
+    /// The maximum time to spend.
+    pub time: Option<Duration>,
+    /// The main time left on the engine's clock.
+    pub time_left: Option<Duration>,
+    /// The time added to the engine's clock after each move.
+    pub increment: Option<Duration>,
+    /// The length of a byo-yomi period, which may be spent on every move once
+    /// the main time has run out.
+    pub byo_yomi: Option<Duration>,
+    /// Whether to search until stopped, ignoring the clock and any default
+    /// limits of the engine. Without a `stop` flag or another limit, the
+    /// search may never end.
+    pub infinite: bool,
+    /// A flag to stop the search early.
+    pub stop: Option<StopFlag>,
+}
+
+impl Limits {
+    const UNLIMITED: Self = Self {
+        depth: None,
+        nodes: None,
+        time: None,
+        time_left: None,
+        increment: None,
+        byo_yomi: None,
+        infinite: false,
+        stop: None,
+    };
+
+    /// Limits the search to `depth` plies.
+    #[must_use]
+    pub const fn depth(depth: u8) -> Self {
+        Self {
+            depth: Some(depth),
+            ..Self::UNLIMITED
+        }
+    }
+
+    /// Limits the search to `nodes` positions.
+    #[must_use]
+    pub const fn nodes(nodes: u64) -> Self {
+        Self {
+            nodes: Some(nodes),
+            ..Self::UNLIMITED
+        }
+    }
+
+    /// Limits the search to `time`.
+    #[must_use]
+    pub const fn time(time: Duration) -> Self {
+        Self {
+            time: Some(time),
+            ..Self::UNLIMITED
+        }
+    }
+
+    /// Searches until `stop` is set.
+    #[must_use]
+    pub const fn infinite(stop: StopFlag) -> Self {
+        Self {
+            infinite: true,
+            stop: Some(stop),
+            ..Self::UNLIMITED
+        }
+    }
+
+    /// These limits, with `stop` to end the search early.
+    #[must_use]
+    pub fn with_stop(self, stop: StopFlag) -> Self {
+        Self {
+            stop: Some(stop),
+            ..self
+        }
+    }
+
+    /// Whether the stop flag has been set.
+    #[must_use]
+    pub fn is_stopped(&self) -> bool {
+        self.stop.as_ref().is_some_and(StopFlag::is_stopped)
+    }
+
+    /// How long to spend on this move: `time` if set, or a share of the
+    /// clock if smaller. With a clock, the share is a thirtieth of the main
+    /// time plus most of the increment or byo-yomi period. An infinite
+    /// search has no time limit.
+    #[must_use]
+    pub fn time_budget(&self) -> Option<Duration> {
+        if self.infinite {
+            return None;
+        }
+        let from_clock = self.time_left.map(|left| {
+            let increment = self.increment.unwrap_or_default();
+            let main = (left / 30 + increment * 3 / 4).min(left * 9 / 10);
+            main + self.byo_yomi.unwrap_or_default() * 9 / 10
+        });
+        match (self.time, from_clock) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+/// Something that can choose a move to play.
+///
+/// Engines may also think on the opponent's time. After choosing a move, the
+/// caller can start pondering on the resulting position, and must end it with
+/// either [`Engine::ponder_hit`] or [`Engine::ponder_stop`] before asking for
+/// the next move. Engines that don't ponder can ignore these methods.
+pub trait Engine<const SIDE_LENGTH: usize> {
+    /// Chooses a move to play in `board`, within `limits`.
+    ///
+    /// Returns the null move if there are no legal moves.
+    fn best_move(&mut self, board: &Board<SIDE_LENGTH>, limits: &Limits) -> Move<SIDE_LENGTH>;
+
+    /// Starts thinking in the background about `board`, in which the opponent
+    /// is to move, and returns immediately.
+    fn ponder_start(&mut self, board: &Board<SIDE_LENGTH>) {
+        let _ = board;
+    }
+
+    /// The opponent has played `mv` in the position being pondered. Stops
+    /// pondering, keeping anything useful for the next [`Engine::best_move`].
+    fn ponder_hit(&mut self, mv: Move<SIDE_LENGTH>) {
+        let _ = mv;
+    }
+
+    /// Stops pondering and discards its results.
+    fn ponder_stop(&mut self) {}
+}
+
+impl<const SIDE_LENGTH: usize, E: Engine<SIDE_LENGTH> + ?Sized> Engine<SIDE_LENGTH> for &mut E {
+    fn best_move(&mut self, board: &Board<SIDE_LENGTH>, limits: &Limits) -> Move<SIDE_LENGTH> {
+        (**self).best_move(board, limits)
+    }
+
+    fn ponder_start(&mut self, board: &Board<SIDE_LENGTH>) {
+        (**self).ponder_start(board);
+    }
+
+    fn ponder_hit(&mut self, mv: Move<SIDE_LENGTH>) {
+        (**self).ponder_hit(mv);
+    }
+
+    fn ponder_stop(&mut self) {
+        (**self).ponder_stop();
+    }
+}
+
+impl<const SIDE_LENGTH: usize, E: Engine<SIDE_LENGTH> + ?Sized> Engine<SIDE_LENGTH> for Box<E> {
+    fn best_move(&mut self, board: &Board<SIDE_LENGTH>, limits: &Limits) -> Move<SIDE_LENGTH> {
+        (**self).best_move(board, limits)
+    }
+
+    fn ponder_start(&mut self, board: &Board<SIDE_LENGTH>) {
+        (**self).ponder_start(board);
+    }
+
+    fn ponder_hit(&mut self, mv: Move<SIDE_LENGTH>) {
+        (**self).ponder_hit(mv);
+    }
+
+    fn ponder_stop(&mut self) {
+        (**self).ponder_stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Engine, HeuristicEngine, Limits, RandomEngine, SearchEngine};
+    use crate::board::{Board, Player};
+
+    fn play<const N: usize>(mut x: impl Engine<N>, mut o: impl Engine<N>) -> Option<Player> {
+        let mut board = Board::<N>::new();
+        let limits = Limits::depth(2);
+        while board.outcome().is_none() {
+            let mv = if board.turn() == Player::X {
+                x.best_move(&board, &limits)
+            } else {
+                o.best_move(&board, &limits)
+            };
+            assert!(board.is_legal(mv));
+            board.make_move(mv);
+        }
+        board.outcome()
+    }
+
+    #[test]
+    fn engines_are_interchangeable() {
+        let engines: Vec<Box<dyn Engine<9>>> = vec![
+            Box::new(RandomEngine::new(1)),
+            Box::new(HeuristicEngine::new()),
+            Box::new(SearchEngine::new()),
+        ];
+        for mut engine in engines {
+            let board = Board::<9>::new();
+            assert!(board.is_legal(engine.best_move(&board, &Limits::default())));
+        }
+        // searching engines beat random play.
+        assert_eq!(
+            play::<9>(HeuristicEngine::new(), RandomEngine::new(7)),
+            Some(Player::X)
+        );
+        assert_eq!(
+            play::<9>(RandomEngine::new(7), SearchEngine::new()),
+            Some(Player::O)
+        );
+    }
+}
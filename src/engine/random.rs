@@ -0,0 +1,53 @@
+//! An engine that plays uniformly random legal moves.
+
+use super::{Engine, Limits};
+use crate::board::{Board, Move};
+
+/// Plays uniformly random legal moves, from a seeded xorshift generator.
+#[derive(Clone, Debug)]
+pub struct RandomEngine {
+    state: u64,
+}
+
+impl RandomEngine {
+    /// Creates an engine whose moves are determined by `seed`.
+    #[must_use]
+    pub const fn new(seed: u64) -> Self {
+        // xorshift gets stuck at zero, so mix the seed with a nonzero constant.
+        Self {
+            state: seed ^ 0x2545_F491_4F6C_DD1D,
+        }
+    }
+
+    /// Returns a value in `lo..hi`, as expected by [`Board::make_random_move`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is empty.
+    pub fn next_in(&mut self, lo: usize, hi: usize) -> usize {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        lo + usize::try_from(self.state % (hi - lo) as u64).unwrap()
+    }
+}
+
+impl Default for RandomEngine {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl<const SIDE_LENGTH: usize> Engine<SIDE_LENGTH> for RandomEngine {
+    fn best_move(&mut self, board: &Board<SIDE_LENGTH>, _: &Limits) -> Move<SIDE_LENGTH> {
+        let mut moves = Vec::new();
+        board.generate_moves(|mv| {
+            moves.push(mv);
+            false
+        });
+        if moves.is_empty() {
+            return Move::null();
+        }
+        moves[self.next_in(0, moves.len())]
+    }
+}
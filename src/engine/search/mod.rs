@@ -0,0 +1,867 @@
+//! An iteratively deepened alpha-beta search engine.
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use super::{Engine, Limits, StopFlag};
+use crate::{
+    board::{Board, Move, Player},
+    eval::{evaluate, WIN_SCORE},
+};
+
+mod ordering;
+pub mod tt;
+
+use ordering::MoveOrdering;
+pub use ordering::SearchStats;
+pub use tt::{Bound, SearchTable, TtEntry};
+
+/// The depth searched when no limit is given.
+const DEFAULT_DEPTH: u8 = 3;
+
+/// The deepest iteration searched.
+const MAX_DEPTH: u8 = 64;
+
+/// A bound beyond any score.
+const INFINITY: i32 = WIN_SCORE + 1;
+
+/// The half-width of the first aspiration window.
+const ASPIRATION_WINDOW: i32 = 32;
+
+/// How many plies shallower the search after a null move is.
+const NULL_MOVE_REDUCTION: u8 = 2;
+
+/// The shallowest depth at which null moves are tried.
+const NULL_MOVE_MIN_DEPTH: u8 = 3;
+
+/// A report on the progress of a search.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchInfo<const SIDE_LENGTH: usize> {
+    /// The depth being searched, in plies.
+    pub depth: u8,
+    /// The number of positions visited so far.
+    pub nodes: u64,
+    /// The time spent so far.
+    pub time: Duration,
+    /// The score of the best line, from the perspective of the side to move.
+    pub score: i32,
+    /// The best line found, starting with the move to play.
+    pub pv: Vec<Move<SIDE_LENGTH>>,
+    /// How full the transposition table is, in entries per thousand.
+    pub hashfull: u16,
+    /// The best lines found, best first, when searching for more than one.
+    /// The first is the same as `score` and `pv`.
+    pub lines: Vec<PvLine<SIDE_LENGTH>>,
+}
+
+/// One of the best lines found by a search for several.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PvLine<const SIDE_LENGTH: usize> {
+    /// The score of the line, from the perspective of the side to move.
+    pub score: i32,
+    /// The line, starting with the move it scores.
+    pub pv: Vec<Move<SIDE_LENGTH>>,
+}
+
+impl<const SIDE_LENGTH: usize> SearchInfo<SIDE_LENGTH> {
+    /// The number of positions visited per second.
+    #[must_use]
+    pub fn nps(&self) -> u64 {
+        #![allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            clippy::cast_precision_loss
+        )]
+        (self.nodes as f64 / self.time.as_secs_f64().max(1e-9)) as u64
+    }
+}
+
+/// Switches for the search's heuristics, mainly to measure their effect,
+/// and the number of threads to search with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SearchOptions {
+    /// Whether to search moves that caused a cutoff at the same ply first.
+    pub killers: bool,
+    /// Whether to order moves by how often they have caused cutoffs.
+    pub history: bool,
+    /// Whether to prune positions that are good enough even if the side to
+    /// move passes. This rarely misses anything, but can be turned off to
+    /// rule it out when debugging.
+    pub null_move: bool,
+    /// The number of threads to search with. With one thread, the search is
+    /// deterministic.
+    pub threads: usize,
+    /// The number of best lines to find, each with its own score. Searching
+    /// for more than one is slower, but shows the alternatives to the best
+    /// move.
+    pub multi_pv: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            killers: true,
+            history: true,
+            null_move: true,
+            threads: 1,
+            multi_pv: 1,
+        }
+    }
+}
+
+type InfoCallback<const SIDE_LENGTH: usize> = Box<dyn FnMut(&SearchInfo<SIDE_LENGTH>) + Send>;
+
+/// Searches the moves near existing stones with iteratively deepened
+/// principal variation search, scoring the leaves with the
+/// [static evaluation](crate::eval).
+///
+/// Wins are scored so that faster wins and slower losses are preferred.
+/// Results are cached in a [`SearchTable`], which is kept between searches.
+///
+/// With more than one [thread](SearchOptions::threads), the search is
+/// parallelised with Lazy SMP: helper threads search the same position
+/// independently, sharing only the table, and the main thread's result is
+/// played. The node limit applies to the main thread, and the helpers stop
+/// when it does.
+#[derive(Default)]
+pub struct SearchEngine<const SIDE_LENGTH: usize> {
+    options: SearchOptions,
+    nodes: u64,
+    on_info: Option<InfoCallback<SIDE_LENGTH>>,
+    iterations: Vec<SearchInfo<SIDE_LENGTH>>,
+    tt: SearchTable<SIDE_LENGTH>,
+    ordering: MoveOrdering<SIDE_LENGTH>,
+}
+
+impl<const SIDE_LENGTH: usize> SearchEngine<SIDE_LENGTH> {
+    /// Creates a new search engine.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The options the search uses.
+    #[must_use]
+    pub const fn options(&self) -> SearchOptions {
+        self.options
+    }
+
+    /// Changes the options the search uses.
+    pub const fn set_options(&mut self, options: SearchOptions) {
+        self.options = options;
+    }
+
+    /// Replaces the transposition table with one using roughly `megabytes`
+    /// megabytes of memory.
+    pub fn set_hash_size_mb(&mut self, megabytes: usize) {
+        self.tt = SearchTable::with_size_mb(megabytes);
+    }
+
+    /// Forgets the results of earlier searches.
+    pub fn clear_hash(&mut self) {
+        self.tt.clear();
+    }
+
+    /// How full the transposition table is, in entries per thousand.
+    #[must_use]
+    pub fn hashfull(&self) -> u16 {
+        self.tt.hashfull()
+    }
+
+    /// Calls `callback` with a [`SearchInfo`] after each iteration of the
+    /// search. To receive the reports on another thread, send them down a
+    /// channel from the callback.
+    pub fn on_info(&mut self, callback: impl FnMut(&SearchInfo<SIDE_LENGTH>) + Send + 'static) {
+        self.on_info = Some(Box::new(callback));
+    }
+
+    /// The number of positions visited by the last search, by all threads.
+    #[must_use]
+    pub const fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// The cutoff counts of the main thread in the last search.
+    #[must_use]
+    pub const fn stats(&self) -> SearchStats {
+        self.ordering.stats
+    }
+
+    /// The best lines found by the last search, best first, as many as
+    /// [`SearchOptions::multi_pv`] asks for.
+    #[must_use]
+    pub fn lines(&self) -> &[PvLine<SIDE_LENGTH>] {
+        self.iterations.last().map_or(&[], |info| &info.lines)
+    }
+
+    /// The results of each completed iteration of the last search.
+    #[must_use]
+    pub fn iterations(&self) -> &[SearchInfo<SIDE_LENGTH>] {
+        &self.iterations
+    }
+}
+
+/// The state of one search thread.
+struct Worker<'a, const SIDE_LENGTH: usize> {
+    tt: &'a SearchTable<SIDE_LENGTH>,
+    /// Set when the main thread finishes, to stop the helpers.
+    abort: &'a AtomicBool,
+    /// The caller's flag to stop the search.
+    stop: Option<&'a StopFlag>,
+    ordering: MoveOrdering<SIDE_LENGTH>,
+    nodes: u64,
+    /// The number of transposition table probes that found an entry.
+    tt_hits: u64,
+    node_limit: Option<u64>,
+    deadline: Option<Instant>,
+    stopped: bool,
+    /// Moves not to search at the root.
+    excluded: Vec<Move<SIDE_LENGTH>>,
+    null_move: bool,
+    /// A ply at which not to try a null move, because the one before was
+    /// a null move or the search there is verifying one.
+    no_null_at: Option<i32>,
+}
+
+impl<const SIDE_LENGTH: usize> Worker<'_, SIDE_LENGTH> {
+    /// A helper thread's worker for the same search, with its own move
+    /// ordering. Helpers have no node limit, as they stop with the main thread.
+    fn helper(&self) -> Self {
+        let mut ordering = MoveOrdering::default();
+        ordering.killers_enabled = self.ordering.killers_enabled;
+        ordering.history_enabled = self.ordering.history_enabled;
+        Self {
+            ordering,
+            nodes: 0,
+            tt_hits: 0,
+            node_limit: None,
+            stopped: false,
+            excluded: Vec::new(),
+            no_null_at: None,
+            ..*self
+        }
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if !self.stopped {
+            // checking the clock is relatively slow, so only do it occasionally.
+            self.stopped = self.node_limit.is_some_and(|limit| self.nodes >= limit)
+                || self.abort.load(Ordering::Relaxed)
+                || self.stop.is_some_and(StopFlag::is_stopped)
+                || (self.nodes.is_multiple_of(1024)
+                    && self
+                        .deadline
+                        .is_some_and(|deadline| Instant::now() >= deadline));
+        }
+        self.stopped
+    }
+
+    /// Searches `board` to `depth` with principal variation search,
+    /// returning its score and setting `pv` to the best line if the score is
+    /// within the window. `hint` is the previous iteration's best line from
+    /// this position, if it passed through it, and is searched first.
+    fn negamax(
+        &mut self,
+        board: &Board<SIDE_LENGTH>,
+        depth: u8,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move<SIDE_LENGTH>>,
+        hint: &[Move<SIDE_LENGTH>],
+    ) -> i32 {
+        #![allow(clippy::too_many_arguments, clippy::too_many_lines)]
+        pv.clear();
+        self.nodes += 1;
+        match board.outcome() {
+            Some(Player::None) => return 0,
+            // the side to move has lost.
+            Some(_) => return -WIN_SCORE + ply,
+            None => {}
+        }
+        if self.should_stop() || depth == 0 {
+            return evaluate(board);
+        }
+        let key = board.zobrist_key();
+        let entry = self.tt.probe(key, ply);
+        self.tt_hits += u64::from(entry.is_some());
+        // cutting off on a stored score would cut the principal variation
+        // short, so only do it with a null window.
+        if let Some(entry) = entry.filter(|e| e.depth >= depth && beta - alpha == 1) {
+            if entry.bound == Bound::Exact
+                || (entry.bound == Bound::Lower && entry.score >= beta)
+                || (entry.bound == Bound::Upper && entry.score <= alpha)
+            {
+                return entry.score;
+            }
+        }
+        let mut moves = board.candidate_moves(Some(2));
+        if ply == 0 {
+            moves.retain(|mv| !self.excluded.contains(mv));
+        }
+        if moves.is_empty() {
+            return 0;
+        }
+        if self.null_move_cutoff(board, depth, ply, beta, &moves) {
+            return beta;
+        }
+        // search the previous iteration's move first, or else the stored one.
+        let first = hint.first().copied().or_else(|| entry.map(|e| e.best_move));
+        let ply_index = usize::try_from(ply).unwrap_or(0);
+        self.ordering
+            .order(&mut moves, first, ply_index, board.turn());
+        let original_alpha = alpha;
+        let mut best = (Move::null(), -WIN_SCORE);
+        let mut child_pv = Vec::new();
+        for (i, &mv) in moves.iter().enumerate() {
+            let child = board.with_move(mv);
+            let hint = match hint {
+                [first, rest @ ..] if i == 0 && *first == mv => rest,
+                _ => &[],
+            };
+            // later moves are expected to be worse, so first try to prove it
+            // with a null window, and search properly only if that fails.
+            let null_window = (i > 0).then(|| {
+                -self.negamax(
+                    &child,
+                    depth - 1,
+                    ply + 1,
+                    -alpha - 1,
+                    -alpha,
+                    &mut child_pv,
+                    &[],
+                )
+            });
+            let score = match null_window {
+                Some(score) if score <= alpha || score >= beta || self.stopped => score,
+                _ => -self.negamax(
+                    &child,
+                    depth - 1,
+                    ply + 1,
+                    -beta,
+                    -alpha,
+                    &mut child_pv,
+                    hint,
+                ),
+            };
+            if score > best.1 || best.0.is_null() {
+                best = (mv, score.max(best.1));
+            }
+            if score > alpha {
+                alpha = score;
+                pv.clear();
+                pv.push(mv);
+                pv.extend_from_slice(&child_pv);
+            }
+            if alpha >= beta {
+                self.ordering.cutoff(mv, i, ply_index, depth, board.turn());
+                break;
+            }
+            if self.stopped {
+                break;
+            }
+        }
+        // results of a search cut short by the limits can't be trusted, nor
+        // can those of a root search that excluded some moves.
+        if !self.stopped && (ply > 0 || self.excluded.is_empty()) {
+            let bound = match best.1 {
+                score if score <= original_alpha => Bound::Upper,
+                score if score >= beta => Bound::Lower,
+                _ => Bound::Exact,
+            };
+            let (best_move, score) = best;
+            self.tt.store(
+                key,
+                ply,
+                TtEntry {
+                    score,
+                    bound,
+                    depth,
+                    best_move,
+                },
+            );
+        }
+        best.1
+    }
+
+    /// Whether passing in `board`, where `moves` are the moves to search,
+    /// still scores at least `beta` in a shallower search, so that the node
+    /// can be pruned.
+    ///
+    /// Passing is skipped in positions with an immediate win or threat, where
+    /// a move is forced and a pass would be meaningless. A pass that holds is
+    /// verified with a shallow search of the real moves, to guard against
+    /// positions where every move makes things worse.
+    fn null_move_cutoff(
+        &mut self,
+        board: &Board<SIDE_LENGTH>,
+        depth: u8,
+        ply: i32,
+        beta: i32,
+        moves: &[Move<SIDE_LENGTH>],
+    ) -> bool {
+        // in a forced position, the candidate moves are all wins or blocks.
+        let forced = moves.first().is_some_and(|&mv| {
+            board.is_winning_move(mv, Player::X) || board.is_winning_move(mv, Player::O)
+        });
+        if !self.null_move
+            || ply == 0
+            || depth < NULL_MOVE_MIN_DEPTH
+            || self.no_null_at == Some(ply)
+            || beta.abs() >= WIN_SCORE - i32::from(MAX_DEPTH)
+            || forced
+            || evaluate(board) < beta
+        {
+            return false;
+        }
+        let reduced = depth - NULL_MOVE_REDUCTION;
+        let mut passed = *board;
+        passed.make_null_move();
+        let mut pv = Vec::new();
+        let saved = self.no_null_at.replace(ply + 1);
+        let score = -self.negamax(
+            &passed,
+            reduced - 1,
+            ply + 1,
+            -beta,
+            -beta + 1,
+            &mut pv,
+            &[],
+        );
+        self.no_null_at = Some(ply);
+        let holds = score >= beta
+            && !self.stopped
+            && self.negamax(board, reduced, ply, beta - 1, beta, &mut pv, &[]) >= beta;
+        self.no_null_at = saved;
+        holds && !self.stopped
+    }
+
+    /// Searches the root to `depth`, starting with a narrow window around
+    /// `guess` and widening it until the score falls inside.
+    fn aspiration(
+        &mut self,
+        board: &Board<SIDE_LENGTH>,
+        depth: u8,
+        guess: Option<i32>,
+        hint: &[Move<SIDE_LENGTH>],
+    ) -> (i32, Vec<Move<SIDE_LENGTH>>) {
+        let mut pv = Vec::new();
+        // windows around a win or loss are pointless, as its score is exact.
+        let Some(guess) = guess.filter(|guess| guess.abs() < WIN_SCORE - i32::from(MAX_DEPTH))
+        else {
+            let score = self.negamax(board, depth, 0, -INFINITY, INFINITY, &mut pv, hint);
+            return (score, pv);
+        };
+        let mut delta = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = (guess - delta, guess + delta);
+        loop {
+            let score = self.negamax(board, depth, 0, alpha, beta, &mut pv, hint);
+            if self.stopped {
+                return (score, pv);
+            }
+            delta = delta.saturating_mul(4);
+            if score <= alpha {
+                alpha = score.saturating_sub(delta).max(-INFINITY);
+            } else if score >= beta {
+                beta = score.saturating_add(delta).min(INFINITY);
+            } else {
+                return (score, pv);
+            }
+        }
+    }
+
+    /// Deepens the search of `board` one ply at a time from `first_depth` to
+    /// `max_depth`, calling `on_iteration` with the depth and the best lines
+    /// of each completed iteration, and returns the best lines found.
+    ///
+    /// Each iteration finds up to `multi_pv` lines, each searched with the
+    /// first moves of the earlier lines excluded.
+    fn deepen(
+        &mut self,
+        board: &Board<SIDE_LENGTH>,
+        first_depth: u8,
+        max_depth: u8,
+        multi_pv: usize,
+        mut on_iteration: impl FnMut(&Self, u8, &[PvLine<SIDE_LENGTH>]) -> bool,
+    ) -> Vec<PvLine<SIDE_LENGTH>> {
+        let multi_pv = multi_pv.clamp(1, board.candidate_moves(Some(2)).len().max(1));
+        let mut lines: Vec<PvLine<SIDE_LENGTH>> = Vec::new();
+        for depth in first_depth..=max_depth {
+            let mut new_lines = Vec::with_capacity(multi_pv);
+            self.excluded.clear();
+            for i in 0..multi_pv {
+                let previous = lines.get(i);
+                let (score, pv) = self.aspiration(
+                    board,
+                    depth,
+                    previous.map(|line| line.score),
+                    previous.map_or(&[], |line| &line.pv),
+                );
+                if pv.is_empty() && !self.stopped {
+                    break;
+                }
+                self.excluded.extend(pv.first());
+                new_lines.push(PvLine { score, pv });
+                if self.stopped {
+                    break;
+                }
+            }
+            self.excluded.clear();
+            if self.stopped {
+                // a cut-short iteration is only better than nothing.
+                if lines.is_empty() {
+                    lines = new_lines;
+                    lines.retain(|line| !line.pv.is_empty());
+                }
+                break;
+            }
+            new_lines.sort_by_key(|line| std::cmp::Reverse(line.score));
+            lines = new_lines;
+            if !on_iteration(self, depth, &lines) {
+                break;
+            }
+        }
+        lines
+    }
+}
+
+impl<const SIDE_LENGTH: usize> Engine<SIDE_LENGTH> for SearchEngine<SIDE_LENGTH> {
+    /// Searches one ply deeper at a time, up to the depth limit, reporting
+    /// each completed iteration. Without a depth limit, it searches to
+    /// [`DEFAULT_DEPTH`] unless a node or time limit is given or the search
+    /// is infinite, in which case it keeps deepening until stopped.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "search", skip_all, fields(position = %board.fen()))
+    )]
+    fn best_move(&mut self, board: &Board<SIDE_LENGTH>, limits: &Limits) -> Move<SIDE_LENGTH> {
+        #![allow(clippy::too_many_lines)]
+        let start = Instant::now();
+        let budget = limits.time_budget();
+        let deadline = budget.map(|time| start + time);
+        self.iterations.clear();
+        let max_depth = limits.depth.map_or_else(
+            || {
+                if limits.infinite || limits.nodes.is_some() || budget.is_some() {
+                    MAX_DEPTH
+                } else {
+                    DEFAULT_DEPTH
+                }
+            },
+            |depth| depth.clamp(1, MAX_DEPTH),
+        );
+        let options = self.options;
+        self.ordering.killers_enabled = options.killers;
+        self.ordering.history_enabled = options.history;
+        self.ordering.new_search();
+
+        let abort = AtomicBool::new(false);
+        let tt = &self.tt;
+        let mut main = Worker {
+            tt,
+            abort: &abort,
+            stop: limits.stop.as_ref(),
+            ordering: std::mem::take(&mut self.ordering),
+            nodes: 0,
+            tt_hits: 0,
+            node_limit: limits.nodes,
+            deadline,
+            stopped: false,
+            excluded: Vec::new(),
+            null_move: options.null_move,
+            no_null_at: None,
+        };
+        let helpers = (1..options.threads)
+            .map(|_| main.helper())
+            .collect::<Vec<_>>();
+        let iterations = &mut self.iterations;
+        let on_info = &mut self.on_info;
+        let (lines, helper_nodes) = std::thread::scope(|scope| {
+            let helpers = helpers
+                .into_iter()
+                .zip(1..)
+                .map(|(mut helper, id)| {
+                    scope.spawn(move || {
+                        // start half of the helpers a ply deeper, so that the
+                        // threads spread out over different depths.
+                        let first_depth = 1 + u8::from(id % 2 == 1);
+                        helper.deepen(board, first_depth, MAX_DEPTH, 1, |_, _, _| true);
+                        helper.nodes
+                    })
+                })
+                .collect::<Vec<_>>();
+            let lines = main.deepen(
+                board,
+                1,
+                max_depth,
+                options.multi_pv,
+                |main, depth, lines| {
+                    let info = SearchInfo {
+                        depth,
+                        nodes: main.nodes,
+                        time: start.elapsed(),
+                        score: lines[0].score,
+                        pv: lines[0].pv.clone(),
+                        hashfull: tt.hashfull(),
+                        lines: lines.to_vec(),
+                    };
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(
+                        depth,
+                        nodes = main.nodes,
+                        tt_hits = main.tt_hits,
+                        score = info.score,
+                        time_ms = info.time.as_millis(),
+                        pv = %info.pv.first().copied().unwrap_or_else(Move::null),
+                        "search iteration"
+                    );
+                    if let Some(callback) = on_info {
+                        callback(&info);
+                    }
+                    iterations.push(info);
+                    // the next iteration will take longer than all the previous
+                    // ones, so don't start it without a good chance of finishing.
+                    budget.is_none_or(|budget| start.elapsed() * 2 < budget)
+                },
+            );
+            abort.store(true, Ordering::Relaxed);
+            let helper_nodes = helpers
+                .into_iter()
+                .map(|helper| helper.join().expect("a search thread panicked"))
+                .sum::<u64>();
+            (lines, helper_nodes)
+        });
+        self.nodes = main.nodes + helper_nodes;
+        self.ordering = main.ordering;
+        let mv = lines
+            .first()
+            .and_then(|line| line.pv.first())
+            .copied()
+            .or_else(|| board.candidate_moves(Some(2)).first().copied())
+            .unwrap_or_else(Move::null);
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            %mv,
+            nodes = self.nodes,
+            tt_hits = main.tt_hits,
+            threads = options.threads,
+            time_ms = start.elapsed().as_millis(),
+            "search finished"
+        );
+        mv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::mpsc,
+        time::{Duration, Instant},
+    };
+
+    use super::{SearchEngine, SearchOptions};
+    use crate::{
+        board::{Board, Move},
+        engine::{Engine, Limits, StopFlag},
+    };
+
+    #[test]
+    fn finds_wins_and_blocks() {
+        let moves = ["f8", "a1", "g8", "a15", "h8", "o1"].map(|mv| mv.parse::<Move<15>>().unwrap());
+        let board = Board::<15>::new().apply_moves(&moves).unwrap();
+        let mut engine = SearchEngine::new();
+        // X extends the open three to an open four.
+        let mv = engine.best_move(&board, &Limits::depth(2)).to_string();
+        assert!(mv == "E8" || mv == "I8", "unexpected move {mv}");
+        // O must block the closed four.
+        let moves = ["f8", "e8", "g8", "a1", "h8", "a15", "i8"].map(|mv| mv.parse().unwrap());
+        let board = Board::<15>::new().apply_moves(&moves).unwrap();
+        assert_eq!(
+            engine.best_move(&board, &Limits::depth(2)).to_string(),
+            "J8"
+        );
+        assert!(engine.nodes() > 0);
+    }
+
+    #[test]
+    fn respects_node_limit() {
+        let mut engine = SearchEngine::new();
+        let board = Board::<15>::new().with_move("h8".parse().unwrap());
+        let mv = engine.best_move(&board, &Limits::nodes(100));
+        assert!(board.is_legal(mv));
+        // the search stops as soon as the limit is noticed.
+        assert!(engine.nodes() <= 100 + 3);
+    }
+
+    #[test]
+    fn deepens_iteratively() {
+        let mut engine = SearchEngine::<15>::new();
+        let moves = ["h8", "h9", "i9"].map(|mv| mv.parse().unwrap());
+        let board = Board::<15>::new().apply_moves(&moves).unwrap();
+        let mv = engine.best_move(&board, &Limits::depth(3));
+        let iterations = engine.iterations();
+        assert_eq!(
+            iterations.iter().map(|info| info.depth).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+        assert!(iterations.windows(2).all(|w| w[0].nodes < w[1].nodes));
+        assert_eq!(iterations[2].pv[0], mv);
+        assert_eq!(iterations[2].pv.len(), 3);
+
+        // with only a time limit, it deepens until the time runs out.
+        let mv = engine.best_move(&board, &Limits::time(Duration::from_millis(100)));
+        assert!(board.is_legal(mv));
+        assert!(!engine.iterations().is_empty());
+    }
+
+    #[test]
+    fn stops_an_infinite_search() {
+        let mut engine = SearchEngine::<15>::new();
+        let moves = ["h8", "h9", "i9"].map(|mv| mv.parse().unwrap());
+        let board = Board::<15>::new().apply_moves(&moves).unwrap();
+        let stop = StopFlag::new();
+        let stopper = {
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                stop.stop();
+            })
+        };
+        let start = Instant::now();
+        let mv = engine.best_move(&board, &Limits::infinite(stop));
+        stopper.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(board.is_legal(mv));
+    }
+
+    #[test]
+    fn reuses_the_transposition_table() {
+        let mut engine = SearchEngine::<15>::new();
+        assert_eq!(engine.hashfull(), 0);
+        let moves = ["h8", "h9", "i9", "g7"].map(|mv| mv.parse().unwrap());
+        let board = Board::<15>::new().apply_moves(&moves).unwrap();
+        let mv = engine.best_move(&board, &Limits::depth(3));
+        let first = engine.nodes();
+        assert!(engine.hashfull() > 0);
+        assert_eq!(engine.iterations()[2].hashfull, engine.hashfull());
+        // searching again finds the stored results.
+        assert_eq!(engine.best_move(&board, &Limits::depth(3)), mv);
+        assert!(engine.nodes() < first);
+        engine.clear_hash();
+        assert_eq!(engine.hashfull(), 0);
+    }
+
+    #[test]
+    fn move_ordering_saves_nodes() {
+        let moves = ["h8", "h9", "i9", "g7", "j10"].map(|mv| mv.parse().unwrap());
+        let board = Board::<15>::new().apply_moves(&moves).unwrap();
+        let mut plain = SearchEngine::<15>::new();
+        plain.set_options(SearchOptions {
+            killers: false,
+            history: false,
+            ..SearchOptions::default()
+        });
+        plain.best_move(&board, &Limits::depth(3));
+        assert_eq!(plain.stats().killer_cutoffs, 0);
+        let mut ordered = SearchEngine::<15>::new();
+        assert_eq!(ordered.options(), SearchOptions::default());
+        ordered.best_move(&board, &Limits::depth(3));
+        let stats = ordered.stats();
+        assert!(stats.killer_cutoffs > 0);
+        assert!(stats.first_move_rate().unwrap() > plain.stats().first_move_rate().unwrap());
+        assert!(
+            ordered.nodes() < plain.nodes(),
+            "{} >= {}",
+            ordered.nodes(),
+            plain.nodes()
+        );
+    }
+
+    #[test]
+    fn null_move_pruning_saves_nodes() {
+        let moves = ["h8", "h9", "i9", "g7", "j10"].map(|mv| mv.parse().unwrap());
+        let board = Board::<15>::new().apply_moves(&moves).unwrap();
+        let mut full = SearchEngine::<15>::new();
+        full.set_options(SearchOptions {
+            null_move: false,
+            ..SearchOptions::default()
+        });
+        let expected = full.best_move(&board, &Limits::depth(4));
+        let mut pruned = SearchEngine::<15>::new();
+        let mv = pruned.best_move(&board, &Limits::depth(4));
+        assert!(
+            pruned.nodes() < full.nodes(),
+            "{} >= {}",
+            pruned.nodes(),
+            full.nodes()
+        );
+        assert_eq!(mv, expected);
+    }
+
+    #[test]
+    fn searches_with_several_threads() {
+        let mut engine = SearchEngine::<15>::new();
+        engine.set_options(SearchOptions {
+            threads: 4,
+            ..SearchOptions::default()
+        });
+        // O must block the closed four.
+        let moves = ["f8", "e8", "g8", "a1", "h8", "a15", "i8"].map(|mv| mv.parse().unwrap());
+        let board = Board::<15>::new().apply_moves(&moves).unwrap();
+        let mv = engine.best_move(&board, &Limits::depth(2));
+        assert_eq!(mv.to_string(), "J8");
+        // the helpers' nodes are counted too.
+        let main_nodes = engine.iterations().last().unwrap().nodes;
+        assert!(engine.nodes() > main_nodes);
+        let moves = ["h8", "h9", "i9"].map(|mv| mv.parse().unwrap());
+        let board = Board::<15>::new().apply_moves(&moves).unwrap();
+        let mv = engine.best_move(&board, &Limits::nodes(2000));
+        assert!(board.is_legal(mv));
+    }
+
+    #[test]
+    fn finds_several_lines() {
+        let mut engine = SearchEngine::<15>::new();
+        engine.set_options(SearchOptions {
+            multi_pv: 3,
+            ..SearchOptions::default()
+        });
+        let moves = ["h8", "h9", "i9"].map(|mv| mv.parse().unwrap());
+        let board = Board::<15>::new().apply_moves(&moves).unwrap();
+        let mv = engine.best_move(&board, &Limits::depth(2));
+        let lines = engine.lines();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].pv[0], mv);
+        assert!(lines.windows(2).all(|w| w[0].score >= w[1].score));
+        assert!(lines[0].pv[0] != lines[1].pv[0] && lines[1].pv[0] != lines[2].pv[0]);
+        // the best line is the one a single-line search finds.
+        let mut single = SearchEngine::<15>::new();
+        assert_eq!(single.best_move(&board, &Limits::depth(2)), mv);
+        assert_eq!(single.lines()[0].score, lines[0].score);
+        // only one move is worth considering when the opponent threatens to win.
+        let moves = ["f8", "e8", "g8", "a1", "h8", "a15", "i8"].map(|mv| mv.parse().unwrap());
+        let board = Board::<15>::new().apply_moves(&moves).unwrap();
+        engine.best_move(&board, &Limits::depth(2));
+        assert_eq!(engine.lines().len(), 1);
+    }
+
+    #[test]
+    fn reports_progress() {
+        let (sender, receiver) = mpsc::channel();
+        let mut engine = SearchEngine::<15>::new();
+        engine.on_info(move |info| sender.send(info.clone()).unwrap());
+        // X to move can win at once.
+        let moves =
+            ["f8", "a1", "g8", "a15", "h8", "o1", "i8", "o15"].map(|mv| mv.parse().unwrap());
+        let board = Board::<15>::new().apply_moves(&moves).unwrap();
+        let mv = engine.best_move(&board, &Limits::depth(3));
+        drop(engine);
+        let reports = receiver.iter().collect::<Vec<_>>();
+        let last = reports.last().unwrap();
+        assert_eq!(last.pv, [mv]);
+        assert_eq!(last.depth, 3);
+        assert!(last.score > 0 && last.nodes > 0);
+    }
+}
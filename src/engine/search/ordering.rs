@@ -0,0 +1,182 @@
+//! Move ordering from killer moves and the history heuristic.
+//!
+//! Alpha-beta prunes the most when the best move is searched first. Moves
+//! that caused a cutoff at the same ply elsewhere in the tree ("killers"),
+//! and moves that have caused many deep cutoffs anywhere ("history"), are
+//! likely to be good, so they are tried before the rest.
+
+use crate::board::{Move, Player};
+
+/// The number of killer moves kept for each ply.
+const KILLER_SLOTS: usize = 2;
+
+/// Counts of the beta cutoffs in a search, to measure how well its moves
+/// are ordered.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SearchStats {
+    /// The number of nodes where a move caused a beta cutoff.
+    pub beta_cutoffs: u64,
+    /// The number of those cutoffs caused by the first move searched.
+    pub first_move_cutoffs: u64,
+    /// The number of those cutoffs caused by a killer move.
+    pub killer_cutoffs: u64,
+}
+
+impl SearchStats {
+    /// The fraction of cutoffs caused by the first move searched, or `None`
+    /// if there were no cutoffs.
+    #[must_use]
+    pub fn first_move_rate(&self) -> Option<f64> {
+        #![allow(clippy::cast_precision_loss)]
+        (self.beta_cutoffs > 0).then(|| self.first_move_cutoffs as f64 / self.beta_cutoffs as f64)
+    }
+}
+
+/// The killer and history tables of one search.
+#[derive(Clone, Debug)]
+pub(super) struct MoveOrdering<const SIDE_LENGTH: usize> {
+    pub killers_enabled: bool,
+    pub history_enabled: bool,
+    killers: Vec<[Move<SIDE_LENGTH>; KILLER_SLOTS]>,
+    /// Cutoff scores indexed by the side to move, then the move.
+    history: [Vec<u32>; 2],
+    pub stats: SearchStats,
+}
+
+impl<const SIDE_LENGTH: usize> Default for MoveOrdering<SIDE_LENGTH> {
+    fn default() -> Self {
+        Self {
+            killers_enabled: true,
+            history_enabled: true,
+            killers: Vec::new(),
+            history: [
+                vec![0; SIDE_LENGTH * SIDE_LENGTH],
+                vec![0; SIDE_LENGTH * SIDE_LENGTH],
+            ],
+            stats: SearchStats::default(),
+        }
+    }
+}
+
+const fn side(player: Player) -> usize {
+    match player {
+        Player::O => 1,
+        _ => 0,
+    }
+}
+
+impl<const SIDE_LENGTH: usize> MoveOrdering<SIDE_LENGTH> {
+    /// Prepares for a new search, forgetting the killers and the statistics.
+    /// The history is kept, but aged so that recent cutoffs count for more.
+    pub fn new_search(&mut self) {
+        self.killers.clear();
+        self.stats = SearchStats::default();
+        for score in self.history.iter_mut().flatten() {
+            *score /= 2;
+        }
+    }
+
+    fn killers(&self, ply: usize) -> &[Move<SIDE_LENGTH>] {
+        self.killers.get(ply).map_or(&[], |k| k.as_slice())
+    }
+
+    /// Sorts `moves` for `player` at `ply`: `first` if present, then the
+    /// killers, then the rest by history score.
+    pub fn order(
+        &self,
+        moves: &mut [Move<SIDE_LENGTH>],
+        first: Option<Move<SIDE_LENGTH>>,
+        ply: usize,
+        player: Player,
+    ) {
+        let history = &self.history[side(player)];
+        let killers = if self.killers_enabled {
+            self.killers(ply)
+        } else {
+            &[]
+        };
+        moves.sort_by_cached_key(|&mv| {
+            let class = if Some(mv) == first {
+                0
+            } else if killers.contains(&mv) {
+                1
+            } else {
+                2
+            };
+            let history = if self.history_enabled {
+                history[mv.index()]
+            } else {
+                0
+            };
+            (class, std::cmp::Reverse(history))
+        });
+    }
+
+    /// Records that `mv`, the `index`th move searched, caused a beta cutoff
+    /// for `player` at `ply` with `depth` plies left.
+    pub fn cutoff(
+        &mut self,
+        mv: Move<SIDE_LENGTH>,
+        index: usize,
+        ply: usize,
+        depth: u8,
+        player: Player,
+    ) {
+        self.stats.beta_cutoffs += 1;
+        if index == 0 {
+            self.stats.first_move_cutoffs += 1;
+        }
+        if self.killers(ply).contains(&mv) {
+            self.stats.killer_cutoffs += 1;
+        }
+        if self.killers_enabled {
+            if self.killers.len() <= ply {
+                self.killers.resize(ply + 1, [Move::null(); KILLER_SLOTS]);
+            }
+            let killers = &mut self.killers[ply];
+            if killers[0] != mv {
+                killers.rotate_right(1);
+                killers[0] = mv;
+            }
+        }
+        if self.history_enabled {
+            let score = &mut self.history[side(player)][mv.index()];
+            *score = score.saturating_add(u32::from(depth) * u32::from(depth));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MoveOrdering;
+    use crate::board::{Move, Player};
+
+    #[test]
+    fn killers_then_history() {
+        let [a, b, c, d] = ["a1", "b1", "c1", "d1"].map(|mv| mv.parse::<Move<9>>().unwrap());
+        let mut ordering = MoveOrdering::<9>::default();
+        ordering.cutoff(c, 3, 0, 4, Player::X);
+        ordering.cutoff(d, 1, 2, 2, Player::X);
+        let mut moves = [a, b, c, d];
+        // at ply 0, c is a killer, and d has a history score.
+        ordering.order(&mut moves, Some(b), 0, Player::X);
+        assert_eq!(moves, [b, c, d, a]);
+        // at ply 1 there are no killers, so c leads on history.
+        ordering.order(&mut moves, None, 1, Player::X);
+        assert_eq!(moves, [c, d, b, a]);
+        // the other side's history is separate.
+        let mut moves = [a, b, c, d];
+        ordering.order(&mut moves, None, 1, Player::O);
+        assert_eq!(moves, [a, b, c, d]);
+
+        ordering.cutoff(c, 0, 0, 1, Player::X);
+        assert_eq!(ordering.stats.beta_cutoffs, 3);
+        assert_eq!(ordering.stats.first_move_cutoffs, 1);
+        assert_eq!(ordering.stats.killer_cutoffs, 1);
+        ordering.new_search();
+        assert_eq!(ordering.stats.beta_cutoffs, 0);
+        let mut moves = [a, b, c, d];
+        ordering.order(&mut moves, None, 0, Player::X);
+        assert_eq!(moves, [c, d, a, b]);
+    }
+}
@@ -0,0 +1,284 @@
+//! The transposition table used by the alpha-beta search.
+//!
+//! Unlike the general-purpose [`crate::tt::TranspositionTable`], each bucket
+//! has two slots with different replacement policies: one keeps the deepest
+//! result seen for the bucket, and the other always takes the newest. Deep
+//! results survive, but recent shallow ones still have somewhere to go.
+//!
+//! The table is shared between the threads of a parallel search, so entries
+//! are packed into atomic words rather than guarded by a lock.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{board::Move, eval::WIN_SCORE};
+
+/// Scores beyond this are wins or losses, counted in plies from the root.
+const WIN_THRESHOLD: i32 = WIN_SCORE - 1000;
+
+/// How a stored score relates to the true score of the position.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Bound {
+    /// The score is exact.
+    Exact,
+    /// The true score is at least the stored score.
+    Lower,
+    /// The true score is at most the stored score.
+    Upper,
+}
+
+/// A stored search result.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TtEntry<const SIDE_LENGTH: usize> {
+    pub score: i32,
+    pub bound: Bound,
+    /// The depth the position was searched to.
+    pub depth: u8,
+    /// The best move found, or the null move if there was none.
+    pub best_move: Move<SIDE_LENGTH>,
+}
+
+/// A bucket of two entries, each stored as its packed data and its key
+/// xored with the data. Threads can read and write entries without locking:
+/// if two writes to an entry interleave, its key no longer matches, so the
+/// torn entry is never returned.
+#[derive(Debug, Default)]
+struct Bucket {
+    depth_preferred: [AtomicU64; 2],
+    always_replace: [AtomicU64; 2],
+}
+
+/// Reads the data of `slot` if it holds `key`.
+fn load(slot: &[AtomicU64; 2], key: u64) -> Option<u64> {
+    let data = slot[1].load(Ordering::Relaxed);
+    (data != 0 && slot[0].load(Ordering::Relaxed) ^ data == key).then_some(data)
+}
+
+fn save(slot: &[AtomicU64; 2], key: u64, data: u64) {
+    slot[0].store(key ^ data, Ordering::Relaxed);
+    slot[1].store(data, Ordering::Relaxed);
+}
+
+/// The bit set in the packed data of every entry, so that zero means empty.
+const OCCUPIED: u64 = 1 << 63;
+
+impl<const SIDE_LENGTH: usize> TtEntry<SIDE_LENGTH> {
+    fn pack(self) -> u64 {
+        #![allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        OCCUPIED
+            | u64::from(self.score as u32)
+            | (self.best_move.index() as u64 & 0xFFFF) << 32
+            | u64::from(self.depth) << 48
+            | bound << 56
+    }
+
+    const fn unpack(data: u64) -> Self {
+        #![allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+        let bound = match (data >> 56) & 3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        Self {
+            score: data as u32 as i32,
+            bound,
+            depth: (data >> 48) as u8,
+            best_move: Move::from_index((data >> 32) as u16),
+        }
+    }
+}
+
+/// A fixed-size table of search results, keyed by Zobrist key, that can be
+/// shared between search threads.
+#[derive(Debug)]
+pub struct SearchTable<const SIDE_LENGTH: usize> {
+    buckets: Vec<Bucket>,
+}
+
+impl<const SIDE_LENGTH: usize> SearchTable<SIDE_LENGTH> {
+    /// Creates a table with room for at least `entries` entries.
+    #[must_use]
+    pub fn new(entries: usize) -> Self {
+        Self {
+            buckets: std::iter::repeat_with(Bucket::default)
+                .take(entries.div_ceil(2).max(1))
+                .collect(),
+        }
+    }
+
+    /// Creates a table using roughly `megabytes` megabytes of memory.
+    #[must_use]
+    pub fn with_size_mb(megabytes: usize) -> Self {
+        let bucket_size = std::mem::size_of::<Bucket>();
+        Self::new(megabytes * 1024 * 1024 / bucket_size * 2)
+    }
+
+    /// The number of entries the table can hold.
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.buckets.len() * 2
+    }
+
+    fn bucket_index(&self, key: u64) -> usize {
+        #![allow(clippy::cast_possible_truncation)]
+        // map the key onto the buckets without a division.
+        ((u128::from(key) * self.buckets.len() as u128) >> 64) as usize
+    }
+
+    /// Looks up the result stored for `key`, with win and loss scores
+    /// adjusted to be counted from the root of a search at `ply`.
+    #[must_use]
+    pub fn probe(&self, key: u64, ply: i32) -> Option<TtEntry<SIDE_LENGTH>> {
+        let bucket = &self.buckets[self.bucket_index(key)];
+        let data =
+            load(&bucket.depth_preferred, key).or_else(|| load(&bucket.always_replace, key))?;
+        let entry = TtEntry::unpack(data);
+        Some(TtEntry {
+            score: from_tt(entry.score, ply),
+            ..entry
+        })
+    }
+
+    /// Stores `entry` for `key`, found at `ply` from the root of the search.
+    ///
+    /// The entry replaces the bucket's depth-preferred entry if it is at least
+    /// as deep or for the same position, and its always-replace entry otherwise.
+    pub fn store(&self, key: u64, ply: i32, entry: TtEntry<SIDE_LENGTH>) {
+        let bucket = &self.buckets[self.bucket_index(key)];
+        let data = TtEntry {
+            score: to_tt(entry.score, ply),
+            ..entry
+        }
+        .pack();
+        let old_data = bucket.depth_preferred[1].load(Ordering::Relaxed);
+        let old_key = bucket.depth_preferred[0].load(Ordering::Relaxed) ^ old_data;
+        if old_data != 0
+            && old_key != key
+            && TtEntry::<SIDE_LENGTH>::unpack(old_data).depth > entry.depth
+        {
+            save(&bucket.always_replace, key, data);
+        } else {
+            if load(&bucket.always_replace, key).is_some() {
+                save(&bucket.always_replace, 0, 0);
+            }
+            save(&bucket.depth_preferred, key, data);
+        }
+    }
+
+    /// How full the table is, in entries per thousand, estimated from the
+    /// first thousand entries.
+    #[must_use]
+    pub fn hashfull(&self) -> u16 {
+        #![allow(clippy::cast_possible_truncation)]
+        let sample = &self.buckets[..self.buckets.len().min(500)];
+        let used = sample
+            .iter()
+            .flat_map(|b| [&b.depth_preferred[1], &b.always_replace[1]])
+            .filter(|data| data.load(Ordering::Relaxed) != 0)
+            .count();
+        (used * 1000 / (sample.len() * 2)) as u16
+    }
+
+    /// Removes every entry from the table.
+    pub fn clear(&mut self) {
+        for slot in self
+            .buckets
+            .iter_mut()
+            .flat_map(|b| [&mut b.depth_preferred, &mut b.always_replace])
+        {
+            *slot = Default::default();
+        }
+    }
+}
+
+impl<const SIDE_LENGTH: usize> Default for SearchTable<SIDE_LENGTH> {
+    /// A table with room for 2^16 entries.
+    fn default() -> Self {
+        Self::new(1 << 16)
+    }
+}
+
+/// Converts a win or loss score counted from the root to one counted from
+/// the position being stored, so it stays correct when found at another ply.
+const fn to_tt(score: i32, ply: i32) -> i32 {
+    if score > WIN_THRESHOLD {
+        score + ply
+    } else if score < -WIN_THRESHOLD {
+        score - ply
+    } else {
+        score
+    }
+}
+
+/// The inverse of [`to_tt`].
+const fn from_tt(score: i32, ply: i32) -> i32 {
+    if score > WIN_THRESHOLD {
+        score - ply
+    } else if score < -WIN_THRESHOLD {
+        score + ply
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bound, SearchTable, TtEntry};
+    use crate::{board::Move, eval::WIN_SCORE};
+
+    fn entry(score: i32, depth: u8) -> TtEntry<15> {
+        TtEntry {
+            score,
+            bound: Bound::Exact,
+            depth,
+            best_move: "h8".parse::<Move<15>>().unwrap(),
+        }
+    }
+
+    #[test]
+    fn two_tier_replacement() {
+        // a single bucket, so every key collides.
+        let mut tt = SearchTable::<15>::new(2);
+        assert_eq!(tt.capacity(), 2);
+        assert_eq!(tt.hashfull(), 0);
+        tt.store(1, 0, entry(10, 5));
+        // shallower entries for other positions go in the always-replace slot.
+        tt.store(2, 0, entry(20, 1));
+        tt.store(3, 0, entry(30, 2));
+        assert_eq!(tt.probe(1, 0), Some(entry(10, 5)));
+        assert_eq!(tt.probe(2, 0), None);
+        assert_eq!(tt.probe(3, 0), Some(entry(30, 2)));
+        assert_eq!(tt.hashfull(), 1000);
+        // deeper entries take over the depth-preferred slot.
+        tt.store(4, 0, entry(40, 6));
+        assert_eq!(tt.probe(1, 0), None);
+        assert_eq!(tt.probe(4, 0), Some(entry(40, 6)));
+        tt.clear();
+        assert_eq!(tt.probe(4, 0), None);
+        assert_eq!(tt.hashfull(), 0);
+    }
+
+    #[test]
+    fn win_scores_are_relative_to_the_position() {
+        let tt = SearchTable::<15>::new(16);
+        // a win three plies below a node at ply 2.
+        tt.store(7, 2, entry(WIN_SCORE - 5, 3));
+        assert_eq!(tt.probe(7, 4).unwrap().score, WIN_SCORE - 7);
+        tt.store(8, 2, entry(-WIN_SCORE + 5, 3));
+        assert_eq!(tt.probe(8, 0).unwrap().score, -WIN_SCORE + 3);
+        tt.store(9, 2, entry(100, 3));
+        assert_eq!(tt.probe(9, 6).unwrap().score, 100);
+        let no_move = TtEntry {
+            score: -42,
+            bound: Bound::Upper,
+            depth: 255,
+            best_move: Move::null(),
+        };
+        tt.store(10, 0, no_move);
+        assert_eq!(tt.probe(10, 0), Some(no_move));
+    }
+}
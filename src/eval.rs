@@ -0,0 +1,119 @@
+//! Static evaluation of positions.
+//!
+//! Every window of `win_length` cells that holds stones of only one player
+//! could still become a winning line for that player, and is worth more the
+//! fewer stones it is missing.
+
+use crate::board::{Board, Player};
+
+/// The score of a position that is won for the side to move.
+pub const WIN_SCORE: i32 = 1_000_000;
+
+/// The weights of the static evaluation: the value of a window that holds
+/// stones of only one player, by the number of stones it is missing.
+///
+/// A complete window is a win, and is always worth [`WIN_SCORE`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EvalWeights {
+    /// The values of windows missing one, two, three, and four stones.
+    pub missing: [i32; 4],
+}
+
+impl EvalWeights {
+    /// The weights used by [`evaluate`].
+    pub const DEFAULT: Self = Self {
+        missing: [512, 64, 8, 1],
+    };
+}
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Counts the windows that hold stones of only one player, by the number of
+/// stones they are missing, up to four: the count for the side to move less
+/// the count for their opponent.
+pub(crate) fn window_counts<const SIDE_LENGTH: usize>(board: &Board<SIDE_LENGTH>) -> [i32; 5] {
+    let mut counts = [0; 5];
+    for window in board.windows() {
+        let (mut x, mut o) = (0, 0);
+        for cell in window.cells() {
+            match cell {
+                Player::X => x += 1,
+                Player::O => o += 1,
+                Player::None => {}
+            }
+        }
+        match (x, o) {
+            (0, 0) => {}
+            (x, 0) => {
+                if let Some(count) = counts.get_mut(window.len() - x) {
+                    *count += 1;
+                }
+            }
+            (0, o) => {
+                if let Some(count) = counts.get_mut(window.len() - o) {
+                    *count -= 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    if board.turn() == Player::O {
+        for count in &mut counts {
+            *count = -*count;
+        }
+    }
+    counts
+}
+
+/// Scores `board` from the perspective of the side to move: positive scores
+/// are good for the player to move.
+#[must_use]
+pub fn evaluate<const SIDE_LENGTH: usize>(board: &Board<SIDE_LENGTH>) -> i32 {
+    evaluate_with(board, &EvalWeights::DEFAULT)
+}
+
+/// Scores `board` like [`evaluate`], with the given weights.
+#[must_use]
+pub fn evaluate_with<const SIDE_LENGTH: usize>(
+    board: &Board<SIDE_LENGTH>,
+    weights: &EvalWeights,
+) -> i32 {
+    let counts = window_counts(board);
+    counts[1..].iter().zip(weights.missing).fold(
+        counts[0].saturating_mul(WIN_SCORE),
+        |score, (&count, weight)| score.saturating_add(count.saturating_mul(weight)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evaluate, evaluate_with, EvalWeights};
+    use crate::board::Board;
+
+    #[test]
+    fn symmetric_and_side_relative() {
+        let board = Board::<15>::new();
+        assert_eq!(evaluate(&board), 0);
+        let board = board.with_move("h8".parse().unwrap());
+        // O to move, and X's centre stone is in more windows than anything else.
+        assert!(evaluate(&board) < 0);
+        let board = board.with_move("a1".parse().unwrap());
+        assert!(evaluate(&board) > 0);
+    }
+
+    #[test]
+    fn weights_are_configurable() {
+        let board = Board::<15>::new().with_move("h8".parse().unwrap());
+        let default = EvalWeights::default();
+        assert_eq!(evaluate_with(&board, &default), evaluate(&board));
+        // a lone stone is only in windows missing four stones.
+        let doubled = EvalWeights {
+            missing: [512, 64, 8, 2],
+        };
+        assert_eq!(evaluate_with(&board, &doubled), 2 * evaluate(&board));
+    }
+}
@@ -0,0 +1,164 @@
+//! Exporting positions with their metadata, for consumers such as Python data
+//! tools.
+//!
+//! With the `json` feature, positions can be written as JSON lines, one
+//! [`PositionRecord`] per line, which `pandas.read_json(path, lines=True)`
+//! loads directly:
+//!
+//! ```json
+//! {"fen":"...","ply":3,"side_to_move":"o","outcome":null,"eval":-48.0}
+//! ```
+//!
+//! `outcome` is `"x"` or `"o"` for a win, `"draw"` for a full board, and
+//! `null` while the game goes on; `eval` is `null` when no evaluation was
+//! given.
+
+use crate::board::{Board, Player};
+
+/// A position and its metadata, in the form used by the JSON lines format
+/// described in the [module docs](self).
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PositionRecord {
+    /// The position.
+    pub fen: String,
+    /// The number of moves played.
+    pub ply: usize,
+    /// The side to move, `'x'` or `'o'`.
+    pub side_to_move: char,
+    /// The result if the game is over: `"x"` or `"o"` for a win, or `"draw"`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub outcome: Option<String>,
+    /// An evaluation of the position, on whatever scale the producer chose.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub eval: Option<f64>,
+}
+
+impl PositionRecord {
+    /// Describes `board`, without an evaluation.
+    #[must_use]
+    pub fn new<const SIDE_LENGTH: usize>(board: &Board<SIDE_LENGTH>) -> Self {
+        let side = |player| match player {
+            Player::X => "x",
+            Player::O => "o",
+            Player::None => "draw",
+        };
+        Self {
+            fen: board.fen(),
+            ply: board.ply(),
+            side_to_move: if board.turn() == Player::X { 'x' } else { 'o' },
+            outcome: board.outcome().map(|winner| side(winner).to_string()),
+            eval: None,
+        }
+    }
+
+    /// Attaches an evaluation to the record.
+    #[must_use]
+    pub const fn with_eval(mut self, eval: f64) -> Self {
+        self.eval = Some(eval);
+        self
+    }
+}
+
+/// Writes `records` as JSON lines, one record per line, returning the number
+/// written.
+///
+/// # Errors
+///
+/// Returns any error from the underlying writer.
+#[cfg(feature = "json")]
+pub fn write_jsonl(
+    mut writer: impl std::io::Write,
+    records: impl IntoIterator<Item = PositionRecord>,
+) -> std::io::Result<u64> {
+    let mut count = 0;
+    for record in records {
+        serde_json::to_writer(&mut writer, &record)?;
+        writer.write_all(b"\n")?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Writes every position `depth` plies after `board` as JSON lines, with the
+/// evaluation `evaluate` gives it, if any, returning the number written.
+///
+/// # Errors
+///
+/// Returns any error from the underlying writer.
+#[cfg(feature = "json")]
+pub fn write_depth_n_jsonl<const SIDE_LENGTH: usize>(
+    board: Board<SIDE_LENGTH>,
+    mut writer: impl std::io::Write,
+    depth: u8,
+    evaluate: impl Fn(&Board<SIDE_LENGTH>) -> Option<f64>,
+) -> std::io::Result<u64> {
+    fn visit<const SIDE_LENGTH: usize>(
+        board: Board<SIDE_LENGTH>,
+        writer: &mut dyn std::io::Write,
+        depth: u8,
+        evaluate: &dyn Fn(&Board<SIDE_LENGTH>) -> Option<f64>,
+    ) -> std::io::Result<u64> {
+        if depth == 0 {
+            let record = PositionRecord::new(&board);
+            let record = match evaluate(&board) {
+                Some(eval) => record.with_eval(eval),
+                None => record,
+            };
+            return write_jsonl(writer, [record]);
+        }
+
+        let mut count = 0;
+        for (_, child) in board.successors() {
+            count += visit(child, writer, depth - 1, evaluate)?;
+        }
+        Ok(count)
+    }
+
+    visit(board, &mut writer, depth, &evaluate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PositionRecord;
+    use crate::board::{Board, Move};
+
+    #[test]
+    fn describes_positions() {
+        let record = PositionRecord::new(&Board::<9>::new().with_move("e5".parse().unwrap()));
+        assert_eq!(record.ply, 1);
+        assert_eq!(record.side_to_move, 'o');
+        assert_eq!(record.outcome, None);
+        assert_eq!(record.eval, None);
+
+        let moves = ["a1", "a2", "b1", "b2", "c1", "c2", "d1", "d2", "e1"]
+            .map(|mv| mv.parse::<Move<9>>().unwrap());
+        let won = Board::new().apply_moves(&moves).unwrap();
+        let record = PositionRecord::new(&won).with_eval(1.0);
+        assert_eq!(record.outcome.as_deref(), Some("x"));
+        assert_eq!(record.eval, Some(1.0));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn writes_json_lines() {
+        let mut output = Vec::new();
+        let count = super::write_depth_n_jsonl(Board::<3>::new(), &mut output, 1, |board| {
+            (board.ply() == 1).then_some(0.5)
+        })
+        .unwrap();
+        assert_eq!(count, 9);
+        let output = String::from_utf8(output).unwrap();
+        let records = output
+            .lines()
+            .map(|line| serde_json::from_str::<PositionRecord>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 9);
+        assert!(records
+            .iter()
+            .all(|record| record.ply == 1 && record.side_to_move == 'o'));
+        assert!(output
+            .lines()
+            .all(|line| line.ends_with(r#","outcome":null,"eval":0.5}"#)));
+    }
+}
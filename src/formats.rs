@@ -0,0 +1,259 @@
+//! Reading and writing game records in the formats used by gomoku tools.
+//!
+//! - [`psq`]: Piskvork's PSQ files, in which Gomocup publishes its games.
+//! - [`sgf`]: the Smart Game Format, as game `GM[4]`.
+//! - FEN lists: every position of the game, one FEN per line, from the start.
+//! - JSON, with the `json` feature: the crate's own record of a [`Game`],
+//!   its serde form.
+//! - [`collection`]: the crate's own binary files of many games, which can
+//!   be streamed or read by number.
+//!
+//! [`Format`] chooses between them by name or by file extension, for tools
+//! that convert between formats:
+//!
+//! ```
+//! use gomokugen::{formats::Format, game::Game};
+//!
+//! let psq = "Piskvorky 15x15, 11:11, 0\n8,8,0\n9,9,0\n";
+//! let game = Format::Psq.read::<15>(psq).unwrap();
+//! assert_eq!(game.moves().len(), 2);
+//! assert_eq!(
+//!     Format::Sgf.write(&game).unwrap(),
+//!     "(;FF[4]GM[4]SZ[15];B[hh];W[ig])\n"
+//! );
+//! ```
+
+pub mod collection;
+pub mod psq;
+pub mod sgf;
+
+use std::{fmt::Display, path::Path, str::FromStr};
+
+use crate::{
+    board::{Board, Move},
+    game::Game,
+};
+
+/// A game record format.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// Piskvork's PSQ files.
+    Psq,
+    /// The Smart Game Format.
+    Sgf,
+    /// One FEN per line, for every position of the game.
+    Fens,
+    /// The serde form of [`Game`], as JSON.
+    #[cfg(feature = "json")]
+    Json,
+}
+
+impl Format {
+    /// Guesses the format of a file from its extension.
+    #[must_use]
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+
+    /// The usual extension of files in this format.
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Psq => "psq",
+            Self::Sgf => "sgf",
+            Self::Fens => "fens",
+            #[cfg(feature = "json")]
+            Self::Json => "json",
+        }
+    }
+
+    /// Finds the side length of the board a record in this format is played
+    /// on, so that callers can pick the `SIDE_LENGTH` to [`read`](Self::read)
+    /// it with. Returns `None` if the record does not say.
+    #[must_use]
+    pub fn side_length(self, text: &str) -> Option<usize> {
+        match self {
+            Self::Psq => psq::side_length(text),
+            Self::Sgf => sgf::side_length(text),
+            Self::Fens => fen_side_length(text.lines().find(|line| !line.trim().is_empty())?),
+            #[cfg(feature = "json")]
+            Self::Json => {
+                let record = serde_json::from_str::<serde_json::Value>(text).ok()?;
+                let start = record.get("start")?;
+                match start.as_str() {
+                    Some(fen) => fen_side_length(fen),
+                    None => usize::try_from(start.get("size")?.as_u64()?).ok(),
+                }
+            }
+        }
+    }
+
+    /// Reads a game in this format.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `text` is not a valid record of a game on a board
+    /// of side length `SIDE_LENGTH`.
+    pub fn read<const SIDE_LENGTH: usize>(
+        self,
+        text: &str,
+    ) -> Result<Game<SIDE_LENGTH>, &'static str> {
+        match self {
+            Self::Psq => psq::read(text),
+            Self::Sgf => sgf::read(text),
+            Self::Fens => read_fens(text),
+            #[cfg(feature = "json")]
+            Self::Json => serde_json::from_str(text).map_err(|_| "Invalid JSON game record"),
+        }
+    }
+
+    /// Writes `game` in this format.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the format cannot express the game, as PSQ cannot
+    /// express games that start from a position with stones on the board.
+    ///
+    /// # Panics
+    ///
+    /// Never panics in practice: games always serialize.
+    pub fn write<const SIDE_LENGTH: usize>(
+        self,
+        game: &Game<SIDE_LENGTH>,
+    ) -> Result<String, &'static str> {
+        match self {
+            Self::Psq => psq::write(game),
+            Self::Sgf => Ok(sgf::write(game)),
+            Self::Fens => Ok(write_fens(game)),
+            #[cfg(feature = "json")]
+            Self::Json => {
+                let mut json = serde_json::to_string(game).expect("games always serialize");
+                json.push('\n');
+                Ok(json)
+            }
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "psq" => Ok(Self::Psq),
+            "sgf" => Ok(Self::Sgf),
+            "fen" | "fens" => Ok(Self::Fens),
+            #[cfg(feature = "json")]
+            "json" => Ok(Self::Json),
+            _ => Err("Unknown record format"),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// Reads a game from a list of FENs, one per line, each following the one
+/// before by a single move of the side to move. Blank lines are ignored.
+///
+/// # Errors
+///
+/// Returns an error if a line is not a valid FEN, or if a position does not
+/// follow from the one before it by one move.
+pub fn read_fens<const SIDE_LENGTH: usize>(text: &str) -> Result<Game<SIDE_LENGTH>, &'static str> {
+    let mut fens = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    let start = fens
+        .next()
+        .ok_or("Empty FEN list")?
+        .parse::<Board<SIDE_LENGTH>>()?;
+    let mut game = Game::new(start);
+    for fen in fens {
+        let next = fen.parse::<Board<SIDE_LENGTH>>()?;
+        let Ok(&[(mv, _)]) = game.board().diff(&next).as_deref() else {
+            return Err("Position does not follow from the one before by one move");
+        };
+        if game.result().is_some() {
+            return Err("Position after the end of the game");
+        }
+        game.play(mv);
+    }
+    Ok(game)
+}
+
+/// Writes every position of `game`, from its start, as a FEN per line.
+#[must_use]
+pub fn write_fens<const SIDE_LENGTH: usize>(game: &Game<SIDE_LENGTH>) -> String {
+    let mut board = *game.start();
+    let mut text = board.fen();
+    text.push('\n');
+    for &mv in game.moves() {
+        board.make_move(mv);
+        text.push_str(&board.fen());
+        text.push('\n');
+    }
+    text
+}
+
+/// The side length of the board in `fen`, from its number of rows.
+fn fen_side_length(fen: &str) -> Option<usize> {
+    Some(fen.split_whitespace().next()?.split('/').count())
+}
+
+/// The move on the cell at `row` and `col`, if it is on the board.
+fn cell<const SIDE_LENGTH: usize>(row: usize, col: usize) -> Option<Move<SIDE_LENGTH>> {
+    if row >= SIDE_LENGTH || col >= SIDE_LENGTH {
+        return None;
+    }
+    u16::try_from(row * SIDE_LENGTH + col)
+        .ok()
+        .map(Move::from_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Format;
+    use crate::{board::Board, game::Game};
+
+    #[test]
+    fn converts_between_formats() {
+        let mut game = Game::new(Board::<15>::new());
+        for mv in ["h8", "i9", "h9", "g8", "h10"] {
+            game.play(mv.parse().unwrap());
+        }
+        game.resign(game.board().turn());
+        let formats = [Format::Psq, Format::Sgf, Format::Fens];
+        for from in formats {
+            let text = from.write(&game).unwrap();
+            assert_eq!(from.side_length(&text), Some(15));
+            let read = from.read::<15>(&text).unwrap();
+            assert_eq!(read.moves(), game.moves());
+            for to in formats {
+                let converted = to.read::<15>(&to.write(&read).unwrap()).unwrap();
+                assert_eq!(converted.moves(), game.moves());
+            }
+        }
+        assert_eq!(
+            Format::Sgf.read::<15>(&Format::Sgf.write(&game).unwrap()),
+            Ok(game.clone())
+        );
+        #[cfg(feature = "json")]
+        assert_eq!(
+            Format::Json.read::<15>(&Format::Json.write(&game).unwrap()),
+            Ok(game)
+        );
+    }
+
+    #[test]
+    fn fen_lists_must_follow_by_one_move() {
+        let fens = "..../..../..../.... x 0\nx.../..../..../.... o 1\n";
+        assert_eq!(super::read_fens::<4>(fens).unwrap().moves().len(), 1);
+        let skips = "..../..../..../.... x 0\nx.../o.../..../.... x 2\n";
+        assert!(super::read_fens::<4>(skips).is_err());
+        assert!(super::read_fens::<4>("").is_err());
+        assert_eq!("FENS".parse::<Format>(), Ok(Format::Fens),);
+        assert_eq!(Format::from_path("games/a.sgf".as_ref()), Some(Format::Sgf));
+    }
+}
@@ -0,0 +1,494 @@
+//! Files holding many games, for processing large game databases.
+//!
+//! A collection is written game by game with a [`GameWriter`], and read back
+//! either in order with a [`GameReader`], which streams the games without
+//! loading the whole file, or one at a time by number with an
+//! [`IndexedGames`], which uses the index at the end of the file.
+//!
+//! A collection is a header (the magic bytes `GMKG`, a big-endian `u16`
+//! format version, and a byte giving the board's side length), then each
+//! game as a big-endian `u32` length and that many bytes, then a zero
+//! length marking the end of the games, then the index: a big-endian `u64`
+//! count of the games and the `u64` offset of each game's length from the
+//! start of the file. The file ends with the offset of the end marker, as a
+//! `u64`, and the magic bytes `GMKX`, so that the index can be found from
+//! the end.
+//!
+//! A game is stored as a big-endian `u16` length and the FEN of its start,
+//! a byte for its [`Rule`] (0 for freestyle, 1 for pro, 2 for long pro, and
+//! 3 for Renju), a `u16` move count and each move's cell index as a `u16`, and its result:
+//! a byte for the winner (0 for a draw, 1 for X, 2 for O, or 255 if the game
+//! is unfinished, in which case nothing follows), a byte for the
+//! [`Termination`] in declaration order, and for a line, the cell indices of
+//! its ends.
+//!
+//! ```
+//! use gomokugen::{
+//!     board::Board,
+//!     formats::collection::{GameReader, GameWriter},
+//!     game::Game,
+//! };
+//!
+//! let mut writer = GameWriter::<_, 15>::new(Vec::new()).unwrap();
+//! let mut game = Game::new(Board::new());
+//! game.play("h8".parse().unwrap());
+//! writer.write(&game).unwrap();
+//! let bytes = writer.finish().unwrap();
+//!
+//! let games = GameReader::<_, 15>::new(bytes.as_slice()).unwrap();
+//! assert_eq!(games.map(Result::unwrap).collect::<Vec<_>>(), [game]);
+//! ```
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::{
+    board::{Board, Move, Player},
+    game::{Game, GameResult, Termination},
+    positions::{invalid_data, FixedBytes},
+    rules::Rule,
+};
+
+const MAGIC: &[u8; 4] = b"GMKG";
+const INDEX_MAGIC: &[u8; 4] = b"GMKX";
+const VERSION: u16 = 1;
+
+/// The size of the header, in bytes.
+const HEADER_SIZE: usize = 7;
+
+/// The largest game record read, in bytes.
+const MAX_RECORD: u32 = 1 << 20;
+
+/// Splits the first `n` bytes off `bytes`.
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> io::Result<&'a [u8]> {
+    if bytes.len() < n {
+        return Err(invalid_data("truncated game record"));
+    }
+    let (head, tail) = bytes.split_at(n);
+    *bytes = tail;
+    Ok(head)
+}
+
+fn read_u16(bytes: &mut &[u8]) -> io::Result<u16> {
+    take(bytes, 2).map(u16::read_bytes)
+}
+
+/// Appends the encoding of `game` described in the [module docs](self) to
+/// `out`.
+pub(crate) fn encode_game<const SIDE_LENGTH: usize>(
+    game: &Game<SIDE_LENGTH>,
+    out: &mut Vec<u8>,
+) -> io::Result<()> {
+    #![allow(clippy::cast_possible_truncation)]
+    let fen = game.start().fen();
+    u16::try_from(fen.len())
+        .map_err(|_| invalid_data("FEN too long"))?
+        .write_bytes(out);
+    out.extend_from_slice(fen.as_bytes());
+    out.push(match game.rule() {
+        Rule::Freestyle => 0,
+        Rule::Pro => 1,
+        Rule::LongPro => 2,
+        Rule::Renju => 3,
+    });
+    u16::try_from(game.moves().len())
+        .map_err(|_| invalid_data("too many moves"))?
+        .write_bytes(out);
+    for mv in game.moves() {
+        (mv.index() as u16).write_bytes(out);
+    }
+    let Some(result) = game.result() else {
+        out.push(255);
+        return Ok(());
+    };
+    out.push(match result.winner {
+        Player::None => 0,
+        Player::X => 1,
+        Player::O => 2,
+    });
+    match result.termination {
+        Termination::Line(from, to) => {
+            out.push(0);
+            (from.index() as u16).write_bytes(out);
+            (to.index() as u16).write_bytes(out);
+        }
+        Termination::BoardFull => out.push(1),
+        Termination::Resignation => out.push(2),
+        Termination::Timeout => out.push(3),
+        Termination::IllegalMove => out.push(4),
+        Termination::Adjudication => out.push(5),
+    }
+    Ok(())
+}
+
+/// Decodes a game encoded by [`encode_game`], replaying its moves.
+pub(crate) fn decode_game<const SIDE_LENGTH: usize>(
+    mut bytes: &[u8],
+) -> io::Result<Game<SIDE_LENGTH>> {
+    let bytes = &mut bytes;
+    let fen_len = read_u16(bytes)?;
+    let start = std::str::from_utf8(take(bytes, usize::from(fen_len))?)
+        .ok()
+        .and_then(|fen| fen.parse::<Board<SIDE_LENGTH>>().ok())
+        .ok_or_else(|| invalid_data("invalid starting position"))?;
+    let rule = match take(bytes, 1)?[0] {
+        0 => Rule::Freestyle,
+        1 => Rule::Pro,
+        2 => Rule::LongPro,
+        3 => Rule::Renju,
+        _ => return Err(invalid_data("invalid rule")),
+    };
+    let mut game = Game::new(start).with_rule(rule);
+    for _ in 0..read_u16(bytes)? {
+        let mv = Move::from_index(read_u16(bytes)?);
+        if mv.index() >= SIDE_LENGTH * SIDE_LENGTH
+            || game.result().is_some()
+            || !game.board().is_legal(mv)
+        {
+            return Err(invalid_data("illegal move in game"));
+        }
+        game.play(mv);
+    }
+    let winner = match take(bytes, 1)?[0] {
+        0 => Player::None,
+        1 => Player::X,
+        2 => Player::O,
+        255 if game.result().is_none() => return Ok(game),
+        _ => return Err(invalid_data("invalid winner")),
+    };
+    let termination = match take(bytes, 1)?[0] {
+        0 => {
+            let (from, to) = (read_u16(bytes)?, read_u16(bytes)?);
+            Termination::Line(Move::from_index(from), Move::from_index(to))
+        }
+        1 => Termination::BoardFull,
+        2 => Termination::Resignation,
+        3 => Termination::Timeout,
+        4 => Termination::IllegalMove,
+        5 => Termination::Adjudication,
+        _ => return Err(invalid_data("invalid termination")),
+    };
+    let result = GameResult {
+        winner,
+        termination,
+    };
+    match game.result() {
+        None => game.end(result),
+        Some(ours) if ours == result => {}
+        Some(_) => return Err(invalid_data("result does not match the moves")),
+    }
+    Ok(game)
+}
+
+/// Reads the header of the collection in `reader`, returning the side length
+/// of the boards its games are played on, so that the right [`GameReader`]
+/// can be chosen.
+///
+/// # Errors
+///
+/// Returns an error if reading fails, or if the input is not a game
+/// collection of this version.
+pub fn side_length(mut reader: impl Read) -> io::Result<usize> {
+    let mut header = [0; HEADER_SIZE];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(invalid_data("not a gomokugen game collection"));
+    }
+    if u16::read_bytes(&header[4..6]) != VERSION {
+        return Err(invalid_data("unsupported game collection version"));
+    }
+    Ok(usize::from(header[6]))
+}
+
+/// Reads and checks the header of a collection.
+fn read_header<const SIDE_LENGTH: usize>(reader: impl Read) -> io::Result<()> {
+    if side_length(reader)? != SIDE_LENGTH {
+        return Err(invalid_data("game collection is for another board size"));
+    }
+    Ok(())
+}
+
+/// Reads the length of the next record, or `None` at the end marker.
+fn read_length(mut reader: impl Read) -> io::Result<Option<u32>> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    match u32::read_bytes(&len) {
+        0 => Ok(None),
+        len if len > MAX_RECORD => Err(invalid_data("game record too large")),
+        len => Ok(Some(len)),
+    }
+}
+
+fn read_record<const SIDE_LENGTH: usize>(
+    mut reader: impl Read,
+    len: u32,
+) -> io::Result<Game<SIDE_LENGTH>> {
+    let mut record = vec![0; len as usize];
+    reader.read_exact(&mut record)?;
+    decode_game(&record)
+}
+
+/// Writes games to a collection, in the format described in the
+/// [module docs](self).
+///
+/// The index is written by [`GameWriter::finish`]. A collection that was
+/// never finished can still be streamed with a [`GameReader`], up to the
+/// last complete game.
+#[derive(Debug)]
+pub struct GameWriter<W: Write, const SIDE_LENGTH: usize> {
+    writer: W,
+    offsets: Vec<u64>,
+    position: u64,
+    record: Vec<u8>,
+}
+
+impl<W: Write, const SIDE_LENGTH: usize> GameWriter<W, SIDE_LENGTH> {
+    /// Starts a collection in `writer`, writing its header.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from the underlying writer.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        #![allow(clippy::cast_possible_truncation)]
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.extend_from_slice(MAGIC);
+        VERSION.write_bytes(&mut header);
+        header.push(SIDE_LENGTH as u8);
+        writer.write_all(&header)?;
+        Ok(Self {
+            writer,
+            offsets: Vec::new(),
+            position: HEADER_SIZE as u64,
+            record: Vec::new(),
+        })
+    }
+
+    /// Adds `game` to the collection.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from the underlying writer, or an error if the game
+    /// is too long to store.
+    pub fn write(&mut self, game: &Game<SIDE_LENGTH>) -> io::Result<()> {
+        self.record.clear();
+        0_u32.write_bytes(&mut self.record);
+        encode_game(game, &mut self.record)?;
+        let len = u32::try_from(self.record.len() - 4)
+            .ok()
+            .filter(|&len| len <= MAX_RECORD)
+            .ok_or_else(|| invalid_data("game record too large"))?;
+        self.record[..4].copy_from_slice(&len.to_be_bytes());
+        self.writer.write_all(&self.record)?;
+        self.offsets.push(self.position);
+        self.position += self.record.len() as u64;
+        Ok(())
+    }
+
+    /// The number of games written so far.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Whether no games have been written yet.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Writes the end marker and the index, and returns the underlying
+    /// writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut index = Vec::with_capacity(self.offsets.len() * 8 + 28);
+        0_u32.write_bytes(&mut index);
+        (self.offsets.len() as u64).write_bytes(&mut index);
+        for offset in &self.offsets {
+            offset.write_bytes(&mut index);
+        }
+        self.position.write_bytes(&mut index);
+        index.extend_from_slice(INDEX_MAGIC);
+        self.writer.write_all(&index)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Streams the games of a collection in order, reading one at a time.
+///
+/// The iterator ends at the end of the games, or at the end of the input if
+/// the collection was never finished. An error ends the iteration.
+#[derive(Debug)]
+pub struct GameReader<R: Read, const SIDE_LENGTH: usize> {
+    reader: R,
+    done: bool,
+}
+
+impl<R: Read, const SIDE_LENGTH: usize> GameReader<R, SIDE_LENGTH> {
+    /// Starts reading the collection in `reader`, checking its header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, or if the input is not a
+    /// collection of games on boards of side length `SIDE_LENGTH`.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        read_header::<SIDE_LENGTH>(&mut reader)?;
+        Ok(Self {
+            reader,
+            done: false,
+        })
+    }
+}
+
+impl<R: Read, const SIDE_LENGTH: usize> Iterator for GameReader<R, SIDE_LENGTH> {
+    type Item = io::Result<Game<SIDE_LENGTH>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = match read_length(&mut self.reader) {
+            Ok(Some(len)) => read_record(&mut self.reader, len).map(Some),
+            Ok(None) => Ok(None),
+            // an unfinished collection ends without its end marker.
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e),
+        };
+        self.done = !matches!(next, Ok(Some(_)));
+        next.transpose()
+    }
+}
+
+/// A finished collection, whose games can be read in any order by number.
+#[derive(Debug)]
+pub struct IndexedGames<R: Read + Seek, const SIDE_LENGTH: usize> {
+    reader: R,
+    offsets: Vec<u64>,
+}
+
+impl<R: Read + Seek, const SIDE_LENGTH: usize> IndexedGames<R, SIDE_LENGTH> {
+    /// Opens the collection in `reader`, reading its index.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, or if the input is not a finished
+    /// collection of games on boards of side length `SIDE_LENGTH`.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        reader.seek(SeekFrom::Start(0))?;
+        read_header::<SIDE_LENGTH>(&mut reader)?;
+        reader.seek(SeekFrom::End(-12))?;
+        let mut trailer = [0; 12];
+        reader.read_exact(&mut trailer)?;
+        if &trailer[8..] != INDEX_MAGIC {
+            return Err(invalid_data("game collection has no index"));
+        }
+        reader.seek(SeekFrom::Start(u64::read_bytes(&trailer[..8])))?;
+        if read_length(&mut reader)?.is_some() {
+            return Err(invalid_data("corrupt game collection index"));
+        }
+        let mut count = [0; 8];
+        reader.read_exact(&mut count)?;
+        let count = usize::try_from(u64::read_bytes(&count))
+            .map_err(|_| invalid_data("corrupt game collection index"))?;
+        // the count is not trusted to size the allocation up front.
+        let mut offsets = Vec::with_capacity(count.min(1 << 16));
+        let mut offset = [0; 8];
+        for _ in 0..count {
+            reader.read_exact(&mut offset)?;
+            offsets.push(u64::read_bytes(&offset));
+        }
+        Ok(Self { reader, offsets })
+    }
+
+    /// The number of games in the collection.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Whether the collection holds no games.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Reads game number `index`, counting from zero, or returns `None` if
+    /// there are not that many games.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails or the game is corrupt.
+    pub fn get(&mut self, index: usize) -> Option<io::Result<Game<SIDE_LENGTH>>> {
+        let &offset = self.offsets.get(index)?;
+        let mut read = || {
+            self.reader.seek(SeekFrom::Start(offset))?;
+            let len = read_length(&mut self.reader)?
+                .ok_or_else(|| invalid_data("corrupt game collection index"))?;
+            read_record(&mut self.reader, len)
+        };
+        Some(read())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{side_length, GameReader, GameWriter, IndexedGames};
+    use crate::{board::Board, game::Game, rules::Rule};
+
+    fn games() -> Vec<Game<7>> {
+        let mut games = Vec::new();
+        for (moves, rule) in [
+            (&["d4", "c3", "e5"][..], Rule::Pro),
+            (&["a1"], Rule::Freestyle),
+            (&[], Rule::Renju),
+        ] {
+            let mut game = Game::new(Board::new()).with_rule(rule);
+            for mv in moves {
+                game.play(mv.parse().unwrap());
+            }
+            games.push(game);
+        }
+        games[1].resign(crate::board::Player::O);
+        games
+    }
+
+    #[test]
+    fn streams_and_indexes_games() {
+        let games = games();
+        let mut writer = GameWriter::<_, 7>::new(Vec::new()).unwrap();
+        for game in &games {
+            writer.write(game).unwrap();
+        }
+        assert_eq!(writer.len(), 3);
+        let unfinished = writer.writer.clone();
+        let bytes = writer.finish().unwrap();
+
+        assert_eq!(side_length(bytes.as_slice()).unwrap(), 7);
+        let streamed = GameReader::<_, 7>::new(bytes.as_slice())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(streamed, games);
+        let streamed = GameReader::<_, 7>::new(unfinished.as_slice())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(streamed, games);
+
+        let mut indexed = IndexedGames::<_, 7>::new(Cursor::new(&bytes)).unwrap();
+        assert_eq!(indexed.len(), 3);
+        assert_eq!(indexed.get(1).unwrap().unwrap(), games[1]);
+        assert_eq!(indexed.get(0).unwrap().unwrap(), games[0]);
+        assert!(indexed.get(3).is_none());
+
+        assert!(IndexedGames::<_, 7>::new(Cursor::new(&unfinished)).is_err());
+        assert!(GameReader::<_, 9>::new(bytes.as_slice()).is_err());
+        let mut truncated = GameReader::<_, 7>::new(&bytes[..20]).unwrap();
+        assert!(truncated.next().unwrap().is_err());
+        assert!(truncated.next().is_none());
+    }
+}
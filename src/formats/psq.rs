@@ -0,0 +1,116 @@
+//! Piskvork's PSQ game records, in which Gomocup publishes its games.
+//!
+//! A record starts with a header line giving the board size, such as
+//! `Piskvorky 15x15, 11:11, 0`, followed by a line per move, `x,y,time`, with
+//! the time taken in milliseconds. Coordinates are counted from one, with `x`
+//! the column and `y` the row, as in the Gomocup protocol. X moves first.
+//! The lines after the moves, which name the engines, are ignored.
+//!
+//! PSQ has no field for how a game ended, so only results that follow from
+//! the moves survive a round trip: a game won with a line or drawn on a full
+//! board reads back finished, but one that ended by resignation, timeout, an
+//! illegal move or adjudication reads back unfinished.
+
+use std::fmt::Write;
+
+use super::cell;
+use crate::{
+    board::{Board, Player},
+    game::Game,
+};
+
+/// The board size given in the header of `text`.
+#[must_use]
+pub fn side_length(text: &str) -> Option<usize> {
+    let (width, height) = size(text.lines().next()?)?;
+    (width == height).then_some(width)
+}
+
+/// Parses the `WxH` size from a header line.
+fn size(header: &str) -> Option<(usize, usize)> {
+    let size = header.strip_prefix("Piskvorky")?.split(',').next()?;
+    let (width, height) = size.trim().split_once('x')?;
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+/// Reads a game from a PSQ record.
+///
+/// # Errors
+///
+/// Returns an error if the header is missing or gives a size other than
+/// `SIDE_LENGTH`, or if a move is off the board or on an occupied cell.
+pub fn read<const SIDE_LENGTH: usize>(text: &str) -> Result<Game<SIDE_LENGTH>, &'static str> {
+    let mut lines = text.lines();
+    let (width, height) = lines.next().and_then(size).ok_or("Missing PSQ header")?;
+    if (width, height) != (SIDE_LENGTH, SIDE_LENGTH) {
+        return Err("Wrong board size in PSQ header");
+    }
+    let mut game = Game::new(Board::new());
+    for line in lines {
+        let mut fields = line.split(',').map(|field| field.trim().parse::<usize>());
+        let (Some(Ok(x)), Some(Ok(y))) = (fields.next(), fields.next()) else {
+            break;
+        };
+        let mv = x
+            .checked_sub(1)
+            .zip(y.checked_sub(1))
+            .and_then(|(col, row)| cell(row, col))
+            .ok_or("Move off the board in PSQ record")?;
+        if game.result().is_some() || !game.board().is_legal(mv) {
+            return Err("Illegal move in PSQ record");
+        }
+        game.play(mv);
+    }
+    Ok(game)
+}
+
+/// Writes `game` as a PSQ record, with every move's time given as zero. The
+/// result is left out unless it follows from the moves.
+///
+/// # Errors
+///
+/// Returns an error if the game starts from a position with stones on the
+/// board, which PSQ cannot express.
+pub fn write<const SIDE_LENGTH: usize>(game: &Game<SIDE_LENGTH>) -> Result<String, &'static str> {
+    if game.start().stones(Player::None).count() != SIDE_LENGTH * SIDE_LENGTH {
+        return Err("PSQ records cannot start with stones on the board");
+    }
+    let mut text = format!("Piskvorky {SIDE_LENGTH}x{SIDE_LENGTH}, 11:11, 0\n");
+    for mv in game.moves() {
+        let coord = mv.coord();
+        let _ = writeln!(text, "{},{},0", coord.col + 1, coord.row + 1);
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        board::{Board, Player},
+        game::Game,
+    };
+
+    #[test]
+    fn reads_gomocup_records() {
+        let psq = "Piskvorky 20x20, 11:11, 0\n10,10,1203\n11,11,845\n10,11,0\npbrain-a.exe\npbrain-b.exe\n-1\n";
+        assert_eq!(super::side_length(psq), Some(20));
+        let game = super::read::<20>(psq).unwrap();
+        let moves = game
+            .moves()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(moves, ["J10", "K11", "J11"]);
+        assert_eq!(
+            super::write(&game).unwrap(),
+            "Piskvorky 20x20, 11:11, 0\n10,10,0\n11,11,0\n10,11,0\n"
+        );
+
+        assert!(super::read::<15>(psq).is_err());
+        assert!(super::read::<20>("Piskvorky 20x20, 11:11, 0\n21,1,0\n").is_err());
+        assert!(super::read::<20>("Piskvorky 20x20, 11:11, 0\n1,1,0\n1,1,0\n").is_err());
+        let setup =
+            Board::<20>::from_setup(&[("a1".parse().unwrap(), Player::O)], Player::X).unwrap();
+        assert!(super::write(&Game::new(setup)).is_err());
+    }
+}
@@ -0,0 +1,333 @@
+//! Game records in the Smart Game Format, as game `GM[4]` (gomoku).
+//!
+//! A record is a tree of nodes, each a list of properties such as `B[hh]`.
+//! Only the main line, the first variation at every branch, is read. The
+//! root node gives the board size with `SZ` (15 if missing), any stones on
+//! the board before the first move with `AB` and `AW`, the side to move
+//! first with `PL`, and the result with `RE`. Each later node holds a move,
+//! `B` for X and `W` for O.
+//!
+//! A point is written as two letters from `a`, the column and then the row,
+//! with rows counted from the top of the board, so that `aa` is the top-left
+//! corner, rank [`SIDE_LENGTH`](Board) of file A.
+
+use std::fmt::Write;
+
+use super::cell;
+use crate::{
+    board::{Board, Move, Player},
+    game::{Game, GameResult, Termination},
+};
+
+/// A node's properties, as `(identifier, values)` pairs.
+type Node<'a> = Vec<(&'a str, Vec<String>)>;
+
+/// The board size given by the `SZ` property of the root node of `text`, or
+/// 15 if the root node does not give one.
+#[must_use]
+pub fn side_length(text: &str) -> Option<usize> {
+    let nodes = parse(text).ok()?;
+    property(nodes.first()?, "SZ").map_or(Some(15), square_size)
+}
+
+/// Parses an `SZ` value, which is either one number or `columns:rows`.
+fn square_size(size: &str) -> Option<usize> {
+    match size.split_once(':') {
+        Some((width, height)) if width == height => width.trim().parse().ok(),
+        Some(_) => None,
+        None => size.trim().parse().ok(),
+    }
+}
+
+/// The first value of the property `id` in `node`.
+fn property<'a>(node: &'a Node<'_>, id: &str) -> Option<&'a str> {
+    node.iter()
+        .find(|(name, _)| *name == id)
+        .and_then(|(_, values)| values.first())
+        .map(String::as_str)
+}
+
+/// Splits the main line of a record into its nodes.
+fn parse(text: &str) -> Result<Vec<Node<'_>>, &'static str> {
+    let mut nodes = Vec::new();
+    let mut rest = text
+        .trim_start()
+        .strip_prefix('(')
+        .ok_or("SGF record must start with '('")?;
+    loop {
+        rest = rest.trim_start();
+        let mut chars = rest.chars();
+        match chars.next() {
+            None => return Err("Unterminated SGF record"),
+            // the main line continues into the first variation.
+            Some('(') => rest = chars.as_str(),
+            Some(')') => {
+                // the end of the main line's innermost variation; everything
+                // after it is a sibling variation or the end of the record.
+                return if nodes.is_empty() {
+                    Err("SGF record has no nodes")
+                } else {
+                    Ok(nodes)
+                };
+            }
+            Some(';') => {
+                rest = chars.as_str();
+                let mut node = Node::new();
+                loop {
+                    rest = rest.trim_start();
+                    let id_len = rest
+                        .find(|c: char| !c.is_ascii_uppercase())
+                        .unwrap_or(rest.len());
+                    if id_len == 0 {
+                        break;
+                    }
+                    let id = &rest[..id_len];
+                    rest = &rest[id_len..];
+                    let mut values = Vec::new();
+                    while let Some(value) = rest.trim_start().strip_prefix('[') {
+                        let (text, after) = value_text(value)?;
+                        values.push(text);
+                        rest = after;
+                    }
+                    if values.is_empty() {
+                        return Err("SGF property without a value");
+                    }
+                    node.push((id, values));
+                }
+                nodes.push(node);
+            }
+            Some(_) => return Err("Unexpected character in SGF record"),
+        }
+    }
+}
+
+/// Reads a property value up to its closing `]`, undoing escapes, and
+/// returns it with the text after the bracket.
+fn value_text(text: &str) -> Result<(String, &str), &'static str> {
+    let mut value = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            ']' => return Ok((value, &text[i + 1..])),
+            '\\' => value.extend(chars.next().map(|(_, c)| c)),
+            c => value.push(c),
+        }
+    }
+    Err("Unterminated SGF property value")
+}
+
+/// Parses a point such as `hh`.
+fn point<const SIDE_LENGTH: usize>(value: &str) -> Result<Move<SIDE_LENGTH>, &'static str> {
+    let &[col, row] = value.as_bytes() else {
+        return Err("Invalid SGF point");
+    };
+    let (col, row) = (col.wrapping_sub(b'a'), row.wrapping_sub(b'a'));
+    (usize::from(row) < SIDE_LENGTH)
+        .then(|| cell(SIDE_LENGTH - 1 - usize::from(row), usize::from(col)))
+        .flatten()
+        .ok_or("SGF point off the board")
+}
+
+/// Writes `mv` as a point.
+fn write_point<const SIDE_LENGTH: usize>(text: &mut String, mv: Move<SIDE_LENGTH>) {
+    #![allow(clippy::cast_possible_truncation)]
+    let coord = mv.coord();
+    text.push('[');
+    text.push(char::from(b'a' + coord.col as u8));
+    text.push(char::from(b'a' + (SIDE_LENGTH - 1 - coord.row) as u8));
+    text.push(']');
+}
+
+/// Reads a game from an SGF record.
+///
+/// The result is taken from `RE` when the moves do not decide it: `B+R` or
+/// `W+R` for a resignation, `+T` for a loss on time, `+F` for a forfeit, and
+/// `0` or `Draw` for a draw by agreement, which is read as adjudication.
+///
+/// # Errors
+///
+/// Returns an error if the record is malformed, is not a gomoku record, is
+/// for a board of another size, or has moves that are illegal or out of turn.
+pub fn read<const SIDE_LENGTH: usize>(text: &str) -> Result<Game<SIDE_LENGTH>, &'static str> {
+    let nodes = parse(text)?;
+    let root = &nodes[0];
+    if property(root, "GM").is_some_and(|game| game.trim() != "4") {
+        return Err("SGF record is not for gomoku");
+    }
+    if property(root, "SZ").map_or(Some(15), square_size) != Some(SIDE_LENGTH) {
+        return Err("Wrong board size in SGF record");
+    }
+    let mut stones = Vec::new();
+    for (id, player) in [("AB", Player::X), ("AW", Player::O)] {
+        for (_, values) in root.iter().filter(|(name, _)| *name == id) {
+            for value in values {
+                stones.push((point(value)?, player));
+            }
+        }
+    }
+    let to_move = match property(root, "PL") {
+        Some("B") => Player::X,
+        Some("W") => Player::O,
+        Some(_) => return Err("Invalid player in SGF record"),
+        None => {
+            let x = stones.iter().filter(|(_, p)| *p == Player::X).count();
+            if x * 2 > stones.len() {
+                Player::O
+            } else {
+                Player::X
+            }
+        }
+    };
+    let start = if stones.is_empty() && to_move == Player::X {
+        Board::new()
+    } else {
+        Board::from_setup(&stones, to_move)?
+    };
+    let mut game = Game::new(start);
+    for node in &nodes {
+        for &(id, ref values) in node {
+            let player = match id {
+                "B" => Player::X,
+                "W" => Player::O,
+                _ => continue,
+            };
+            let mv = point(&values[0])?;
+            if player != game.board().turn() {
+                return Err("Move out of turn in SGF record");
+            }
+            if game.result().is_some() || !game.board().is_legal(mv) {
+                return Err("Illegal move in SGF record");
+            }
+            game.play(mv);
+        }
+    }
+    if let Some(result) = property(root, "RE") {
+        apply_result(&mut game, result)?;
+    }
+    Ok(game)
+}
+
+/// Ends `game` as the `RE` value `result` says, if its moves have not.
+fn apply_result<const SIDE_LENGTH: usize>(
+    game: &mut Game<SIDE_LENGTH>,
+    result: &str,
+) -> Result<(), &'static str> {
+    if game.result().is_some() {
+        return Ok(());
+    }
+    let (winner, reason) = match result.trim() {
+        "0" | "Draw" => {
+            game.end(GameResult {
+                winner: Player::None,
+                termination: Termination::Adjudication,
+            });
+            return Ok(());
+        }
+        "?" | "Void" => return Ok(()),
+        result => result
+            .split_once('+')
+            .ok_or("Invalid result in SGF record")?,
+    };
+    let winner = match winner {
+        "B" => Player::X,
+        "W" => Player::O,
+        _ => return Err("Invalid result in SGF record"),
+    };
+    let termination = match reason {
+        "R" | "Resign" => Termination::Resignation,
+        "T" | "Time" => Termination::Timeout,
+        "F" | "Forfeit" => Termination::IllegalMove,
+        // a win by a line the moves do not show; nothing to record.
+        _ => return Ok(()),
+    };
+    game.end(GameResult::loss(-winner, termination));
+    Ok(())
+}
+
+/// Writes `game` as an SGF record on one line.
+#[must_use]
+pub fn write<const SIDE_LENGTH: usize>(game: &Game<SIDE_LENGTH>) -> String {
+    let mut text = format!("(;FF[4]GM[4]SZ[{SIDE_LENGTH}]");
+    let start = game.start();
+    for (id, player) in [("AB", Player::X), ("AW", Player::O)] {
+        let mut stones = start.stones(player).peekable();
+        if stones.peek().is_some() {
+            text.push_str(id);
+            stones.for_each(|mv| write_point(&mut text, mv));
+        }
+    }
+    if *start != Board::new() || start.turn() != Player::X {
+        text.push_str(if start.turn() == Player::X {
+            "PL[B]"
+        } else {
+            "PL[W]"
+        });
+    }
+    if let Some(result) = game.result() {
+        let winner = match result.winner {
+            Player::X => "B+",
+            Player::O => "W+",
+            Player::None => "0",
+        };
+        let reason = match result.termination {
+            _ if result.is_draw() => "",
+            Termination::Resignation => "R",
+            Termination::Timeout => "T",
+            Termination::IllegalMove => "F",
+            _ => "",
+        };
+        let _ = write!(text, "RE[{winner}{reason}]");
+    }
+    let mut player = start.turn();
+    for &mv in game.moves() {
+        text.push_str(if player == Player::X { ";B" } else { ";W" });
+        write_point(&mut text, mv);
+        player = -player;
+    }
+    text.push_str(")\n");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        board::{Board, Player},
+        game::{Game, Termination},
+    };
+
+    #[test]
+    fn reads_main_line_and_result() {
+        let sgf = "(;FF[4]GM[4]SZ[15]C[a \\] comment]RE[W+R];B[hh];W[ig]\n(;B[gg];W[ii])(;B[aa]))";
+        assert_eq!(super::side_length(sgf), Some(15));
+        let game = super::read::<15>(sgf).unwrap();
+        let moves = game
+            .moves()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(moves, ["H8", "I9", "G9", "I7"]);
+        let result = game.result().unwrap();
+        assert_eq!(result.winner, Player::O);
+        assert_eq!(result.termination, Termination::Resignation);
+        assert_eq!(
+            super::write(&game),
+            "(;FF[4]GM[4]SZ[15]RE[W+R];B[hh];W[ig];B[gg];W[ii])\n"
+        );
+
+        assert!(super::read::<19>(sgf).is_err());
+        assert!(super::read::<15>("(;GM[1]SZ[15])").is_err());
+        assert!(super::read::<15>("(;SZ[15];W[hh])").is_err());
+        assert!(super::read::<15>("(;SZ[15];B[hh]").is_err());
+    }
+
+    #[test]
+    fn round_trips_setup_positions() {
+        let stones = [("h8", Player::X), ("i9", Player::O), ("g7", Player::X)]
+            .map(|(mv, player)| (mv.parse().unwrap(), player));
+        let mut game = Game::new(Board::<15>::from_setup(&stones, Player::X).unwrap());
+        game.play("a1".parse().unwrap());
+        let sgf = super::write(&game);
+        assert_eq!(sgf, "(;FF[4]GM[4]SZ[15]AB[gi][hh]AW[ig]PL[B];B[ao])\n");
+        assert_eq!(super::read::<15>(&sgf), Ok(game));
+    }
+}
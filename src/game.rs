@@ -0,0 +1,574 @@
+//! Playing games between engines.
+
+use std::{fmt, time::Instant};
+
+use crate::{
+    board::{Board, Move, Player},
+    clock::Clock,
+    engine::{Engine, Limits},
+    rules::Rule,
+};
+
+/// Why a game ended.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Termination<const SIDE_LENGTH: usize> {
+    /// The winner completed a line, which runs between these two cells.
+    Line(Move<SIDE_LENGTH>, Move<SIDE_LENGTH>),
+    /// The board filled up without either side completing a line.
+    BoardFull,
+    /// The loser resigned.
+    Resignation,
+    /// The loser ran out of time.
+    Timeout,
+    /// The loser played a move that was illegal, or forbidden by the game's
+    /// [`Rule`].
+    IllegalMove,
+    /// The game was drawn by [adjudication](Adjudication).
+    Adjudication,
+}
+
+/// How a game ended: who won, and why.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GameResult<const SIDE_LENGTH: usize> {
+    /// The winner, or [`Player::None`] for a draw.
+    pub winner: Player,
+    /// Why the game ended.
+    pub termination: Termination<SIDE_LENGTH>,
+}
+
+impl<const SIDE_LENGTH: usize> GameResult<SIDE_LENGTH> {
+    /// The result of a game that `loser` lost for `termination`.
+    #[must_use]
+    pub fn loss(loser: Player, termination: Termination<SIDE_LENGTH>) -> Self {
+        Self {
+            winner: -loser,
+            termination,
+        }
+    }
+
+    /// Whether the game was drawn.
+    #[must_use]
+    pub fn is_draw(&self) -> bool {
+        self.winner == Player::None
+    }
+}
+
+impl<const SIDE_LENGTH: usize> fmt::Display for GameResult<SIDE_LENGTH> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.winner {
+            Player::X => f.write_str("X wins")?,
+            Player::O => f.write_str("O wins")?,
+            Player::None => f.write_str("draw")?,
+        }
+        match self.termination {
+            Termination::Line(from, to) => write!(f, " by a line from {from} to {to}"),
+            Termination::BoardFull => f.write_str(" by a full board"),
+            Termination::Resignation => f.write_str(" by resignation"),
+            Termination::Timeout => f.write_str(" on time"),
+            Termination::IllegalMove => f.write_str(" by an illegal move"),
+            Termination::Adjudication => f.write_str(" by adjudication"),
+        }
+    }
+}
+
+/// Rules for declaring a game drawn before the board fills up, so that
+/// self-play doesn't waste time on dead positions.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Adjudication {
+    /// If set, the game is drawn once this many moves have been played on
+    /// the board, counting any in the starting position.
+    pub max_plies: Option<usize>,
+    /// If set, the game is drawn once this many moves in a row have been
+    /// played without a threat: a move after which the side that played it
+    /// could win with its next move.
+    pub quiet_moves: Option<usize>,
+}
+
+impl Adjudication {
+    /// No adjudication: games run until someone wins or the board is full.
+    pub const NONE: Self = Self {
+        max_plies: None,
+        quiet_moves: None,
+    };
+}
+
+/// A game in progress or finished, remembering the moves played from its start.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Game<const SIDE_LENGTH: usize> {
+    start: Board<SIDE_LENGTH>,
+    board: Board<SIDE_LENGTH>,
+    moves: Vec<Move<SIDE_LENGTH>>,
+    ending: Option<GameResult<SIDE_LENGTH>>,
+    clock: Option<Clock>,
+    rule: Rule,
+    adjudication: Adjudication,
+    quiet_moves: usize,
+}
+
+impl<const SIDE_LENGTH: usize> Game<SIDE_LENGTH> {
+    /// Creates a game starting from `start`.
+    #[must_use]
+    pub const fn new(start: Board<SIDE_LENGTH>) -> Self {
+        Self {
+            start,
+            board: start,
+            moves: Vec::new(),
+            ending: None,
+            clock: None,
+            rule: Rule::Freestyle,
+            adjudication: Adjudication::NONE,
+            quiet_moves: 0,
+        }
+    }
+
+    /// Creates a timed game starting from `start`. A player who runs out of
+    /// time in [`Game::play_out`] loses.
+    #[must_use]
+    pub const fn with_clock(start: Board<SIDE_LENGTH>, clock: Clock) -> Self {
+        Self {
+            start,
+            board: start,
+            moves: Vec::new(),
+            ending: None,
+            clock: Some(clock),
+            rule: Rule::Freestyle,
+            adjudication: Adjudication::NONE,
+            quiet_moves: 0,
+        }
+    }
+
+    /// Sets the rules for drawing the game early. By default, games run until
+    /// someone wins or the board is full.
+    #[must_use]
+    pub const fn with_adjudication(mut self, adjudication: Adjudication) -> Self {
+        self.adjudication = adjudication;
+        self
+    }
+
+    /// The rules for drawing the game early.
+    #[must_use]
+    pub const fn adjudication(&self) -> &Adjudication {
+        &self.adjudication
+    }
+
+    /// Sets the rule set the game is played under. A move the rule forbids
+    /// forfeits the game, as an illegal move does. By default, games are
+    /// played under [`Rule::Freestyle`].
+    #[must_use]
+    pub const fn with_rule(mut self, rule: Rule) -> Self {
+        self.rule = rule;
+        self
+    }
+
+    /// The rule set the game is played under.
+    #[must_use]
+    pub const fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    /// The game's clock, if it is timed.
+    #[must_use]
+    pub const fn clock(&self) -> Option<&Clock> {
+        self.clock.as_ref()
+    }
+
+    /// The starting position.
+    #[must_use]
+    pub const fn start(&self) -> &Board<SIDE_LENGTH> {
+        &self.start
+    }
+
+    /// The current position.
+    #[must_use]
+    pub const fn board(&self) -> &Board<SIDE_LENGTH> {
+        &self.board
+    }
+
+    /// The moves played so far.
+    #[must_use]
+    pub fn moves(&self) -> &[Move<SIDE_LENGTH>] {
+        &self.moves
+    }
+
+    /// The result of the game, if it is over.
+    #[must_use]
+    pub fn result(&self) -> Option<GameResult<SIDE_LENGTH>> {
+        if self.ending.is_some() {
+            return self.ending;
+        }
+        match self.board.outcome()? {
+            Player::None => Some(GameResult {
+                winner: Player::None,
+                termination: Termination::BoardFull,
+            }),
+            winner => {
+                let (from, to) = self.board.winning_line()?;
+                Some(GameResult {
+                    winner,
+                    termination: Termination::Line(from, to),
+                })
+            }
+        }
+    }
+
+    /// The winner of the game, if it is over.
+    ///
+    /// `Some(Player::None)` means the game is a draw.
+    #[must_use]
+    pub fn winner(&self) -> Option<Player> {
+        self.result().map(|result| result.winner)
+    }
+
+    /// Plays `mv` for the side to move. A move that is illegal, or that the
+    /// game's rule forbids, forfeits the game.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the game is already over.
+    pub fn play(&mut self, mv: Move<SIDE_LENGTH>) {
+        assert!(self.result().is_none(), "The game is already over.");
+        let us = self.board.turn();
+        if self.board.is_legal_under(self.rule, mv) {
+            self.board.make_move(mv);
+            self.moves.push(mv);
+            self.adjudicate(us);
+        } else {
+            self.ending = Some(GameResult::loss(us, Termination::IllegalMove));
+        }
+    }
+
+    /// Takes back the last move, reopening the game if it had ended, and
+    /// returns the move, or `None` if no moves have been played.
+    ///
+    /// The clock, if any, is not wound back.
+    pub fn undo(&mut self) -> Option<Move<SIDE_LENGTH>> {
+        let mut moves = std::mem::take(&mut self.moves);
+        let mv = moves.pop()?;
+        self.board = self.start;
+        self.ending = None;
+        self.quiet_moves = 0;
+        for mv in moves {
+            self.play(mv);
+        }
+        Some(mv)
+    }
+
+    /// Ends the game with `player` resigning.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the game is already over, or `player` is [`Player::None`].
+    pub fn resign(&mut self, player: Player) {
+        self.forfeit(player, Termination::Resignation);
+    }
+
+    /// Ends the game with `player` losing on time, as when a clock kept
+    /// outside the game, such as a protocol adapter's, runs out.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the game is already over, or `player` is [`Player::None`].
+    pub fn flag(&mut self, player: Player) {
+        self.forfeit(player, Termination::Timeout);
+    }
+
+    /// Ends the game with `result`, as a record of the game says it ended.
+    pub(crate) fn end(&mut self, result: GameResult<SIDE_LENGTH>) {
+        assert!(self.result().is_none(), "The game is already over.");
+        self.ending = Some(result);
+    }
+
+    fn forfeit(&mut self, loser: Player, termination: Termination<SIDE_LENGTH>) {
+        assert!(self.result().is_none(), "The game is already over.");
+        assert!(loser != Player::None, "No player to forfeit.");
+        self.ending = Some(GameResult::loss(loser, termination));
+    }
+
+    /// Updates the count of quiet moves after `us` has played, and draws the
+    /// game if the adjudication rules say so.
+    fn adjudicate(&mut self, us: Player) {
+        let Adjudication {
+            max_plies,
+            quiet_moves,
+        } = self.adjudication;
+        if quiet_moves.is_some() {
+            let mut threat = false;
+            self.board.generate_moves(|reply| {
+                threat = self.board.is_winning_move(reply, us);
+                threat
+            });
+            self.quiet_moves = if threat { 0 } else { self.quiet_moves + 1 };
+        }
+        if self.board.outcome().is_none()
+            && (max_plies.is_some_and(|max| self.board.ply() >= max)
+                || quiet_moves.is_some_and(|max| self.quiet_moves >= max))
+        {
+            self.ending = Some(GameResult {
+                winner: Player::None,
+                termination: Termination::Adjudication,
+            });
+        }
+    }
+
+    /// Plays the game to the end, asking `x` and `o` for their moves, and
+    /// returns the result.
+    ///
+    /// In a timed game, each engine is given `limits` with its clock filled in.
+    pub fn play_out(
+        &mut self,
+        x: &mut dyn Engine<SIDE_LENGTH>,
+        o: &mut dyn Engine<SIDE_LENGTH>,
+        limits: &Limits,
+    ) -> GameResult<SIDE_LENGTH> {
+        loop {
+            if let Some(result) = self.result() {
+                return result;
+            }
+            let us = self.board.turn();
+            let limits = self
+                .clock
+                .map_or_else(|| limits.clone(), |clock| clock.limits(us, limits.clone()));
+            let start = Instant::now();
+            let mv = if us == Player::X {
+                x.best_move(&self.board, &limits)
+            } else {
+                o.best_move(&self.board, &limits)
+            };
+            if let Some(clock) = &mut self.clock {
+                if !clock.punch(us, start.elapsed()) {
+                    self.flag(us);
+                    continue;
+                }
+            }
+            self.play(mv);
+        }
+    }
+}
+
+/// With the `serde` feature, a game is stored as its starting position, its
+/// moves, and its result if it ended other than on the board, by a forfeit or
+/// adjudication. The clock, rule set, and adjudication rules are not stored,
+/// and the current position is rebuilt by replaying the moves.
+#[cfg(feature = "serde")]
+impl<const SIDE_LENGTH: usize> serde::Serialize for Game<SIDE_LENGTH> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Game", 3)?;
+        state.serialize_field("start", &self.start)?;
+        state.serialize_field("moves", &self.moves)?;
+        state.serialize_field("ending", &self.ending)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const SIDE_LENGTH: usize> serde::Deserialize<'de> for Game<SIDE_LENGTH> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "Game")]
+        struct Record<const SIDE_LENGTH: usize> {
+            start: Board<SIDE_LENGTH>,
+            moves: Vec<Move<SIDE_LENGTH>>,
+            #[serde(default)]
+            ending: Option<GameResult<SIDE_LENGTH>>,
+        }
+
+        let record = Record::<SIDE_LENGTH>::deserialize(deserializer)?;
+        let board = record
+            .start
+            .apply_moves(&record.moves)
+            .map_err(|i| serde::de::Error::custom(format!("illegal move {}", i + 1)))?;
+        Ok(Self {
+            start: record.start,
+            board,
+            moves: record.moves,
+            ending: record.ending,
+            clock: None,
+            rule: Rule::Freestyle,
+            adjudication: Adjudication::default(),
+            quiet_moves: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Adjudication, Game, GameResult, Termination};
+    use crate::{
+        board::{Board, Move, Player},
+        clock::{Clock, TimeControl},
+        engine::{Engine, Limits, RandomEngine},
+        rules::Rule,
+    };
+
+    struct Resigner;
+
+    impl Engine<9> for Resigner {
+        fn best_move(&mut self, _: &Board<9>, _: &Limits) -> Move<9> {
+            Move::null()
+        }
+    }
+
+    #[test]
+    fn illegal_moves_forfeit() {
+        let mut game = Game::new(Board::<9>::new());
+        let result = game.play_out(&mut RandomEngine::new(3), &mut Resigner, &Limits::default());
+        assert_eq!(
+            result,
+            GameResult::loss(Player::O, Termination::IllegalMove)
+        );
+        assert_eq!(result.to_string(), "X wins by an illegal move");
+        assert_eq!(game.moves().len(), 1);
+
+        // so do forbidden ones.
+        let mut game = Game::new(Board::<15>::new()).with_rule(Rule::Pro);
+        game.play("a1".parse().unwrap());
+        assert_eq!(game.winner(), Some(Player::O));
+        assert!(game.moves().is_empty());
+    }
+
+    struct Sleeper;
+
+    impl Engine<9> for Sleeper {
+        fn best_move(&mut self, board: &Board<9>, limits: &Limits) -> Move<9> {
+            std::thread::sleep(limits.time_left.unwrap() + Duration::from_millis(1));
+            RandomEngine::new(0).best_move(board, limits)
+        }
+    }
+
+    #[test]
+    fn running_out_of_time_forfeits() {
+        let clock = Clock::new(TimeControl::SuddenDeath(Duration::from_millis(5)));
+        let mut game = Game::with_clock(Board::<9>::new(), clock);
+        let result = game.play_out(&mut RandomEngine::new(3), &mut Sleeper, &Limits::default());
+        assert_eq!(result, GameResult::loss(Player::O, Termination::Timeout));
+        assert_eq!(game.moves().len(), 1);
+        assert_eq!(game.clock().unwrap().flagged(), Some(Player::O));
+    }
+
+    #[test]
+    fn random_games_finish() {
+        let mut game = Game::new(Board::<7>::new());
+        let result = game.play_out(
+            &mut RandomEngine::new(1),
+            &mut RandomEngine::new(2),
+            &Limits::default(),
+        );
+        assert_eq!(game.result(), Some(result));
+        assert_eq!(
+            Board::<7>::new().apply_moves(game.moves()).unwrap(),
+            *game.board()
+        );
+        match result.termination {
+            Termination::Line(from, to) => {
+                assert_eq!(game.board().player_at(from.coord()), result.winner);
+                assert_eq!(game.board().player_at(to.coord()), result.winner);
+            }
+            termination => assert_eq!(termination, Termination::BoardFull),
+        }
+    }
+
+    #[test]
+    fn adjudication_draws_dead_games() {
+        let adjudication = Adjudication {
+            max_plies: Some(10),
+            quiet_moves: None,
+        };
+        let mut game = Game::new(Board::<9>::new()).with_adjudication(adjudication);
+        let result = game.play_out(
+            &mut RandomEngine::new(1),
+            &mut RandomEngine::new(2),
+            &Limits::default(),
+        );
+        assert!(game.moves().len() <= 10);
+        if game.board().outcome().is_none() {
+            assert!(result.is_draw());
+            assert_eq!(result.termination, Termination::Adjudication);
+        }
+
+        // moves far apart on a big board never threaten anything.
+        let adjudication = Adjudication {
+            max_plies: None,
+            quiet_moves: Some(4),
+        };
+        let mut game = Game::new(Board::<15>::new()).with_adjudication(adjudication);
+        for mv in ["a1", "o15", "a15"] {
+            game.play(mv.parse().unwrap());
+        }
+        assert_eq!(game.result(), None);
+        game.play("o1".parse().unwrap());
+        assert_eq!(game.winner(), Some(Player::None));
+        assert_eq!(game.result().unwrap().to_string(), "draw by adjudication");
+
+        // a four resets the count.
+        let adjudication = Adjudication {
+            quiet_moves: Some(8),
+            ..adjudication
+        };
+        let mut game = Game::new(Board::<15>::new()).with_adjudication(adjudication);
+        for mv in ["h8", "a1", "h9", "a3", "h10", "a5", "h11", "a7"] {
+            game.play(mv.parse().unwrap());
+        }
+        assert_eq!(game.result(), None);
+    }
+
+    #[test]
+    fn resigning_and_flagging() {
+        let mut game = Game::new(Board::<9>::new());
+        game.play("e5".parse().unwrap());
+        game.resign(Player::O);
+        assert_eq!(
+            game.result(),
+            Some(GameResult::loss(Player::O, Termination::Resignation))
+        );
+        assert_eq!(game.result().unwrap().to_string(), "X wins by resignation");
+
+        // either side may flag, whoever is to move.
+        let mut game = Game::new(Board::<9>::new());
+        game.flag(Player::X);
+        assert_eq!(game.winner(), Some(Player::O));
+        assert_eq!(game.result().unwrap().termination, Termination::Timeout);
+    }
+
+    #[test]
+    #[should_panic = "already over"]
+    fn cannot_resign_a_finished_game() {
+        let mut game = Game::new(Board::<9>::new());
+        game.resign(Player::X);
+        game.resign(Player::O);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_keeps_resignations() {
+        let mut game = Game::new(Board::<9>::new());
+        game.play("e5".parse().unwrap());
+        game.resign(Player::X);
+        let json = serde_json::to_string(&game).unwrap();
+        assert!(json.ends_with(r#""ending":{"winner":"O","termination":"Resignation"}}"#));
+        let decoded = serde_json::from_str::<Game<9>>(&json).unwrap();
+        assert_eq!(decoded.result(), game.result());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn bincode_round_trip() {
+        let mut game = Game::new(Board::<9>::new());
+        for mv in ["e5", "e6", "f5"] {
+            game.play(mv.parse().unwrap());
+        }
+        game.play(Move::null());
+        let bytes = bincode::serialize(&game).unwrap();
+        // the packed board, three moves with their length, and the result,
+        // with the winner and the reason.
+        assert_eq!(bytes.len(), 8 + 21 + 2 + 1 + 1 + 1 + 8 + 3 * 2 + 1 + 4 + 4);
+        let decoded = bincode::deserialize::<Game<9>>(&bytes).unwrap();
+        assert_eq!(decoded, game);
+        assert_eq!(decoded.result(), game.result());
+        assert_eq!(decoded.winner(), Some(Player::X));
+    }
+}
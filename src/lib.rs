@@ -1,11 +1,63 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+// no license has been chosen for the crate yet.
+#![allow(clippy::cargo_common_metadata)]
 
+pub mod analysis;
+pub mod batch;
 pub mod board;
+pub mod book;
+#[cfg(feature = "rayon")]
+pub mod bulk;
+pub mod checkpoint;
+pub mod clock;
+pub mod database;
+pub mod engine;
+pub mod eval;
+pub mod export;
+pub mod formats;
+pub mod game;
+pub mod lines;
+#[cfg(feature = "mmap")]
+pub mod mapped;
+pub mod matches;
+pub mod openings;
 pub mod perft;
-
+pub mod positions;
+pub mod prelude;
+pub mod presets;
+pub mod protocol;
+pub mod puzzles;
+pub mod render;
+pub mod renju;
+pub mod rules;
+pub mod sink;
+pub mod solver;
+pub mod symmetry;
+pub mod tables;
 #[cfg(test)]
-mod tests {
-    use super::*;
+mod test_util;
+pub mod threats;
+pub mod tt;
+pub mod tuning;
+mod zobrist;
 
-    
+/// Runs `$body` with `$side` bound to the side length `$n` as a constant,
+/// for the board sizes the crate supports, or evaluates `$otherwise`. The
+/// binaries use this to pick a `Board<SIDE>` from a size read at runtime.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! with_side_length {
+    ($n:expr, $side:ident => $body:expr, $otherwise:expr) => {
+        $crate::with_side_length!(@sizes $n, $side, $body, $otherwise;
+            3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20)
+    };
+    (@sizes $n:expr, $side:ident, $body:expr, $otherwise:expr; $($size:literal)*) => {
+        match $n {
+            $($size => {
+                const $side: usize = $size;
+                $body
+            })*
+            _ => $otherwise,
+        }
+    };
 }
@@ -0,0 +1,246 @@
+use crate::board::{Board, Coord, Player};
+
+/// Counts of the runs of one player's stones, from [`Board::line_stats`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LineStats {
+    /// The length of the longest run.
+    pub longest: usize,
+    /// The number of runs of exactly two stones.
+    pub twos: usize,
+    /// The number of runs of exactly three stones.
+    pub threes: usize,
+    /// The number of runs of exactly four stones.
+    pub fours: usize,
+}
+
+/// A row, column, or diagonal of a board, walked from one edge to the other.
+#[derive(Clone, Copy, Debug)]
+pub struct Line<'a, const SIDE_LENGTH: usize> {
+    board: &'a Board<SIDE_LENGTH>,
+    start: Coord,
+    step: (isize, isize),
+    len: usize,
+}
+
+impl<'a, const SIDE_LENGTH: usize> Line<'a, SIDE_LENGTH> {
+    /// The first cell of the line.
+    #[must_use]
+    pub const fn start(&self) -> Coord {
+        self.start
+    }
+
+    /// The `(row, col)` offset between consecutive cells of the line.
+    #[must_use]
+    pub const fn step(&self) -> (isize, isize) {
+        self.step
+    }
+
+    /// The number of cells in the line.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the line has no cells. Lines yielded by [`Board::lines`] never are.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The coordinate of the `i`th cell of the line.
+    #[must_use]
+    pub const fn coord(&self, i: usize) -> Coord {
+        #![allow(clippy::cast_sign_loss, clippy::cast_possible_wrap)]
+        Coord {
+            row: (self.start.row as isize + self.step.0 * i as isize) as usize,
+            col: (self.start.col as isize + self.step.1 * i as isize) as usize,
+        }
+    }
+
+    /// Returns an iterator over the coordinates of the line, in order.
+    pub fn coords(&self) -> impl Iterator<Item = Coord> + 'a {
+        let line = *self;
+        (0..self.len).map(move |i| line.coord(i))
+    }
+
+    /// Returns an iterator over the contents of the line, in order.
+    pub fn cells(&self) -> impl Iterator<Item = Player> + 'a {
+        let line = *self;
+        (0..self.len).map(move |i| line.board.player_at(line.coord(i)))
+    }
+}
+
+impl<const SIDE_LENGTH: usize> Board<SIDE_LENGTH> {
+    /// Returns an iterator over every row, column, diagonal, and anti-diagonal
+    /// of the board that is long enough to contain a winning line.
+    ///
+    /// Rows come first, then columns, then diagonals (running towards higher
+    /// rows and columns), then anti-diagonals (towards higher rows and lower columns).
+    pub fn lines(&self) -> impl Iterator<Item = Line<'_, SIDE_LENGTH>> {
+        let win_length = self.win_length();
+        self.all_lines().filter(move |line| line.len >= win_length)
+    }
+
+    /// Every row, column, diagonal, and anti-diagonal, however short, in the
+    /// order of [`Board::lines`].
+    fn all_lines(&self) -> impl Iterator<Item = Line<'_, SIDE_LENGTH>> {
+        let line = move |start: Coord, step, len| Line {
+            board: self,
+            start,
+            step,
+            len,
+        };
+        let n = SIDE_LENGTH;
+        let rows = (0..n).map(move |row| line(Coord::new(row, 0), (0, 1), n));
+        let cols = (0..n).map(move |col| line(Coord::new(0, col), (1, 0), n));
+        let diagonals = (0..n)
+            .rev()
+            .map(move |row| line(Coord::new(row, 0), (1, 1), n - row))
+            .chain((1..n).map(move |col| line(Coord::new(0, col), (1, 1), n - col)));
+        let anti_diagonals = (0..n)
+            .map(move |col| line(Coord::new(0, col), (1, -1), col + 1))
+            .chain((1..n).map(move |row| line(Coord::new(row, n - 1), (1, -1), n - row)));
+        rows.chain(cols).chain(diagonals).chain(anti_diagonals)
+    }
+
+    /// Summarises the runs of `player`'s stones: unbroken sequences of their
+    /// stones along a row, column, or diagonal, counted in every direction.
+    ///
+    /// A lone stone is a run of one in each of the four directions.
+    #[must_use]
+    pub fn line_stats(&self, player: Player) -> LineStats {
+        let mut stats = LineStats::default();
+        let mut finish = |run: usize| {
+            stats.longest = stats.longest.max(run);
+            match run {
+                2 => stats.twos += 1,
+                3 => stats.threes += 1,
+                4 => stats.fours += 1,
+                _ => {}
+            }
+        };
+        for line in self.all_lines() {
+            let mut run = 0;
+            for cell in line.cells() {
+                if cell == player {
+                    run += 1;
+                } else {
+                    finish(run);
+                    run = 0;
+                }
+            }
+            finish(run);
+        }
+        stats
+    }
+
+    /// Returns an iterator over every run of [`Board::win_length`] consecutive
+    /// cells on the board, in any direction. Each window is a [`Line`], so it
+    /// gives access to both the cells and their coordinates.
+    ///
+    /// Windows are yielded line by line, in the order of [`Board::lines`].
+    pub fn windows(&self) -> impl Iterator<Item = Line<'_, SIDE_LENGTH>> {
+        let win_length = self.win_length();
+        self.lines().flat_map(move |line| {
+            (0..=line.len - win_length).map(move |offset| Line {
+                start: line.coord(offset),
+                len: win_length,
+                ..line
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LineStats;
+    use crate::board::{Board, Coord, Player};
+
+    #[test]
+    fn line_counts() {
+        // 15 rows, 15 columns, and 21 diagonals of length >= 5 in each direction.
+        let board = Board::<15>::new();
+        assert_eq!(board.lines().count(), 15 + 15 + 21 + 21);
+        let board = Board::<5>::new();
+        assert_eq!(board.lines().count(), 5 + 5 + 1 + 1);
+        let board = Board::<4>::new();
+        assert_eq!(board.lines().count(), 4 + 4 + 1 + 1);
+    }
+
+    #[test]
+    fn every_cell_is_on_four_lines() {
+        let board = Board::<7>::new();
+        let mut counts = [[0; 7]; 7];
+        for line in board.lines() {
+            for Coord { row, col } in line.coords() {
+                counts[row][col] += 1;
+            }
+        }
+        // cells near the corners miss out on the short diagonals.
+        assert_eq!(counts[3][3], 4);
+        assert_eq!(counts[0][0], 3);
+        assert_eq!(counts[0][6], 3);
+    }
+
+    #[test]
+    fn line_contents() {
+        let board: Board<5> = "x..../.x.../..x../...o./....o o 5".parse().unwrap();
+        let diagonal = board.lines().find(|line| line.step() == (1, 1)).unwrap();
+        assert_eq!(diagonal.start(), Coord::new(0, 0));
+        assert_eq!(
+            diagonal.cells().collect::<Vec<_>>(),
+            [Player::X, Player::X, Player::X, Player::O, Player::O]
+        );
+    }
+
+    #[test]
+    fn window_counts() {
+        // each line of length n holds n - 4 windows.
+        let board = Board::<15>::new();
+        let diagonal_windows: usize = (5..=15).map(|n| n - 4).sum::<usize>() * 2 - 11;
+        assert_eq!(board.windows().count(), 2 * 15 * 11 + 2 * diagonal_windows);
+        assert!(board.windows().all(|w| w.len() == 5));
+    }
+
+    #[test]
+    fn windows_agree_with_outcome() {
+        let mut rng = crate::test_util::test_rng(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..50 {
+            let mut board = Board::<9>::new();
+            while board.outcome().is_none() {
+                board.make_random_move(&mut rng);
+            }
+            let five = |player| board.windows().any(|w| w.cells().all(|c| c == player));
+            match board.outcome() {
+                Some(Player::None) => assert!(!five(Player::X) && !five(Player::O)),
+                Some(winner) => assert!(five(winner) && !five(-winner)),
+                None => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn run_statistics() {
+        let moves =
+            ["a1", "e5", "b1", "e6", "c1", "f6", "d1", "i9", "c2"].map(|mv| mv.parse().unwrap());
+        let board = Board::<9>::new().apply_moves(&moves).unwrap();
+        // X has four in a row on rank 1, and c2 makes a pair with each of
+        // b1, c1, and d1.
+        assert_eq!(
+            board.line_stats(Player::X),
+            LineStats {
+                longest: 4,
+                twos: 3,
+                threes: 0,
+                fours: 1,
+            }
+        );
+        // O's e5, e6, and f6 make a pair in each of three directions.
+        let o = board.line_stats(Player::O);
+        assert_eq!((o.longest, o.twos, o.threes, o.fours), (2, 3, 0, 0));
+        assert_eq!(
+            Board::<9>::new().line_stats(Player::X),
+            LineStats::default()
+        );
+    }
+}
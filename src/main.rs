@@ -1,35 +1,420 @@
-use gomokugen::{board::Board, perft};
-
-fn main() {
-    // run benchmarks...
-
-    // println!("Starting position (9x9): \n{}", Board::<9>::default());
-    // println!("Starting position (15x15): \n{}", Board::<15>::default());
-    // let mut b = Board::<9>::default();
-    // // play f3
-    // b.make_move("f3".parse().unwrap());
-    // println!("After f3: \n{}", b);
-
-    // // perft depth 4 on a 15x15 board:
-    // let start_time = std::time::Instant::now();
-    // let count = perft::perft(board::Board::<15>::new(), 4);
-    // let elapsed = start_time.elapsed();
-    // println!("perft depth 4 on a 15x15 board: {} nodes in {}.{:03}s", count, elapsed.as_secs(), elapsed.subsec_millis());
-    // println!("nodes per second: {:.2}", count as f64 / elapsed.as_secs_f64());
-
-    // // perft depth 4 on a 17x17 board:
-    // let start_time = std::time::Instant::now();
-    // let count = perft::perft(board::Board::<17>::new(), 4);
-    // let elapsed = start_time.elapsed();
-    // println!("perft depth 4 on a 17x17 board: {} nodes in {}.{:03}s", count, elapsed.as_secs(), elapsed.subsec_millis());
-    // println!("nodes per second: {:.2}", count as f64 / elapsed.as_secs_f64());
-
-    // // perft depth 4 on a 19x19 board:
-    // let start_time = std::time::Instant::now();
-    // let count = perft::perft(board::Board::<19>::new(), 4);
-    // let elapsed = start_time.elapsed();
-    // println!("perft depth 4 on a 19x19 board: {} nodes in {}.{:03}s", count, elapsed.as_secs(), elapsed.subsec_millis());
-    // println!("nodes per second: {:.2}", count as f64 / elapsed.as_secs_f64());
-
-    perft::generate_depth_n_fens(Board::<15>::default(), |fen| println!("{fen}"), 2);
-}
\ No newline at end of file
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read, Seek},
+    path::Path,
+    process::ExitCode,
+};
+
+use gomokugen::{
+    analysis::{self, PlayoutPolicy},
+    board::{Board, Player},
+    book::BookBuilder,
+    database::GameStats,
+    formats::{collection, Format},
+    render::Heatmap,
+    renju, with_side_length,
+};
+
+const USAGE: &str = "\
+usage: gomokugen <command> [options]
+
+commands:
+  show [--threats] [--forbidden] [--winrate] [<fen>...]
+      Draws each position, reading FENs from standard input, one per line,
+      if none are given. --threats highlights the cells where either side
+      can win at once, and --forbidden the cells Renju forbids for X.
+      --winrate adds the results of 1000 random playouts for the side to
+      move.
+
+  convert [--from <format>] --to <format> [<file>...]
+      Converts game records between formats: psq, sgf, fens (one FEN per
+      position), and json (the crate's own records, with the json feature).
+      Each file is written beside the original, with the extension of the
+      new format; --from defaults to the format its extension names. With
+      no files, one record is read from standard input and written to
+      standard output.
+
+  stats <file>...
+      Reads game collections and reports the number of games, their
+      average length, and the first player's results by board size and
+      rule and by opening move.
+
+  book [--plies <n>] <output> <file>...
+      Builds an opening book from game collections, such as those saved
+      from self-play matches, scoring the moves of the first n plies (12 by
+      default) of each finished game by its result, and writes it to
+      output in the binary book format. Every collection must be for the
+      same board size.
+
+  serve [--size <n>] [<address>]
+      With the http feature, serves analysis of positions on n-by-n boards
+      (15 by default) over HTTP on address (127.0.0.1:8080 by default).
+      Searches are limited to ten seconds.";
+
+/// The number of playouts behind `show --winrate`.
+const WINRATE_PLAYOUTS: u32 = 1000;
+
+/// What `show` marks on the board besides the stones.
+#[derive(Copy, Clone, Debug, Default)]
+struct Highlights {
+    threats: bool,
+    forbidden: bool,
+    winrate: bool,
+}
+
+/// Draws the position `fen`, whose size is given by its number of rows.
+fn show(fen: &str, highlights: Highlights) -> Result<(), &'static str> {
+    let rows = fen
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .split('/')
+        .count();
+    with_side_length!(
+        rows,
+        SIDE => {
+            show_board(&fen.parse::<Board<SIDE>>()?, highlights);
+            Ok(())
+        },
+        Err("Unsupported board size")
+    )
+}
+
+fn show_board<const SIDE_LENGTH: usize>(board: &Board<SIDE_LENGTH>, highlights: Highlights) {
+    let mut notes = Vec::new();
+    if highlights.winrate {
+        let rate = analysis::winrate(board, WINRATE_PLAYOUTS, PlayoutPolicy::Nearby, 0);
+        notes.push(format!("{:?} to move: {rate}", board.turn()));
+    }
+    if !highlights.threats && !highlights.forbidden {
+        println!("{board}");
+        for note in notes {
+            println!("{note}");
+        }
+        return;
+    }
+    let mut values = vec![f32::NAN; SIDE_LENGTH * SIDE_LENGTH];
+    if highlights.threats {
+        for player in [board.turn(), -board.turn()] {
+            let wins = board
+                .stones(Player::None)
+                .filter(|&mv| board.is_winning_move(mv, player))
+                .collect::<Vec<_>>();
+            for mv in &wins {
+                values[mv.index()] = 1.0;
+            }
+            if !wins.is_empty() {
+                let wins = wins.iter().map(ToString::to_string).collect::<Vec<_>>();
+                notes.push(format!("{player:?} wins at {}", wins.join(" ")));
+            }
+        }
+    }
+    if highlights.forbidden {
+        let forbidden = renju::forbidden_points(board);
+        for (mv, _) in &forbidden {
+            values[mv.index()] = 1.0;
+        }
+        if !forbidden.is_empty() {
+            let points = forbidden
+                .iter()
+                .map(|(mv, reason)| format!("{mv} ({reason})"))
+                .collect::<Vec<_>>();
+            notes.push(format!("forbidden for X: {}", points.join(", ")));
+        }
+    }
+    println!("{}", Heatmap::new(board, &values));
+    for note in notes {
+        println!("{note}");
+    }
+}
+
+fn run_show(args: &[String]) -> ExitCode {
+    let mut highlights = Highlights::default();
+    let mut fens = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--threats" => highlights.threats = true,
+            "--forbidden" => highlights.forbidden = true,
+            "--winrate" => highlights.winrate = true,
+            flag if flag.starts_with("--") => {
+                eprintln!("unknown option {flag}\n\n{USAGE}");
+                return ExitCode::FAILURE;
+            }
+            fen => fens.push(fen.to_owned()),
+        }
+    }
+    if fens.is_empty() {
+        fens = io::stdin()
+            .lock()
+            .lines()
+            .map_while(Result::ok)
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+    }
+    let mut status = ExitCode::SUCCESS;
+    for (i, fen) in fens.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        if let Err(e) = show(fen.trim(), highlights) {
+            eprintln!("invalid FEN {fen:?}: {e}");
+            status = ExitCode::FAILURE;
+        }
+    }
+    status
+}
+
+/// Converts the record `text` from one format to another.
+fn convert(text: &str, from: Format, to: Format) -> Result<String, &'static str> {
+    let side_length = from.side_length(text).ok_or("Cannot tell the board size")?;
+    with_side_length!(
+        side_length,
+        SIDE => to.write(&from.read::<SIDE>(text)?),
+        Err("Unsupported board size")
+    )
+}
+
+/// Converts the record in the file at `path`, writing it beside the original.
+fn convert_file(path: &Path, from: Option<Format>, to: Format) -> Result<(), String> {
+    let from = from
+        .or_else(|| Format::from_path(path))
+        .ok_or("unknown format; pass --from")?;
+    let output = path.with_extension(to.extension());
+    if output == path {
+        return Err("already in the target format".into());
+    }
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let converted = convert(&text, from, to)?;
+    fs::write(&output, converted).map_err(|e| format!("writing {}: {e}", output.display()))
+}
+
+fn run_convert(args: &[String]) -> ExitCode {
+    let mut from = None;
+    let mut to = None;
+    let mut files = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let format = match arg.as_str() {
+            "--from" => &mut from,
+            "--to" => &mut to,
+            flag if flag.starts_with("--") => {
+                eprintln!("unknown option {flag}\n\n{USAGE}");
+                return ExitCode::FAILURE;
+            }
+            file => {
+                files.push(file);
+                continue;
+            }
+        };
+        match args.next().map(|name| name.parse::<Format>()) {
+            Some(Ok(parsed)) => *format = Some(parsed),
+            Some(Err(e)) => {
+                eprintln!("{arg}: {e}");
+                return ExitCode::FAILURE;
+            }
+            None => {
+                eprintln!("{arg} needs a format\n\n{USAGE}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let Some(to) = to else {
+        eprintln!("convert needs --to\n\n{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    if files.is_empty() {
+        let Some(from) = from else {
+            eprintln!("converting standard input needs --from");
+            return ExitCode::FAILURE;
+        };
+        let mut text = String::new();
+        if let Err(e) = io::stdin().read_to_string(&mut text) {
+            eprintln!("reading standard input: {e}");
+            return ExitCode::FAILURE;
+        }
+        return match convert(&text, from, to) {
+            Ok(converted) => {
+                print!("{converted}");
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("invalid record: {e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+    let mut status = ExitCode::SUCCESS;
+    for file in files {
+        if let Err(e) = convert_file(Path::new(file), from, to) {
+            eprintln!("{file}: {e}");
+            status = ExitCode::FAILURE;
+        }
+    }
+    status
+}
+
+/// Adds the games in the collection at `path` to `stats`.
+fn add_collection(path: &Path, stats: &mut GameStats) -> io::Result<()> {
+    let mut file = BufReader::new(File::open(path)?);
+    let side_length = collection::side_length(&mut file)?;
+    file.rewind()?;
+    with_side_length!(
+        side_length,
+        SIDE => {
+            for game in collection::GameReader::<_, SIDE>::new(file)? {
+                stats.add(&game?);
+            }
+            Ok(())
+        },
+        Err(io::Error::other("unsupported board size"))
+    )
+}
+
+fn run_stats(args: &[String]) -> ExitCode {
+    if args.is_empty() {
+        eprintln!("stats needs at least one file\n\n{USAGE}");
+        return ExitCode::FAILURE;
+    }
+    let mut stats = GameStats::default();
+    let mut status = ExitCode::SUCCESS;
+    for file in args {
+        if let Err(e) = add_collection(Path::new(file), &mut stats) {
+            eprintln!("{file}: {e}");
+            status = ExitCode::FAILURE;
+        }
+    }
+    print!("{stats}");
+    status
+}
+
+/// Builds a book from the first `plies` plies of the games in the
+/// collections at `paths`, and saves it to `output`, returning the number of
+/// entries.
+fn build_book(output: &Path, plies: usize, paths: &[String]) -> io::Result<usize> {
+    let mut first = BufReader::new(File::open(&paths[0])?);
+    let side_length = collection::side_length(&mut first)?;
+    with_side_length!(
+        side_length,
+        SIDE => {
+            let mut builder = BookBuilder::<SIDE>::new(plies);
+            for path in paths {
+                let mut file = BufReader::new(File::open(path)?);
+                if collection::side_length(&mut file)? != SIDE {
+                    return Err(io::Error::other(format!(
+                        "{path}: collection is for a different board size"
+                    )));
+                }
+                file.rewind()?;
+                for game in collection::GameReader::<_, SIDE>::new(file)? {
+                    builder.add_record(&game.map_err(|e| io::Error::other(format!("{path}: {e}")))?);
+                }
+            }
+            let book = builder.build();
+            book.save(output)?;
+            Ok(book.len())
+        },
+        Err(io::Error::other("unsupported board size"))
+    )
+}
+
+fn run_book(args: &[String]) -> ExitCode {
+    let mut plies = 12;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--plies" => match args.next().map(|n| n.parse()) {
+                Some(Ok(n)) => plies = n,
+                _ => {
+                    eprintln!("--plies needs a number\n\n{USAGE}");
+                    return ExitCode::FAILURE;
+                }
+            },
+            flag if flag.starts_with("--") => {
+                eprintln!("unknown option {flag}\n\n{USAGE}");
+                return ExitCode::FAILURE;
+            }
+            path => paths.push(path.to_owned()),
+        }
+    }
+    let Some((output, files)) = paths.split_first() else {
+        eprintln!("book needs an output file and at least one collection\n\n{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    if files.is_empty() {
+        eprintln!("book needs at least one collection\n\n{USAGE}");
+        return ExitCode::FAILURE;
+    }
+    match build_book(Path::new(output), plies, files) {
+        Ok(entries) => {
+            eprintln!("wrote {entries} entries to {output}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+fn run_serve(args: &[String]) -> ExitCode {
+    use gomokugen::protocol::AnalysisServer;
+
+    let mut size = 15;
+    let mut address = "127.0.0.1:8080";
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => match args.next().map(|n| n.parse()) {
+                Some(Ok(n)) => size = n,
+                _ => {
+                    eprintln!("--size needs a number\n\n{USAGE}");
+                    return ExitCode::FAILURE;
+                }
+            },
+            flag if flag.starts_with("--") => {
+                eprintln!("unknown option {flag}\n\n{USAGE}");
+                return ExitCode::FAILURE;
+            }
+            arg => address = arg,
+        }
+    }
+    let server = AnalysisServer {
+        max_time: Some(std::time::Duration::from_secs(10)),
+        ..AnalysisServer::default()
+    };
+    eprintln!("serving {size}x{size} analysis on http://{address}");
+    let served = with_side_length!(
+        size,
+        SIDE => server.serve::<SIDE>(address),
+        Err(io::Error::other("unsupported board size"))
+    );
+    match served {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.first().map(String::as_str) {
+        Some("show") => run_show(&args[1..]),
+        Some("convert") => run_convert(&args[1..]),
+        Some("stats") => run_stats(&args[1..]),
+        Some("book") => run_book(&args[1..]),
+        #[cfg(feature = "http")]
+        Some("serve") => run_serve(&args[1..]),
+        Some("help" | "--help" | "-h") => {
+            println!("{USAGE}");
+            ExitCode::SUCCESS
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,343 @@
+//! Reading position containers and opening books straight from memory-mapped
+//! files.
+//!
+//! [`PositionMap::load`](crate::positions::PositionMap::load) and
+//! [`Book::load`](crate::book::Book::load) read a whole file into memory,
+//! which is impractical for datasets of many gigabytes. The readers here map
+//! the file instead, and decode records only as they are visited, so the
+//! operating system pages in just the parts of the file that are used. Both
+//! formats keep their records sorted by key, so lookups are binary searches.
+
+use std::{fs::File, io, marker::PhantomData, path::Path};
+
+use memmap2::Mmap;
+
+use crate::{
+    board::{Board, Move},
+    book::{self, BookEntry},
+    positions::{self, FixedBytes},
+};
+
+/// A file of fixed-size records, each starting with a big-endian `u64` key,
+/// following a header.
+#[derive(Debug)]
+struct Records {
+    map: Mmap,
+    header: usize,
+    size: usize,
+}
+
+impl Records {
+    fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the mapping is only ever read, and the caller is warned
+        // that modifying the file while it is mapped is not supported.
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(Self {
+            map,
+            header: 0,
+            size: 0,
+        })
+    }
+
+    fn len(&self) -> usize {
+        (self.map.len() - self.header) / self.size
+    }
+
+    fn record(&self, index: usize) -> &[u8] {
+        let start = self.header + index * self.size;
+        &self.map[start..start + self.size]
+    }
+
+    fn key(&self, index: usize) -> u64 {
+        u64::read_bytes(&self.record(index)[..8])
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.map[self.header..].chunks_exact(self.size)
+    }
+
+    /// The range of indices of the records stored under `key`.
+    fn find(&self, key: u64) -> std::ops::Range<usize> {
+        let partition_point = |pred: &dyn Fn(u64) -> bool| {
+            let (mut lo, mut hi) = (0, self.len());
+            while lo < hi {
+                let mid = lo + (hi - lo) / 2;
+                if pred(self.key(mid)) {
+                    lo = mid + 1;
+                } else {
+                    hi = mid;
+                }
+            }
+            lo
+        };
+        partition_point(&|k| k < key)..partition_point(&|k| k <= key)
+    }
+}
+
+/// A read-only [`PositionMap`](crate::positions::PositionMap) file, mapped
+/// into memory.
+///
+/// Modifying the file while it is mapped is not supported, and may cause
+/// reads to return garbage or the process to crash.
+#[derive(Debug)]
+pub struct MappedPositions<V> {
+    records: Records,
+    shape: Option<(u8, u8)>,
+    values: PhantomData<fn() -> V>,
+}
+
+impl<V: FixedBytes> MappedPositions<V> {
+    /// Maps the position container at `path`.
+    ///
+    /// Only the header and the file length are checked up front.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or mapped, or is not a
+    /// position container holding values of type `V`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut records = Records::open(path)?;
+        let Some((header, data)) = records.map.split_first_chunk::<{ positions::HEADER_LEN }>()
+        else {
+            return Err(positions::invalid_data("truncated position container"));
+        };
+        let (shape, count) = positions::read_header(header, V::SIZE)?;
+        let record_size = 8 + V::SIZE;
+        if data.len() % record_size != 0 || (data.len() / record_size) as u64 != count {
+            return Err(positions::invalid_data(
+                "position container has the wrong length",
+            ));
+        }
+        records.header = positions::HEADER_LEN;
+        records.size = record_size;
+        Ok(Self {
+            records,
+            shape,
+            values: PhantomData,
+        })
+    }
+
+    /// The number of distinct positions in the container.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Whether the container is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Looks up the value stored under a canonical key.
+    #[must_use]
+    pub fn get_key(&self, key: u64) -> Option<V> {
+        let range = self.records.find(key);
+        (!range.is_empty()).then(|| V::read_bytes(&self.records.record(range.start)[8..]))
+    }
+
+    /// The side length and win length of the boards in the container, as
+    /// for [`PositionMap::shape`](crate::positions::PositionMap::shape).
+    #[must_use]
+    pub fn shape(&self) -> Option<(usize, usize)> {
+        self.shape
+            .map(|(side, win)| (usize::from(side), usize::from(win)))
+    }
+
+    /// Whether `board` has the shape of the boards in the container.
+    fn holds<const SIDE_LENGTH: usize>(&self, board: &Board<SIDE_LENGTH>) -> bool {
+        self.shape.is_none() || self.shape == Some(positions::shape_of(board))
+    }
+
+    /// Looks up the value stored for `board` or any of its symmetric images.
+    /// Nothing is stored for boards of a different size or win length to
+    /// those in the container.
+    #[must_use]
+    pub fn get<const SIDE_LENGTH: usize>(&self, board: &Board<SIDE_LENGTH>) -> Option<V> {
+        if !self.holds(board) {
+            return None;
+        }
+        self.get_key(board.canonical_key())
+    }
+
+    /// Whether a value is stored for `board` or any of its symmetric images,
+    /// as for [`MappedPositions::get`].
+    #[must_use]
+    pub fn contains<const SIDE_LENGTH: usize>(&self, board: &Board<SIDE_LENGTH>) -> bool {
+        self.holds(board) && !self.records.find(board.canonical_key()).is_empty()
+    }
+
+    /// Returns an iterator over the canonical keys and values, in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, V)> + '_ {
+        self.records.iter().map(|record| {
+            let (key, value) = record.split_at(8);
+            (u64::read_bytes(key), V::read_bytes(value))
+        })
+    }
+}
+
+/// A read-only [`Book`](crate::book::Book) file, mapped into memory.
+///
+/// Modifying the file while it is mapped is not supported, and may cause
+/// reads to return garbage or the process to crash.
+#[derive(Debug)]
+pub struct MappedBook<const SIDE_LENGTH: usize> {
+    records: Records,
+}
+
+impl<const SIDE_LENGTH: usize> MappedBook<SIDE_LENGTH> {
+    /// Maps the book at `path`.
+    ///
+    /// Only the header and the file length are checked up front; entries
+    /// whose moves are off the board are skipped when they are read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or mapped, or is not a
+    /// book for boards of size `SIDE_LENGTH`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut records = Records::open(path)?;
+        let Some((header, data)) = records.map.split_first_chunk::<8>() else {
+            return Err(positions::invalid_data("truncated book"));
+        };
+        if &header[..4] != book::MAGIC {
+            return Err(positions::invalid_data("not a gomokugen book"));
+        }
+        if u16::read_bytes(&header[4..6]) != book::VERSION {
+            return Err(positions::invalid_data("unsupported book version"));
+        }
+        if usize::from(u16::read_bytes(&header[6..])) != SIDE_LENGTH {
+            return Err(positions::invalid_data(
+                "book is for a different board size",
+            ));
+        }
+        if data.len() % BookEntry::SIZE != 0 {
+            return Err(positions::invalid_data("truncated book entry"));
+        }
+        records.header = 8;
+        records.size = BookEntry::SIZE;
+        Ok(Self { records })
+    }
+
+    /// The number of entries in the book.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Whether the book has no entries.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn entry(record: &[u8]) -> Option<BookEntry> {
+        let mut bytes = [0; BookEntry::SIZE];
+        bytes.copy_from_slice(record);
+        let entry = BookEntry::from_bytes(bytes);
+        (usize::from(entry.mv) < SIDE_LENGTH * SIDE_LENGTH).then_some(entry)
+    }
+
+    /// Returns an iterator over the entries in the book, sorted by key and
+    /// then move.
+    pub fn entries(&self) -> impl Iterator<Item = BookEntry> + '_ {
+        self.records.iter().filter_map(Self::entry)
+    }
+
+    /// The entries stored under exactly `key`.
+    pub fn entries_for_key(&self, key: u64) -> impl Iterator<Item = BookEntry> + '_ {
+        self.records
+            .find(key)
+            .filter_map(|index| Self::entry(self.records.record(index)))
+    }
+
+    /// Looks up the moves recommended in `board`, with their weights, as for
+    /// [`Book::probe`](crate::book::Book::probe).
+    #[must_use]
+    pub fn probe(&self, board: &Board<SIDE_LENGTH>) -> Vec<(Move<SIDE_LENGTH>, u16)> {
+        book::probe_with(board, |key| self.entries_for_key(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::{MappedBook, MappedPositions};
+    use crate::{
+        board::{Board, Move},
+        book::{Book, BookEntry},
+        positions::PositionMap,
+    };
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("gomokugen-{}-{name}", std::process::id()))
+    }
+
+    fn board(moves: &[&str]) -> Board<9> {
+        let moves = moves
+            .iter()
+            .map(|mv| mv.parse::<Move<9>>().unwrap())
+            .collect::<Vec<_>>();
+        Board::new().apply_moves(&moves).unwrap()
+    }
+
+    #[test]
+    fn reads_positions_in_place() {
+        let mut map = PositionMap::new();
+        for (moves, i) in [&["e5"][..], &["a1", "b2"], &["e5", "e6", "f6"]]
+            .into_iter()
+            .zip(0u8..)
+        {
+            map.insert(&board(moves), (u32::from(i), -i16::from(i)));
+        }
+        let path = temp_path("positions.gmkp");
+        map.save(&path).unwrap();
+        let mapped = MappedPositions::<(u32, i16)>::open(&path).unwrap();
+        assert!(MappedPositions::<u32>::open(&path).is_err());
+        assert_eq!(mapped.len(), 3);
+        assert_eq!(mapped.get(&board(&["i9", "h8"])), Some((1, -1)));
+        assert_eq!(mapped.get(&board(&["e5", "e4", "d4"])), Some((2, -2)));
+        assert!(!mapped.contains(&board(&["a1"])));
+        assert_eq!(mapped.shape(), Some((9, 5)));
+        assert!(!mapped.contains(&board(&["e5", "e4", "d4"]).with_win_length(4)));
+        let entries = mapped.iter().collect::<Vec<_>>();
+        assert!(entries.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(entries
+            .iter()
+            .all(|&(key, value)| map.get_key(key) == Some(&value)));
+        drop(mapped);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reads_books_in_place() {
+        let root = Board::<9>::new();
+        let e5 = "e5".parse::<Move<9>>().unwrap();
+        let after = root.with_move(e5);
+        let book = Book::<9>::from_entries(vec![
+            BookEntry {
+                key: root.zobrist_key(),
+                mv: u16::try_from(e5.index()).unwrap(),
+                weight: 3,
+            },
+            BookEntry {
+                key: after.zobrist_key(),
+                mv: u16::try_from("f6".parse::<Move<9>>().unwrap().index()).unwrap(),
+                weight: 1,
+            },
+        ]);
+        let path = temp_path("book.gmkb");
+        book.save(&path).unwrap();
+        assert!(MappedBook::<15>::open(&path).is_err());
+        let mapped = MappedBook::<9>::open(&path).unwrap();
+        assert_eq!(mapped.len(), 2);
+        assert_eq!(mapped.entries().collect::<Vec<_>>(), book.entries());
+        assert_eq!(mapped.probe(&root), book.probe(&root));
+        assert_eq!(mapped.probe(&after), book.probe(&after));
+        assert_eq!(mapped.entries_for_key(1).count(), 0);
+        drop(mapped);
+        fs::remove_file(path).unwrap();
+    }
+}
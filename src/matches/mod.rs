@@ -0,0 +1,178 @@
+//! Running matches between engines and measuring the difference in their strength.
+
+use crate::{
+    board::{Board, Player},
+    clock::{Clock, TimeControl},
+    engine::{Engine, Limits},
+    game::{Adjudication, Game, GameResult},
+};
+
+pub mod remote;
+pub mod stats;
+pub mod tournament;
+
+pub use remote::{MatchClient, MatchServer};
+pub use stats::{Score, Sprt, SprtStatus};
+pub use tournament::Crosstable;
+
+/// A series of games between two engines.
+///
+/// Games are played in pairs from each starting position, with the engines
+/// swapping colours between the two games of a pair, so that neither engine
+/// benefits from an unbalanced opening.
+#[derive(Clone, Debug)]
+pub struct Match<const SIDE_LENGTH: usize> {
+    /// The starting positions, used in turn. An empty list means every game
+    /// starts from the empty board.
+    pub openings: Vec<Board<SIDE_LENGTH>>,
+    /// The maximum number of games to play.
+    pub games: usize,
+    /// The limits given to both engines for each move.
+    pub limits: Limits,
+    /// If set, games are timed, and an engine that runs out of time loses.
+    pub time_control: Option<TimeControl>,
+    /// If set, the match stops as soon as the test reaches a conclusion.
+    pub sprt: Option<Sprt>,
+    /// The rules for drawing games early.
+    pub adjudication: Adjudication,
+}
+
+impl<const SIDE_LENGTH: usize> Default for Match<SIDE_LENGTH> {
+    fn default() -> Self {
+        Self {
+            openings: Vec::new(),
+            games: 100,
+            limits: Limits::default(),
+            time_control: None,
+            sprt: None,
+            adjudication: Adjudication::default(),
+        }
+    }
+}
+
+impl<const SIDE_LENGTH: usize> Match<SIDE_LENGTH> {
+    /// Plays the match, returning the score from the perspective of `first`.
+    ///
+    /// `on_game` is called with the score so far after each game.
+    pub fn run(
+        &self,
+        first: &mut dyn Engine<SIDE_LENGTH>,
+        second: &mut dyn Engine<SIDE_LENGTH>,
+        mut on_game: impl FnMut(&Game<SIDE_LENGTH>, &Score),
+    ) -> Score {
+        let mut score = Score::default();
+        for i in 0..self.games {
+            let game = self.play_game(i, first, second, &mut score);
+            on_game(&game, &score);
+            if self
+                .sprt
+                .is_some_and(|sprt| sprt.status(&score) != SprtStatus::Continue)
+            {
+                break;
+            }
+        }
+        score
+    }
+
+    /// The starting position of game number `index` of the match.
+    #[must_use]
+    pub fn opening(&self, index: usize) -> Board<SIDE_LENGTH> {
+        if self.openings.is_empty() {
+            Board::new()
+        } else {
+            self.openings[(index / 2) % self.openings.len()]
+        }
+    }
+
+    /// Plays game number `index` of the match, adding the result to `score`,
+    /// which is from the perspective of `first`.
+    ///
+    /// Games are numbered from zero; `first` plays X in even-numbered games.
+    pub fn play_game(
+        &self,
+        index: usize,
+        first: &mut dyn Engine<SIDE_LENGTH>,
+        second: &mut dyn Engine<SIDE_LENGTH>,
+        score: &mut Score,
+    ) -> Game<SIDE_LENGTH> {
+        let start = self.opening(index);
+        let first_plays_x = index.is_multiple_of(2);
+        let mut game = self
+            .time_control
+            .map_or_else(
+                || Game::new(start),
+                |control| Game::with_clock(start, Clock::new(control)),
+            )
+            .with_adjudication(self.adjudication);
+        let result = if first_plays_x {
+            game.play_out(first, second, &self.limits)
+        } else {
+            game.play_out(second, first, &self.limits)
+        };
+        add_result(index, result, score);
+        game
+    }
+}
+
+/// Adds `result`, the result of game number `index` of a match, to `score`,
+/// which is from the perspective of the engine that plays X in even-numbered
+/// games.
+fn add_result<const SIDE_LENGTH: usize>(
+    index: usize,
+    result: GameResult<SIDE_LENGTH>,
+    score: &mut Score,
+) {
+    let first_colour = if index.is_multiple_of(2) {
+        Player::X
+    } else {
+        Player::O
+    };
+    match result.winner {
+        Player::None => score.draws += 1,
+        winner if winner == first_colour => score.wins += 1,
+        _ => score.losses += 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Match, Sprt, SprtStatus};
+    use crate::engine::{HeuristicEngine, Limits, RandomEngine};
+
+    #[test]
+    fn heuristic_beats_random() {
+        let mut games = 0;
+        let runner = Match::<9> {
+            games: 20,
+            limits: Limits::depth(1),
+            ..Match::default()
+        };
+        let score = runner.run(
+            &mut HeuristicEngine::new(),
+            &mut RandomEngine::new(5),
+            |game, _| {
+                assert!(game.result().is_some());
+                games += 1;
+            },
+        );
+        assert_eq!(games, 20);
+        assert_eq!(score.games(), 20);
+        assert_eq!(score.wins, 20);
+    }
+
+    #[test]
+    fn sprt_stops_early() {
+        let runner = Match::<9> {
+            games: 1000,
+            sprt: Some(Sprt::new(0.0, 10.0)),
+            ..Match::default()
+        };
+        let score = runner.run(
+            &mut HeuristicEngine::new(),
+            &mut RandomEngine::new(5),
+            |_, _| {},
+        );
+        assert!(score.games() < 1000);
+        assert_eq!(runner.sprt.unwrap().status(&score), SprtStatus::AcceptH1);
+    }
+}
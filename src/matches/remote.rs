@@ -0,0 +1,487 @@
+//! Running a match across machines: a [`MatchServer`] hands out the games of
+//! a [`Match`] to [`MatchClient`]s over TCP, which play them with their own
+//! engines and send the games back.
+//!
+//! Both sides are set up with the same [`Match`]. The server decides which
+//! games are played and keeps the score, calling back after each game as
+//! [`Match::run`] does. A game whose client disconnects before finishing it
+//! is handed to the next client that asks for one, as is a game whose client
+//! goes quiet for longer than the server's [timeout](MatchServer::with_timeout).
+//!
+//! Every message is a frame: a big-endian `u32` length, then that many
+//! bytes, the first of which is a tag saying what the message is:
+//!
+//! - `H`, from the client on connecting: a big-endian `u16` protocol version
+//!   and a byte giving the board's side length.
+//! - `P`, from the server: a big-endian `u64` index of a game to play, as
+//!   passed to [`Match::play_game`].
+//! - `R`, from the client: the game's index, then the finished game,
+//!   encoded as a record in a [game collection](crate::formats::collection).
+//!   A game that does not start from the match's opening for that index is
+//!   refused, and handed to another client.
+//! - `D`, from the server: there are no more games to play.
+//! - `E`, from the server: the connection was refused, with the reason in
+//!   UTF-8.
+
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Condvar, Mutex},
+    thread,
+    time::Duration,
+};
+
+use super::{add_result, Match, Score, Sprt, SprtStatus};
+use crate::{
+    board::Board,
+    engine::Engine,
+    formats::collection::{decode_game, encode_game},
+    game::{Game, GameResult},
+    positions::{invalid_data, FixedBytes},
+};
+
+/// The version of the protocol, sent by clients on connecting.
+const VERSION: u16 = 1;
+
+/// The largest frame accepted, in bytes.
+const MAX_FRAME: u32 = 1 << 20;
+
+/// How long the server sleeps between checks for new clients.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long the server waits on a client by default, for a message or to
+/// take one, before giving up on it.
+// `Duration::from_mins` is too recent for the toolchains the crate supports.
+#[allow(clippy::duration_suboptimal_units)]
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(600);
+
+fn write_frame(stream: &mut TcpStream, tag: u8, body: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(body.len() + 1).map_err(|_| invalid_data("frame too large"))?;
+    let mut frame = Vec::with_capacity(body.len() + 5);
+    len.write_bytes(&mut frame);
+    frame.push(tag);
+    frame.extend_from_slice(body);
+    stream.write_all(&frame)?;
+    stream.flush()
+}
+
+/// Reads a frame, returning its tag and body.
+fn read_frame(stream: &mut TcpStream) -> io::Result<(u8, Vec<u8>)> {
+    let mut len = [0; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::read_bytes(&len);
+    if len == 0 || len > MAX_FRAME {
+        return Err(invalid_data("bad frame length"));
+    }
+    let mut frame = vec![0; len as usize];
+    stream.read_exact(&mut frame)?;
+    let body = frame.split_off(1);
+    Ok((frame[0], body))
+}
+
+/// The progress of a match being served.
+struct State<F> {
+    games: usize,
+    next: usize,
+    retry: Vec<usize>,
+    in_flight: usize,
+    score: Score,
+    stopped: bool,
+    on_game: F,
+}
+
+impl<F> State<F> {
+    const fn is_done(&self) -> bool {
+        self.stopped || (self.next >= self.games && self.retry.is_empty() && self.in_flight == 0)
+    }
+
+    fn take(&mut self) -> Option<usize> {
+        let index = self.retry.pop().or_else(|| {
+            (self.next < self.games).then(|| {
+                self.next += 1;
+                self.next - 1
+            })
+        })?;
+        self.in_flight += 1;
+        Some(index)
+    }
+}
+
+impl<F> State<F> {
+    /// Records the outcome of handing out game `index`: its result if it was
+    /// played, or else puts it back to be handed out again.
+    fn finish<const SIDE_LENGTH: usize>(
+        &mut self,
+        index: usize,
+        played: io::Result<(Game<SIDE_LENGTH>, GameResult<SIDE_LENGTH>)>,
+        sprt: Option<Sprt>,
+    ) -> io::Result<()>
+    where
+        F: FnMut(&Game<SIDE_LENGTH>, &Score),
+    {
+        self.in_flight -= 1;
+        let (game, result) = match played {
+            Ok(played) => played,
+            Err(e) => {
+                self.retry.push(index);
+                return Err(e);
+            }
+        };
+        if !self.stopped {
+            add_result(index, result, &mut self.score);
+            (self.on_game)(&game, &self.score);
+            self.stopped =
+                sprt.is_some_and(|sprt| sprt.status(&self.score) != SprtStatus::Continue);
+        }
+        Ok(())
+    }
+}
+
+/// Hands out the games of a match to [`MatchClient`]s, as described in the
+/// [module docs](self).
+#[derive(Debug)]
+pub struct MatchServer<const SIDE_LENGTH: usize> {
+    runner: Match<SIDE_LENGTH>,
+    listener: TcpListener,
+    timeout: Duration,
+}
+
+impl<const SIDE_LENGTH: usize> MatchServer<SIDE_LENGTH> {
+    /// Creates a server for `runner`'s games, listening on `address`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server cannot listen on `address`.
+    pub fn bind(runner: Match<SIDE_LENGTH>, address: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            runner,
+            listener,
+            timeout: CLIENT_TIMEOUT,
+        })
+    }
+
+    /// Sets how long the server waits on a client, for a message or to take
+    /// one, before dropping it and handing its game to another. This must be
+    /// longer than a client takes to play a game; the default is ten minutes.
+    #[must_use]
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The address the server is listening on.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address cannot be found.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serves clients until every game has been played, or the match's test
+    /// reaches a conclusion, and returns the score from the perspective of
+    /// the engine the clients pass as `first`.
+    ///
+    /// `on_game` is called with the score so far after each game, in the
+    /// order the games finish.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if accepting a connection fails. Failures of single
+    /// clients, including those that time out, are not errors: their games
+    /// are played by other clients.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `on_game` panics.
+    pub fn run(self, on_game: impl FnMut(&Game<SIDE_LENGTH>, &Score) + Send) -> io::Result<Score> {
+        let state = Mutex::new(State {
+            games: self.runner.games,
+            next: 0,
+            retry: Vec::new(),
+            in_flight: 0,
+            score: Score::default(),
+            stopped: false,
+            on_game,
+        });
+        let changed = Condvar::new();
+        thread::scope(|scope| loop {
+            if state.lock().unwrap().is_done() {
+                return Ok(());
+            }
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    stream.set_read_timeout(Some(self.timeout))?;
+                    stream.set_write_timeout(Some(self.timeout))?;
+                    let (server, state, changed) = (&self, &state, &changed);
+                    // a client that fails leaves its game to the others.
+                    scope.spawn(move || server.serve_client(stream, state, changed));
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_INTERVAL),
+                Err(e) => return Err(e),
+            }
+        })?;
+        Ok(state.into_inner().unwrap().score)
+    }
+
+    fn serve_client<F: FnMut(&Game<SIDE_LENGTH>, &Score)>(
+        &self,
+        mut stream: TcpStream,
+        state: &Mutex<State<F>>,
+        changed: &Condvar,
+    ) -> io::Result<()> {
+        let (tag, body) = read_frame(&mut stream)?;
+        let hello = (body.len() == 3).then(|| (u16::read_bytes(&body[..2]), usize::from(body[2])));
+        let refusal = match hello {
+            _ if tag != b'H' => Some("expected hello"),
+            Some((version, _)) if version != VERSION => Some("unsupported protocol version"),
+            Some((_, side_length)) if side_length != SIDE_LENGTH => Some("wrong board size"),
+            Some(_) => None,
+            None => Some("malformed hello"),
+        };
+        if let Some(reason) = refusal {
+            write_frame(&mut stream, b'E', reason.as_bytes())?;
+            return Err(invalid_data(reason));
+        }
+        loop {
+            let index = {
+                let mut state = state.lock().unwrap();
+                loop {
+                    if state.is_done() {
+                        break None;
+                    }
+                    if let Some(index) = state.take() {
+                        break Some(index);
+                    }
+                    state = changed.wait(state).unwrap();
+                }
+            };
+            let Some(index) = index else {
+                return write_frame(&mut stream, b'D', &[]);
+            };
+            let played = request_game(&mut stream, index, &self.runner.opening(index));
+            let finished = state
+                .lock()
+                .unwrap()
+                .finish(index, played, self.runner.sprt);
+            changed.notify_all();
+            finished?;
+        }
+    }
+}
+
+/// Asks the client to play game `index`, which starts from `opening`, and
+/// reads the game back, with its result.
+fn request_game<const SIDE_LENGTH: usize>(
+    stream: &mut TcpStream,
+    index: usize,
+    opening: &Board<SIDE_LENGTH>,
+) -> io::Result<(Game<SIDE_LENGTH>, GameResult<SIDE_LENGTH>)> {
+    let mut body = Vec::new();
+    (index as u64).write_bytes(&mut body);
+    write_frame(stream, b'P', &body)?;
+    let (tag, body) = read_frame(stream)?;
+    if tag != b'R' || body.len() < 8 {
+        return Err(invalid_data("expected a game result"));
+    }
+    if u64::read_bytes(&body[..8]) != index as u64 {
+        return Err(invalid_data("result for the wrong game"));
+    }
+    let game = decode_game::<SIDE_LENGTH>(&body[8..])?;
+    if game.start() != opening {
+        return Err(invalid_data("game starts from the wrong opening"));
+    }
+    let Some(result) = game.result() else {
+        return Err(invalid_data("game is unfinished"));
+    };
+    Ok((game, result))
+}
+
+/// Plays the games a [`MatchServer`] hands out, as described in the
+/// [module docs](self).
+#[derive(Debug)]
+pub struct MatchClient<const SIDE_LENGTH: usize> {
+    runner: Match<SIDE_LENGTH>,
+    stream: TcpStream,
+}
+
+impl<const SIDE_LENGTH: usize> MatchClient<SIDE_LENGTH> {
+    /// Connects to the server at `address`, to play games of `runner`, which
+    /// should be set up as the server's match is.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection fails.
+    pub fn connect(runner: Match<SIDE_LENGTH>, address: impl ToSocketAddrs) -> io::Result<Self> {
+        #![allow(clippy::cast_possible_truncation)]
+        let mut stream = TcpStream::connect(address)?;
+        let mut hello = Vec::new();
+        VERSION.write_bytes(&mut hello);
+        hello.push(SIDE_LENGTH as u8);
+        write_frame(&mut stream, b'H', &hello)?;
+        Ok(Self { runner, stream })
+    }
+
+    /// Plays games between `first` and `second` until the server has no
+    /// more, and returns the number played.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if communication with the server fails, or the server
+    /// refuses the connection.
+    pub fn run(
+        mut self,
+        first: &mut dyn Engine<SIDE_LENGTH>,
+        second: &mut dyn Engine<SIDE_LENGTH>,
+    ) -> io::Result<usize> {
+        let mut played = 0;
+        loop {
+            let (tag, body) = read_frame(&mut self.stream)?;
+            match tag {
+                b'P' if body.len() == 8 => {
+                    let index = u64::read_bytes(&body);
+                    let game = self.runner.play_game(
+                        usize::try_from(index).map_err(|_| invalid_data("game index too large"))?,
+                        first,
+                        second,
+                        &mut Score::default(),
+                    );
+                    let mut reply = Vec::new();
+                    index.write_bytes(&mut reply);
+                    encode_game(&game, &mut reply)?;
+                    write_frame(&mut self.stream, b'R', &reply)?;
+                    played += 1;
+                }
+                b'D' => return Ok(played),
+                b'E' => {
+                    return Err(io::Error::other(format!(
+                        "server refused the connection: {}",
+                        String::from_utf8_lossy(&body)
+                    )))
+                }
+                _ => return Err(invalid_data("unexpected message from server")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpStream, thread, time::Duration};
+
+    use super::{read_frame, write_frame, MatchClient, MatchServer};
+    use crate::{
+        board::Board,
+        engine::{HeuristicEngine, Limits, RandomEngine},
+        matches::Match,
+    };
+
+    #[test]
+    fn clients_play_every_game() {
+        let runner = Match::<9> {
+            games: 8,
+            limits: Limits::depth(1),
+            ..Match::default()
+        };
+        let server = MatchServer::bind(runner.clone(), "127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+        let mut finished = Vec::new();
+        thread::scope(|scope| {
+            let serving = scope.spawn(|| {
+                server.run(|game, score| {
+                    assert!(game.result().is_some());
+                    finished.push(score.games());
+                })
+            });
+            // a client that takes a game and leaves without playing it.
+            let mut quitter = TcpStream::connect(address).unwrap();
+            write_frame(&mut quitter, b'H', &[0, 1, 9]).unwrap();
+            assert_eq!(read_frame(&mut quitter).unwrap().0, b'P');
+            drop(quitter);
+            // one for the wrong board size.
+            let mut refused = MatchClient::<7>::connect(Match::default(), address).unwrap();
+            assert_eq!(read_frame(&mut refused.stream).unwrap().0, b'E');
+
+            let clients = [0, 1].map(|_| {
+                let client = MatchClient::connect(runner.clone(), address).unwrap();
+                scope.spawn(|| {
+                    client
+                        .run(&mut HeuristicEngine::new(), &mut RandomEngine::new(5))
+                        .unwrap()
+                })
+            });
+            let played = clients
+                .into_iter()
+                .map(|c| c.join().unwrap())
+                .sum::<usize>();
+            assert_eq!(played, 8);
+            let score = serving.join().unwrap().unwrap();
+            assert_eq!(score.games(), 8);
+            assert_eq!(score.wins, 8);
+        });
+        assert_eq!(finished, (1..=8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn quiet_clients_time_out() {
+        let runner = Match::<9> {
+            games: 2,
+            limits: Limits::depth(1),
+            ..Match::default()
+        };
+        let server = MatchServer::bind(runner.clone(), "127.0.0.1:0")
+            .unwrap()
+            .with_timeout(Duration::from_millis(200));
+        let address = server.local_addr().unwrap();
+        thread::scope(|scope| {
+            let serving = scope.spawn(|| server.run(|_, _| {}));
+            // a connection that never says hello.
+            let _idle = TcpStream::connect(address).unwrap();
+            // a client that takes a game and never plays it.
+            let mut staller = TcpStream::connect(address).unwrap();
+            write_frame(&mut staller, b'H', &[0, 1, 9]).unwrap();
+            assert_eq!(read_frame(&mut staller).unwrap().0, b'P');
+
+            let client = MatchClient::connect(runner.clone(), address).unwrap();
+            let played = client
+                .run(&mut HeuristicEngine::new(), &mut RandomEngine::new(5))
+                .unwrap();
+            assert_eq!(played, 2);
+            assert_eq!(serving.join().unwrap().unwrap().games(), 2);
+        });
+    }
+
+    #[test]
+    fn games_from_other_openings_are_refused() {
+        let runner = Match::<9> {
+            openings: vec![Board::new().with_move("e5".parse().unwrap())],
+            games: 2,
+            limits: Limits::depth(1),
+            ..Match::default()
+        };
+        let server = MatchServer::bind(runner.clone(), "127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+        thread::scope(|scope| {
+            let serving = scope.spawn(|| server.run(|_, _| {}));
+            // a client whose games start from the empty board.
+            let cheat = Match {
+                openings: Vec::new(),
+                ..runner.clone()
+            };
+            let cheater = MatchClient::connect(cheat, address).unwrap();
+            assert!(cheater
+                .run(&mut HeuristicEngine::new(), &mut RandomEngine::new(5))
+                .is_err());
+
+            let client = MatchClient::connect(runner.clone(), address).unwrap();
+            let played = client
+                .run(&mut HeuristicEngine::new(), &mut RandomEngine::new(5))
+                .unwrap();
+            assert_eq!(played, 2);
+            assert_eq!(serving.join().unwrap().unwrap().games(), 2);
+        });
+    }
+}
@@ -0,0 +1,205 @@
+//! Win/draw/loss statistics, Elo estimates, and the sequential probability ratio test.
+
+/// The quantile of the standard normal distribution for a 95% confidence interval.
+const Z_95: f64 = 1.959_964;
+
+/// Wins, draws, and losses from one engine's perspective.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Score {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Score {
+    /// The number of games played.
+    #[must_use]
+    pub const fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The fraction of the available points scored, counting a draw as half a
+    /// win, or `None` if no games have been played.
+    #[must_use]
+    pub fn points(&self) -> Option<f64> {
+        (self.games() > 0)
+            .then(|| (f64::from(self.wins) + f64::from(self.draws) / 2.0) / f64::from(self.games()))
+    }
+
+    /// The estimated Elo difference, or `None` if no games have been played.
+    /// A perfect or zero score gives an infinite estimate.
+    #[must_use]
+    pub fn elo(&self) -> Option<f64> {
+        self.points().map(elo_from_points)
+    }
+
+    /// The estimated Elo difference with the half-width of its 95% confidence
+    /// interval, or `None` if fewer than two games have been played.
+    ///
+    /// The interval is computed in points and converted to Elo, so it is not
+    /// symmetric in Elo; the larger of the two halves is returned.
+    #[must_use]
+    pub fn elo_with_error(&self) -> Option<(f64, f64)> {
+        if self.games() < 2 {
+            return None;
+        }
+        let (mean, variance) = self.mean_and_variance(0.0);
+        let margin = Z_95 * (variance / f64::from(self.games())).sqrt();
+        let elo = elo_from_points(mean);
+        let low = elo_from_points((mean - margin).max(0.0));
+        let high = elo_from_points((mean + margin).min(1.0));
+        Some((elo, (elo - low).max(high - elo)))
+    }
+
+    /// The mean and per-game variance of the points scored, with `prior`
+    /// games added to each of the wins, draws, and losses.
+    fn mean_and_variance(&self, prior: f64) -> (f64, f64) {
+        #![allow(clippy::suboptimal_flops)]
+        let wins = f64::from(self.wins) + prior;
+        let draws = f64::from(self.draws) + prior;
+        let losses = f64::from(self.losses) + prior;
+        let n = wins + draws + losses;
+        let mean = (wins + draws / 2.0) / n;
+        let variance =
+            (wins * (1.0 - mean).powi(2) + draws * (0.5 - mean).powi(2) + losses * mean.powi(2))
+                / n;
+        (mean, variance)
+    }
+}
+
+/// Converts an expected score to an Elo difference under the logistic model.
+fn elo_from_points(points: f64) -> f64 {
+    -400.0 * (1.0 / points - 1.0).log10()
+}
+
+/// Converts an Elo difference to an expected score under the logistic model.
+fn points_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// The state of a sequential probability ratio test.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SprtStatus {
+    /// More games are needed.
+    Continue,
+    /// The Elo difference is at most `elo0`.
+    AcceptH0,
+    /// The Elo difference is at least `elo1`.
+    AcceptH1,
+}
+
+/// A sequential probability ratio test between two hypotheses about the Elo
+/// difference, `elo0` (typically "no gain") and `elo1` (the gain to detect).
+///
+/// The log-likelihood ratio uses the normal approximation to the distribution
+/// of game scores. To keep the variance estimate well defined with few games,
+/// half a game is added to each of the wins, draws, and losses.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    /// The probability of accepting H1 when H0 is true.
+    pub alpha: f64,
+    /// The probability of accepting H0 when H1 is true.
+    pub beta: f64,
+}
+
+impl Sprt {
+    /// A test of `elo0` against `elo1` with the usual error rates of 5%.
+    #[must_use]
+    pub const fn new(elo0: f64, elo1: f64) -> Self {
+        Self {
+            elo0,
+            elo1,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+
+    /// The log-likelihood ratio bounds for accepting H0 and H1.
+    #[must_use]
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// The log-likelihood ratio of H1 against H0 given `score`.
+    #[must_use]
+    pub fn llr(&self, score: &Score) -> f64 {
+        #![allow(clippy::suboptimal_flops)]
+        if score.games() == 0 {
+            return 0.0;
+        }
+        let (mean, variance) = score.mean_and_variance(0.5);
+        let s0 = points_from_elo(self.elo0);
+        let s1 = points_from_elo(self.elo1);
+        let n = f64::from(score.games()) + 1.5;
+        n * (s1 - s0) * (2.0 * mean - s0 - s1) / (2.0 * variance)
+    }
+
+    /// Whether the test has concluded given `score`.
+    #[must_use]
+    pub fn status(&self, score: &Score) -> SprtStatus {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            SprtStatus::AcceptH1
+        } else if llr <= lower {
+            SprtStatus::AcceptH0
+        } else {
+            SprtStatus::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Score, Sprt, SprtStatus};
+
+    #[test]
+    fn elo_estimates() {
+        let even = Score {
+            wins: 10,
+            draws: 5,
+            losses: 10,
+        };
+        assert_eq!(even.elo(), Some(0.0));
+        let (elo, error) = even.elo_with_error().unwrap();
+        assert!(elo.abs() < 1e-9 && error > 100.0);
+        // 75% of the points is about +191 Elo.
+        let strong = Score {
+            wins: 300,
+            draws: 0,
+            losses: 100,
+        };
+        assert!((strong.elo().unwrap() - 190.85).abs() < 0.01);
+        assert_eq!(Score::default().elo(), None);
+    }
+
+    #[test]
+    fn sprt_decisions() {
+        let sprt = Sprt::new(0.0, 5.0);
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.944).abs() < 1e-3 && (upper - 2.944).abs() < 1e-3);
+        let even = Score {
+            wins: 40000,
+            draws: 20000,
+            losses: 40000,
+        };
+        assert_eq!(sprt.status(&even), SprtStatus::AcceptH0);
+        let better = Score {
+            wins: 4300,
+            draws: 2000,
+            losses: 3700,
+        };
+        assert_eq!(sprt.status(&better), SprtStatus::AcceptH1);
+        let early = Score {
+            wins: 3,
+            draws: 1,
+            losses: 2,
+        };
+        assert_eq!(sprt.status(&early), SprtStatus::Continue);
+    }
+}
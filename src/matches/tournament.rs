@@ -0,0 +1,305 @@
+//! Round-robin tournaments between several engines.
+//!
+//! The state of a tournament is its [`Crosstable`], which can be saved after
+//! every game and loaded again to resume an interrupted tournament. It is
+//! stored as text:
+//!
+//! ```text
+//! gomokugen crosstable 1
+//! engine <name>
+//! ...
+//! score <i> <j> <wins> <draws> <losses>
+//! ...
+//! ```
+//!
+//! with one `engine` line per participant, and one `score` line per pairing
+//! `i < j`, from the perspective of engine `i`.
+
+use std::{
+    fmt::{self, Display},
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use super::{Match, Score};
+use crate::engine::Engine;
+
+const HEADER: &str = "gomokugen crosstable 1";
+
+/// The results of every pairing in a tournament.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Crosstable {
+    names: Vec<String>,
+    /// `scores[i][j]` is engine `i`'s score against engine `j`.
+    scores: Vec<Vec<Score>>,
+}
+
+impl Crosstable {
+    /// Creates an empty crosstable for engines with the given names.
+    #[must_use]
+    pub fn new(names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let names = names.into_iter().map(Into::into).collect::<Vec<String>>();
+        let n = names.len();
+        Self {
+            names,
+            scores: vec![vec![Score::default(); n]; n],
+        }
+    }
+
+    /// The names of the participants.
+    #[must_use]
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Engine `i`'s score against engine `j`.
+    #[must_use]
+    pub fn score(&self, i: usize, j: usize) -> Score {
+        self.scores[i][j]
+    }
+
+    /// Engine `i`'s score against all opponents.
+    #[must_use]
+    pub fn total(&self, i: usize) -> Score {
+        self.scores[i]
+            .iter()
+            .fold(Score::default(), |acc, s| Score {
+                wins: acc.wins + s.wins,
+                draws: acc.draws + s.draws,
+                losses: acc.losses + s.losses,
+            })
+    }
+
+    /// The participants' indices, ordered by points scored, best first.
+    #[must_use]
+    pub fn standings(&self) -> Vec<usize> {
+        let points = |i: usize| {
+            let total = self.total(i);
+            2 * total.wins + total.draws
+        };
+        let mut order = (0..self.names.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| std::cmp::Reverse(points(i)));
+        order
+    }
+
+    /// Plays every pairing until each has `pairing.games` games, skipping
+    /// games already recorded, so that a loaded crosstable resumes where it
+    /// left off. The pairing's SPRT, if any, is ignored.
+    ///
+    /// `on_game` is called with the crosstable after each game, for example to save it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of engines differs from the number of names.
+    pub fn play<const SIDE_LENGTH: usize>(
+        &mut self,
+        pairing: &Match<SIDE_LENGTH>,
+        engines: &mut [Box<dyn Engine<SIDE_LENGTH>>],
+        mut on_game: impl FnMut(&Self),
+    ) {
+        assert_eq!(
+            engines.len(),
+            self.names.len(),
+            "One engine per name is needed."
+        );
+        for i in 0..engines.len() {
+            for j in i + 1..engines.len() {
+                let (left, right) = engines.split_at_mut(j);
+                let (first, second) = (&mut left[i], &mut right[0]);
+                loop {
+                    let mut score = self.scores[i][j];
+                    let index = score.games() as usize;
+                    if index >= pairing.games {
+                        break;
+                    }
+                    pairing.play_game(index, first.as_mut(), second.as_mut(), &mut score);
+                    self.scores[i][j] = score;
+                    self.scores[j][i] = Score {
+                        wins: score.losses,
+                        draws: score.draws,
+                        losses: score.wins,
+                    };
+                    on_game(self);
+                }
+            }
+        }
+    }
+
+    /// Writes the crosstable in the text format described in the [module docs](self).
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from the underlying writer.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "{HEADER}")?;
+        for name in &self.names {
+            writeln!(writer, "engine {name}")?;
+        }
+        for i in 0..self.names.len() {
+            for j in i + 1..self.names.len() {
+                let Score {
+                    wins,
+                    draws,
+                    losses,
+                } = self.scores[i][j];
+                writeln!(writer, "score {i} {j} {wins} {draws} {losses}")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a crosstable in the text format described in the [module docs](self).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails or the input is malformed.
+    pub fn read_from(reader: impl BufRead) -> io::Result<Self> {
+        let invalid =
+            |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        let mut lines = reader.lines();
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            return Err(invalid("not a crosstable"));
+        }
+        let mut names = Vec::new();
+        let mut pairs = Vec::new();
+        for line in lines {
+            let line = line?;
+            if let Some(name) = line.strip_prefix("engine ") {
+                names.push(name.to_string());
+            } else if let Some(rest) = line.strip_prefix("score ") {
+                let fields = rest
+                    .split_whitespace()
+                    .map(str::parse::<u32>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| invalid("invalid score line"))?;
+                let &[i, j, wins, draws, losses] = fields.as_slice() else {
+                    return Err(invalid("invalid score line"));
+                };
+                pairs.push((i as usize, j as usize, wins, draws, losses));
+            } else if !line.trim().is_empty() {
+                return Err(invalid("unexpected line in crosstable"));
+            }
+        }
+        let mut table = Self::new(names);
+        for (i, j, wins, draws, losses) in pairs {
+            if i >= j || j >= table.names.len() {
+                return Err(invalid("score for an unknown pairing"));
+            }
+            table.scores[i][j] = Score {
+                wins,
+                draws,
+                losses,
+            };
+            table.scores[j][i] = Score {
+                wins: losses,
+                draws,
+                losses: wins,
+            };
+        }
+        Ok(table)
+    }
+
+    /// Saves the crosstable to the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be created or written.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Loads a crosstable from the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or is malformed.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+}
+
+impl Display for Crosstable {
+    /// Shows the standings, with each engine's total and its results against
+    /// every opponent as `wins-draws-losses`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.names.iter().map(String::len).max().unwrap_or(0);
+        let standings = self.standings();
+        for (rank, &i) in standings.iter().enumerate() {
+            let total = self.total(i);
+            let points = f64::from(total.wins) + f64::from(total.draws) / 2.0;
+            write!(
+                f,
+                "{:>2}. {:<width$} {points:>6.1} / {:<4}",
+                rank + 1,
+                self.names[i],
+                total.games()
+            )?;
+            for &j in &standings {
+                if i == j {
+                    write!(f, " {:^11}", "-")?;
+                } else {
+                    let s = self.scores[i][j];
+                    write!(f, " {:^11}", format!("{}-{}-{}", s.wins, s.draws, s.losses))?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Crosstable;
+    use crate::{
+        engine::{Engine, HeuristicEngine, Limits, RandomEngine},
+        matches::Match,
+    };
+
+    fn engines() -> Vec<Box<dyn Engine<9>>> {
+        vec![
+            Box::new(RandomEngine::new(1)),
+            Box::new(HeuristicEngine::new()),
+            Box::new(RandomEngine::new(2)),
+        ]
+    }
+
+    #[test]
+    fn round_robin_and_resume() {
+        let pairing = Match::<9> {
+            games: 4,
+            limits: Limits::depth(1),
+            ..Match::default()
+        };
+        let mut table = Crosstable::new(["random 1", "heuristic", "random 2"]);
+        // stop partway through by saving a copy after the fifth game.
+        let mut saved = Vec::new();
+        let mut games = 0;
+        table.play(&pairing, &mut engines(), |table| {
+            games += 1;
+            if games == 5 {
+                table.write_to(&mut saved).unwrap();
+            }
+        });
+        assert_eq!(games, 12);
+        assert_eq!(table.standings()[0], 1);
+        assert_eq!(table.total(1).wins, 8);
+        for i in 0..3 {
+            assert_eq!(table.total(i).games(), 8);
+        }
+
+        let mut resumed = Crosstable::read_from(&saved[..]).unwrap();
+        assert_eq!(resumed.names(), table.names());
+        let mut remaining = 0;
+        resumed.play(&pairing, &mut engines(), |_| remaining += 1);
+        assert_eq!(remaining, 7);
+        assert_eq!(resumed.total(1), table.total(1));
+
+        let shown = table.to_string();
+        assert!(shown.lines().next().unwrap().contains("heuristic"));
+        assert!(shown.contains("4-0-0"));
+    }
+}
@@ -0,0 +1,164 @@
+//! The 26 standard Renju openings.
+//!
+//! Each opening is three stones placed around the centre of the board: X in
+//! the centre, O next to it either orthogonally (the 13 "direct" openings) or
+//! diagonally (the 13 "indirect" openings), and a third X stone within two
+//! cells of the centre. Openings can be set up on any board of side length 5 or more:
+//!
+//! ```
+//! use gomokugen::openings;
+//!
+//! let board = openings::KAGETSU.board::<15>();
+//! assert_eq!(board.fen().split(' ').nth(2), Some("3"));
+//! ```
+//!
+//! Freestyle gomoku has no comparable set of named openings: tournaments
+//! such as Gomocup draw their starting positions from lists of balanced
+//! positions instead, so none are included here.
+
+use crate::board::{Board, Move};
+
+/// A named opening, stored as `(row, column)` offsets of its stones from the centre.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Opening {
+    /// The conventional (Japanese) name of the opening.
+    pub name: &'static str,
+    offsets: [(i8, i8); 3],
+}
+
+impl Opening {
+    const fn direct(name: &'static str, third: (i8, i8)) -> Self {
+        Self {
+            name,
+            offsets: [(0, 0), (1, 0), third],
+        }
+    }
+
+    const fn indirect(name: &'static str, third: (i8, i8)) -> Self {
+        Self {
+            name,
+            offsets: [(0, 0), (1, 1), third],
+        }
+    }
+
+    /// Whether the second stone is orthogonally adjacent to the first.
+    #[must_use]
+    pub const fn is_direct(&self) -> bool {
+        self.offsets[1].1 == 0
+    }
+
+    /// The moves of the opening on a board of side length `SIDE_LENGTH`, in order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `SIDE_LENGTH` is less than 5.
+    #[must_use]
+    pub fn moves<const SIDE_LENGTH: usize>(&self) -> [Move<SIDE_LENGTH>; 3] {
+        assert!(SIDE_LENGTH >= 5, "Openings need a board of at least 5x5.");
+        let center = SIDE_LENGTH / 2;
+        self.offsets.map(|(d_row, d_col)| {
+            let row = center.checked_add_signed(d_row.into()).unwrap();
+            let col = center.checked_add_signed(d_col.into()).unwrap();
+            Move::from_index(u16::try_from(row * SIDE_LENGTH + col).unwrap())
+        })
+    }
+
+    /// The position after the opening's moves have been played.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `SIDE_LENGTH` is less than 5.
+    #[must_use]
+    pub fn board<const SIDE_LENGTH: usize>(&self) -> Board<SIDE_LENGTH> {
+        let mut board = Board::new();
+        for mv in self.moves() {
+            board.make_move(mv);
+        }
+        board
+    }
+}
+
+pub const KANSEI: Opening = Opening::direct("Kansei", (2, 0));
+pub const KEIGETSU: Opening = Opening::direct("Keigetsu", (2, 1));
+pub const SOSEI: Opening = Opening::direct("Sosei", (2, 2));
+pub const KAGETSU: Opening = Opening::direct("Kagetsu", (1, 1));
+pub const ZANGETSU: Opening = Opening::direct("Zangetsu", (1, 2));
+pub const UGETSU: Opening = Opening::direct("Ugetsu", (0, 1));
+pub const KINSEI: Opening = Opening::direct("Kinsei", (0, 2));
+pub const SHOGETSU: Opening = Opening::direct("Shogetsu", (-1, 0));
+pub const KYUGETSU: Opening = Opening::direct("Kyugetsu", (-1, 1));
+pub const SHINGETSU: Opening = Opening::direct("Shingetsu", (-1, 2));
+pub const ZUISEI: Opening = Opening::direct("Zuisei", (-2, 0));
+pub const SANGETSU: Opening = Opening::direct("Sangetsu", (-2, 1));
+pub const YUSEI: Opening = Opening::direct("Yusei", (-2, 2));
+
+pub const CHOSEI: Opening = Opening::indirect("Chosei", (2, 2));
+pub const KYOGETSU: Opening = Opening::indirect("Kyogetsu", (2, 1));
+pub const KOSEI: Opening = Opening::indirect("Kosei", (2, 0));
+pub const SUIGETSU: Opening = Opening::indirect("Suigetsu", (2, -1));
+pub const RYUSEI: Opening = Opening::indirect("Ryusei", (2, -2));
+pub const UNGETSU: Opening = Opening::indirect("Ungetsu", (1, 0));
+pub const HOGETSU: Opening = Opening::indirect("Hogetsu", (1, -1));
+pub const RANGETSU: Opening = Opening::indirect("Rangetsu", (1, -2));
+pub const GINGETSU: Opening = Opening::indirect("Gingetsu", (0, -1));
+pub const MYOJO: Opening = Opening::indirect("Myojo", (0, -2));
+pub const SHAGETSU: Opening = Opening::indirect("Shagetsu", (-1, -1));
+pub const MEIGETSU: Opening = Opening::indirect("Meigetsu", (-1, -2));
+pub const SUISEI: Opening = Opening::indirect("Suisei", (-2, -2));
+
+/// The 13 direct openings, in their conventional order (D1 to D13).
+pub const DIRECT: [Opening; 13] = [
+    KANSEI, KEIGETSU, SOSEI, KAGETSU, ZANGETSU, UGETSU, KINSEI, SHOGETSU, KYUGETSU, SHINGETSU,
+    ZUISEI, SANGETSU, YUSEI,
+];
+
+/// The 13 indirect openings, in their conventional order (I1 to I13).
+pub const INDIRECT: [Opening; 13] = [
+    CHOSEI, KYOGETSU, KOSEI, SUIGETSU, RYUSEI, UNGETSU, HOGETSU, RANGETSU, GINGETSU, MYOJO,
+    SHAGETSU, MEIGETSU, SUISEI,
+];
+
+/// Looks up an opening by name, ignoring case.
+#[must_use]
+pub fn by_name(name: &str) -> Option<Opening> {
+    DIRECT
+        .into_iter()
+        .chain(INDIRECT)
+        .find(|opening| opening.name.eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{by_name, DIRECT, INDIRECT, KAGETSU};
+    use crate::{board::Board, symmetry::Symmetry};
+
+    #[test]
+    fn kagetsu_on_15x15() {
+        let moves = KAGETSU.moves::<15>().map(|mv| mv.to_string());
+        assert_eq!(moves, ["H8", "H9", "I9"]);
+        assert_eq!(by_name("kagetsu"), Some(KAGETSU));
+        assert!(KAGETSU.is_direct());
+    }
+
+    #[test]
+    fn openings_are_distinct_up_to_symmetry() {
+        let boards = DIRECT
+            .into_iter()
+            .chain(INDIRECT)
+            .map(|opening| opening.board::<15>())
+            .collect::<Vec<Board<15>>>();
+        for (i, a) in boards.iter().enumerate() {
+            for b in &boards[i + 1..] {
+                assert!(Symmetry::ALL.iter().all(|&sym| a.transform(sym) != *b));
+            }
+        }
+    }
+
+    #[test]
+    fn openings_fit_small_boards() {
+        for opening in DIRECT.into_iter().chain(INDIRECT) {
+            let board = opening.board::<5>();
+            assert_eq!(board.outcome(), None);
+        }
+    }
+}
@@ -1,6 +1,21 @@
-use std::{collections::HashMap, hash::BuildHasher};
+use std::{
+    io::{self, Write},
+    ops::RangeInclusive,
+    time::{Duration, Instant},
+};
 
-use crate::board::Board;
+use crate::{
+    board::{Board, Player, Playout},
+    engine::{Engine, Limits, SearchEngine},
+    eval::evaluate,
+    tt::TranspositionTable,
+};
+
+pub mod suite;
+
+/// The number of random playouts [`generate_stratified_fens`] makes for each
+/// position requested before giving up on the buckets still short.
+const PLAYOUTS_PER_POSITION: usize = 64;
 
 #[must_use]
 pub fn perft<const BOARD_SIZE: usize>(board: Board<BOARD_SIZE>, depth: u8) -> u64 {
@@ -20,7 +35,7 @@ pub fn perft<const BOARD_SIZE: usize>(board: Board<BOARD_SIZE>, depth: u8) -> u6
     let mut count = 0;
     board.generate_moves(|mv| {
         let mut board = board;
-        board.make_move(mv);
+        board.place(mv);
         count += perft(board, depth - 1);
         false
     });
@@ -28,53 +43,867 @@ pub fn perft<const BOARD_SIZE: usize>(board: Board<BOARD_SIZE>, depth: u8) -> u6
     count
 }
 
-#[allow(clippy::module_name_repetitions)]
+/// Counts the leaves of the game tree `depth` plies deep, stopping where the
+/// game ends.
+///
+/// Unlike [`perft`], this does not play on after a win or a draw: a
+/// finished position is a leaf wherever it falls.
+#[must_use]
+pub fn perft_terminal<const BOARD_SIZE: usize>(board: Board<BOARD_SIZE>, depth: u8) -> u64 {
+    if depth == 0 || board.outcome().is_some() {
+        return 1;
+    }
+
+    let mut count = 0;
+    board.generate_moves(|mv| {
+        if depth == 1 {
+            count += 1;
+        } else {
+            let mut board = board;
+            board.place(mv);
+            count += perft_terminal(board, depth - 1);
+        }
+        false
+    });
+
+    count
+}
+
+/// The leaves counted by [`perft_terminal`], by outcome.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TerminalCounts {
+    /// The number of leaves, finished or not.
+    pub leaves: u64,
+    /// The number of leaves won by X.
+    pub x_wins: u64,
+    /// The number of leaves won by O.
+    pub o_wins: u64,
+    /// The number of leaves drawn on a full board.
+    pub draws: u64,
+}
+
+impl TerminalCounts {
+    /// The number of leaves where the game goes on, `depth` plies deep.
+    #[must_use]
+    pub const fn unfinished(&self) -> u64 {
+        self.leaves - self.x_wins - self.o_wins - self.draws
+    }
+
+    const fn add(&mut self, other: &Self) {
+        self.leaves += other.leaves;
+        self.x_wins += other.x_wins;
+        self.o_wins += other.o_wins;
+        self.draws += other.draws;
+    }
+}
+
+/// Counts the leaves of the game tree like [`perft_terminal`], and how many
+/// of them each side has won or are drawn.
+#[must_use]
+pub fn perft_terminal_outcomes<const BOARD_SIZE: usize>(
+    board: Board<BOARD_SIZE>,
+    depth: u8,
+) -> TerminalCounts {
+    let outcome = board.outcome();
+    if depth == 0 || outcome.is_some() {
+        return TerminalCounts {
+            leaves: 1,
+            x_wins: u64::from(outcome == Some(Player::X)),
+            o_wins: u64::from(outcome == Some(Player::O)),
+            draws: u64::from(outcome == Some(Player::None)),
+        };
+    }
+
+    let mut counts = TerminalCounts::default();
+    board.generate_moves(|mv| {
+        let mut board = board;
+        board.place(mv);
+        counts.add(&perft_terminal_outcomes(board, depth - 1));
+        false
+    });
+
+    counts
+}
+
+/// How far a long run has got, as reported after each root move.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Progress {
+    /// The number of root moves finished.
+    pub root_moves_done: usize,
+    /// The number of root moves in the run.
+    pub root_moves: usize,
+    /// The number of leaf nodes counted or positions generated so far.
+    pub nodes: u64,
+    /// The time since the run started.
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// The fraction of root moves finished, from 0 to 1.
+    #[must_use]
+    pub fn fraction(&self) -> f64 {
+        #![allow(clippy::cast_precision_loss)]
+        if self.root_moves == 0 {
+            return 1.0;
+        }
+        self.root_moves_done as f64 / self.root_moves as f64
+    }
+
+    /// An estimate of the time left, assuming the remaining root moves take
+    /// as long on average as the finished ones, or `None` before any has
+    /// finished.
+    #[must_use]
+    pub fn eta(&self) -> Option<Duration> {
+        #![allow(clippy::cast_possible_truncation)]
+        if self.root_moves_done == 0 {
+            return None;
+        }
+        let remaining = (self.root_moves - self.root_moves_done) as u32;
+        Some(self.elapsed / self.root_moves_done as u32 * remaining)
+    }
+}
+
+/// An estimate of a perft count from random samples, as returned by
+/// [`perft_estimate`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PerftEstimate {
+    /// The number of random paths sampled.
+    pub samples: usize,
+    /// The estimated count, the mean over the samples.
+    pub mean: f64,
+    /// The standard error of the mean, infinite with a single sample.
+    pub std_error: f64,
+}
+
+impl PerftEstimate {
+    /// A 95% confidence interval for the count, from the normal
+    /// approximation: the mean plus or minus 1.96 standard errors, clamped
+    /// at zero.
+    #[must_use]
+    pub fn confidence_interval(&self) -> (f64, f64) {
+        let margin = 1.96 * self.std_error;
+        ((self.mean - margin).max(0.0), self.mean + margin)
+    }
+}
+
+/// Estimates [`perft`] from `samples` random paths, for depths where
+/// counting every leaf would take too long.
+///
+/// Each sample follows one path of `depth` random moves and multiplies the
+/// numbers of moves on offer along it, which is an unbiased estimate of the
+/// number of leaves (Knuth's estimator). As [`perft`] plays on after a game
+/// is won, every position a given number of plies on has the same number of
+/// moves, and every sample is the same; the estimate is exact, but cheap,
+/// where the full count is out of reach. Trees that end in places, as with
+/// [`perft_terminal_estimate`], give a spread.
+///
+/// `rng(lo, hi)` must return a value in `lo..hi`, as for
+/// [`Board::make_random_move`].
+///
+/// # Panics
+///
+/// Panics if `samples` is zero, or if `rng` returns an out-of-range value.
+pub fn perft_estimate<const BOARD_SIZE: usize>(
+    board: Board<BOARD_SIZE>,
+    depth: u8,
+    samples: usize,
+    rng: impl FnMut(usize, usize) -> usize,
+) -> PerftEstimate {
+    estimate(board, depth, samples, false, rng)
+}
+
+/// Estimates [`perft_terminal`] from `samples` random paths.
+///
+/// This works like [`perft_estimate`], but a path ends early when the game
+/// does, so the samples differ and the standard error measures how far to
+/// trust the mean.
+///
+/// # Panics
+///
+/// Panics if `samples` is zero, or if `rng` returns an out-of-range value.
+pub fn perft_terminal_estimate<const BOARD_SIZE: usize>(
+    board: Board<BOARD_SIZE>,
+    depth: u8,
+    samples: usize,
+    rng: impl FnMut(usize, usize) -> usize,
+) -> PerftEstimate {
+    estimate(board, depth, samples, true, rng)
+}
+
+/// Averages `samples` of Knuth's estimator, ending each path at a finished
+/// position if `terminal` is set.
+fn estimate<const BOARD_SIZE: usize>(
+    board: Board<BOARD_SIZE>,
+    depth: u8,
+    samples: usize,
+    terminal: bool,
+    mut rng: impl FnMut(usize, usize) -> usize,
+) -> PerftEstimate {
+    #![allow(clippy::cast_precision_loss)]
+    assert!(samples > 0, "Cannot estimate perft from no samples");
+    let (mut sum, mut sum_of_squares) = (0.0, 0.0);
+    for _ in 0..samples {
+        let mut playout = Playout::new(board);
+        let mut product = 1.0;
+        for _ in 0..depth {
+            if terminal && playout.board().outcome().is_some() {
+                break;
+            }
+            product *= playout.empty_cells() as f64;
+            if playout.make_random_move(&mut rng).is_none() {
+                break;
+            }
+        }
+        sum += product;
+        sum_of_squares += product * product;
+    }
+    let n = samples as f64;
+    let mean = sum / n;
+    let std_error = if samples < 2 {
+        f64::INFINITY
+    } else {
+        let variance = sum.mul_add(-mean, sum_of_squares) / (n - 1.0);
+        (variance.max(0.0) / n).sqrt()
+    };
+    PerftEstimate {
+        samples,
+        mean,
+        std_error,
+    }
+}
+
+/// Calls `visit` on each child of `board`, reporting progress to `progress`
+/// after each one with the running total of what `visit` returns.
+fn over_root_moves<const BOARD_SIZE: usize>(
+    board: Board<BOARD_SIZE>,
+    mut visit: impl FnMut(Board<BOARD_SIZE>) -> u64,
+    mut progress: impl FnMut(&Progress),
+) -> u64 {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("root_moves", position = %board.fen()).entered();
+    let start = Instant::now();
+    let root_moves = board.successors().count();
+    let mut report = Progress {
+        root_moves_done: 0,
+        root_moves,
+        nodes: 0,
+        elapsed: Duration::ZERO,
+    };
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    for (mv, child) in board.successors() {
+        let nodes = visit(child);
+        report.nodes += nodes;
+        report.root_moves_done += 1;
+        report.elapsed = start.elapsed();
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            %mv,
+            nodes,
+            done = report.root_moves_done,
+            of = report.root_moves,
+            "root move finished"
+        );
+        progress(&report);
+    }
+    #[cfg(feature = "tracing")]
+    tracing::info!(
+        nodes = report.nodes,
+        time_ms = report.elapsed.as_millis(),
+        "run finished"
+    );
+    report.nodes
+}
+
+/// Counts leaf nodes like [`perft`], calling `progress` after each root move
+/// so that long runs can show a progress bar.
 #[must_use]
-pub fn perft_cached<const BOARD_SIZE: usize, S: BuildHasher>(
+pub fn perft_with_progress<const BOARD_SIZE: usize>(
     board: Board<BOARD_SIZE>,
     depth: u8,
-    cache: &mut HashMap<(Board<BOARD_SIZE>, u8), u64, S>,
+    progress: impl FnMut(&Progress),
 ) -> u64 {
     if depth == 0 {
         return 1;
     }
+    over_root_moves(board, |child| perft(child, depth - 1), progress)
+}
 
-    if depth == 1 {
-        let mut count = 0;
-        board.generate_moves(|_| {
-            count += 1;
-            false
-        });
-        return count;
+/// Counts leaf nodes like [`perft`], caching subtree counts in `table`.
+///
+/// Each entry keeps the whole position beside its count, and a cached count
+/// is only used for the very position it was stored for, so unlike
+/// [`perft_tt`] this can never confuse two positions, at the cost of memory
+/// for a board in every entry.
+#[allow(clippy::module_name_repetitions)]
+#[must_use]
+pub fn perft_cached<const BOARD_SIZE: usize>(
+    board: Board<BOARD_SIZE>,
+    depth: u8,
+    table: &mut TranspositionTable<(Board<BOARD_SIZE>, u64)>,
+) -> u64 {
+    if depth <= 1 {
+        return perft(board, depth);
     }
 
-    if let Some(&count) = cache.get(&(board, depth)) {
-        return count;
+    let key = board.zobrist_key();
+    if let Some((stored_depth, &(stored, count))) = table.probe(key) {
+        if stored_depth == depth && stored == board {
+            return count;
+        }
     }
 
     let mut count = 0;
     board.generate_moves(|mv| {
         let mut board = board;
-        board.make_move(mv);
-        count += perft_cached(board, depth - 1, cache);
+        board.place(mv);
+        count += perft_cached(board, depth - 1, table);
         false
     });
 
-    cache.insert((board, depth), count);
+    table.store(key, depth, (board, count));
 
     count
 }
 
-pub fn generate_depth_n_fens<const BOARD_SIZE: usize>(board: Board<BOARD_SIZE>, mut fen_receiver: impl FnMut(String) + Copy, depth: u8) {
-    if depth == 0 {
-        fen_receiver(board.fen());
-        return;
+/// Counts leaf nodes like [`perft`], caching subtree counts in `table`.
+///
+/// Entries are matched on 64-bit Zobrist keys alone, so a key collision
+/// silently corrupts the count; [`perft_tt_verified`] rules that out.
+#[must_use]
+pub fn perft_tt<const BOARD_SIZE: usize>(
+    board: Board<BOARD_SIZE>,
+    depth: u8,
+    table: &mut TranspositionTable<u64>,
+) -> u64 {
+    if depth <= 1 {
+        return perft(board, depth);
+    }
+
+    let key = board.zobrist_key();
+    if let Some((stored_depth, &count)) = table.probe(key) {
+        if stored_depth == depth {
+            return count;
+        }
     }
 
+    let mut count = 0;
     board.generate_moves(|mv| {
         let mut board = board;
-        board.make_move(mv);
-        generate_depth_n_fens(board, fen_receiver, depth - 1);
+        board.place(mv);
+        count += perft_tt(board, depth - 1, table);
         false
     });
-}
\ No newline at end of file
+
+    table.store(key, depth, count);
+
+    count
+}
+
+/// Counts leaf nodes like [`perft_tt`], but checks each cached count against
+/// the full 128-bit [`Board::zobrist_key128`] before using it.
+///
+/// The table stores the low half of the key alongside each count, and a
+/// mismatch is treated as a miss. This costs a little speed and memory, and
+/// makes a wrong count from a collision practically impossible.
+#[must_use]
+pub fn perft_tt_verified<const BOARD_SIZE: usize>(
+    board: Board<BOARD_SIZE>,
+    depth: u8,
+    table: &mut TranspositionTable<(u64, u64)>,
+) -> u64 {
+    #![allow(clippy::cast_possible_truncation)]
+    if depth <= 1 {
+        return perft(board, depth);
+    }
+
+    let full = board.zobrist_key128();
+    let (key, check) = ((full >> 64) as u64, full as u64);
+    if let Some((stored_depth, &(stored_check, count))) = table.probe(key) {
+        if stored_depth == depth && stored_check == check {
+            return count;
+        }
+    }
+
+    let mut count = 0;
+    board.generate_moves(|mv| {
+        let mut board = board;
+        board.place(mv);
+        count += perft_tt_verified(board, depth - 1, table);
+        false
+    });
+
+    table.store(key, depth, (check, count));
+
+    count
+}
+
+pub fn generate_depth_n_fens<const BOARD_SIZE: usize>(
+    board: Board<BOARD_SIZE>,
+    fen_receiver: impl FnMut(String),
+    depth: u8,
+) {
+    generate_unique_depth_n_fens(board, |_| true, fen_receiver, depth);
+}
+
+/// Sends FENs to `fen_receiver` like [`generate_depth_n_fens`], calling
+/// `progress` after each root move so that long runs can show a progress bar.
+pub fn generate_depth_n_fens_with_progress<const BOARD_SIZE: usize>(
+    board: Board<BOARD_SIZE>,
+    mut fen_receiver: impl FnMut(String),
+    depth: u8,
+    progress: impl FnMut(&Progress),
+) {
+    if depth == 0 {
+        fen_receiver(board.fen());
+        return;
+    }
+    over_root_moves(
+        board,
+        |child| {
+            let mut count = 0;
+            generate_depth_n_fens(
+                child,
+                |fen| {
+                    count += 1;
+                    fen_receiver(fen);
+                },
+                depth - 1,
+            );
+            count
+        },
+        progress,
+    );
+}
+
+/// Writes the FEN of every position `depth` plies after `board` to `writer`,
+/// one per line, returning the number written.
+///
+/// To compress the output, pass a [`Sink`](crate::sink::Sink).
+///
+/// # Errors
+///
+/// Returns any error from the underlying writer.
+pub fn write_depth_n_fens<const BOARD_SIZE: usize>(
+    board: Board<BOARD_SIZE>,
+    mut writer: impl Write,
+    depth: u8,
+) -> io::Result<u64> {
+    fn visit<const BOARD_SIZE: usize>(
+        board: Board<BOARD_SIZE>,
+        writer: &mut dyn Write,
+        depth: u8,
+    ) -> io::Result<u64> {
+        if depth == 0 {
+            writeln!(writer, "{}", board.fen())?;
+            return Ok(1);
+        }
+
+        let mut count = 0;
+        for (_, child) in board.successors() {
+            count += visit(child, writer, depth - 1)?;
+        }
+        Ok(count)
+    }
+
+    visit(board, &mut writer, depth)
+}
+
+/// Sends the FEN of every position `depth` plies after `board` to
+/// `fen_receiver`, skipping positions for which `is_new` returns `false`.
+///
+/// Passing [`PositionSet::insert`](crate::positions::PositionSet::insert)
+/// as `is_new` removes duplicates exactly, up to symmetry. For runs too large
+/// for that to fit in memory,
+/// [`PositionFilter::insert`](crate::positions::PositionFilter::insert)
+/// removes them approximately, occasionally dropping a unique position too.
+pub fn generate_unique_depth_n_fens<const BOARD_SIZE: usize>(
+    board: Board<BOARD_SIZE>,
+    mut is_new: impl FnMut(&Board<BOARD_SIZE>) -> bool,
+    mut fen_receiver: impl FnMut(String),
+    depth: u8,
+) {
+    fn visit<const BOARD_SIZE: usize>(
+        board: Board<BOARD_SIZE>,
+        is_new: &mut dyn FnMut(&Board<BOARD_SIZE>) -> bool,
+        fen_receiver: &mut dyn FnMut(String),
+        depth: u8,
+    ) {
+        if depth == 0 {
+            if is_new(&board) {
+                fen_receiver(board.fen());
+            }
+            return;
+        }
+
+        for (_, child) in board.successors() {
+            visit(child, is_new, fen_receiver, depth - 1);
+        }
+    }
+
+    visit(board, &mut is_new, &mut fen_receiver, depth);
+}
+
+/// How [`generate_banded_depth_n_fens`] scores each position.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Scorer {
+    /// The static evaluation, [`evaluate`].
+    Eval,
+    /// A single-threaded search to this many plies.
+    Search(u8),
+}
+
+/// A range of scores, from the perspective of the side to move, that a
+/// generated position must fall in to be kept, such as a narrow band around
+/// zero for a training set of balanced positions.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ScoreBand {
+    /// How positions are scored.
+    pub scorer: Scorer,
+    /// The scores kept, inclusive at both ends.
+    pub range: RangeInclusive<i32>,
+}
+
+impl ScoreBand {
+    /// Keeps positions whose static evaluation is in `range`.
+    #[must_use]
+    pub const fn eval(range: RangeInclusive<i32>) -> Self {
+        Self {
+            scorer: Scorer::Eval,
+            range,
+        }
+    }
+
+    /// Keeps positions whose score from a search to `depth` plies is in
+    /// `range`.
+    #[must_use]
+    pub const fn search(depth: u8, range: RangeInclusive<i32>) -> Self {
+        Self {
+            scorer: Scorer::Search(depth),
+            range,
+        }
+    }
+}
+
+/// Sends the FEN and score of every position `depth` plies after `board`
+/// whose score falls in `band` to `fen_receiver`.
+///
+/// Positions for which `is_new` returns `false` are skipped, as for
+/// [`generate_unique_depth_n_fens`], and positions are scored only once
+/// `is_new` has accepted them. Finished positions are given their static
+/// evaluation, which for a win is far outside any band that excludes decided
+/// games.
+pub fn generate_banded_depth_n_fens<const BOARD_SIZE: usize>(
+    board: Board<BOARD_SIZE>,
+    band: &ScoreBand,
+    mut is_new: impl FnMut(&Board<BOARD_SIZE>) -> bool,
+    mut fen_receiver: impl FnMut(String, i32),
+    depth: u8,
+) {
+    fn visit<const BOARD_SIZE: usize>(
+        board: Board<BOARD_SIZE>,
+        score: &mut dyn FnMut(&Board<BOARD_SIZE>) -> i32,
+        range: &RangeInclusive<i32>,
+        is_new: &mut dyn FnMut(&Board<BOARD_SIZE>) -> bool,
+        fen_receiver: &mut dyn FnMut(String, i32),
+        depth: u8,
+    ) {
+        if depth == 0 {
+            if is_new(&board) {
+                let score = score(&board);
+                if range.contains(&score) {
+                    fen_receiver(board.fen(), score);
+                }
+            }
+            return;
+        }
+
+        for (_, child) in board.successors() {
+            visit(child, score, range, is_new, fen_receiver, depth - 1);
+        }
+    }
+
+    let mut engine = SearchEngine::new();
+    let mut score = |board: &Board<BOARD_SIZE>| match band.scorer {
+        Scorer::Search(depth) if depth > 0 && board.outcome().is_none() => {
+            engine.best_move(board, &Limits::depth(depth));
+            engine
+                .iterations()
+                .last()
+                .map_or_else(|| evaluate(board), |info| info.score)
+        }
+        _ => evaluate(board),
+    };
+    visit(
+        board,
+        &mut score,
+        &band.range,
+        &mut is_new,
+        &mut fen_receiver,
+        depth,
+    );
+}
+
+/// Sends `per_ply` random positions at each ply in `plies` to
+/// `fen_receiver`, so that a data set is not dominated by the shallow
+/// positions that exhaustive generation mostly yields.
+///
+/// Positions are sampled from random playouts from `board`, each giving at
+/// most one position to each bucket still short of `per_ply`, and finished
+/// positions are never sent. Positions for which `is_new` returns `false`
+/// are skipped, as for [`generate_unique_depth_n_fens`]. If a bucket cannot
+/// be filled, as when there are fewer distinct positions at its ply than
+/// requested, generation stops after 64 playouts per position requested.
+///
+/// `rng(lo, hi)` must return a value in `lo..hi`, as for
+/// [`Board::make_random_move`].
+///
+/// Returns the number of positions sent at each ply, starting with the
+/// first ply of `plies`.
+pub fn generate_stratified_fens<const BOARD_SIZE: usize>(
+    board: Board<BOARD_SIZE>,
+    plies: RangeInclusive<usize>,
+    per_ply: usize,
+    mut is_new: impl FnMut(&Board<BOARD_SIZE>) -> bool,
+    mut fen_receiver: impl FnMut(String),
+    mut rng: impl FnMut(usize, usize) -> usize,
+) -> Vec<usize> {
+    let first = *plies.start();
+    let mut counts = vec![0; plies.clone().count()];
+    let mut playouts = counts.len() * per_ply * PLAYOUTS_PER_POSITION;
+    while playouts > 0 && counts.iter().any(|&count| count < per_ply) {
+        playouts -= 1;
+        let mut playout = Playout::new(board);
+        loop {
+            let position = playout.board();
+            if position.outcome().is_some() || position.ply() > *plies.end() {
+                break;
+            }
+            if let Some(count) = position
+                .ply()
+                .checked_sub(first)
+                .and_then(|bucket| counts.get_mut(bucket))
+            {
+                if *count < per_ply && is_new(position) {
+                    *count += 1;
+                    fen_receiver(position.fen());
+                }
+            }
+            if playout.make_random_move(&mut rng).is_none() {
+                break;
+            }
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        generate_banded_depth_n_fens, generate_depth_n_fens_with_progress,
+        generate_stratified_fens, generate_unique_depth_n_fens, perft, perft_cached,
+        perft_estimate, perft_terminal, perft_terminal_estimate, perft_terminal_outcomes, perft_tt,
+        perft_tt_verified, perft_with_progress, write_depth_n_fens, ScoreBand,
+    };
+    use crate::{
+        board::Board,
+        eval::evaluate,
+        positions::{PositionFilter, PositionSet},
+        tt::TranspositionTable,
+    };
+
+    #[test]
+    fn cached_counts_agree() {
+        let board = Board::<7>::new();
+        let expected = perft(board, 3);
+        assert_eq!(expected, 49 * 48 * 47);
+        let mut table = TranspositionTable::new(1024);
+        assert_eq!(perft_cached(board, 3, &mut table), expected);
+        assert_eq!(perft_cached(board, 3, &mut table), expected);
+        let mut table = TranspositionTable::new(1024);
+        assert_eq!(perft_tt(board, 3, &mut table), expected);
+        assert_eq!(perft_tt(board, 3, &mut table), expected);
+        let mut table = TranspositionTable::new(1024);
+        assert_eq!(perft_tt_verified(board, 3, &mut table), expected);
+        assert_eq!(perft_tt_verified(board, 3, &mut table), expected);
+    }
+
+    #[test]
+    fn verified_table_rejects_collisions() {
+        let board = Board::<5>::new();
+        let expected = perft(board, 3);
+        // plant a bogus count under the root's 64-bit key, as a colliding
+        // position would.
+        let mut table = TranspositionTable::new(1024);
+        table.store(board.zobrist_key(), 3, 12345);
+        assert_eq!(perft_tt(board, 3, &mut table), 12345);
+        let mut table = TranspositionTable::new(1024);
+        table.store(board.zobrist_key(), 3, (u64::MAX, 12345));
+        assert_eq!(perft_tt_verified(board, 3, &mut table), expected);
+    }
+
+    #[test]
+    fn progress_is_reported_per_root_move() {
+        let board = Board::<5>::new();
+        let mut reports = Vec::new();
+        let count = perft_with_progress(board, 3, |p| reports.push(*p));
+        assert_eq!(count, perft(board, 3));
+        assert_eq!(reports.len(), 25);
+        assert!(reports
+            .iter()
+            .enumerate()
+            .all(|(i, p)| p.root_moves_done == i + 1 && p.root_moves == 25));
+        assert_eq!(reports[0].nodes, 24 * 23);
+        assert_eq!(reports[24].nodes, count);
+        assert!((reports[24].fraction() - 1.0).abs() < f64::EPSILON);
+        assert_eq!(reports[24].eta(), Some(std::time::Duration::ZERO));
+
+        let mut fens = 0;
+        let mut last = None;
+        generate_depth_n_fens_with_progress(board, |_| fens += 1, 2, |p| last = Some(*p));
+        assert_eq!(fens, 25 * 24);
+        assert_eq!(last.map(|p| p.nodes), Some(fens));
+    }
+
+    #[test]
+    fn unique_generation_removes_transpositions() {
+        let board = Board::<7>::new();
+        let mut set = PositionSet::new();
+        let mut exact = Vec::new();
+        generate_unique_depth_n_fens(board, |b| set.insert(b), |fen| exact.push(fen), 3);
+        assert!(exact.len() < 49 * 48 * 47 / 2);
+        assert_eq!(exact.len(), set.len());
+
+        let mut filter = PositionFilter::new(exact.len(), 0.001);
+        let mut approximate = Vec::new();
+        generate_unique_depth_n_fens(board, |b| filter.insert(b), |fen| approximate.push(fen), 3);
+        assert!(approximate.len() <= exact.len());
+        assert!(approximate.len() * 100 > exact.len() * 99);
+        let exact = exact.into_iter().collect::<std::collections::HashSet<_>>();
+        assert!(approximate.iter().all(|fen| exact.contains(fen)));
+    }
+
+    #[test]
+    fn generation_walks_past_wins() {
+        // X can win at ply 5, so this panicked under `strict-checks`.
+        let board = Board::<3>::new().with_win_length(3);
+        let mut count = 0;
+        super::generate_depth_n_fens(board, |_| count += 1, 6);
+        assert_eq!(count, 60480);
+        let mut unique = 0;
+        generate_unique_depth_n_fens(board, |_| true, |_| unique += 1, 6);
+        assert_eq!(unique, count);
+        let written = write_depth_n_fens(board, std::io::sink(), 6).unwrap();
+        assert_eq!(written, 60480);
+    }
+
+    #[test]
+    fn writes_one_fen_per_line() {
+        let board = Board::<5>::new();
+        let mut output = Vec::new();
+        assert_eq!(write_depth_n_fens(board, &mut output, 2).unwrap(), 25 * 24);
+        let output = String::from_utf8(output).unwrap();
+        let mut expected = Vec::new();
+        super::generate_depth_n_fens(board, |fen| expected.push(fen), 2);
+        assert!(output.lines().eq(expected.iter().map(String::as_str)));
+    }
+
+    #[test]
+    fn banded_generation_keeps_balanced_positions() {
+        let board = Board::<7>::new();
+        let band = ScoreBand::eval(-2..=2);
+        let mut kept = Vec::new();
+        generate_banded_depth_n_fens(
+            board,
+            &band,
+            |_| true,
+            |fen, score| kept.push((fen, score)),
+            2,
+        );
+        assert!(!kept.is_empty() && kept.len() < 49 * 48);
+        for (fen, score) in &kept {
+            let board = fen.parse::<Board<7>>().unwrap();
+            assert_eq!(evaluate(&board), *score);
+            assert!(band.range.contains(score));
+        }
+
+        let band = ScoreBand::search(2, -2..=2);
+        let mut set = PositionSet::new();
+        let mut searched = 0;
+        generate_banded_depth_n_fens(board, &band, |b| set.insert(b), |_, _| searched += 1, 2);
+        assert!(searched > 0 && searched <= set.len());
+    }
+
+    #[test]
+    fn stratified_generation_fills_each_ply() {
+        let mut rng = crate::test_util::test_rng(0x2545_F491_4F6C_DD1D);
+        let mut set = PositionSet::new();
+        let mut plies = Vec::new();
+        let counts = generate_stratified_fens(
+            Board::<7>::new(),
+            2..=10,
+            20,
+            |b| set.insert(b),
+            |fen| plies.push(fen.parse::<Board<7>>().unwrap().ply()),
+            &mut rng,
+        );
+        assert_eq!(counts, [20; 9]);
+        assert_eq!(plies.len(), 20 * 9);
+        assert!((2..=10).all(|ply| plies.iter().filter(|&&p| p == ply).count() == 20));
+
+        // up to symmetry, one move on a 3x3 board leaves only three
+        // positions: a corner, an edge, or the centre.
+        let mut set = PositionSet::new();
+        let counts = generate_stratified_fens(
+            Board::<3>::new(),
+            1..=1,
+            5,
+            |b| set.insert(b),
+            |_| {},
+            &mut rng,
+        );
+        assert_eq!(counts, [3]);
+    }
+
+    #[test]
+    fn estimates_match_exact_counts() {
+        let mut rng = crate::test_util::test_rng(0x2545_F491_4F6C_DD1D);
+        let board = Board::<5>::new().with_move("c3".parse().unwrap());
+        let estimate = perft_estimate(board, 3, 100, &mut rng);
+        #[allow(clippy::cast_precision_loss)]
+        let exact = perft(board, 3) as f64;
+        assert_eq!(estimate.samples, 100);
+        assert!((estimate.mean - exact).abs() < 1e-9);
+        assert!(estimate.std_error.abs() < 1e-9);
+        let (low, high) = estimate.confidence_interval();
+        assert!(low <= exact && exact <= high);
+        // past a full board there is nothing left to count.
+        assert!(perft_estimate(board, 30, 3, &mut rng).mean.abs() < f64::EPSILON);
+        assert!(perft_estimate(board, 0, 1, &mut rng)
+            .std_error
+            .is_infinite());
+    }
+
+    #[test]
+    fn terminal_perft_stops_at_wins() {
+        // on a 3x3 board with a win length of 3, games can end from ply 5.
+        let board = Board::<3>::new().with_win_length(3);
+        for depth in 0..=4 {
+            assert_eq!(perft_terminal(board, depth), perft(board, depth));
+        }
+        assert!(perft_terminal(board, 6) < perft(board, 6));
+        let counts = perft_terminal_outcomes(board, 9);
+        assert_eq!(counts.leaves, perft_terminal(board, 9));
+        // the well-known tic-tac-toe game tree.
+        assert_eq!(counts.leaves, 255_168);
+        assert_eq!(counts.x_wins, 131_184);
+        assert_eq!(counts.o_wins, 77_904);
+        assert_eq!(counts.draws, 46_080);
+        assert_eq!(counts.unfinished(), 0);
+
+        let mut rng = crate::test_util::test_rng(0x2545_F491_4F6C_DD1D);
+        let estimate = perft_terminal_estimate(board, 9, 20_000, &mut rng);
+        assert!(estimate.std_error > 0.0);
+        // the seed is fixed, so this bound on the error cannot flake.
+        assert!((estimate.mean - 255_168.0).abs() < 4.0 * estimate.std_error);
+    }
+}
@@ -0,0 +1,200 @@
+//! Perft test suites: positions with their expected perft counts, for
+//! checking move generation.
+//!
+//! A suite is a text file with one position per line, in the style of the
+//! EPD perft suites used for chess: the FEN of the position, then the
+//! expected count at each depth, each introduced by a semicolon.
+//!
+//! ```text
+//! # the empty 5x5 board
+//! ...../...../...../...../..... x 0 ;D1 25 ;D2 600 ;D3 13800
+//! ```
+//!
+//! Blank lines and lines starting with `#` are ignored. Positions use the
+//! default win length.
+//!
+//! The crate ships baseline suites for several board sizes, which any change
+//! to move generation should leave passing:
+//!
+//! ```
+//! use gomokugen::perft::suite::{self, PerftCase};
+//!
+//! let cases = PerftCase::<5>::parse_suite(suite::BASELINE_5X5).unwrap();
+//! assert!(suite::run(&cases, Some(3)).is_empty());
+//! ```
+
+use std::{fmt::Display, str::FromStr};
+
+use super::perft;
+use crate::board::Board;
+
+/// The baseline suite for 5x5 boards.
+pub const BASELINE_5X5: &str = include_str!("../../suites/perft-5x5.epd");
+
+/// The baseline suite for 7x7 boards.
+pub const BASELINE_7X7: &str = include_str!("../../suites/perft-7x7.epd");
+
+/// The baseline suite for 9x9 boards.
+pub const BASELINE_9X9: &str = include_str!("../../suites/perft-9x9.epd");
+
+/// The baseline suite for 15x15 boards.
+pub const BASELINE_15X15: &str = include_str!("../../suites/perft-15x15.epd");
+
+/// A position with its expected perft counts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PerftCase<const SIDE_LENGTH: usize> {
+    /// The position.
+    pub board: Board<SIDE_LENGTH>,
+    /// The expected counts, as `(depth, count)` pairs.
+    pub counts: Vec<(u8, u64)>,
+}
+
+impl<const SIDE_LENGTH: usize> PerftCase<SIDE_LENGTH> {
+    /// Parses every case in a suite, in the format described in the
+    /// [module docs](self).
+    ///
+    /// # Errors
+    ///
+    /// Returns the line number, counting from one, and the reason for the
+    /// first line that is not a valid case.
+    pub fn parse_suite(text: &str) -> Result<Vec<Self>, (usize, &'static str)> {
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| {
+                let line = line.trim();
+                !line.is_empty() && !line.starts_with('#')
+            })
+            .map(|(i, line)| line.parse().map_err(|e| (i + 1, e)))
+            .collect()
+    }
+}
+
+impl<const SIDE_LENGTH: usize> FromStr for PerftCase<SIDE_LENGTH> {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split(';');
+        let board = fields.next().unwrap_or_default().trim().parse()?;
+        let counts = fields
+            .map(|field| {
+                let (depth, count) = field
+                    .trim()
+                    .split_once(' ')
+                    .ok_or("Expected a depth and a count")?;
+                let depth = depth
+                    .strip_prefix('D')
+                    .and_then(|depth| depth.parse().ok())
+                    .ok_or("Invalid depth")?;
+                let count = count.trim().parse().map_err(|_| "Invalid count")?;
+                Ok::<_, &str>((depth, count))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if counts.is_empty() {
+            return Err("No expected counts");
+        }
+        Ok(Self { board, counts })
+    }
+}
+
+impl<const SIDE_LENGTH: usize> Display for PerftCase<SIDE_LENGTH> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.board.fen())?;
+        for (depth, count) in &self.counts {
+            write!(f, " ;D{depth} {count}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A count that did not match a suite.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch<const SIDE_LENGTH: usize> {
+    /// The index of the case in the suite.
+    pub case: usize,
+    /// The position.
+    pub board: Board<SIDE_LENGTH>,
+    /// The depth searched.
+    pub depth: u8,
+    /// The count the suite expects.
+    pub expected: u64,
+    /// The count [`perft`] gave.
+    pub actual: u64,
+}
+
+impl<const SIDE_LENGTH: usize> Display for Mismatch<SIDE_LENGTH> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "case {} ({}) at depth {}: expected {}, got {}",
+            self.case,
+            self.board.fen(),
+            self.depth,
+            self.expected,
+            self.actual
+        )
+    }
+}
+
+/// Checks [`perft`] against every count in `cases`, skipping depths beyond
+/// `max_depth` if one is given, and returns the counts that differ.
+#[must_use]
+pub fn run<const SIDE_LENGTH: usize>(
+    cases: &[PerftCase<SIDE_LENGTH>],
+    max_depth: Option<u8>,
+) -> Vec<Mismatch<SIDE_LENGTH>> {
+    cases
+        .iter()
+        .enumerate()
+        .flat_map(|(i, case)| {
+            case.counts
+                .iter()
+                .filter(|&&(depth, _)| max_depth.is_none_or(|max| depth <= max))
+                .filter_map(move |&(depth, expected)| {
+                    let actual = perft(case.board, depth);
+                    (actual != expected).then_some(Mismatch {
+                        case: i,
+                        board: case.board,
+                        depth,
+                        expected,
+                        actual,
+                    })
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, PerftCase};
+
+    #[test]
+    fn baselines_pass() {
+        let cases = PerftCase::<5>::parse_suite(super::BASELINE_5X5).unwrap();
+        assert_eq!(run(&cases, None), []);
+        let cases = PerftCase::<7>::parse_suite(super::BASELINE_7X7).unwrap();
+        assert_eq!(run(&cases, None), []);
+        let cases = PerftCase::<9>::parse_suite(super::BASELINE_9X9).unwrap();
+        assert_eq!(run(&cases, Some(2)), []);
+        let cases = PerftCase::<15>::parse_suite(super::BASELINE_15X15).unwrap();
+        assert_eq!(run(&cases, Some(2)), []);
+    }
+
+    #[test]
+    fn reports_bad_lines_and_counts() {
+        let case = "...../...../...../...../..... x 0 ;D1 25 ;D2 601";
+        let parsed = case.parse::<PerftCase<5>>().unwrap();
+        assert_eq!(parsed.to_string(), case);
+        let mismatches = run(&[parsed], None);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!((mismatches[0].depth, mismatches[0].actual), (2, 600));
+
+        let suite = "# comment\n\n...../...../...../...../..... x 0 ;D1 25\n...../...../...../...../..... x 0 ;X1 25\n";
+        assert_eq!(
+            PerftCase::<5>::parse_suite(suite),
+            Err((4, "Invalid depth"))
+        );
+        assert!("...../...../...../...../..... x 0"
+            .parse::<PerftCase<5>>()
+            .is_err());
+    }
+}
@@ -0,0 +1,695 @@
+//! Position containers keyed by canonical Zobrist keys.
+//!
+//! Positions that are rotations or reflections of each other share a key, so a
+//! [`PositionSet`] or [`PositionMap`] automatically unifies them.
+//!
+//! Keys do not record the size of the board or the length of a winning line,
+//! so a container remembers these from the first board put in it, and only
+//! holds boards of that shape.
+//!
+//! On disk, a container is an 18-byte header (the magic bytes `GMKP`, a
+//! big-endian `u16` format version, a byte each for the side length and the
+//! win length, both zero if no board has been put in the container, a
+//! big-endian `u16` value size, and a big-endian `u64` entry count) followed
+//! by the entries sorted by key: a big-endian `u64` key and then the value's
+//! bytes.
+
+use std::{
+    collections::{hash_map, HashMap},
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::{board::Board, zobrist::splitmix64};
+
+pub(crate) const MAGIC: &[u8; 4] = b"GMKP";
+/// Version 2 is the first with stable Zobrist keys; see [`ZOBRIST_VERSION`].
+/// Version 3 adds the side length and win length to the header.
+///
+/// [`ZOBRIST_VERSION`]: crate::board::ZOBRIST_VERSION
+pub(crate) const VERSION: u16 = 3;
+/// The length of the header that starts a saved container.
+pub(crate) const HEADER_LEN: usize = 18;
+
+/// The side length and win length of a board, as a container records them.
+pub(crate) fn shape_of<const SIDE_LENGTH: usize>(board: &Board<SIDE_LENGTH>) -> (u8, u8) {
+    (
+        u8::try_from(SIDE_LENGTH).expect("side length fits in a byte"),
+        u8::try_from(board.win_length()).expect("win length fits in a byte"),
+    )
+}
+
+/// Checks the header of a saved container holding values of `value_size`
+/// bytes, returning the shape of its boards, if known, and its entry count.
+pub(crate) fn read_header(
+    header: &[u8; HEADER_LEN],
+    value_size: usize,
+) -> io::Result<(Option<(u8, u8)>, u64)> {
+    let (magic, rest) = header.split_at(4);
+    if magic != MAGIC {
+        return Err(invalid_data("not a gomokugen position container"));
+    }
+    if u16::read_bytes(&rest[..2]) != VERSION {
+        return Err(invalid_data("unsupported position container version"));
+    }
+    let shape = match (rest[2], rest[3]) {
+        (0, 0) => None,
+        (side, win) if (2..=side).contains(&win) => Some((side, win)),
+        _ => return Err(invalid_data("position container has an invalid shape")),
+    };
+    if usize::from(u16::read_bytes(&rest[4..6])) != value_size {
+        return Err(invalid_data(
+            "position container holds a different value type",
+        ));
+    }
+    Ok((shape, u64::read_bytes(&rest[6..])))
+}
+
+/// Values that can be persisted as a fixed number of bytes.
+pub trait FixedBytes: Sized {
+    /// The number of bytes in the encoding.
+    const SIZE: usize;
+
+    /// Appends the encoding of `self` to `out`.
+    fn write_bytes(&self, out: &mut Vec<u8>);
+
+    /// Decodes a value from exactly [`Self::SIZE`] bytes.
+    fn read_bytes(bytes: &[u8]) -> Self;
+}
+
+impl FixedBytes for () {
+    const SIZE: usize = 0;
+
+    fn write_bytes(&self, _: &mut Vec<u8>) {}
+
+    fn read_bytes(_: &[u8]) -> Self {}
+}
+
+macro_rules! impl_fixed_bytes {
+    ($($t:ty),*) => {$(
+        impl FixedBytes for $t {
+            const SIZE: usize = std::mem::size_of::<$t>();
+
+            fn write_bytes(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_be_bytes());
+            }
+
+            fn read_bytes(bytes: &[u8]) -> Self {
+                let mut buf = [0; std::mem::size_of::<$t>()];
+                buf.copy_from_slice(bytes);
+                Self::from_be_bytes(buf)
+            }
+        }
+    )*};
+}
+
+impl_fixed_bytes!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl<A: FixedBytes, B: FixedBytes> FixedBytes for (A, B) {
+    const SIZE: usize = A::SIZE + B::SIZE;
+
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        self.0.write_bytes(out);
+        self.1.write_bytes(out);
+    }
+
+    fn read_bytes(bytes: &[u8]) -> Self {
+        (
+            A::read_bytes(&bytes[..A::SIZE]),
+            B::read_bytes(&bytes[A::SIZE..]),
+        )
+    }
+}
+
+impl<A: FixedBytes, B: FixedBytes, C: FixedBytes> FixedBytes for (A, B, C) {
+    const SIZE: usize = A::SIZE + B::SIZE + C::SIZE;
+
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        self.0.write_bytes(out);
+        self.1.write_bytes(out);
+        self.2.write_bytes(out);
+    }
+
+    fn read_bytes(bytes: &[u8]) -> Self {
+        let (a, rest) = bytes.split_at(A::SIZE);
+        let (b, c) = rest.split_at(B::SIZE);
+        (A::read_bytes(a), B::read_bytes(b), C::read_bytes(c))
+    }
+}
+
+/// A map from positions, up to symmetry, to values of type `V`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PositionMap<V> {
+    map: HashMap<u64, V>,
+    shape: Option<(u8, u8)>,
+}
+
+impl<V> Default for PositionMap<V> {
+    fn default() -> Self {
+        Self {
+            map: HashMap::new(),
+            shape: None,
+        }
+    }
+}
+
+impl<V> PositionMap<V> {
+    /// Creates an empty map.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The canonical key under which `board` is stored.
+    #[must_use]
+    pub fn key_of<const SIDE_LENGTH: usize>(board: &Board<SIDE_LENGTH>) -> u64 {
+        board.canonical_key()
+    }
+
+    /// The side length and win length of the boards in the map, or `None`
+    /// if no board has been put in it.
+    #[must_use]
+    pub fn shape(&self) -> Option<(usize, usize)> {
+        self.shape
+            .map(|(side, win)| (usize::from(side), usize::from(win)))
+    }
+
+    /// Whether `board` has the shape of the boards in the map.
+    fn holds<const SIDE_LENGTH: usize>(&self, board: &Board<SIDE_LENGTH>) -> bool {
+        self.shape.is_none() || self.shape == Some(shape_of(board))
+    }
+
+    /// Records the shape of `board` as that of the boards in the map.
+    fn record_shape<const SIDE_LENGTH: usize>(&mut self, board: &Board<SIDE_LENGTH>) {
+        assert!(
+            self.holds(board),
+            "position map holds boards of a different size or win length"
+        );
+        self.shape = Some(shape_of(board));
+    }
+
+    /// Associates `value` with `board` and all its symmetric images,
+    /// returning the previous value, if any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map holds boards of a different size or win length.
+    pub fn insert<const SIDE_LENGTH: usize>(
+        &mut self,
+        board: &Board<SIDE_LENGTH>,
+        value: V,
+    ) -> Option<V> {
+        self.record_shape(board);
+        self.map.insert(board.canonical_key(), value)
+    }
+
+    /// Looks up the value stored for `board` or any of its symmetric images.
+    /// Nothing is stored for boards of a different size or win length to
+    /// those in the map.
+    #[must_use]
+    pub fn get<const SIDE_LENGTH: usize>(&self, board: &Board<SIDE_LENGTH>) -> Option<&V> {
+        if !self.holds(board) {
+            return None;
+        }
+        self.map.get(&board.canonical_key())
+    }
+
+    /// Looks up the value stored for `board` or any of its symmetric images,
+    /// mutably, as for [`PositionMap::get`].
+    pub fn get_mut<const SIDE_LENGTH: usize>(
+        &mut self,
+        board: &Board<SIDE_LENGTH>,
+    ) -> Option<&mut V> {
+        if !self.holds(board) {
+            return None;
+        }
+        self.map.get_mut(&board.canonical_key())
+    }
+
+    /// Whether a value is stored for `board` or any of its symmetric images,
+    /// as for [`PositionMap::get`].
+    #[must_use]
+    pub fn contains<const SIDE_LENGTH: usize>(&self, board: &Board<SIDE_LENGTH>) -> bool {
+        self.get(board).is_some()
+    }
+
+    /// Gets the map entry for `board`, for in-place updates.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map holds boards of a different size or win length.
+    pub fn entry<const SIDE_LENGTH: usize>(
+        &mut self,
+        board: &Board<SIDE_LENGTH>,
+    ) -> hash_map::Entry<'_, u64, V> {
+        self.record_shape(board);
+        self.map.entry(board.canonical_key())
+    }
+
+    /// Associates `value` with a canonical key directly.
+    pub fn insert_key(&mut self, key: u64, value: V) -> Option<V> {
+        self.map.insert(key, value)
+    }
+
+    /// Looks up the value stored under a canonical key.
+    #[must_use]
+    pub fn get_key(&self, key: u64) -> Option<&V> {
+        self.map.get(&key)
+    }
+
+    /// Moves every entry of `other` into `self`, using `combine` to merge the
+    /// values of positions present in both.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the maps hold boards of different sizes or win lengths.
+    pub fn merge(&mut self, other: Self, mut combine: impl FnMut(&mut V, V)) {
+        if let Some(shape) = other.shape {
+            assert!(
+                self.shape.is_none() || self.shape == Some(shape),
+                "position map holds boards of a different size or win length"
+            );
+            self.shape = Some(shape);
+        }
+        for (key, value) in other.map {
+            match self.map.entry(key) {
+                hash_map::Entry::Occupied(mut e) => combine(e.get_mut(), value),
+                hash_map::Entry::Vacant(e) => {
+                    e.insert(value);
+                }
+            }
+        }
+    }
+
+    /// The number of distinct positions in the map.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Whether the map is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns an iterator over the canonical keys and values, in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &V)> {
+        self.map.iter().map(|(&k, v)| (k, v))
+    }
+
+    /// Converts every value with `convert`, keeping the keys and the shape of
+    /// the boards, or returns the first error.
+    pub(crate) fn try_map<W, E>(
+        self,
+        mut convert: impl FnMut(V) -> Result<W, E>,
+    ) -> Result<PositionMap<W>, E> {
+        let map = self
+            .map
+            .into_iter()
+            .map(|(key, value)| Ok((key, convert(value)?)))
+            .collect::<Result<_, E>>()?;
+        Ok(PositionMap {
+            map,
+            shape: self.shape,
+        })
+    }
+}
+
+impl<V: FixedBytes> PositionMap<V> {
+    /// Writes the map in the binary format described in the [module docs](self).
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from the underlying writer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `V::SIZE` does not fit in a `u16`.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        let mut entries = self.map.iter().collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|&(&k, _)| k);
+        let mut buf = Vec::with_capacity(HEADER_LEN + entries.len() * (8 + V::SIZE));
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&VERSION.to_be_bytes());
+        let shape: [u8; 2] = self.shape.unwrap_or_default().into();
+        buf.extend_from_slice(&shape);
+        buf.extend_from_slice(&u16::try_from(V::SIZE).unwrap().to_be_bytes());
+        buf.extend_from_slice(&(entries.len() as u64).to_be_bytes());
+        for (key, value) in entries {
+            buf.extend_from_slice(&key.to_be_bytes());
+            value.write_bytes(&mut buf);
+        }
+        writer.write_all(&buf)
+    }
+
+    /// Reads a map in the binary format described in the [module docs](self).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, or if the data is not a position
+    /// container holding values of type `V`.
+    pub fn read_from(mut reader: impl Read) -> io::Result<Self> {
+        let mut header = [0; HEADER_LEN];
+        reader.read_exact(&mut header)?;
+        let (shape, count) = read_header(&header, V::SIZE)?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        let record_size = 8 + V::SIZE;
+        if data.len() % record_size != 0 || (data.len() / record_size) as u64 != count {
+            return Err(invalid_data("position container has the wrong length"));
+        }
+        let map = data
+            .chunks_exact(record_size)
+            .map(|record| {
+                let (key, value) = record.split_at(8);
+                (u64::read_bytes(key), V::read_bytes(value))
+            })
+            .collect();
+        Ok(Self { map, shape })
+    }
+
+    /// Saves the map to the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created or written.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Loads a map from the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a valid container.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+}
+
+pub(crate) fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// A set of positions, up to symmetry.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PositionSet {
+    map: PositionMap<()>,
+}
+
+impl PositionSet {
+    /// Creates an empty set.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `board` to the set, returning `true` if neither it nor any of its
+    /// symmetric images was already present.
+    pub fn insert<const SIDE_LENGTH: usize>(&mut self, board: &Board<SIDE_LENGTH>) -> bool {
+        self.map.insert(board, ()).is_none()
+    }
+
+    /// Whether `board` or any of its symmetric images is in the set.
+    #[must_use]
+    pub fn contains<const SIDE_LENGTH: usize>(&self, board: &Board<SIDE_LENGTH>) -> bool {
+        self.map.contains(board)
+    }
+
+    /// Adds every position in `other` to the set.
+    pub fn merge(&mut self, other: Self) {
+        self.map.merge(other.map, |(), ()| {});
+    }
+
+    /// The number of distinct positions in the set.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Whether the set is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns an iterator over the canonical keys in the set, in arbitrary order.
+    pub fn keys(&self) -> impl Iterator<Item = u64> + '_ {
+        self.map.iter().map(|(k, ())| k)
+    }
+
+    /// Writes the set in the binary format described in the [module docs](self).
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from the underlying writer.
+    pub fn write_to(&self, writer: impl Write) -> io::Result<()> {
+        self.map.write_to(writer)
+    }
+
+    /// Reads a set in the binary format described in the [module docs](self).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails or the data is not a position set.
+    pub fn read_from(reader: impl Read) -> io::Result<Self> {
+        PositionMap::read_from(reader).map(|map| Self { map })
+    }
+
+    /// Saves the set to the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created or written.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.map.save(path)
+    }
+
+    /// Loads a set from the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a valid position set.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        PositionMap::load(path).map(|map| Self { map })
+    }
+}
+
+/// An approximate set of positions, up to symmetry, for deduplicating more
+/// positions than a [`PositionSet`] could hold in memory.
+///
+/// This is a Bloom filter over canonical keys: it never forgets a position
+/// it has seen, but with a small, configurable probability it wrongly
+/// reports an unseen position as seen.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PositionFilter {
+    bits: Vec<u64>,
+    hashes: u32,
+    len: usize,
+}
+
+impl PositionFilter {
+    /// Creates an empty filter sized to hold `capacity` positions while
+    /// wrongly reporting unseen positions as seen with probability at most
+    /// `false_positive_rate`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `false_positive_rate` is not strictly between zero and one.
+    #[must_use]
+    pub fn new(capacity: usize, false_positive_rate: f64) -> Self {
+        #![allow(
+            clippy::cast_precision_loss,
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss
+        )]
+        assert!(
+            false_positive_rate > 0.0 && false_positive_rate < 1.0,
+            "false positive rate must be between zero and one"
+        );
+        let ln2 = std::f64::consts::LN_2;
+        let capacity = capacity.max(1) as f64;
+        let bits = (-capacity * false_positive_rate.ln() / (ln2 * ln2)).ceil();
+        let hashes = (bits / capacity * ln2).round().max(1.0);
+        Self {
+            bits: vec![0; (bits as usize).div_ceil(64)],
+            hashes: hashes as u32,
+            len: 0,
+        }
+    }
+
+    /// The bit positions for `key`, by double hashing.
+    fn indices(words: usize, hashes: u32, key: u64) -> impl Iterator<Item = usize> {
+        let bits = words as u64 * 64;
+        let (_, step) = splitmix64(key);
+        let step = step | 1;
+        (0..u64::from(hashes)).map(move |i| {
+            // the remainder is below `bits`, which came from a usize.
+            #[allow(clippy::cast_possible_truncation)]
+            let index = (key.wrapping_add(i.wrapping_mul(step)) % bits) as usize;
+            index
+        })
+    }
+
+    /// Adds a canonical key to the filter, returning `true` if it was
+    /// definitely not already present.
+    pub fn insert_key(&mut self, key: u64) -> bool {
+        let mut new = false;
+        for index in Self::indices(self.bits.len(), self.hashes, key) {
+            let (word, bit) = (index / 64, 1 << (index % 64));
+            new |= self.bits[word] & bit == 0;
+            self.bits[word] |= bit;
+        }
+        self.len += usize::from(new);
+        new
+    }
+
+    /// Whether a canonical key may have been added to the filter.
+    #[must_use]
+    pub fn contains_key(&self, key: u64) -> bool {
+        Self::indices(self.bits.len(), self.hashes, key)
+            .all(|index| self.bits[index / 64] & (1 << (index % 64)) != 0)
+    }
+
+    /// Adds `board` to the filter, returning `true` if neither it nor any of
+    /// its symmetric images was definitely already present.
+    pub fn insert<const SIDE_LENGTH: usize>(&mut self, board: &Board<SIDE_LENGTH>) -> bool {
+        self.insert_key(board.canonical_key())
+    }
+
+    /// Whether `board` or any of its symmetric images may be in the filter.
+    #[must_use]
+    pub fn contains<const SIDE_LENGTH: usize>(&self, board: &Board<SIDE_LENGTH>) -> bool {
+        self.contains_key(board.canonical_key())
+    }
+
+    /// The number of insertions that reported a new position. False
+    /// positives make this an undercount of the distinct positions inserted.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether nothing has been inserted.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The memory used by the filter's bits, in bytes.
+    #[must_use]
+    pub const fn size_in_bytes(&self) -> usize {
+        self.bits.len() * 8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PositionFilter, PositionMap, PositionSet};
+    use crate::board::{Board, Move};
+
+    fn board(moves: &[&str]) -> Board<9> {
+        let moves = moves
+            .iter()
+            .map(|mv| mv.parse::<Move<9>>().unwrap())
+            .collect::<Vec<_>>();
+        Board::new().apply_moves(&moves).unwrap()
+    }
+
+    #[test]
+    fn symmetric_positions_collide() {
+        let mut set = PositionSet::new();
+        assert!(set.insert(&board(&["a1", "b2"])));
+        assert!(!set.insert(&board(&["i9", "h8"])));
+        assert!(!set.insert(&board(&["a9", "b8"])));
+        assert!(set.insert(&board(&["a1", "b3"])));
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&board(&["a1", "c2"])));
+        assert!(!set.contains(&board(&["a1", "c3"])));
+    }
+
+    #[test]
+    fn merge_combines_values() {
+        let mut a = PositionMap::new();
+        a.insert(&board(&["e5"]), 1u32);
+        let mut b = PositionMap::new();
+        b.insert(&board(&["e5"]), 2u32);
+        b.insert(&board(&["a1"]), 5u32);
+        a.merge(b, |x, y| *x += y);
+        assert_eq!(a.get(&board(&["e5"])), Some(&3));
+        assert_eq!(a.get(&board(&["i1"])), Some(&5));
+        assert_eq!(a.len(), 2);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let mut map = PositionMap::new();
+        map.insert(&board(&["e5", "e6"]), (3u32, 1u32, 0u32));
+        map.insert(&board(&["a1"]), (1, 0, 1));
+        let mut bytes = Vec::new();
+        map.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 18 + 2 * 20);
+        assert_eq!(map.shape(), Some((9, 5)));
+        let read = PositionMap::<(u32, u32, u32)>::read_from(bytes.as_slice()).unwrap();
+        assert_eq!(read, map);
+        assert!(PositionMap::<u64>::read_from(bytes.as_slice()).is_err());
+        assert!(PositionSet::read_from(bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn maps_hold_one_shape_of_board() {
+        let mut map = PositionMap::new();
+        assert_eq!(map.shape(), None);
+        map.insert(&board(&["e5"]), 1u8);
+        let other_size = Board::<15>::new();
+        let other_rule = Board::<9>::new().with_win_length(4);
+        assert_eq!(map.get(&other_size), None);
+        assert_eq!(map.get(&other_rule), None);
+
+        let mut bytes = Vec::new();
+        map.write_to(&mut bytes).unwrap();
+        let read = PositionMap::<u8>::read_from(bytes.as_slice()).unwrap();
+        assert_eq!(read.shape(), Some((9, 5)));
+        assert_eq!(read.get(&board(&["e5"])), Some(&1));
+        assert!(!read.contains(&other_rule.with_move("e5".parse().unwrap())));
+        // the win length may not exceed the side length.
+        bytes[7] = 10;
+        assert!(PositionMap::<u8>::read_from(bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    #[should_panic = "different size or win length"]
+    fn maps_refuse_other_shapes() {
+        let mut set = PositionSet::new();
+        set.insert(&board(&["e5"]));
+        set.insert(&Board::<9>::new().with_win_length(4));
+    }
+
+    #[test]
+    fn filter_deduplicates_approximately() {
+        let mut filter = PositionFilter::new(1000, 0.01);
+        assert!(filter.insert(&board(&["a1", "b2"])));
+        assert!(!filter.insert(&board(&["i9", "h8"])));
+        assert!(filter.contains(&board(&["a9", "b8"])));
+
+        let mut exact = PositionSet::new();
+        exact.insert(&board(&["a1", "b2"]));
+        let mut missed = 0;
+        for first in 0..81 {
+            for second in (0..81).filter(|&second| second != first) {
+                let moves = [Move::<9>::from_index(first), Move::from_index(second)];
+                let board = Board::new().apply_moves(&moves).unwrap();
+                let new = exact.insert(&board);
+                let probably_new = filter.insert(&board);
+                assert!(new || !probably_new);
+                missed += usize::from(new && !probably_new);
+            }
+        }
+        assert!(missed * 20 < exact.len(), "{missed} of {}", exact.len());
+        assert_eq!(filter.len() + missed, exact.len());
+    }
+}
@@ -0,0 +1,19 @@
+//! The types and traits most programs need, for a single glob import.
+//!
+//! ```
+//! use gomokugen::prelude::*;
+//!
+//! let mut game = Game::new(Board::<15>::new()).with_rule(Rule::Renju);
+//! game.play("h8".parse::<Move<15>>().unwrap());
+//! assert_eq!(game.board().turn(), Player::O);
+//! assert_eq!(game.result(), None);
+//! ```
+
+pub use crate::{
+    board::{Board, Coord, Move, Player},
+    engine::{Engine, Evaluator, Limits},
+    game::{Adjudication, Game, GameResult, Termination},
+    renju::Forbidden,
+    rules::Rule,
+    symmetry::Symmetry,
+};
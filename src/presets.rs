@@ -0,0 +1,85 @@
+//! Ready-made boards for classic m,n,k-games.
+//!
+//! Each alias names the board type for a game, and the function of the same
+//! name builds its starting position with the right win length, so that
+//! simple games need no generic parameters or rule setup:
+//!
+//! ```
+//! use gomokugen::presets::{self, TicTacToe};
+//!
+//! let board: TicTacToe = presets::tic_tac_toe();
+//! assert_eq!(board.win_length(), 3);
+//! ```
+//!
+//! In all of these games a line longer than the win length also wins.
+
+use crate::board::Board;
+
+/// Noughts and crosses: three in a row on a 3x3 board.
+pub type TicTacToe = Board<3>;
+
+/// Gomoku on the standard 15x15 board: five in a row.
+pub type Gomoku15 = Board<15>;
+
+/// Freestyle gomoku on a 19x19 Go board: five or more in a row.
+pub type Freestyle19 = Board<19>;
+
+/// Connect6 on a 19x19 board: six in a row.
+pub type Connect6_19 = Board<19>;
+
+/// The starting position of noughts and crosses.
+#[must_use]
+pub fn tic_tac_toe() -> TicTacToe {
+    Board::new().with_win_length(3)
+}
+
+/// The starting position of gomoku on a 15x15 board.
+#[must_use]
+pub fn gomoku15() -> Gomoku15 {
+    Board::new()
+}
+
+/// The starting position of freestyle gomoku on a 19x19 board.
+#[must_use]
+pub fn freestyle19() -> Freestyle19 {
+    Board::new()
+}
+
+/// The starting position of Connect6 on a 19x19 board.
+///
+/// In Connect6, each turn after the first places two stones. Boards only
+/// model one stone per move, so after the first stone of a turn, hand the
+/// move back with [`Board::set_turn`] before playing the second.
+#[must_use]
+pub fn connect6_19() -> Connect6_19 {
+    Board::new().with_win_length(6)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        board::Player,
+        solver::exhaustive::{Solver, Value},
+    };
+
+    #[test]
+    fn presets_have_the_right_rules() {
+        assert_eq!(super::gomoku15().win_length(), 5);
+        assert_eq!(super::freestyle19().win_length(), 5);
+
+        // a Connect6 turn: X opens with one stone, then O plays two.
+        let mut board = super::connect6_19();
+        assert_eq!(board.win_length(), 6);
+        board.make_move("j10".parse().unwrap());
+        board.make_move("k10".parse().unwrap());
+        board.set_turn(Player::O);
+        board.make_move("k11".parse().unwrap());
+        assert_eq!(board.turn(), Player::X);
+        assert_eq!(board.stones(Player::O).count(), 2);
+    }
+
+    #[test]
+    fn tic_tac_toe_is_a_draw() {
+        assert_eq!(Solver::new().solve(&super::tic_tac_toe()), Value::Draw);
+    }
+}
@@ -0,0 +1,381 @@
+//! Serving an engine over the Gomocup (piskvork) protocol.
+//!
+//! This is the other side of [`GomocupEngine`](crate::engine::GomocupEngine):
+//! the manager sends commands on standard input, and the engine answers on
+//! standard output. Coordinates are `x,y` with `x` the column and `y` the
+//! row, both counted from zero.
+
+use std::{
+    io::{self, BufRead, Write},
+    time::Duration,
+};
+
+use crate::{
+    board::{Board, Player},
+    engine::{gomocup::parse_move, Engine, Limits},
+};
+
+/// Answers Gomocup commands on behalf of an engine.
+///
+/// With `ponder` set, the engine thinks on the opponent's time after each of
+/// its moves, and is told the opponent's reply with [`Engine::ponder_hit`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GomocupServer {
+    /// The reply to `ABOUT`.
+    pub about: String,
+    /// Whether to ponder between moves.
+    pub ponder: bool,
+    /// The limits to search with, before `INFO` adds the clock.
+    pub limits: Limits,
+}
+
+impl Default for GomocupServer {
+    fn default() -> Self {
+        Self {
+            about: format!(
+                "name=\"gomokugen\", version=\"{}\"",
+                env!("CARGO_PKG_VERSION")
+            ),
+            ponder: false,
+            limits: Limits::default(),
+        }
+    }
+}
+
+/// The state of one session.
+struct Session<'a, const SIDE_LENGTH: usize, E: Engine<SIDE_LENGTH>, W: Write> {
+    server: &'a GomocupServer,
+    engine: &'a mut E,
+    output: W,
+    board: Board<SIDE_LENGTH>,
+    limits: Limits,
+    pondering: bool,
+}
+
+impl<const SIDE_LENGTH: usize, E: Engine<SIDE_LENGTH>, W: Write> Session<'_, SIDE_LENGTH, E, W> {
+    fn reply(&mut self, line: &str) -> io::Result<()> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(reply = line, "sent");
+        writeln!(self.output, "{line}")?;
+        self.output.flush()
+    }
+
+    fn stop_pondering(&mut self) {
+        if self.pondering {
+            self.engine.ponder_stop();
+            self.pondering = false;
+        }
+    }
+
+    /// Chooses and plays a move in the current position, and replies with it.
+    fn think(&mut self) -> io::Result<()> {
+        #[cfg(feature = "tracing")]
+        let start = std::time::Instant::now();
+        let mv = self.engine.best_move(&self.board, &self.limits);
+        #[cfg(feature = "tracing")]
+        tracing::info!(%mv, time_ms = start.elapsed().as_millis(), "move chosen");
+        if !self.board.is_legal(mv) {
+            return self.reply("ERROR engine has no move");
+        }
+        self.board.make_move(mv);
+        let coord = mv.coord();
+        self.reply(&format!("{},{}", coord.col, coord.row))?;
+        if self.server.ponder && self.board.outcome().is_none() {
+            self.engine.ponder_start(&self.board);
+            self.pondering = true;
+        }
+        Ok(())
+    }
+
+    /// Reads the stones of a `BOARD` command up to `DONE`, and sets up the
+    /// position they describe with the engine to move.
+    ///
+    /// The stones are placed in the order given, rather than by alternating
+    /// colours. The engine plays X if it has as many stones as the opponent,
+    /// and O if it has one fewer; other counts are refused.
+    fn read_board(
+        &mut self,
+        lines: &mut impl Iterator<Item = io::Result<String>>,
+    ) -> io::Result<bool> {
+        let mut stones = Vec::new();
+        let mut valid = true;
+        for line in lines {
+            let line = line?;
+            let line = line.trim();
+            if line.eq_ignore_ascii_case("DONE") {
+                break;
+            }
+            let Some((coord, who)) = line.rsplit_once(',') else {
+                valid = false;
+                continue;
+            };
+            match (parse_move::<SIDE_LENGTH>(coord), who.trim()) {
+                (Some(mv), "1") => stones.push((mv, true)),
+                (Some(mv), "2") => stones.push((mv, false)),
+                _ => valid = false,
+            }
+        }
+        let ours = stones.iter().filter(|&&(_, ours)| ours).count();
+        let theirs = stones.len() - ours;
+        let us = if ours == theirs {
+            Player::X
+        } else if ours + 1 == theirs {
+            Player::O
+        } else {
+            return Ok(false);
+        };
+        let mut board = Board::new();
+        for (mv, ours) in stones {
+            if board.player_at(mv.coord()) != Player::None {
+                return Ok(false);
+            }
+            board.set_turn(if ours { us } else { -us });
+            board.place(mv);
+        }
+        if !valid {
+            return Ok(false);
+        }
+        // the counts make this agree with the ply, so it is no setup position.
+        board.set_turn(us);
+        self.board = board;
+        Ok(true)
+    }
+}
+
+impl GomocupServer {
+    /// Serves `engine`, reading commands from `input` and writing replies to
+    /// `output`, until `END` or the end of the input.
+    ///
+    /// Only square boards of `SIDE_LENGTH` are supported; other sizes are
+    /// refused with `ERROR`. Unrecognised commands get `UNKNOWN`.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from reading the input or writing the output.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "gomocup", skip_all, fields(size = SIDE_LENGTH))
+    )]
+    pub fn run<const SIDE_LENGTH: usize>(
+        &self,
+        engine: &mut impl Engine<SIDE_LENGTH>,
+        input: impl BufRead,
+        output: impl Write,
+    ) -> io::Result<()> {
+        let mut session = Session {
+            server: self,
+            engine,
+            output,
+            board: Board::new(),
+            limits: self.limits.clone(),
+            pondering: false,
+        };
+        let mut lines = input.lines();
+        while let Some(line) = lines.next() {
+            let line = line?;
+            let line = line.trim();
+            #[cfg(feature = "tracing")]
+            tracing::debug!(command = line, "received");
+            let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+            let args = args.trim();
+            match command.to_ascii_uppercase().as_str() {
+                "" => {}
+                "START" => {
+                    session.stop_pondering();
+                    session.board = Board::new();
+                    if args.parse() == Ok(SIDE_LENGTH) {
+                        session.reply("OK")?;
+                    } else {
+                        session.reply(&format!("ERROR only size {SIDE_LENGTH} is supported"))?;
+                    }
+                }
+                "RECTSTART" => {
+                    session.stop_pondering();
+                    session.board = Board::new();
+                    let size = args
+                        .split_once(',')
+                        .map(|(w, h)| (w.trim().parse(), h.trim().parse()));
+                    if size == Some((Ok(SIDE_LENGTH), Ok(SIDE_LENGTH))) {
+                        session.reply("OK")?;
+                    } else {
+                        session.reply(&format!("ERROR only size {SIDE_LENGTH} is supported"))?;
+                    }
+                }
+                "RESTART" => {
+                    session.stop_pondering();
+                    session.board = Board::new();
+                    session.reply("OK")?;
+                }
+                "BEGIN" => {
+                    session.stop_pondering();
+                    session.think()?;
+                }
+                "TURN" => match parse_move::<SIDE_LENGTH>(args) {
+                    Some(mv) if session.board.is_legal(mv) => {
+                        if session.pondering {
+                            session.engine.ponder_hit(mv);
+                            session.pondering = false;
+                        }
+                        session.board.make_move(mv);
+                        session.think()?;
+                    }
+                    _ => {
+                        session.stop_pondering();
+                        session.reply("ERROR invalid move")?;
+                    }
+                },
+                "BOARD" => {
+                    session.stop_pondering();
+                    if session.read_board(&mut lines)? {
+                        session.think()?;
+                    } else {
+                        session.reply("ERROR invalid board")?;
+                    }
+                }
+                "INFO" => {
+                    let (key, value) = args.split_once(' ').unwrap_or((args, ""));
+                    let millis = value.trim().parse::<u64>().ok().map(Duration::from_millis);
+                    match key {
+                        "timeout_turn" => {
+                            // zero means "as fast as possible", not "no limit".
+                            session.limits.time = millis;
+                        }
+                        "time_left" => session.limits.time_left = millis,
+                        _ => {}
+                    }
+                }
+                "ABOUT" => session.reply(&self.about)?,
+                "END" => break,
+                _ => session.reply("UNKNOWN")?,
+            }
+        }
+        session.stop_pondering();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GomocupServer;
+    use crate::{
+        board::{Board, Move},
+        engine::{Engine, HeuristicEngine, Limits, MctsEngine},
+    };
+
+    fn serve<E: Engine<15>>(server: &GomocupServer, engine: &mut E, input: &str) -> Vec<String> {
+        let mut output = Vec::new();
+        server.run(engine, input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn plays_a_game() {
+        let server = GomocupServer::default();
+        let replies = serve(
+            &server,
+            &mut HeuristicEngine::new(),
+            "START 15\nINFO timeout_turn 1000\nBEGIN\nTURN 0,0\nFOO\nABOUT\nSTART 19\nEND\n",
+        );
+        assert_eq!(replies[0], "OK");
+        // the first move is in the centre.
+        assert_eq!(replies[1], "7,7");
+        assert!(replies[2].contains(','));
+        assert_eq!(replies[3], "UNKNOWN");
+        assert!(replies[4].contains("gomokugen"));
+        assert!(replies[5].starts_with("ERROR"));
+    }
+
+    #[test]
+    fn sets_up_boards() {
+        let server = GomocupServer::default();
+        // O (the engine) must block X's open four.
+        let replies = serve(
+            &server,
+            &mut HeuristicEngine::new(),
+            "START 15\nBOARD\n5,7,2\n0,0,1\n6,7,2\n0,14,1\n7,7,2\n14,0,1\n8,7,2\nDONE\n",
+        );
+        assert_eq!(replies[0], "OK");
+        assert!(replies[1] == "4,7" || replies[1] == "9,7", "{}", replies[1]);
+        // the same stones, not in the order they were played.
+        let replies = serve(
+            &server,
+            &mut HeuristicEngine::new(),
+            "START 15\nBOARD\n0,0,1\n0,14,1\n14,0,1\n5,7,2\n8,7,2\n7,7,2\n6,7,2\nDONE\n",
+        );
+        assert!(replies[1] == "4,7" || replies[1] == "9,7", "{}", replies[1]);
+        let replies = serve(
+            &server,
+            &mut HeuristicEngine::new(),
+            "BOARD\n1,1,1\n2,2,1\nDONE\n",
+        );
+        assert!(replies[0].starts_with("ERROR"));
+    }
+
+    /// Records the pondering calls made to a heuristic engine.
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl Engine<15> for Recorder {
+        fn best_move(&mut self, board: &Board<15>, limits: &Limits) -> Move<15> {
+            self.0.push("think".to_string());
+            HeuristicEngine::new().best_move(board, limits)
+        }
+
+        fn ponder_start(&mut self, board: &Board<15>) {
+            self.0.push(format!("start {}", board.fen()));
+        }
+
+        fn ponder_hit(&mut self, mv: Move<15>) {
+            self.0.push(format!("hit {mv}"));
+        }
+
+        fn ponder_stop(&mut self) {
+            self.0.push("stop".to_string());
+        }
+    }
+
+    #[test]
+    fn ponders_between_moves() {
+        let server = GomocupServer {
+            ponder: true,
+            ..GomocupServer::default()
+        };
+        let mut engine = Recorder::default();
+        let replies = serve(&server, &mut engine, "START 15\nBEGIN\nTURN 6,6\nEND\n");
+        assert_eq!(replies.len(), 3);
+        let after_begin = Board::<15>::new().with_move("h8".parse().unwrap());
+        assert_eq!(
+            engine.0[..3],
+            [
+                "think".to_string(),
+                format!("start {}", after_begin.fen()),
+                "hit G7".to_string(),
+            ]
+        );
+        // the engine ponders again after its reply, until the game ends.
+        assert_eq!(engine.0.len(), 6);
+        assert_eq!(engine.0[3], "think");
+        assert!(engine.0[4].starts_with("start"));
+        assert_eq!(engine.0[5], "stop");
+    }
+
+    #[test]
+    fn serves_a_pondering_mcts_engine() {
+        let server = GomocupServer {
+            ponder: true,
+            limits: Limits::nodes(50),
+            ..GomocupServer::default()
+        };
+        let mut engine = MctsEngine::<15>::new(4);
+        let replies = serve(&server, &mut engine, "START 15\nBEGIN\nTURN 6,6\n");
+        assert_eq!(replies.len(), 3);
+        let moves = ["h8", "g7"].map(|mv| mv.parse::<Move<15>>().unwrap());
+        let board = Board::<15>::new().apply_moves(&moves).unwrap();
+        let mv = super::parse_move::<15>(&replies[2]).unwrap();
+        assert!(board.is_legal(mv));
+    }
+}
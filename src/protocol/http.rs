@@ -0,0 +1,250 @@
+//! Serving analysis over HTTP, with JSON requests and responses, so that web
+//! apps can use the crate as a rules and analysis backend.
+//!
+//! Every endpoint takes a `POST` with a JSON body giving the position as a
+//! FEN:
+//!
+//! - `POST /analyze` searches the position with [`SearchEngine`], optionally
+//!   to a given depth or for a given time, and answers with the best move, its
+//!   score from the perspective of the side to move, and the principal
+//!   variation:
+//!
+//!   ```json
+//!   {"fen":"...","depth":4,"time_ms":500}
+//!   {"best_move":"H8","score":12,"depth":4,"nodes":1834,"pv":["H8","I9","H9"]}
+//!   ```
+//!
+//!   `best_move` is `null` if the game is over.
+//!
+//! - `POST /legal-moves` lists the legal moves, and the outcome if the game is
+//!   over: `"x"` or `"o"` for a win, or `"draw"`.
+//!
+//!   ```json
+//!   {"fen":"..."}
+//!   {"moves":["A1","B1","C1"],"outcome":null}
+//!   ```
+//!
+//! Errors are answered with a 4xx status and a body of the form
+//! `{"error":"..."}`.
+
+use std::{
+    io::{self, Read},
+    net::ToSocketAddrs,
+    time::Duration,
+};
+
+use crate::{
+    board::{Board, Move, Player},
+    engine::{Engine, Limits, SearchEngine},
+};
+
+/// The largest request body read, in bytes.
+const MAX_BODY: u64 = 64 * 1024;
+
+/// The body of a request to `/analyze`.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize)]
+struct AnalyzeRequest {
+    fen: String,
+    #[serde(default)]
+    depth: Option<u8>,
+    #[serde(default)]
+    time_ms: Option<u64>,
+}
+
+/// The body of a response from `/analyze`.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+struct Analysis<const SIDE_LENGTH: usize> {
+    best_move: Option<Move<SIDE_LENGTH>>,
+    score: i32,
+    depth: u8,
+    nodes: u64,
+    pv: Vec<Move<SIDE_LENGTH>>,
+}
+
+/// The body of a request to `/legal-moves`.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize)]
+struct PositionRequest {
+    fen: String,
+}
+
+/// The body of a response from `/legal-moves`.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+struct LegalMoves<const SIDE_LENGTH: usize> {
+    moves: Vec<Move<SIDE_LENGTH>>,
+    outcome: Option<&'static str>,
+}
+
+/// Answers HTTP analysis requests for boards of one size, as described in
+/// the [module docs](self).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnalysisServer {
+    /// The limits to search with when a request gives neither a depth nor a
+    /// time.
+    pub limits: Limits,
+    /// The longest search a request may ask for. Longer requests are cut
+    /// short to this.
+    pub max_time: Option<Duration>,
+}
+
+impl AnalysisServer {
+    /// Serves requests on `address` until the process ends, searching with
+    /// one engine whose table is kept between requests.
+    ///
+    /// Only positions on boards of `SIDE_LENGTH` are analysed; others are
+    /// refused with status 400.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server cannot listen on `address`.
+    pub fn serve<const SIDE_LENGTH: usize>(&self, address: impl ToSocketAddrs) -> io::Result<()> {
+        let server = tiny_http::Server::http(address).map_err(io::Error::other)?;
+        let mut engine = SearchEngine::<SIDE_LENGTH>::new();
+        for mut request in server.incoming_requests() {
+            let mut body = String::new();
+            let (status, response) =
+                match request.as_reader().take(MAX_BODY).read_to_string(&mut body) {
+                    Ok(_) => {
+                        self.handle(&mut engine, request.method().as_str(), request.url(), &body)
+                    }
+                    Err(_) => (400, error("request body is not UTF-8")),
+                };
+            let response = tiny_http::Response::from_string(response)
+                .with_status_code(status)
+                .with_header(header("Content-Type", "application/json"))
+                .with_header(header("Access-Control-Allow-Origin", "*"));
+            // a client that hangs up early is no reason to stop serving.
+            let _ = request.respond(response);
+        }
+        Ok(())
+    }
+
+    /// Answers one request, returning the status code and the JSON body of
+    /// the response.
+    #[must_use]
+    pub fn handle<const SIDE_LENGTH: usize>(
+        &self,
+        engine: &mut SearchEngine<SIDE_LENGTH>,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> (u16, String) {
+        let path = path.split('?').next().unwrap_or_default();
+        if !matches!(path, "/analyze" | "/legal-moves") {
+            return (404, error("no such endpoint"));
+        }
+        if method != "POST" {
+            return (405, error("expected POST"));
+        }
+        let result = if path == "/analyze" {
+            self.analyze(engine, body)
+        } else {
+            legal_moves::<SIDE_LENGTH>(body)
+        };
+        match result {
+            Ok(json) => (200, json),
+            Err(message) => (400, error(message)),
+        }
+    }
+
+    fn analyze<const SIDE_LENGTH: usize>(
+        &self,
+        engine: &mut SearchEngine<SIDE_LENGTH>,
+        body: &str,
+    ) -> Result<String, &'static str> {
+        let request =
+            serde_json::from_str::<AnalyzeRequest>(body).map_err(|_| "expected {\"fen\": ...}")?;
+        let board = request.fen.parse::<Board<SIDE_LENGTH>>()?;
+        let mut limits = self.limits.clone();
+        if request.depth.is_some() || request.time_ms.is_some() {
+            limits.depth = request.depth;
+            limits.time = request.time_ms.map(Duration::from_millis);
+        }
+        if let Some(max) = self.max_time {
+            limits.time = Some(limits.time.map_or(max, |time| time.min(max)));
+        }
+        let analysis = if board.outcome().is_some() {
+            Analysis {
+                best_move: None,
+                score: 0,
+                depth: 0,
+                nodes: 0,
+                pv: Vec::new(),
+            }
+        } else {
+            let best_move = engine.best_move(&board, &limits);
+            let last = engine.iterations().last();
+            Analysis {
+                best_move: Some(best_move),
+                score: last.map_or(0, |info| info.score),
+                depth: last.map_or(0, |info| info.depth),
+                nodes: engine.nodes(),
+                pv: last.map_or_else(|| vec![best_move], |info| info.pv.clone()),
+            }
+        };
+        Ok(serde_json::to_string(&analysis).expect("analyses always serialize"))
+    }
+}
+
+fn legal_moves<const SIDE_LENGTH: usize>(body: &str) -> Result<String, &'static str> {
+    let request =
+        serde_json::from_str::<PositionRequest>(body).map_err(|_| "expected {\"fen\": ...}")?;
+    let board = request.fen.parse::<Board<SIDE_LENGTH>>()?;
+    let mut moves = Vec::new();
+    if board.outcome().is_none() {
+        board.generate_moves(|mv| {
+            moves.push(mv);
+            false
+        });
+    }
+    let outcome = board.outcome().map(|winner| match winner {
+        Player::X => "x",
+        Player::O => "o",
+        Player::None => "draw",
+    });
+    Ok(serde_json::to_string(&LegalMoves { moves, outcome }).expect("move lists always serialize"))
+}
+
+/// The body of an error response.
+fn error(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
+fn header(name: &str, value: &str) -> tiny_http::Header {
+    tiny_http::Header::from_bytes(name, value).expect("header names and values are ASCII")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnalysisServer;
+    use crate::engine::SearchEngine;
+
+    #[test]
+    fn answers_requests() {
+        let server = AnalysisServer::default();
+        let mut engine = SearchEngine::<5>::new();
+        let mut request = |method, path, body| server.handle(&mut engine, method, path, body);
+
+        let fen = r#"{"fen": "xxxx./ooo../...../...../..... o 7"}"#;
+        let (status, body) = request(
+            "POST",
+            "/analyze",
+            r#"{"fen": "xxxx./ooo../...../...../..... o 7", "depth": 2}"#,
+        );
+        assert_eq!(status, 200);
+        let json = serde_json::from_str::<serde_json::Value>(&body).unwrap();
+        assert_eq!(json["best_move"], "E1");
+        assert_eq!(json["pv"][0], "E1");
+
+        let (status, body) = request("POST", "/legal-moves", fen);
+        assert_eq!(status, 200);
+        let json = serde_json::from_str::<serde_json::Value>(&body).unwrap();
+        assert_eq!(json["moves"].as_array().unwrap().len(), 18);
+        assert_eq!(json["outcome"], serde_json::Value::Null);
+
+        assert_eq!(request("GET", "/analyze", fen).0, 405);
+        assert_eq!(request("POST", "/nope", fen).0, 404);
+        let (status, body) = request("POST", "/legal-moves", r#"{"fen": "x"}"#);
+        assert_eq!(status, 400);
+        assert!(body.contains("error"));
+    }
+}
@@ -0,0 +1,11 @@
+//! Adapters that let an [`Engine`](crate::engine::Engine) be driven by
+//! standard game-manager protocols, or served to other programs.
+
+pub mod gomocup;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod sync;
+
+pub use gomocup::GomocupServer;
+#[cfg(feature = "http")]
+pub use http::AnalysisServer;
@@ -0,0 +1,285 @@
+//! Keeping copies of a game in step over a network by sending only what
+//! changed.
+//!
+//! Each change to the game is a [`Message`]: a move, a take-back, or a new
+//! game from a position, numbered in sequence so that a receiver can tell a
+//! duplicate from a message it missed. One side owns the game and makes its
+//! changes through a [`SyncedGame`], which hands back the message to send;
+//! the others apply the messages they receive to their own [`SyncedGame`].
+//!
+//! Messages are written as a line of text: the sequence number, then the
+//! change.
+//!
+//! ```text
+//! 1 move H8
+//! 2 move I9
+//! 3 undo
+//! 4 new ...../...../...../...../..... x 0
+//! ```
+//!
+//! ```
+//! use gomokugen::{board::Board, game::Game, protocol::sync::SyncedGame};
+//!
+//! let mut host = SyncedGame::new(Game::new(Board::<15>::new()));
+//! let mut client = host.clone();
+//! let message = host.play("h8".parse().unwrap());
+//! assert_eq!(message.to_string(), "1 move H8");
+//! assert!(client.apply(&message.to_string().parse().unwrap()).unwrap());
+//! assert_eq!(client.game().board(), host.game().board());
+//! ```
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    board::{Board, Move},
+    game::Game,
+};
+
+/// A change to a game.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Update<const SIDE_LENGTH: usize> {
+    /// The side to move played a move.
+    Move(Move<SIDE_LENGTH>),
+    /// The last move was taken back.
+    Undo,
+    /// A new game started from a position.
+    NewGame(Board<SIDE_LENGTH>),
+}
+
+/// A numbered change to a game, as sent between copies of it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Message<const SIDE_LENGTH: usize> {
+    /// The position of the change in the sequence of changes, counting
+    /// from one.
+    pub sequence: u64,
+    /// The change.
+    pub update: Update<SIDE_LENGTH>,
+}
+
+impl<const SIDE_LENGTH: usize> Display for Message<SIDE_LENGTH> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.update {
+            Update::Move(mv) => write!(f, "{} move {mv}", self.sequence),
+            Update::Undo => write!(f, "{} undo", self.sequence),
+            Update::NewGame(board) => write!(f, "{} new {}", self.sequence, board.fen()),
+        }
+    }
+}
+
+impl<const SIDE_LENGTH: usize> FromStr for Message<SIDE_LENGTH> {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.trim().splitn(3, ' ');
+        let sequence = fields
+            .next()
+            .and_then(|n| n.parse().ok())
+            .ok_or("Invalid sequence number in message")?;
+        let update = match (fields.next(), fields.next()) {
+            (Some("move"), Some(mv)) => Update::Move(mv.parse()?),
+            (Some("undo"), None) => Update::Undo,
+            (Some("new"), Some(fen)) => Update::NewGame(fen.parse()?),
+            _ => return Err("Invalid update in message"),
+        };
+        Ok(Self { sequence, update })
+    }
+}
+
+/// The reasons a message cannot be applied.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SyncError<const SIDE_LENGTH: usize> {
+    /// Messages were missed: the one received is not the next in sequence.
+    Gap {
+        /// The sequence number expected.
+        expected: u64,
+        /// The sequence number received.
+        received: u64,
+    },
+    /// The move cannot be played in the game, which has fallen out of step.
+    IllegalMove(Move<SIDE_LENGTH>),
+    /// There is no move to take back.
+    NothingToUndo,
+}
+
+impl<const SIDE_LENGTH: usize> Display for SyncError<SIDE_LENGTH> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Gap { expected, received } => {
+                write!(f, "expected message {expected} but received {received}")
+            }
+            Self::IllegalMove(mv) => write!(f, "{mv} cannot be played"),
+            Self::NothingToUndo => f.write_str("no move to take back"),
+        }
+    }
+}
+
+impl<const SIDE_LENGTH: usize> std::error::Error for SyncError<SIDE_LENGTH> {}
+
+/// A game kept in step with copies of it elsewhere, as described in the
+/// [module docs](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyncedGame<const SIDE_LENGTH: usize> {
+    game: Game<SIDE_LENGTH>,
+    sequence: u64,
+}
+
+impl<const SIDE_LENGTH: usize> SyncedGame<SIDE_LENGTH> {
+    /// Starts keeping `game` in step, before any changes have been made.
+    #[must_use]
+    pub const fn new(game: Game<SIDE_LENGTH>) -> Self {
+        Self { game, sequence: 0 }
+    }
+
+    /// Takes up `game` after the change numbered `sequence`, as when a copy
+    /// that has fallen out of step is replaced by the owner's.
+    #[must_use]
+    pub const fn resume(game: Game<SIDE_LENGTH>, sequence: u64) -> Self {
+        Self { game, sequence }
+    }
+
+    /// The game.
+    #[must_use]
+    pub const fn game(&self) -> &Game<SIDE_LENGTH> {
+        &self.game
+    }
+
+    /// The sequence number of the last change made or applied, or zero if
+    /// there have been none.
+    #[must_use]
+    pub const fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Plays `mv` for the side to move, and returns the message to send.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the game is already over.
+    pub fn play(&mut self, mv: Move<SIDE_LENGTH>) -> Message<SIDE_LENGTH> {
+        self.game.play(mv);
+        self.next(Update::Move(mv))
+    }
+
+    /// Takes back the last move, and returns the message to send, or `None`
+    /// if no moves have been played.
+    pub fn undo(&mut self) -> Option<Message<SIDE_LENGTH>> {
+        self.game.undo()?;
+        Some(self.next(Update::Undo))
+    }
+
+    /// Starts a new game from `start`, with the same rule and adjudication
+    /// as the last, and returns the message to send.
+    pub fn new_game(&mut self, start: Board<SIDE_LENGTH>) -> Message<SIDE_LENGTH> {
+        self.reset(start);
+        self.next(Update::NewGame(start))
+    }
+
+    const fn next(&mut self, update: Update<SIDE_LENGTH>) -> Message<SIDE_LENGTH> {
+        self.sequence += 1;
+        Message {
+            sequence: self.sequence,
+            update,
+        }
+    }
+
+    fn reset(&mut self, start: Board<SIDE_LENGTH>) {
+        self.game = Game::new(start)
+            .with_rule(self.game.rule())
+            .with_adjudication(*self.game.adjudication());
+    }
+
+    /// Applies a message received from the owner of the game. Returns
+    /// `Ok(false)`, changing nothing, for a message already applied, as when
+    /// one is delivered twice.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error, changing nothing, if messages before this one were
+    /// missed or it does not fit the game. Either way the copy is out of
+    /// step, and should be replaced by the owner's, using [`Self::resume`].
+    pub fn apply(
+        &mut self,
+        message: &Message<SIDE_LENGTH>,
+    ) -> Result<bool, SyncError<SIDE_LENGTH>> {
+        if message.sequence <= self.sequence {
+            return Ok(false);
+        }
+        if message.sequence != self.sequence + 1 {
+            return Err(SyncError::Gap {
+                expected: self.sequence + 1,
+                received: message.sequence,
+            });
+        }
+        match message.update {
+            Update::Move(mv) => {
+                if self.game.result().is_some() || !self.game.board().is_legal(mv) {
+                    return Err(SyncError::IllegalMove(mv));
+                }
+                self.game.play(mv);
+            }
+            Update::Undo => {
+                self.game.undo().ok_or(SyncError::NothingToUndo)?;
+            }
+            Update::NewGame(start) => self.reset(start),
+        }
+        self.sequence = message.sequence;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Message, SyncError, SyncedGame, Update};
+    use crate::{board::Board, game::Game};
+
+    #[test]
+    fn copies_stay_in_step() {
+        let mut host = SyncedGame::new(Game::new(Board::<5>::new()));
+        let mut client = host.clone();
+        let mut messages = vec![
+            host.play("c3".parse().unwrap()),
+            host.play("b2".parse().unwrap()),
+        ];
+        messages.extend(host.undo());
+        messages.push(host.play("d4".parse().unwrap()));
+        let text = messages.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(text, ["1 move C3", "2 move B2", "3 undo", "4 move D4"]);
+
+        assert_eq!(
+            client.apply(&messages[1]),
+            Err(SyncError::Gap {
+                expected: 1,
+                received: 2
+            })
+        );
+        for line in &text {
+            assert_eq!(client.apply(&line.parse().unwrap()), Ok(true));
+        }
+        assert_eq!(client.apply(&messages[2]), Ok(false));
+        assert_eq!(client.game().moves(), host.game().moves());
+        assert_eq!(client.game().board(), host.game().board());
+
+        let start = "x..../...../...../...../..... o 1".parse().unwrap();
+        let message = host.new_game(start);
+        assert_eq!(message.to_string().parse::<Message<5>>(), Ok(message));
+        assert_eq!(client.apply(&message), Ok(true));
+        assert_eq!(client.game().start(), &start);
+        assert_eq!(
+            client.apply(&Message {
+                sequence: 6,
+                update: Update::Undo
+            }),
+            Err(SyncError::NothingToUndo)
+        );
+        assert_eq!(
+            client.apply(&Message {
+                sequence: 6,
+                update: Update::Move("a1".parse().unwrap())
+            }),
+            Err(SyncError::IllegalMove("a1".parse().unwrap()))
+        );
+        assert_eq!(client.sequence(), 5);
+    }
+}
@@ -0,0 +1,396 @@
+//! Mining games for tactical puzzles.
+//!
+//! A puzzle is a position where the side to move has a forced win in a known
+//! number of moves. Candidate positions are taken from the ends of games and
+//! checked with [proof-number search](crate::solver::pn), which also finds the
+//! number of moves in the shortest forced win.
+//!
+//! Puzzles are exchanged as [`PuzzleRecord`]s, which with the `json` feature
+//! can be written and read as a JSON array or as JSON lines, one puzzle per line:
+//!
+//! ```json
+//! {"fen":"...","side_to_move":"x","win_length":5,"win_in":2,"solution":["I8","J8","E8"],"themes":["win-in-2","vcf"]}
+//! ```
+
+use crate::{
+    board::{Board, Move, Player},
+    solver::pn::{PnOutcome, PnSearch, ProofTree},
+};
+
+/// A position with a forced win for the side to move.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Puzzle<const SIDE_LENGTH: usize> {
+    /// The starting position.
+    pub board: Board<SIDE_LENGTH>,
+    /// The number of moves the side to move needs to win, counting the winning move.
+    pub win_in: usize,
+    /// A winning strategy, with a continuation for every defence.
+    pub proof: ProofTree<SIDE_LENGTH>,
+}
+
+impl<const SIDE_LENGTH: usize> Puzzle<SIDE_LENGTH> {
+    /// The main line of the solution, following the first defence at each step.
+    #[must_use]
+    pub fn solution(&self) -> Vec<Move<SIDE_LENGTH>> {
+        let mut line = Vec::new();
+        let mut tree = &self.proof;
+        line.push(tree.attack);
+        while let Some((defence, next)) = tree.defences.first() {
+            line.push(*defence);
+            line.push(next.attack);
+            tree = next;
+        }
+        line
+    }
+
+    /// Descriptive tags for the puzzle: `win-in-N`, and `vcf` if every attack
+    /// before the winning move threatens an immediate win.
+    #[must_use]
+    pub fn themes(&self) -> Vec<String> {
+        let mut themes = vec![format!("win-in-{}", self.win_in)];
+        let attacker = self.board.turn();
+        let all_fours = self.proof.lines().iter().all(|line| {
+            let mut board = self.board;
+            line.iter().enumerate().all(|(i, &mv)| {
+                board.make_move(mv);
+                let is_attack = i % 2 == 0 && i + 1 < line.len();
+                !is_attack
+                    || board
+                        .stones(Player::None)
+                        .any(|mv| board.is_winning_move(mv, attacker))
+            })
+        });
+        if self.win_in > 1 && all_fours {
+            themes.push("vcf".into());
+        }
+        themes
+    }
+
+    /// The puzzle in its portable form.
+    #[must_use]
+    pub fn to_record(&self) -> PuzzleRecord {
+        PuzzleRecord {
+            fen: self.board.fen(),
+            side_to_move: if self.board.turn() == Player::X {
+                'x'
+            } else {
+                'o'
+            },
+            win_length: self.board.win_length(),
+            win_in: self.win_in,
+            solution: self.solution().iter().map(ToString::to_string).collect(),
+            themes: self.themes(),
+        }
+    }
+}
+
+/// A puzzle in the form used by the JSON formats described in the [module docs](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PuzzleRecord {
+    /// The starting position.
+    pub fen: String,
+    /// The side to move and win, `'x'` or `'o'`.
+    pub side_to_move: char,
+    /// The number of stones in a row needed to win.
+    #[cfg_attr(feature = "serde", serde(default = "default_win_length"))]
+    pub win_length: usize,
+    /// The number of moves the side to move needs to win.
+    pub win_in: usize,
+    /// The main line of the solution, in move notation.
+    pub solution: Vec<String>,
+    /// Descriptive tags, such as `win-in-2`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub themes: Vec<String>,
+}
+
+#[cfg(feature = "serde")]
+const fn default_win_length() -> usize {
+    5
+}
+
+impl PuzzleRecord {
+    /// The starting position of the puzzle.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the FEN is invalid, disagrees with `side_to_move`,
+    /// or `win_length` doesn't fit the board.
+    pub fn board<const SIDE_LENGTH: usize>(&self) -> Result<Board<SIDE_LENGTH>, &'static str> {
+        let board = self.fen.parse::<Board<SIDE_LENGTH>>()?;
+        let expected = match self.side_to_move {
+            'x' => Player::X,
+            'o' => Player::O,
+            _ => return Err("Invalid side to move in puzzle"),
+        };
+        if board.turn() != expected {
+            return Err("Side to move disagrees with the FEN");
+        }
+        if !(2..=SIDE_LENGTH).contains(&self.win_length) {
+            return Err("Invalid win length in puzzle");
+        }
+        Ok(board.with_win_length(self.win_length))
+    }
+
+    /// The main line of the solution.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any move is invalid.
+    pub fn solution<const SIDE_LENGTH: usize>(
+        &self,
+    ) -> Result<Vec<Move<SIDE_LENGTH>>, &'static str> {
+        self.solution.iter().map(|mv| mv.parse()).collect()
+    }
+}
+
+/// Writes `puzzles` as JSON lines, one puzzle per line.
+///
+/// # Errors
+///
+/// Returns any error from the underlying writer.
+#[cfg(feature = "json")]
+pub fn write_jsonl(
+    mut writer: impl std::io::Write,
+    puzzles: &[PuzzleRecord],
+) -> std::io::Result<()> {
+    for puzzle in puzzles {
+        serde_json::to_writer(&mut writer, puzzle)?;
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Reads puzzles written as JSON lines. Blank lines are skipped.
+///
+/// # Errors
+///
+/// Returns an error if reading fails or a line is not a valid puzzle.
+#[cfg(feature = "json")]
+pub fn read_jsonl(reader: impl std::io::BufRead) -> std::io::Result<Vec<PuzzleRecord>> {
+    let mut puzzles = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        puzzles.push(serde_json::from_str(&line)?);
+    }
+    Ok(puzzles)
+}
+
+/// Writes `puzzles` as a single JSON array.
+///
+/// # Errors
+///
+/// Returns any error from the underlying writer.
+#[cfg(feature = "json")]
+pub fn write_json(writer: impl std::io::Write, puzzles: &[PuzzleRecord]) -> std::io::Result<()> {
+    serde_json::to_writer_pretty(writer, puzzles)?;
+    Ok(())
+}
+
+/// Reads puzzles written as a single JSON array.
+///
+/// # Errors
+///
+/// Returns an error if reading fails or the input is not an array of puzzles.
+#[cfg(feature = "json")]
+pub fn read_json(reader: impl std::io::Read) -> std::io::Result<Vec<PuzzleRecord>> {
+    Ok(serde_json::from_reader(reader)?)
+}
+
+/// Finds puzzles in games, keeping those whose difficulty is in a given range.
+#[derive(Copy, Clone, Debug)]
+pub struct PuzzleGenerator {
+    /// The fewest moves to win a puzzle may have. Puzzles with a win in 1 are
+    /// rarely interesting, so this defaults to 2.
+    pub min_win_in: usize,
+    /// The most moves to win a puzzle may have.
+    pub max_win_in: usize,
+    /// How many positions from the end of each game are examined.
+    pub tail: usize,
+    /// The search used to prove wins. Its `max_depth` is overridden. With a
+    /// neighbourhood, wins are only proved against the defences near the
+    /// stones; see [the solver's docs](crate::solver::pn).
+    pub search: PnSearch,
+}
+
+impl Default for PuzzleGenerator {
+    fn default() -> Self {
+        Self {
+            min_win_in: 2,
+            max_win_in: 4,
+            tail: 12,
+            search: PnSearch {
+                max_expansions: 20_000,
+                ..PnSearch::default()
+            },
+        }
+    }
+}
+
+impl PuzzleGenerator {
+    /// Checks whether the side to move in `board` has a shortest forced win of
+    /// between `min_win_in` and `max_win_in` moves, returning it as a puzzle if so.
+    /// If the search gives up before ruling out a shorter win, the shortest
+    /// is not known, and there is no puzzle.
+    #[must_use]
+    pub fn examine<const SIDE_LENGTH: usize>(
+        &self,
+        board: &Board<SIDE_LENGTH>,
+    ) -> Option<Puzzle<SIDE_LENGTH>> {
+        if board.outcome().is_some() {
+            return None;
+        }
+        // look for the shortest win first, so that `win_in` is exact.
+        for win_in in 1..=self.max_win_in {
+            let search = PnSearch {
+                max_depth: Some(2 * win_in - 1),
+                ..self.search
+            };
+            match search.prove(board) {
+                PnOutcome::Proven(proof) => {
+                    return (win_in >= self.min_win_in).then_some(Puzzle {
+                        board: *board,
+                        win_in,
+                        proof,
+                    });
+                }
+                // a shorter win may be hiding, so a longer one is no puzzle.
+                PnOutcome::Unknown => return None,
+                PnOutcome::Disproven => {}
+            }
+        }
+        None
+    }
+
+    /// Examines the last `tail` positions of the game played from `start` with
+    /// `moves`, returning the puzzles found in the order they occur.
+    ///
+    /// Moves after an illegal move are ignored.
+    #[must_use]
+    pub fn mine_game<const SIDE_LENGTH: usize>(
+        &self,
+        start: &Board<SIDE_LENGTH>,
+        moves: &[Move<SIDE_LENGTH>],
+    ) -> Vec<Puzzle<SIDE_LENGTH>> {
+        let mut positions = vec![*start];
+        let mut board = *start;
+        for &mv in moves {
+            if !board.is_legal(mv) {
+                break;
+            }
+            board.make_move(mv);
+            positions.push(board);
+        }
+        let skip = positions.len().saturating_sub(self.tail);
+        positions[skip..]
+            .iter()
+            .filter_map(|board| self.examine(board))
+            .collect()
+    }
+
+    /// Plays random games from `start` and mines them until `count` puzzles
+    /// have been found or `max_games` games have been played.
+    ///
+    /// `rng(lo, hi)` must return a value in `lo..hi`, as for [`Board::make_random_move`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rng` returns an out-of-range value.
+    pub fn random_puzzles<const SIDE_LENGTH: usize>(
+        &self,
+        start: &Board<SIDE_LENGTH>,
+        count: usize,
+        max_games: usize,
+        mut rng: impl FnMut(usize, usize) -> usize,
+    ) -> Vec<Puzzle<SIDE_LENGTH>> {
+        let mut puzzles = Vec::new();
+        for _ in 0..max_games {
+            if puzzles.len() >= count {
+                break;
+            }
+            let mut board = *start;
+            let mut moves = Vec::new();
+            let mut legal = Vec::new();
+            while board.outcome().is_none() {
+                legal.clear();
+                board.generate_moves(|mv| {
+                    legal.push(mv);
+                    false
+                });
+                let mv = legal[rng(0, legal.len())];
+                board.make_move(mv);
+                moves.push(mv);
+            }
+            puzzles.extend(self.mine_game(start, &moves));
+        }
+        puzzles.truncate(count);
+        puzzles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PuzzleGenerator;
+    use crate::board::{Board, Move};
+
+    #[test]
+    fn open_three_is_a_win_in_two() {
+        let moves = ["f8", "a1", "g8", "a15", "h8", "o1", "i8", "o15", "j8"]
+            .map(|mv| mv.parse::<Move<15>>().unwrap());
+        let generator = PuzzleGenerator {
+            max_win_in: 2,
+            ..PuzzleGenerator::default()
+        };
+        let puzzles = generator.mine_game(&Board::new(), &moves);
+        assert_eq!(puzzles.len(), 1);
+        let puzzle = &puzzles[0];
+        assert_eq!(puzzle.board, Board::new().apply_moves(&moves[..6]).unwrap());
+        assert_eq!(puzzle.win_in, 2);
+        assert_eq!(puzzle.solution().len(), 3);
+        assert!(puzzle.proof.verify(&puzzle.board));
+        // the open four threatens to win at once, so this is a victory by continuous fours.
+        assert_eq!(puzzle.themes(), ["win-in-2", "vcf"]);
+        let record = puzzle.to_record();
+        assert_eq!(record.board::<15>(), Ok(puzzle.board));
+        assert_eq!(record.solution::<15>(), Ok(puzzle.solution()));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_round_trip() {
+        let moves = ["f8", "a1", "g8", "a15", "h8", "o1"].map(|mv| mv.parse::<Move<15>>().unwrap());
+        let board = Board::new().apply_moves(&moves).unwrap();
+        let records = vec![PuzzleGenerator::default()
+            .examine(&board)
+            .unwrap()
+            .to_record()];
+        let mut lines = Vec::new();
+        super::write_jsonl(&mut lines, &records).unwrap();
+        assert_eq!(String::from_utf8(lines.clone()).unwrap().lines().count(), 1);
+        assert_eq!(super::read_jsonl(&lines[..]).unwrap(), records);
+        let mut array = Vec::new();
+        super::write_json(&mut array, &records).unwrap();
+        assert_eq!(super::read_json(&array[..]).unwrap(), records);
+        assert!(super::read_jsonl(&b"{\"fen\": 3}"[..]).is_err());
+    }
+
+    #[test]
+    fn random_games_yield_puzzles() {
+        let rng = crate::test_util::test_rng(0x2545_F491_4F6C_DD1D);
+        let generator = PuzzleGenerator {
+            min_win_in: 1,
+            max_win_in: 1,
+            tail: 3,
+            ..PuzzleGenerator::default()
+        };
+        let puzzles = generator.random_puzzles(&Board::<9>::new(), 2, 10, rng);
+        assert_eq!(puzzles.len(), 2);
+        for puzzle in puzzles {
+            assert_eq!(puzzle.win_in, 1);
+            assert!(puzzle.proof.verify(&puzzle.board));
+        }
+    }
+}
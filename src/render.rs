@@ -0,0 +1,182 @@
+//! Drawing per-cell values, such as policy probabilities, visit counts, or
+//! evaluations, over a board, for debugging engines and networks.
+//!
+//! A [`Heatmap`] shades each cell by its value, relative to the smallest and
+//! largest values on the board. Its [`Display`] implementation draws it for a
+//! terminal with ANSI true-colour backgrounds, and [`Heatmap::svg`] draws it
+//! as an SVG image.
+
+use std::fmt::{self, Display, Write};
+
+use crate::board::{Board, Coord, Player};
+
+/// The width of a cell in SVG output, in pixels.
+const SVG_CELL: usize = 32;
+
+/// A board with a value for every cell, indexed by [`Move::index`](crate::board::Move::index).
+#[derive(Copy, Clone, Debug)]
+pub struct Heatmap<'a, const SIDE_LENGTH: usize> {
+    board: &'a Board<SIDE_LENGTH>,
+    values: &'a [f32],
+}
+
+impl<'a, const SIDE_LENGTH: usize> Heatmap<'a, SIDE_LENGTH> {
+    /// Pairs `board` with `values`, one per cell.
+    ///
+    /// Values that are not finite, such as the NaNs often used to mark
+    /// illegal moves, are left unshaded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is not exactly one value per cell.
+    #[must_use]
+    pub fn new(board: &'a Board<SIDE_LENGTH>, values: &'a [f32]) -> Self {
+        assert_eq!(
+            values.len(),
+            SIDE_LENGTH * SIDE_LENGTH,
+            "A heatmap needs one value per cell."
+        );
+        Self { board, values }
+    }
+
+    /// The shade of each cell, from 0 for the smallest value to 1 for the
+    /// largest, or `None` for cells whose value is not finite.
+    fn intensities(&self) -> Vec<Option<f32>> {
+        let finite = self.values.iter().copied().filter(|v| v.is_finite());
+        let (lo, hi) = finite.fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), v| {
+            (lo.min(v), hi.max(v))
+        });
+        let range = hi - lo;
+        self.values
+            .iter()
+            .map(|&v| {
+                v.is_finite()
+                    .then(|| if range > 0.0 { (v - lo) / range } else { 1.0 })
+            })
+            .collect()
+    }
+
+    /// Draws the heatmap as an SVG image, with the values shaded in red, the
+    /// stones drawn on top, and each cell's value in a tooltip.
+    #[must_use]
+    pub fn svg(&self) -> String {
+        let size = SIDE_LENGTH * SVG_CELL;
+        let intensities = self.intensities();
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{size}" height="{size}" viewBox="0 0 {size} {size}">"#
+        );
+        let _ = writeln!(
+            out,
+            r##"<rect width="{size}" height="{size}" fill="#e8c17a"/>"##
+        );
+        for (index, ((Coord { row, col }, player), intensity)) in
+            self.board.cells().zip(&intensities).enumerate()
+        {
+            // rank 1 is drawn at the bottom, as in the text rendering.
+            let x = col * SVG_CELL;
+            let y = (SIDE_LENGTH - 1 - row) * SVG_CELL;
+            let _ = write!(
+                out,
+                r##"<rect x="{x}" y="{y}" width="{SVG_CELL}" height="{SVG_CELL}" fill="#d7191c" fill-opacity="{:.3}" stroke="#5a4a2a" stroke-width="0.5">"##,
+                intensity.unwrap_or(0.0)
+            );
+            let _ = writeln!(out, "<title>{}</title></rect>", self.values[index]);
+            let fill = match player {
+                Player::None => continue,
+                Player::X => "#000",
+                Player::O => "#fff",
+            };
+            let _ = writeln!(
+                out,
+                r##"<circle cx="{}" cy="{}" r="{}" fill="{fill}" stroke="#000"/>"##,
+                x + SVG_CELL / 2,
+                y + SVG_CELL / 2,
+                SVG_CELL * 2 / 5
+            );
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+}
+
+impl<const SIDE_LENGTH: usize> Display for Heatmap<'_, SIDE_LENGTH> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        #![allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            clippy::suboptimal_flops
+        )]
+        const RST: &str = "\x1b[0m";
+        let intensities = self.intensities();
+        for rank in (0..SIDE_LENGTH).rev() {
+            write!(f, "{:>2} ", rank + 1)?;
+            for file in 0..SIDE_LENGTH {
+                let index = rank * SIDE_LENGTH + file;
+                // from a dark grey for the smallest value to bright red.
+                let (r, g, b) = intensities[index].map_or((0, 0, 0), |t| {
+                    (
+                        (48.0 + t * 207.0) as u8,
+                        (48.0 - t * 24.0) as u8,
+                        (48.0 - t * 24.0) as u8,
+                    )
+                });
+                let stone = match self.board.player_at(Coord::new(rank, file)) {
+                    Player::None => ' ',
+                    Player::X => 'X',
+                    Player::O => 'O',
+                };
+                write!(f, "\x1b[48;2;{r};{g};{b}m {stone} {RST}")?;
+            }
+            writeln!(f)?;
+        }
+        write!(f, "   ")?;
+        for file in 0..SIDE_LENGTH {
+            write!(f, " {} ", (b'A' + u8::try_from(file).unwrap()) as char)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Heatmap;
+    use crate::board::Board;
+
+    #[test]
+    fn shades_every_cell() {
+        let board = Board::<3>::new().with_move("b2".parse().unwrap());
+        let values = [0.0, 0.1, 0.2, 0.3, f32::NAN, 0.5, 0.6, 0.7, 0.8];
+        let heatmap = Heatmap::new(&board, &values);
+
+        let text = heatmap.to_string();
+        assert_eq!(text.lines().count(), 4);
+        assert_eq!(text.matches("\x1b[48;2;").count(), 9);
+        // the smallest value is at a1, in the bottom left, and the largest
+        // at c3, in the top right.
+        assert!(text
+            .lines()
+            .nth(2)
+            .unwrap()
+            .starts_with(" 1 \x1b[48;2;48;48;48m"));
+        assert!(text
+            .lines()
+            .next()
+            .unwrap()
+            .ends_with("\x1b[48;2;255;24;24m   \x1b[0m"));
+        assert!(text.lines().nth(1).unwrap().contains(" X "));
+
+        let svg = heatmap.svg();
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<rect").count(), 10);
+        assert_eq!(svg.matches("<circle").count(), 1);
+        assert!(svg.contains("<title>0.8</title>"));
+    }
+
+    #[test]
+    #[should_panic = "one value per cell"]
+    fn needs_a_value_per_cell() {
+        let _ = Heatmap::new(&Board::<3>::new(), &[0.0; 8]);
+    }
+}
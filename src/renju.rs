@@ -0,0 +1,283 @@
+//! Renju's restrictions on the first player.
+//!
+//! In Renju, Black (X, who moves first) may not play a move that makes an
+//! overline of six or more stones, two fours at once, or two open threes at
+//! once, unless the move also makes exactly five, which wins. White has no
+//! restrictions, and wins with five or more.
+//!
+//! A *four* is a line where one more stone makes exactly five. An *open
+//! three* is a line where one more stone makes a *straight four*, four in a
+//! row with both ends open, provided that the stone completing it would not
+//! itself be forbidden. That proviso makes the check recursive; it is
+//! followed a few moves deep, which settles every position met in practice.
+
+use std::fmt;
+
+use crate::board::{Board, Coord, Move, Player};
+
+/// How many moves deep to follow the check that an open three's completing
+/// move is not itself forbidden.
+const MAX_DEPTH: usize = 4;
+
+/// The four directions a line can run in.
+const DIRECTIONS: [(isize, isize); 4] = [(0, 1), (1, 0), (1, 1), (1, -1)];
+
+/// Why a move is forbidden to Black.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Forbidden {
+    /// The move makes two or more open threes.
+    DoubleThree,
+    /// The move makes two or more fours.
+    DoubleFour,
+    /// The move makes six or more in a row.
+    Overline,
+}
+
+impl fmt::Display for Forbidden {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::DoubleThree => "double-three",
+            Self::DoubleFour => "double-four",
+            Self::Overline => "overline",
+        })
+    }
+}
+
+/// Whether Black may not play `mv` in `board`, and why.
+///
+/// Black's restrictions apply whoever is to move, so a GUI can show them
+/// while White is thinking. Occupied cells are not forbidden, just illegal.
+#[must_use]
+pub fn forbidden<const SIDE_LENGTH: usize>(
+    board: &Board<SIDE_LENGTH>,
+    mv: Move<SIDE_LENGTH>,
+) -> Option<Forbidden> {
+    Grid::new(board).forbidden(mv.coord(), 0)
+}
+
+/// Every cell Black may not play on in `board`, in index order, with the
+/// reason, for marking on a board like standard Renju software.
+#[must_use]
+pub fn forbidden_points<const SIDE_LENGTH: usize>(
+    board: &Board<SIDE_LENGTH>,
+) -> Vec<(Move<SIDE_LENGTH>, Forbidden)> {
+    let mut grid = Grid::new(board);
+    let mut points = Vec::new();
+    board.generate_moves(|mv| {
+        if let Some(reason) = grid.forbidden(mv.coord(), 0) {
+            points.push((mv, reason));
+        }
+        false
+    });
+    points
+}
+
+/// A scratch copy of the board's cells, for trying stones out.
+struct Grid<const SIDE_LENGTH: usize> {
+    cells: [[Player; SIDE_LENGTH]; SIDE_LENGTH],
+}
+
+impl<const SIDE_LENGTH: usize> Grid<SIDE_LENGTH> {
+    fn new(board: &Board<SIDE_LENGTH>) -> Self {
+        let mut cells = [[Player::None; SIDE_LENGTH]; SIDE_LENGTH];
+        for (Coord { row, col }, player) in board.cells() {
+            cells[row][col] = player;
+        }
+        Self { cells }
+    }
+
+    /// The cell `steps` along `direction` from `from`, if it is on the board.
+    fn offset(from: Coord, (dr, dc): (isize, isize), steps: isize) -> Option<Coord> {
+        let row = from.row.checked_add_signed(dr * steps)?;
+        let col = from.col.checked_add_signed(dc * steps)?;
+        (row < SIDE_LENGTH && col < SIDE_LENGTH).then_some(Coord { row, col })
+    }
+
+    /// The contents of the cell `steps` along `direction` from `from`, or
+    /// `None` off the board.
+    fn at(&self, from: Coord, direction: (isize, isize), steps: isize) -> Option<Player> {
+        Self::offset(from, direction, steps).map(|Coord { row, col }| self.cells[row][col])
+    }
+
+    fn is_black(&self, from: Coord, direction: (isize, isize), steps: isize) -> bool {
+        self.at(from, direction, steps) == Some(Player::X)
+    }
+
+    /// The extent of the run of black stones through `at` along `direction`,
+    /// as the steps to its first and last stones.
+    fn run(&self, at: Coord, direction: (isize, isize)) -> (isize, isize) {
+        let mut first = 0;
+        while self.is_black(at, direction, first - 1) {
+            first -= 1;
+        }
+        let mut last = 0;
+        while self.is_black(at, direction, last + 1) {
+            last += 1;
+        }
+        (first, last)
+    }
+
+    /// The number of distinct fours through the black stone at `at` along
+    /// `direction`: sets of four stones, including `at`, that one more stone
+    /// turns into exactly five.
+    fn fours(&self, at: Coord, direction: (isize, isize)) -> usize {
+        let mut stone_sets = Vec::new();
+        for start in -4..=0 {
+            let window = start..start + 5;
+            if window
+                .clone()
+                .any(|step| Self::offset(at, direction, step).is_none())
+            {
+                continue;
+            }
+            let empty = window
+                .clone()
+                .filter(|&step| self.at(at, direction, step) == Some(Player::None))
+                .count();
+            let black = window
+                .clone()
+                .filter(|&step| self.is_black(at, direction, step))
+                .collect::<Vec<_>>();
+            let exact = !self.is_black(at, direction, start - 1)
+                && !self.is_black(at, direction, start + 5);
+            if empty == 1 && black.len() == 4 && exact && !stone_sets.contains(&black) {
+                stone_sets.push(black);
+            }
+        }
+        stone_sets.len()
+    }
+
+    /// Whether the black stone at `at` is part of a straight four along
+    /// `direction`: exactly four in a row, which can be made into exactly
+    /// five at either end.
+    fn is_straight_four(&self, at: Coord, direction: (isize, isize)) -> bool {
+        let (first, last) = self.run(at, direction);
+        last - first == 3
+            && self.at(at, direction, first - 1) == Some(Player::None)
+            && self.at(at, direction, last + 1) == Some(Player::None)
+            && !self.is_black(at, direction, first - 2)
+            && !self.is_black(at, direction, last + 2)
+    }
+
+    /// Whether the black stone at `at` is part of an open three along
+    /// `direction`.
+    fn is_open_three(&mut self, at: Coord, direction: (isize, isize), depth: usize) -> bool {
+        (-4..=4).any(|step| {
+            let Some(Coord { row, col }) = Self::offset(at, direction, step) else {
+                return false;
+            };
+            if self.cells[row][col] != Player::None {
+                return false;
+            }
+            self.cells[row][col] = Player::X;
+            let straight = self.is_straight_four(at, direction);
+            self.cells[row][col] = Player::None;
+            straight && self.forbidden(Coord { row, col }, depth + 1).is_none()
+        })
+    }
+
+    /// Whether Black may not play at `at`, and why. Past [`MAX_DEPTH`],
+    /// threes are no longer checked.
+    fn forbidden(&mut self, at: Coord, depth: usize) -> Option<Forbidden> {
+        if self.cells[at.row][at.col] != Player::None {
+            return None;
+        }
+        self.cells[at.row][at.col] = Player::X;
+        let runs = DIRECTIONS.map(|direction| {
+            let (first, last) = self.run(at, direction);
+            last - first + 1
+        });
+        let reason = if runs.contains(&5) {
+            None
+        } else if runs.iter().any(|&run| run > 5) {
+            Some(Forbidden::Overline)
+        } else if DIRECTIONS
+            .iter()
+            .map(|&direction| self.fours(at, direction))
+            .sum::<usize>()
+            >= 2
+        {
+            Some(Forbidden::DoubleFour)
+        } else if depth < MAX_DEPTH
+            && DIRECTIONS
+                .iter()
+                .filter(|&&direction| self.is_open_three(at, direction, depth))
+                .count()
+                >= 2
+        {
+            Some(Forbidden::DoubleThree)
+        } else {
+            None
+        };
+        self.cells[at.row][at.col] = Player::None;
+        reason
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{forbidden, forbidden_points, Forbidden};
+    use crate::board::{Board, Move};
+
+    /// A board with X's stones on `black` and O's on `white`, with X to move.
+    fn position(black: &[&str], white: &[&str]) -> Board<15> {
+        assert!(black.len() == white.len());
+        let mv = |s: &&str| s.parse::<Move<15>>().unwrap();
+        let moves = black
+            .iter()
+            .zip(white)
+            .flat_map(|(b, w)| [mv(b), mv(w)])
+            .collect::<Vec<_>>();
+        Board::new().apply_moves(&moves).unwrap()
+    }
+
+    fn check(board: &Board<15>, mv: &str) -> Option<Forbidden> {
+        forbidden(board, mv.parse().unwrap())
+    }
+
+    #[test]
+    fn recognises_each_restriction() {
+        // h8 makes open threes with f8-g8 and with h9-h10.
+        let board = position(&["f8", "g8", "h9", "h10"], &["a1", "a3", "a5", "a7"]);
+        assert_eq!(check(&board, "h8"), Some(Forbidden::DoubleThree));
+        assert_eq!(check(&board, "e8"), None);
+
+        let board = position(
+            &["e8", "f8", "g8", "h9", "h10", "h11"],
+            &["a1", "a3", "a5", "a7", "a9", "a11"],
+        );
+        assert_eq!(check(&board, "h8"), Some(Forbidden::DoubleFour));
+
+        let board = position(
+            &["c8", "d8", "e8", "g8", "h8"],
+            &["a1", "a3", "a5", "a7", "a9"],
+        );
+        assert_eq!(check(&board, "f8"), Some(Forbidden::Overline));
+
+        // making exactly five wins, even with a four elsewhere.
+        let board = position(
+            &["d8", "e8", "f8", "g8", "h9", "h10", "h11"],
+            &["a1", "a3", "a5", "a7", "a9", "a11", "a13"],
+        );
+        assert_eq!(check(&board, "h8"), None);
+    }
+
+    #[test]
+    fn blocked_threes_are_not_open() {
+        // O's stone on e8 stops f8-g8-h8 becoming a straight four.
+        let board = position(&["f8", "g8", "h9", "h10"], &["e8", "a3", "a5", "a7"]);
+        assert_eq!(check(&board, "h8"), None);
+    }
+
+    #[test]
+    fn lists_every_forbidden_point() {
+        let board = position(&["f8", "g8", "h9", "h10"], &["a1", "a3", "a5", "a7"]);
+        let points = forbidden_points(&board);
+        assert!(points.contains(&("h8".parse().unwrap(), Forbidden::DoubleThree)));
+        assert!(points
+            .iter()
+            .all(|&(mv, reason)| forbidden(&board, mv) == Some(reason)));
+        assert!(forbidden_points(&Board::<15>::new()).is_empty());
+        assert_eq!(Forbidden::DoubleFour.to_string(), "double-four");
+    }
+}
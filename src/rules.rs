@@ -0,0 +1,154 @@
+//! Rule sets that restrict where stones may be played, beyond the cell being
+//! empty.
+
+use std::fmt;
+
+use crate::{
+    board::{Board, Move, Player},
+    renju,
+};
+
+/// A rule set, deciding which empty cells the side to move may play on.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Rule {
+    /// Any empty cell may be played.
+    #[default]
+    Freestyle,
+    /// X's first move must be in the centre, and X's second move at least
+    /// three cells away from it.
+    Pro,
+    /// As [`Rule::Pro`], but X's second move must be at least four cells
+    /// away from the centre.
+    LongPro,
+    /// X may not play the points listed by [`renju::forbidden_points`].
+    Renju,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Freestyle => "freestyle",
+            Self::Pro => "pro",
+            Self::LongPro => "long pro",
+            Self::Renju => "renju",
+        })
+    }
+}
+
+impl Rule {
+    /// The smallest distance from the centre allowed for X's second move.
+    const fn second_move_distance(self) -> Option<usize> {
+        match self {
+            Self::Pro => Some(3),
+            Self::LongPro => Some(4),
+            Self::Freestyle | Self::Renju => None,
+        }
+    }
+}
+
+impl<const SIDE_LENGTH: usize> Board<SIDE_LENGTH> {
+    /// Generates the moves allowed under `rule` and calls `callback` with
+    /// each one, in index order. Iteration short-circuits if `callback`
+    /// returns `true`.
+    ///
+    /// Unlike [`Board::generate_moves`], this never offers a move that
+    /// `rule` forbids, so an engine choosing among these moves cannot play
+    /// an illegal one.
+    pub fn generate_legal_moves(
+        &self,
+        rule: Rule,
+        mut callback: impl FnMut(Move<SIDE_LENGTH>) -> bool,
+    ) {
+        let forbidden = if rule == Rule::Renju && self.turn() == Player::X {
+            renju::forbidden_points(self)
+        } else {
+            Vec::new()
+        };
+        self.generate_moves(|mv| {
+            self.is_allowed_by(rule, mv)
+                && !forbidden.iter().any(|&(point, _)| point == mv)
+                && callback(mv)
+        });
+    }
+
+    /// Returns `true` if `mv` is legal, as for [`Board::is_legal`], and
+    /// `rule` allows it.
+    #[must_use]
+    pub fn is_legal_under(&self, rule: Rule, mv: Move<SIDE_LENGTH>) -> bool {
+        self.is_legal(mv)
+            && self.is_allowed_by(rule, mv)
+            && !(rule == Rule::Renju
+                && self.turn() == Player::X
+                && renju::forbidden(self, mv).is_some())
+    }
+
+    /// Checks the opening restrictions of `rule`.
+    fn is_allowed_by(&self, rule: Rule, mv: Move<SIDE_LENGTH>) -> bool {
+        let Some(distance) = rule.second_move_distance() else {
+            return true;
+        };
+        match self.ply() {
+            0 => mv == Self::center(),
+            2 => mv.chebyshev_distance(&Self::center()) >= distance,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rule;
+    use crate::board::{Board, Move};
+
+    fn legal_moves(board: &Board<15>, rule: Rule) -> Vec<Move<15>> {
+        let mut moves = Vec::new();
+        board.generate_legal_moves(rule, |mv| {
+            moves.push(mv);
+            false
+        });
+        moves
+    }
+
+    #[test]
+    fn pro_openings() {
+        let board = Board::<15>::new();
+        assert_eq!(legal_moves(&board, Rule::Pro), [Board::<15>::center()]);
+        assert_eq!(legal_moves(&board, Rule::Freestyle).len(), 225);
+
+        let board = board
+            .apply_moves(&[Board::center(), "h9".parse().unwrap()])
+            .unwrap();
+        // a 5x5 square around the centre is closed, less the two stones.
+        assert_eq!(legal_moves(&board, Rule::Pro).len(), 225 - 25);
+        // and a 7x7 square under long pro.
+        assert_eq!(legal_moves(&board, Rule::LongPro).len(), 225 - 49);
+        assert!(!board.is_legal_under(Rule::Pro, "j10".parse().unwrap()));
+        assert!(board.is_legal_under(Rule::Pro, "k11".parse().unwrap()));
+
+        let board = board.with_move("k11".parse().unwrap());
+        assert_eq!(legal_moves(&board, Rule::Pro).len(), 222);
+    }
+
+    #[test]
+    fn renju_excludes_forbidden_points() {
+        let moves = ["f8", "a1", "g8", "a3", "h9", "a5", "h10", "a7"];
+        let moves = moves.map(|mv| mv.parse::<Move<15>>().unwrap());
+        let board = Board::<15>::new().apply_moves(&moves).unwrap();
+        let h8 = "h8".parse().unwrap();
+        assert!(!legal_moves(&board, Rule::Renju).contains(&h8));
+        assert!(legal_moves(&board, Rule::Freestyle).contains(&h8));
+        assert!(!board.is_legal_under(Rule::Renju, h8));
+
+        // white is not restricted.
+        let board = board.with_move("o15".parse().unwrap());
+        assert!(legal_moves(&board, Rule::Renju).contains(&h8));
+        assert!(board.is_legal_under(Rule::Renju, h8));
+
+        let mut first = None;
+        board.generate_legal_moves(Rule::Renju, |mv| {
+            first = Some(mv);
+            true
+        });
+        assert_eq!(first, Some("b1".parse().unwrap()));
+    }
+}
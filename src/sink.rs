@@ -0,0 +1,194 @@
+//! Output streams that compress what is written to them, for generation runs
+//! whose raw output would run to tens of gigabytes.
+//!
+//! Gzip support needs the `gzip` feature, and zstd support the `zstd`
+//! feature; plain output is always available.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// How a [`Sink`] compresses its output.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// No compression.
+    #[default]
+    None,
+    /// Gzip, at the default level.
+    #[cfg(feature = "gzip")]
+    Gzip,
+    /// Zstandard, at the default level.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    /// Picks the compression suggested by the extension of `path`: `.gz` for
+    /// gzip and `.zst` for zstd, if those are enabled, and none otherwise.
+    #[must_use]
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            #[cfg(feature = "gzip")]
+            Some("gz") => Self::Gzip,
+            #[cfg(feature = "zstd")]
+            Some("zst") => Self::Zstd,
+            _ => Self::None,
+        }
+    }
+}
+
+enum Encoder<W: Write> {
+    Plain(W),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+/// A writer that compresses its output.
+///
+/// Call [`Sink::finish`] when done: compressed formats end with a trailer
+/// that is only written then, and errors writing it are reported there.
+pub struct Sink<W: Write> {
+    compression: Compression,
+    encoder: Encoder<W>,
+}
+
+impl<W: Write> std::fmt::Debug for Sink<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sink")
+            .field("compression", &self.compression)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: Write> Sink<W> {
+    /// Wraps `writer`, compressing what is written with `compression`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the compressor cannot be set up.
+    pub fn new(writer: W, compression: Compression) -> io::Result<Self> {
+        // only const without any compression features.
+        #![allow(clippy::missing_const_for_fn)]
+        let encoder = match compression {
+            Compression::None => Encoder::Plain(writer),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Encoder::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::default(),
+            )),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Encoder::Zstd(zstd::Encoder::new(writer, 0)?),
+        };
+        Ok(Self {
+            compression,
+            encoder,
+        })
+    }
+
+    /// The compression applied to the output.
+    #[must_use]
+    pub const fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Finishes the compressed stream and returns the underlying writer,
+    /// flushed.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from writing the end of the stream.
+    pub fn finish(self) -> io::Result<W> {
+        #![allow(clippy::infallible_destructuring_match)]
+        let mut writer = match self.encoder {
+            Encoder::Plain(writer) => writer,
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.finish()?,
+        };
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+impl Sink<BufWriter<File>> {
+    /// Creates the file at `path`, compressing it as its extension suggests
+    /// (see [`Compression::from_path`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let compression = Compression::from_path(&path);
+        Self::new(BufWriter::new(File::create(path)?), compression)
+    }
+}
+
+impl<W: Write> Write for Sink<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.encoder {
+            Encoder::Plain(writer) => writer.write(buf),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.encoder {
+            Encoder::Plain(writer) => writer.flush(),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{Compression, Sink};
+
+    fn written(compression: Compression) -> Vec<u8> {
+        let mut sink = Sink::new(Vec::new(), compression).unwrap();
+        for _ in 0..100 {
+            writeln!(sink, "15/15/15/15/15/15/15/7x7/15/15/15/15/15/15/15 o").unwrap();
+        }
+        sink.finish().unwrap()
+    }
+
+    #[test]
+    fn compresses_by_extension() {
+        assert_eq!(Compression::from_path("fens.txt"), Compression::None);
+        let plain = written(Compression::None);
+        assert_eq!(plain.len(), 100 * 48);
+
+        #[cfg(feature = "gzip")]
+        {
+            use std::io::Read;
+            assert_eq!(Compression::from_path("fens.txt.gz"), Compression::Gzip);
+            let compressed = written(Compression::Gzip);
+            assert!(compressed.len() < plain.len() / 10);
+            let mut decoded = Vec::new();
+            flate2::read::GzDecoder::new(compressed.as_slice())
+                .read_to_end(&mut decoded)
+                .unwrap();
+            assert_eq!(decoded, plain);
+        }
+
+        #[cfg(feature = "zstd")]
+        {
+            assert_eq!(Compression::from_path("fens.zst"), Compression::Zstd);
+            let compressed = written(Compression::Zstd);
+            assert!(compressed.len() < plain.len() / 10);
+            assert_eq!(zstd::decode_all(compressed.as_slice()).unwrap(), plain);
+        }
+    }
+}
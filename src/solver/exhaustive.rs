@@ -0,0 +1,350 @@
+//! Strong solutions of small m,n,k games by exhaustive search.
+//!
+//! Every position reachable from the one being solved is searched with
+//! negamax, and results are memoized per canonical (symmetry-reduced)
+//! position. This is only practical for small boards: tic-tac-toe and 4x4
+//! variants solve instantly, and 5x5 boards take seconds to a minute in
+//! optimised builds; larger boards are out of reach.
+//!
+//! ```
+//! use gomokugen::{board::Board, solver::exhaustive::{Solver, Value}};
+//!
+//! let tic_tac_toe = Board::<3>::new().with_win_length(3);
+//! assert_eq!(Solver::new().solve(&tic_tac_toe), Value::Draw);
+//! ```
+
+use std::{collections::HashMap, time::Instant};
+
+use crate::{
+    board::{Board, Coord, Move, Player},
+    engine::Limits,
+    symmetry::Symmetry,
+    tables, zobrist,
+};
+
+/// The game-theoretic value of a position, from the point of view of the player to move.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+pub enum Value {
+    /// The player to move loses against best play.
+    Loss,
+    /// Best play leads to a draw.
+    Draw,
+    /// The player to move wins against best play.
+    Win,
+}
+
+impl std::ops::Neg for Value {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        match self {
+            Self::Loss => Self::Win,
+            Self::Draw => Self::Draw,
+            Self::Win => Self::Loss,
+        }
+    }
+}
+
+/// How a cached value relates to the true value of a position.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Bound {
+    Exact,
+    /// The true value is at least the cached one.
+    Lower,
+    /// The true value is at most the cached one.
+    Upper,
+}
+
+/// A memoizing exhaustive solver for boards of size `SIDE_LENGTH`.
+///
+/// Results are cached across calls, so solving a position and then asking
+/// for the values of positions within its game tree is cheap. A solver must
+/// only be used with boards that share a win length.
+#[derive(Clone, Debug, Default)]
+pub struct Solver<const SIDE_LENGTH: usize> {
+    table: HashMap<u64, (Value, Bound)>,
+    win_length: Option<usize>,
+    nodes: u64,
+    budget: Budget,
+}
+
+/// The limits of the current call, and whether they have run out.
+#[derive(Clone, Debug, Default)]
+struct Budget {
+    limits: Limits,
+    node_limit: Option<u64>,
+    deadline: Option<Instant>,
+    exhausted: bool,
+}
+
+impl<const SIDE_LENGTH: usize> Solver<SIDE_LENGTH> {
+    /// Creates a solver with an empty cache.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of positions searched so far, excluding cache hits.
+    #[must_use]
+    pub const fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// The number of distinct positions with cached results.
+    #[must_use]
+    pub fn cached_positions(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Computes the value of `board` for the player to move.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `board` has a different win length to boards previously
+    /// given to this solver.
+    pub fn solve(&mut self, board: &Board<SIDE_LENGTH>) -> Value {
+        self.solve_within(board, &Limits::default())
+            .expect("an unlimited search always finishes")
+    }
+
+    /// Computes the value of `board` like [`Solver::solve`], or returns
+    /// `None` if `limits` run out first. A depth limit gives up on lines
+    /// longer than that many plies, and a node limit counts the positions
+    /// searched in this call. Results found before giving up are kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `board` has a different win length to boards previously
+    /// given to this solver.
+    pub fn solve_within(&mut self, board: &Board<SIDE_LENGTH>, limits: &Limits) -> Option<Value> {
+        let win_length = *self.win_length.get_or_insert_with(|| board.win_length());
+        assert_eq!(
+            win_length,
+            board.win_length(),
+            "A solver must only be used with one win length."
+        );
+        let keys = Symmetry::ALL.map(|sym| board.transform(sym).zobrist_key());
+        self.budget = Budget {
+            limits: limits.clone(),
+            node_limit: limits.nodes.map(|nodes| self.nodes.saturating_add(nodes)),
+            deadline: limits.time_budget().map(|time| Instant::now() + time),
+            exhausted: false,
+        };
+        let value = self.negamax(board, keys, Value::Loss, Value::Win, 0);
+        let exhausted = self.budget.exhausted;
+        self.budget = Budget::default();
+        (!exhausted).then_some(value)
+    }
+
+    /// Whether the limits have run out, after which nothing more is cached.
+    fn out_of_budget(&mut self, ply: u8) -> bool {
+        let budget = &mut self.budget;
+        if !budget.exhausted {
+            // checking the clock is relatively slow, so only do it occasionally.
+            budget.exhausted = budget.limits.depth.is_some_and(|depth| ply >= depth)
+                || budget.node_limit.is_some_and(|limit| self.nodes >= limit)
+                || budget.limits.is_stopped()
+                || (self.nodes.is_multiple_of(1024)
+                    && budget.deadline.is_some_and(|d| Instant::now() >= d));
+        }
+        budget.exhausted
+    }
+
+    /// Computes the value of every legal move in `board`, for the player making it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `board` has a different win length to boards previously
+    /// given to this solver.
+    pub fn move_values(&mut self, board: &Board<SIDE_LENGTH>) -> Vec<(Move<SIDE_LENGTH>, Value)> {
+        if board.outcome().is_some() {
+            return Vec::new();
+        }
+        board
+            .children()
+            .map(|(mv, child)| (mv, -self.solve(&child)))
+            .collect()
+    }
+
+    /// Alpha-beta negamax over the three possible values. `keys` holds the
+    /// Zobrist keys of the eight symmetric images of `board`; the smallest
+    /// one identifies the position in the cache.
+    fn negamax(
+        &mut self,
+        board: &Board<SIDE_LENGTH>,
+        keys: [u64; 8],
+        mut alpha: Value,
+        mut beta: Value,
+        ply: u8,
+    ) -> Value {
+        match board.outcome() {
+            Some(Player::None) => return Value::Draw,
+            // the previous move won the game.
+            Some(_) => return Value::Loss,
+            None => {}
+        }
+        let key = keys.iter().copied().min().unwrap_or_default();
+        if let Some(&(value, bound)) = self.table.get(&key) {
+            match bound {
+                Bound::Exact => return value,
+                Bound::Lower => alpha = alpha.max(value),
+                Bound::Upper => beta = beta.min(value),
+            }
+            if alpha >= beta {
+                return value;
+            }
+        }
+        self.nodes += 1;
+
+        let us = board.turn();
+        let mut moves = Self::ordered_moves(board);
+        if moves.iter().any(|&mv| board.is_winning_move(mv, us)) {
+            self.table.insert(key, (Value::Win, Bound::Exact));
+            return Value::Win;
+        }
+        let mut threats = moves
+            .iter()
+            .copied()
+            .filter(|&mv| board.is_winning_move(mv, -us));
+        if let Some(threat) = threats.next() {
+            if threats.next().is_some() {
+                // two ways to lose, and we can only block one.
+                self.table.insert(key, (Value::Loss, Bound::Exact));
+                return Value::Loss;
+            }
+            moves = vec![threat];
+        } else if !Self::anyone_can_win(board) {
+            self.table.insert(key, (Value::Draw, Bound::Exact));
+            return Value::Draw;
+        }
+        if self.out_of_budget(ply) {
+            return Value::Draw;
+        }
+
+        let original_alpha = alpha;
+        let mut best = Value::Loss;
+        for mv in moves {
+            let mut child_keys = keys;
+            for (child_key, sym) in child_keys.iter_mut().zip(Symmetry::ALL) {
+                *child_key ^= zobrist::stone(mv.transform(sym).index(), us) ^ zobrist::SIDE;
+            }
+            let value = -self.negamax(
+                &board.with_move(mv),
+                child_keys,
+                -beta,
+                -alpha,
+                ply.saturating_add(1),
+            );
+            if self.budget.exhausted {
+                return Value::Draw;
+            }
+            best = best.max(value);
+            alpha = alpha.max(value);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best <= original_alpha {
+            Bound::Upper
+        } else if best >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.insert(key, (best, bound));
+        best
+    }
+
+    /// Whether any line could still be completed by either player.
+    fn anyone_can_win(board: &Board<SIDE_LENGTH>) -> bool {
+        board.windows().any(|window| {
+            let mut cells = window.cells().filter(|&c| c != Player::None);
+            cells.next().is_none_or(|first| cells.all(|c| c == first))
+        })
+    }
+
+    /// Legal moves, closest to the centre first, as those tend to be strongest.
+    fn ordered_moves(board: &Board<SIDE_LENGTH>) -> Vec<Move<SIDE_LENGTH>> {
+        let mut moves = Vec::new();
+        board.generate_moves(|mv| {
+            moves.push(mv);
+            false
+        });
+        let distances = tables::distance_to_center::<SIDE_LENGTH>();
+        moves.sort_by_key(|mv| {
+            let Coord { row, col } = mv.coord();
+            distances[row][col]
+        });
+        moves
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Solver, Value};
+    use crate::{
+        board::Board,
+        engine::{Limits, StopFlag},
+    };
+
+    #[test]
+    fn tic_tac_toe_is_a_draw() {
+        let mut solver = Solver::new();
+        let board = Board::<3>::new().with_win_length(3);
+        assert_eq!(solver.solve(&board), Value::Draw);
+        // every opening move holds the draw, by symmetry considerations or otherwise.
+        let values = solver.move_values(&board);
+        assert_eq!(values.len(), 9);
+        assert!(values.iter().all(|&(_, v)| v == Value::Draw));
+        // an edge reply to a corner opening loses.
+        let cornered = board.with_move("a1".parse().unwrap());
+        let replies = solver.move_values(&cornered);
+        let edge = replies
+            .iter()
+            .find(|(mv, _)| mv.to_string() == "B1")
+            .unwrap();
+        assert_eq!(edge.1, Value::Loss);
+    }
+
+    #[test]
+    fn three_in_a_row_on_4x4_is_a_win() {
+        let board = Board::<4>::new().with_win_length(3);
+        assert_eq!(Solver::new().solve(&board), Value::Win);
+    }
+
+    #[test]
+    fn four_in_a_row_on_4x4_is_a_draw() {
+        let board = Board::<4>::new().with_win_length(4);
+        assert_eq!(Solver::new().solve(&board), Value::Draw);
+    }
+
+    #[test]
+    fn gives_up_when_limits_run_out() {
+        let board = Board::<4>::new().with_win_length(4);
+        let mut solver = Solver::new();
+        assert_eq!(solver.solve_within(&board, &Limits::nodes(10)), None);
+        assert_eq!(solver.solve_within(&board, &Limits::depth(3)), None);
+        let stop = StopFlag::new();
+        stop.stop();
+        assert_eq!(solver.solve_within(&board, &Limits::infinite(stop)), None);
+        // nothing wrong was cached along the way.
+        assert_eq!(solver.solve(&board), Value::Draw);
+        assert_eq!(
+            solver.solve_within(&board, &Limits::nodes(10)),
+            Some(Value::Draw)
+        );
+    }
+
+    #[test]
+    fn finished_games_are_lost_for_the_side_to_move() {
+        let moves = ["a1", "a2", "b1", "b2", "c1"].map(|mv| mv.parse().unwrap());
+        let board = Board::<3>::new()
+            .with_win_length(3)
+            .apply_moves(&moves)
+            .unwrap();
+        let mut solver = Solver::new();
+        assert_eq!(solver.solve(&board), Value::Loss);
+        assert!(solver.move_values(&board).is_empty());
+    }
+}
@@ -0,0 +1,4 @@
+//! Solvers that compute game-theoretic results for positions.
+
+pub mod exhaustive;
+pub mod pn;
@@ -0,0 +1,427 @@
+//! Proof-number search for forced wins.
+//!
+//! Proof-number search grows a game tree towards the positions that are
+//! cheapest to prove or disprove, which makes it very effective at finding
+//! long forcing sequences. The side to move in the root position is the
+//! attacker, and the search tries to prove that it wins by force.
+//!
+//! By default every empty cell is a candidate move, so a proof answers every
+//! defence and a disproof covers every attack; [`ProofTree::verify`] checks
+//! the former. Moves that ignore an immediate threat lose at once, so they
+//! are pruned without loss.
+//!
+//! Restricting moves to cells near existing stones (see
+//! [`PnSearch::neighbourhood`]), as many gomoku solvers do, makes searches
+//! much faster but unsound: it applies to both sides, so a proof may be
+//! refuted by a reply further away, and a disproof only covers the nearby
+//! attacks.
+
+use std::time::Instant;
+
+use crate::{
+    board::{Board, Move, Player},
+    engine::Limits,
+};
+
+/// A proof or disproof number that can never be reduced to zero.
+const INFINITY: u32 = u32::MAX;
+
+/// The result of a proof-number search.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PnOutcome<const SIDE_LENGTH: usize> {
+    /// The attacker wins by force, following the given strategy. With a
+    /// [neighbourhood](PnSearch::neighbourhood), only against the defences
+    /// near the stones.
+    Proven(ProofTree<SIDE_LENGTH>),
+    /// The attacker cannot force a win, within the search's `max_depth`.
+    /// With a [neighbourhood](PnSearch::neighbourhood), only the attacks
+    /// near the stones are ruled out.
+    Disproven,
+    /// The search ran out of nodes or memory before reaching a conclusion.
+    Unknown,
+}
+
+/// A winning strategy for the attacker: a move to play, and a continuation
+/// for every defence against it that does not lose at once.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProofTree<const SIDE_LENGTH: usize> {
+    /// The attacker's move.
+    pub attack: Move<SIDE_LENGTH>,
+    /// Each defender reply searched, with the attacker's strategy after it.
+    /// Empty if `attack` wins the game on the spot.
+    pub defences: Vec<(Move<SIDE_LENGTH>, Self)>,
+}
+
+impl<const SIDE_LENGTH: usize> ProofTree<SIDE_LENGTH> {
+    /// The number of plies in the longest line of the proof.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        1 + self
+            .defences
+            .iter()
+            .map(|(_, tree)| 1 + tree.depth())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Every line of play in the proof, from the first attack to a winning move.
+    #[must_use]
+    pub fn lines(&self) -> Vec<Vec<Move<SIDE_LENGTH>>> {
+        if self.defences.is_empty() {
+            return vec![vec![self.attack]];
+        }
+        let mut out = Vec::new();
+        for (defence, tree) in &self.defences {
+            for line in tree.lines() {
+                let mut full = vec![self.attack, *defence];
+                full.extend(line);
+                out.push(full);
+            }
+        }
+        out
+    }
+
+    /// Checks the proof from `board`: that each move is legal, that every
+    /// line ends with a win for the player to move in `board`, and that
+    /// every legal defence is answered. A defence the proof leaves out must
+    /// lose at once, by letting the attacker win on the next move.
+    #[must_use]
+    pub fn verify(&self, board: &Board<SIDE_LENGTH>) -> bool {
+        self.verify_for(board, board.turn())
+    }
+
+    fn verify_for(&self, board: &Board<SIDE_LENGTH>, attacker: Player) -> bool {
+        if !board.is_legal(self.attack) {
+            return false;
+        }
+        let after = board.with_move(self.attack);
+        match after.outcome() {
+            Some(winner) => return winner == attacker,
+            None if self.defences.is_empty() => return false,
+            None => {}
+        }
+        let mut replies = Vec::new();
+        after.generate_moves(|mv| {
+            replies.push(mv);
+            false
+        });
+        replies.into_iter().all(|reply| {
+            let next = after.with_move(reply);
+            match self.defences.iter().find(|&&(mv, _)| mv == reply) {
+                Some((_, tree)) => tree.verify_for(&next, attacker),
+                None => {
+                    next.outcome().is_none()
+                        && next
+                            .moves_where(|mv| next.is_winning_move(mv, attacker))
+                            .next()
+                            .is_some()
+                }
+            }
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Node<const SIDE_LENGTH: usize> {
+    mv: Move<SIDE_LENGTH>,
+    parent: u32,
+    children: Vec<u32>,
+    pn: u32,
+    dn: u32,
+    /// Whether the attacker is to move at this node.
+    is_or: bool,
+    expanded: bool,
+}
+
+/// A configurable proof-number search.
+#[derive(Copy, Clone, Debug)]
+pub struct PnSearch {
+    /// The maximum number of node expansions.
+    pub max_expansions: u64,
+    /// The maximum number of nodes kept in the search tree, bounding memory use.
+    pub max_tree_nodes: usize,
+    /// Only cells within this Chebyshev distance of an existing stone are
+    /// considered as moves, for either side, or every empty cell if `None`,
+    /// as by default. Only with `None` are proofs and disproofs sound; see
+    /// the [module docs](self).
+    pub neighbourhood: Option<usize>,
+    /// If set, only wins within this many plies are searched for.
+    pub max_depth: Option<usize>,
+}
+
+impl Default for PnSearch {
+    fn default() -> Self {
+        Self {
+            max_expansions: 100_000,
+            max_tree_nodes: 4_000_000,
+            neighbourhood: None,
+            max_depth: None,
+        }
+    }
+}
+
+impl PnSearch {
+    /// Searches for a forced win for the side to move in `board`.
+    #[must_use]
+    pub fn prove<const SIDE_LENGTH: usize>(
+        &self,
+        board: &Board<SIDE_LENGTH>,
+    ) -> PnOutcome<SIDE_LENGTH> {
+        self.prove_within(board, &Limits::default())
+    }
+
+    /// Searches for a forced win like [`PnSearch::prove`], also giving up
+    /// with [`PnOutcome::Unknown`] when `limits` run out. A node limit caps
+    /// the expansions, and a depth limit caps the length of the win.
+    #[must_use]
+    pub fn prove_within<const SIDE_LENGTH: usize>(
+        &self,
+        board: &Board<SIDE_LENGTH>,
+        limits: &Limits,
+    ) -> PnOutcome<SIDE_LENGTH> {
+        let search = Self {
+            max_expansions: limits
+                .nodes
+                .map_or(self.max_expansions, |nodes| nodes.min(self.max_expansions)),
+            max_depth: match (self.max_depth, limits.depth) {
+                (Some(ours), Some(theirs)) => Some(ours.min(usize::from(theirs))),
+                (ours, theirs) => ours.or_else(|| theirs.map(usize::from)),
+            },
+            ..*self
+        };
+        let deadline = limits.time_budget().map(|time| Instant::now() + time);
+        if board.outcome().is_some() {
+            return PnOutcome::Disproven;
+        }
+        let attacker = board.turn();
+        let mut tree = vec![Node {
+            mv: Move::null(),
+            parent: 0,
+            children: Vec::new(),
+            pn: 1,
+            dn: 1,
+            is_or: true,
+            expanded: false,
+        }];
+        let mut expansions = 0;
+        while tree[0].pn != 0 && tree[0].dn != 0 {
+            if expansions >= search.max_expansions
+                || tree.len() >= search.max_tree_nodes
+                || limits.is_stopped()
+                || (expansions.is_multiple_of(64) && deadline.is_some_and(|d| Instant::now() >= d))
+            {
+                return PnOutcome::Unknown;
+            }
+            let (leaf, leaf_board, ply) = Self::most_proving(&tree, board);
+            search.expand(&mut tree, leaf, &leaf_board, ply, attacker);
+            Self::update_ancestors(&mut tree, leaf);
+            expansions += 1;
+        }
+        if tree[0].pn == 0 {
+            PnOutcome::Proven(Self::extract_proof(&tree, 0))
+        } else {
+            PnOutcome::Disproven
+        }
+    }
+
+    /// Walks from the root to the leaf that most cheaply advances the search.
+    fn most_proving<const SIDE_LENGTH: usize>(
+        tree: &[Node<SIDE_LENGTH>],
+        root: &Board<SIDE_LENGTH>,
+    ) -> (usize, Board<SIDE_LENGTH>, usize) {
+        let mut index = 0;
+        let mut board = *root;
+        let mut ply = 0;
+        while tree[index].expanded {
+            let node = &tree[index];
+            let next = node.children.iter().map(|&c| c as usize).find(|&c| {
+                if node.is_or {
+                    tree[c].pn == node.pn
+                } else {
+                    tree[c].dn == node.dn
+                }
+            });
+            let Some(next) = next else { break };
+            board.make_move(tree[next].mv);
+            index = next;
+            ply += 1;
+        }
+        (index, board, ply)
+    }
+
+    fn expand<const SIDE_LENGTH: usize>(
+        &self,
+        tree: &mut Vec<Node<SIDE_LENGTH>>,
+        index: usize,
+        board: &Board<SIDE_LENGTH>,
+        ply: usize,
+        attacker: Player,
+    ) {
+        #![allow(clippy::cast_possible_truncation)]
+        let is_or = tree[index].is_or;
+        let moves = board.candidate_moves(self.neighbourhood);
+        let first_child = tree.len();
+        for mv in moves {
+            let child = board.with_move(mv);
+            let (pn, dn) = match child.outcome() {
+                Some(p) if p == attacker => (0, INFINITY),
+                Some(_) => (INFINITY, 0),
+                // too deep to count as a win.
+                None if self.max_depth.is_some_and(|depth| ply + 1 >= depth) => (INFINITY, 0),
+                None => (1, 1),
+            };
+            tree.push(Node {
+                mv,
+                parent: index as u32,
+                children: Vec::new(),
+                pn,
+                dn,
+                is_or: !is_or,
+                expanded: false,
+            });
+        }
+        tree[index].children = (first_child as u32..tree.len() as u32).collect();
+        tree[index].expanded = true;
+    }
+
+    fn update_ancestors<const SIDE_LENGTH: usize>(
+        tree: &mut [Node<SIDE_LENGTH>],
+        mut index: usize,
+    ) {
+        loop {
+            let node = &tree[index];
+            let children = node.children.iter().map(|&c| &tree[c as usize]);
+            let (pn, dn) = if node.children.is_empty() {
+                // no moves at all: the board is full, which is not a win.
+                (INFINITY, 0)
+            } else if node.is_or {
+                let pn = children.clone().map(|c| c.pn).min().unwrap_or(INFINITY);
+                let dn = children.fold(0u32, |acc, c| acc.saturating_add(c.dn));
+                (pn, dn)
+            } else {
+                let pn = children
+                    .clone()
+                    .fold(0u32, |acc, c| acc.saturating_add(c.pn));
+                let dn = children.map(|c| c.dn).min().unwrap_or(INFINITY);
+                (pn, dn)
+            };
+            let unchanged = pn == tree[index].pn && dn == tree[index].dn;
+            tree[index].pn = pn;
+            tree[index].dn = dn;
+            if index == 0 || unchanged {
+                return;
+            }
+            index = tree[index].parent as usize;
+        }
+    }
+
+    fn extract_proof<const SIDE_LENGTH: usize>(
+        tree: &[Node<SIDE_LENGTH>],
+        or_node: usize,
+    ) -> ProofTree<SIDE_LENGTH> {
+        let attack = tree[or_node]
+            .children
+            .iter()
+            .map(|&c| c as usize)
+            .find(|&c| tree[c].pn == 0)
+            .expect("proven OR node has a proven child");
+        let defences = tree[attack]
+            .children
+            .iter()
+            .map(|&c| {
+                let c = c as usize;
+                (tree[c].mv, Self::extract_proof(tree, c))
+            })
+            .collect();
+        ProofTree {
+            attack: tree[attack].mv,
+            defences,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PnOutcome, PnSearch, ProofTree};
+    use crate::{
+        board::{Board, Move},
+        engine::{Limits, StopFlag},
+    };
+
+    fn board(moves: &[&str]) -> Board<15> {
+        let moves = moves
+            .iter()
+            .map(|mv| mv.parse::<Move<15>>().unwrap())
+            .collect::<Vec<_>>();
+        Board::new().apply_moves(&moves).unwrap()
+    }
+
+    #[test]
+    fn open_three_is_a_forced_win() {
+        // X has an open three on the eighth row, and O's stones are far away.
+        let position = board(&["f8", "a1", "g8", "a15", "h8", "o1"]);
+        let PnOutcome::Proven(proof) = PnSearch::default().prove(&position) else {
+            panic!("expected a proof");
+        };
+        assert!(proof.verify(&position));
+        // make an open four, then complete the five whichever end O blocks.
+        assert_eq!(proof.depth(), 3);
+        assert!(proof.lines().len() >= 2);
+        // a quiet move first lets O block the three, which this leaves out.
+        let quiet = ["c3", "m12"].map(|mv| mv.parse::<Move<15>>().unwrap());
+        assert!(proof.verify(&position.apply_moves(&quiet).unwrap()));
+        let partial = ProofTree {
+            attack: quiet[0],
+            defences: vec![(quiet[1], proof)],
+        };
+        assert!(!partial.verify(&position));
+    }
+
+    #[test]
+    fn immediate_win() {
+        let position = board(&["f8", "a1", "g8", "a15", "h8", "o1", "i8", "o15"]);
+        let PnOutcome::Proven(proof) = PnSearch::default().prove(&position) else {
+            panic!("expected a proof");
+        };
+        assert_eq!(proof.depth(), 1);
+        assert!(proof.verify(&position));
+    }
+
+    #[test]
+    fn limits_and_disproofs() {
+        let search = PnSearch {
+            max_expansions: 50,
+            ..PnSearch::default()
+        };
+        assert_eq!(search.prove(&Board::<15>::new()), PnOutcome::Unknown);
+        // tic-tac-toe is a draw.
+        let tiny = Board::<3>::new();
+        let search = PnSearch {
+            neighbourhood: None,
+            max_expansions: 1_000_000,
+            ..PnSearch::default()
+        };
+        assert_eq!(search.prove(&tiny.with_win_length(3)), PnOutcome::Disproven);
+    }
+
+    #[test]
+    fn obeys_search_limits() {
+        let position = board(&["f8", "a1", "g8", "a15", "h8", "o1"]);
+        let search = PnSearch::default();
+        assert!(matches!(
+            search.prove_within(&position, &Limits::default()),
+            PnOutcome::Proven(_)
+        ));
+        // the win takes three plies.
+        assert_eq!(
+            search.prove_within(&position, &Limits::depth(2)),
+            PnOutcome::Disproven
+        );
+        let stop = StopFlag::new();
+        stop.stop();
+        assert_eq!(
+            search.prove_within(&Board::<15>::new(), &Limits::infinite(stop)),
+            PnOutcome::Unknown
+        );
+    }
+}
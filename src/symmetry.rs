@@ -0,0 +1,115 @@
+use crate::board::Coord;
+
+/// One of the eight symmetries of a square board.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Symmetry {
+    /// Leaves the board unchanged.
+    Identity,
+    /// Rotates the board a quarter turn.
+    Rotate90,
+    /// Rotates the board a half turn.
+    Rotate180,
+    /// Rotates the board three quarter turns.
+    Rotate270,
+    /// Mirrors the board left-to-right.
+    FlipHorizontal,
+    /// Mirrors the board top-to-bottom.
+    FlipVertical,
+    /// Mirrors the board about the diagonal through A1.
+    FlipDiagonal,
+    /// Mirrors the board about the other diagonal.
+    FlipAntiDiagonal,
+}
+
+impl Symmetry {
+    /// All eight symmetries, starting with the identity.
+    pub const ALL: [Self; 8] = [
+        Self::Identity,
+        Self::Rotate90,
+        Self::Rotate180,
+        Self::Rotate270,
+        Self::FlipHorizontal,
+        Self::FlipVertical,
+        Self::FlipDiagonal,
+        Self::FlipAntiDiagonal,
+    ];
+
+    /// The symmetry that undoes this one.
+    #[must_use]
+    pub const fn inverse(self) -> Self {
+        match self {
+            Self::Rotate90 => Self::Rotate270,
+            Self::Rotate270 => Self::Rotate90,
+            other => other,
+        }
+    }
+
+    /// Maps `coord` on a board of side length `side_length` to its image under this symmetry.
+    #[must_use]
+    pub const fn apply(self, Coord { row, col }: Coord, side_length: usize) -> Coord {
+        let n = side_length - 1;
+        let (row, col) = match self {
+            Self::Identity => (row, col),
+            Self::Rotate90 => (col, n - row),
+            Self::Rotate180 => (n - row, n - col),
+            Self::Rotate270 => (n - col, row),
+            Self::FlipHorizontal => (row, n - col),
+            Self::FlipVertical => (n - row, col),
+            Self::FlipDiagonal => (col, row),
+            Self::FlipAntiDiagonal => (n - col, n - row),
+        };
+        Coord { row, col }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Symmetry;
+    use crate::board::{Board, Coord, Move};
+
+    #[test]
+    fn inverses_undo() {
+        for sym in Symmetry::ALL {
+            for row in 0..7 {
+                for col in 0..7 {
+                    let coord = Coord::new(row, col);
+                    let image = sym.apply(coord, 7);
+                    assert_eq!(sym.inverse().apply(image, 7), coord);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn quarter_turns_compose() {
+        let coord = Coord::new(1, 4);
+        let mut image = coord;
+        for _ in 0..4 {
+            image = Symmetry::Rotate90.apply(image, 9);
+        }
+        assert_eq!(image, coord);
+        assert_eq!(
+            Symmetry::Rotate90.apply(Symmetry::Rotate90.apply(coord, 9), 9),
+            Symmetry::Rotate180.apply(coord, 9)
+        );
+    }
+
+    #[test]
+    fn board_transforms() {
+        let moves = ["a1", "c2"].map(|mv| mv.parse::<Move<9>>().unwrap());
+        let board = Board::<9>::new().apply_moves(&moves).unwrap();
+        let flipped = board.transform(Symmetry::FlipHorizontal);
+        let expected = Board::<9>::new()
+            .apply_moves(&["i1", "g2"].map(|mv| mv.parse().unwrap()))
+            .unwrap();
+        assert_eq!(flipped, expected);
+        assert_eq!(flipped.zobrist_key(), expected.zobrist_key());
+        assert_eq!(
+            moves[1].transform(Symmetry::FlipHorizontal).to_string(),
+            "G2"
+        );
+        for sym in Symmetry::ALL {
+            assert_eq!(board.transform(sym).transform(sym.inverse()), board);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Precomputed lookup tables shared by the evaluation and move ordering
+//! heuristics.
+//!
+//! Tables are computed at compile time for each board size that uses them,
+//! and indexed by row and then column.
+
+/// The Chebyshev distance from each cell to the middle of the board. On
+/// boards of even size, the four middle cells are all at distance zero.
+const fn distances<const SIDE_LENGTH: usize>() -> [[u8; SIDE_LENGTH]; SIDE_LENGTH] {
+    #![allow(clippy::cast_possible_truncation)]
+    let mut table = [[0; SIDE_LENGTH]; SIDE_LENGTH];
+    let mut row = 0;
+    while row < SIDE_LENGTH {
+        let mut col = 0;
+        while col < SIDE_LENGTH {
+            // measured in half-cells from the exact middle, which may fall
+            // between cells.
+            let rows = (2 * row).abs_diff(SIDE_LENGTH - 1);
+            let cols = (2 * col).abs_diff(SIDE_LENGTH - 1);
+            let distance = if rows > cols { rows } else { cols };
+            table[row][col] = (distance / 2) as u8;
+            col += 1;
+        }
+        row += 1;
+    }
+    table
+}
+
+const fn centralities<const SIDE_LENGTH: usize>() -> [[u8; SIDE_LENGTH]; SIDE_LENGTH] {
+    let mut table = distances::<SIDE_LENGTH>();
+    let edge = table[0][0];
+    let mut row = 0;
+    while row < SIDE_LENGTH {
+        let mut col = 0;
+        while col < SIDE_LENGTH {
+            table[row][col] = edge - table[row][col];
+            col += 1;
+        }
+        row += 1;
+    }
+    table
+}
+
+struct Tables<const SIDE_LENGTH: usize>;
+
+impl<const SIDE_LENGTH: usize> Tables<SIDE_LENGTH> {
+    const DISTANCE_TO_CENTER: [[u8; SIDE_LENGTH]; SIDE_LENGTH] = distances();
+    const CENTRALITY: [[u8; SIDE_LENGTH]; SIDE_LENGTH] = centralities();
+}
+
+/// The Chebyshev distance from each cell to the middle of the board, from
+/// zero at the centre to `(SIDE_LENGTH - 1) / 2` on the edges.
+#[must_use]
+pub const fn distance_to_center<const SIDE_LENGTH: usize>(
+) -> &'static [[u8; SIDE_LENGTH]; SIDE_LENGTH] {
+    &Tables::<SIDE_LENGTH>::DISTANCE_TO_CENTER
+}
+
+/// How central each cell is, from zero on the edges of the board to
+/// `(SIDE_LENGTH - 1) / 2` at the centre.
+#[must_use]
+pub const fn centrality<const SIDE_LENGTH: usize>() -> &'static [[u8; SIDE_LENGTH]; SIDE_LENGTH] {
+    &Tables::<SIDE_LENGTH>::CENTRALITY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{centrality, distance_to_center};
+
+    #[test]
+    fn tables_are_symmetric_and_peak_in_the_middle() {
+        let odd = centrality::<15>();
+        assert_eq!(odd[7][7], 7);
+        assert_eq!(odd[0][7], 0);
+        assert_eq!(odd[5][9], 5);
+        assert_eq!(distance_to_center::<15>()[5][9], 2);
+
+        let even = distance_to_center::<8>();
+        for middle in [(3, 3), (3, 4), (4, 3), (4, 4)] {
+            assert_eq!(even[middle.0][middle.1], 0);
+        }
+        assert_eq!(even[0][0], 3);
+        assert!((0..8).all(|row| (0..8).all(|col| {
+            even[row][col] == even[7 - row][col] && even[row][col] == even[col][row]
+        })));
+    }
+}
@@ -0,0 +1,13 @@
+//! Helpers shared by the test modules.
+
+/// A seeded xorshift random number generator, in the form that takes a range
+/// `lo..hi` and returns a number in it.
+pub fn test_rng(seed: u64) -> impl FnMut(usize, usize) -> usize {
+    let mut state = seed;
+    move |lo, hi| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        lo + usize::try_from(state % (hi - lo) as u64).unwrap()
+    }
+}
@@ -0,0 +1,291 @@
+//! Threats to win, and the moves that stop them.
+//!
+//! With a win length of five, a *four* is a line that one more stone would
+//! make a win, and an *open three* a line that one more stone would make an
+//! *open four*: a four with two winning cells, which cannot be blocked. Both
+//! are defined here for any win length, by the winning cells a stone makes.
+//!
+//! A *fork* is a move that makes two threats at once, so that blocking one
+//! leaves the other; it is how most games of gomoku are won.
+
+use std::fmt;
+
+use crate::{
+    board::{Board, Coord, Move, Player},
+    renju,
+    rules::Rule,
+};
+
+/// The four directions a line can run in.
+const DIRECTIONS: [(isize, isize); 4] = [(0, 1), (1, 0), (1, 1), (1, -1)];
+
+/// The two threats a fork makes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Fork {
+    /// The move makes two winning cells, with two fours or an open four.
+    DoubleFour,
+    /// The move makes a four and, on another line, an open three.
+    FourThree,
+    /// The move makes open threes on two or more lines.
+    DoubleThree,
+}
+
+impl fmt::Display for Fork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::DoubleFour => "double-four",
+            Self::FourThree => "four-three",
+            Self::DoubleThree => "double-three",
+        })
+    }
+}
+
+impl<const SIDE_LENGTH: usize> Board<SIDE_LENGTH> {
+    /// The empty cells where a stone for `player` would complete a winning
+    /// line, in index order, whether or not it is `player`'s turn.
+    #[must_use]
+    pub fn winning_moves(&self, player: Player) -> Vec<Move<SIDE_LENGTH>> {
+        self.moves_where(|mv| self.is_winning_move(mv, player))
+            .collect()
+    }
+
+    /// The moves for the side to move that stop the opponent's threats, in
+    /// index order.
+    ///
+    /// If the opponent has a four, these are the cells that block it; if it
+    /// has two, there may be no single block, but every blocking cell is
+    /// listed. Otherwise, if the opponent has open threes, these are the
+    /// moves after which the opponent cannot make an open four, or none if
+    /// no single move stops every open three. With no threats, there are no
+    /// defensive moves.
+    ///
+    /// Counter-attacks, such as winning at once or making a four of one's
+    /// own, are not included.
+    #[must_use]
+    pub fn defensive_moves(&self) -> Vec<Move<SIDE_LENGTH>> {
+        let us = self.turn();
+        let them = -us;
+        let fours = self.winning_moves(them);
+        if !fours.is_empty() {
+            return fours;
+        }
+        let open_fours = self.open_four_moves(them);
+        if open_fours.is_empty() {
+            return Vec::new();
+        }
+        // a defence must take one of the cells of an open four.
+        let mut candidates = open_fours.clone();
+        for &mv in &open_fours {
+            candidates.extend(self.new_wins(mv, them));
+        }
+        candidates.sort_unstable_by_key(Move::index);
+        candidates.dedup();
+        candidates.retain(|&defence| {
+            let after = self.with_stone(defence, us);
+            open_fours
+                .iter()
+                .all(|&mv| mv == defence || after.new_wins(mv, them).len() < 2)
+        });
+        candidates
+    }
+
+    /// The moves that make a fork for `player`, in index order, with the
+    /// threats each makes, whether or not it is `player`'s turn.
+    ///
+    /// Moves that win at once are not forks. A double-three is no threat if
+    /// the opponent has a four of its own, and neither is any fork if the
+    /// opponent can win first; see [`Board::defensive_moves`].
+    #[must_use]
+    pub fn forks(&self, player: Player) -> Vec<(Move<SIDE_LENGTH>, Fork)> {
+        self.moves_where(|mv| !self.is_winning_move(mv, player))
+            .filter_map(|mv| Some((mv, self.fork(mv, player)?)))
+            .collect()
+    }
+
+    /// The forks for `player`, as for [`Board::forks`], that `rule` lets
+    /// `player` play: in Renju, the first player may not make a double-four
+    /// or a double-three.
+    #[must_use]
+    pub fn forks_under(&self, rule: Rule, player: Player) -> Vec<(Move<SIDE_LENGTH>, Fork)> {
+        let mut forks = self.forks(player);
+        if rule == Rule::Renju && player == Player::X {
+            forks.retain(|&(mv, _)| renju::forbidden(self, mv).is_none());
+        }
+        forks
+    }
+
+    /// The empty cells where a stone for `player` would make two winning
+    /// cells at once, the completions of `player`'s open threes, in index
+    /// order.
+    pub(crate) fn open_four_moves(&self, player: Player) -> Vec<Move<SIDE_LENGTH>> {
+        self.moves_where(|mv| self.new_wins(mv, player).len() >= 2)
+            .collect()
+    }
+
+    /// The fork that a stone for `player` at `mv` makes, if any.
+    fn fork(&self, mv: Move<SIDE_LENGTH>, player: Player) -> Option<Fork> {
+        let after = self.with_stone(mv, player);
+        let fours = self.new_wins(mv, player).len();
+        if fours >= 2 {
+            return Some(Fork::DoubleFour);
+        }
+        let mut threes = 0;
+        for direction in DIRECTIONS {
+            if self
+                .new_wins_along(&after, mv, direction, player)
+                .next()
+                .is_some()
+            {
+                continue;
+            }
+            if after.open_three_along(mv, direction, player) {
+                threes += 1;
+            }
+        }
+        match (fours, threes) {
+            (1, 1..) => Some(Fork::FourThree),
+            (0, 2..) => Some(Fork::DoubleThree),
+            _ => None,
+        }
+    }
+
+    /// Whether `player` has an open three on the line through `mv` along
+    /// `direction`: a stone on that line would make an open four there.
+    fn open_three_along(
+        &self,
+        mv: Move<SIDE_LENGTH>,
+        direction: (isize, isize),
+        player: Player,
+    ) -> bool {
+        self.line_cells(mv, direction).any(|cell| {
+            let after = self.with_stone(cell, player);
+            self.new_wins_along(&after, cell, direction, player).count() >= 2
+        })
+    }
+
+    /// A copy of the board with a stone for `player` at `mv`.
+    pub(crate) fn with_stone(&self, mv: Move<SIDE_LENGTH>, player: Player) -> Self {
+        let mut board = self.with_turn(player);
+        board.place(mv);
+        board
+    }
+
+    /// The winning cells that a stone for `player` at `mv` would make, which
+    /// are not winning cells already, in index order.
+    pub(crate) fn new_wins(&self, mv: Move<SIDE_LENGTH>, player: Player) -> Vec<Move<SIDE_LENGTH>> {
+        let after = self.with_stone(mv, player);
+        let mut wins = DIRECTIONS
+            .into_iter()
+            .flat_map(|direction| self.new_wins_along(&after, mv, direction, player))
+            .collect::<Vec<_>>();
+        wins.sort_unstable_by_key(Move::index);
+        wins.dedup();
+        wins
+    }
+
+    /// The cells on the line through `mv` along `direction` that are winning
+    /// cells for `player` in `after`, the board with a stone at `mv`, but
+    /// not in this board.
+    pub(crate) fn new_wins_along<'a>(
+        &'a self,
+        after: &'a Self,
+        mv: Move<SIDE_LENGTH>,
+        (dr, dc): (isize, isize),
+        player: Player,
+    ) -> impl Iterator<Item = Move<SIDE_LENGTH>> + 'a {
+        after.line_cells(mv, (dr, dc)).filter(move |&cell| {
+            after.is_winning_move(cell, player) && !self.is_winning_move(cell, player)
+        })
+    }
+
+    /// The empty cells on the line through `mv` along `direction` that a
+    /// winning line through `mv` could reach.
+    fn line_cells(
+        &self,
+        mv: Move<SIDE_LENGTH>,
+        (dr, dc): (isize, isize),
+    ) -> impl Iterator<Item = Move<SIDE_LENGTH>> + '_ {
+        #![allow(clippy::cast_possible_wrap, clippy::cast_possible_truncation)]
+        let reach = self.win_length() as isize - 1;
+        let Coord { row, col } = mv.coord();
+        (-reach..=reach).filter_map(move |steps| {
+            let row = row.checked_add_signed(dr * steps)?;
+            let col = col.checked_add_signed(dc * steps)?;
+            (row < SIDE_LENGTH
+                && col < SIDE_LENGTH
+                && self.player_at(Coord { row, col }) == Player::None)
+                .then(|| Move::from_index((row * SIDE_LENGTH + col) as u16))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fork;
+    use crate::{
+        board::{Board, Move, Player},
+        rules::Rule,
+    };
+
+    fn position(x: &[&str], o: &[&str]) -> Board<15> {
+        let stones = x
+            .iter()
+            .map(|mv| (mv.parse().unwrap(), Player::X))
+            .chain(o.iter().map(|mv| (mv.parse().unwrap(), Player::O)))
+            .collect::<Vec<_>>();
+        Board::from_setup(&stones, Player::X).unwrap()
+    }
+
+    fn names(moves: &[Move<15>]) -> Vec<String> {
+        moves.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn blocks_fours_before_threes() {
+        // O has a four on row 2 and an open three on row 10.
+        let board = position(
+            &["a1", "a3", "a5", "a7", "a9"],
+            &["b2", "c2", "d2", "e2", "g10", "h10", "i10"],
+        );
+        assert_eq!(names(&board.winning_moves(Player::O)), ["A2", "F2"]);
+        assert_eq!(names(&board.defensive_moves()), ["A2", "F2"]);
+    }
+
+    #[test]
+    fn stops_open_threes() {
+        let board = position(&["a1", "a3", "a5"], &["g10", "h10", "i10"]);
+        // blocking a cell further away still leaves an open four.
+        assert_eq!(names(&board.defensive_moves()), ["F10", "J10"]);
+        // a broken three is stopped only in or beside its gap.
+        let board = position(&["a1", "a3", "a5"], &["g10", "h10", "j10"]);
+        assert_eq!(names(&board.defensive_moves()), ["F10", "I10", "K10"]);
+        // a three against the edge or a stone is no threat.
+        let board = position(&["a1", "a3", "j10"], &["g10", "h10", "i10"]);
+        assert!(board.defensive_moves().is_empty());
+    }
+
+    #[test]
+    fn finds_forks() {
+        // O's stones are out of the way; it is X's turn.
+        let o = ["a15", "c15", "e15", "g15", "i15", "k15", "m15"];
+        // a four on row 3 and a three on column h cross at H3.
+        let board = position(&["d3", "e3", "f3", "h5", "h6"], &o);
+        let forks = board.forks(Player::X);
+        assert!(forks.contains(&("h3".parse().unwrap(), Fork::FourThree)));
+        // two twos cross at H8.
+        let board = position(&["f8", "g8", "h9", "h10"], &o);
+        let forks = board.forks(Player::X);
+        assert!(forks.contains(&("h8".parse().unwrap(), Fork::DoubleThree)));
+        assert!(board
+            .forks_under(Rule::Renju, Player::X)
+            .iter()
+            .all(|&(_, fork)| fork != Fork::DoubleThree));
+        assert_eq!(board.forks_under(Rule::Freestyle, Player::X), forks);
+        // three in a row with open ends makes an open four at either end.
+        let board = position(&["f8", "g8", "h8"], &o);
+        let forks = board.forks(Player::X);
+        assert!(forks.contains(&("e8".parse().unwrap(), Fork::DoubleFour)));
+        assert!(forks.contains(&("i8".parse().unwrap(), Fork::DoubleFour)));
+        assert_eq!(Fork::FourThree.to_string(), "four-three");
+    }
+}
@@ -0,0 +1,253 @@
+//! A fixed-size hash table for caching search results by Zobrist key.
+//!
+//! A table can be saved and reloaded, so that long runs can resume with a
+//! warm cache. On disk, a table is a 16-byte header (the magic bytes `GMKT`,
+//! a big-endian `u16` format version, a big-endian `u16` value size, and a
+//! big-endian `u64` bucket count) followed by every slot in order: a flag
+//! byte that is 1 if the slot is full, a big-endian `u64` key, a depth byte,
+//! and the value's bytes. Empty slots are written as zeros.
+
+use std::io::{self, Read, Write};
+
+use crate::positions::{invalid_data, FixedBytes};
+
+const MAGIC: &[u8; 4] = b"GMKT";
+const VERSION: u16 = 1;
+
+/// The number of entries that share a bucket.
+const BUCKET_SIZE: usize = 4;
+
+#[derive(Clone, Debug)]
+struct Entry<V> {
+    key: u64,
+    depth: u8,
+    value: V,
+}
+
+/// A fixed-size transposition table mapping Zobrist keys to values of type `V`.
+///
+/// Entries are grouped into buckets of four. When a bucket is full, storing a
+/// new entry evicts the one with the smallest depth, so the table prefers
+/// keeping results that were expensive to compute.
+#[derive(Clone, Debug)]
+pub struct TranspositionTable<V> {
+    buckets: Vec<[Option<Entry<V>>; BUCKET_SIZE]>,
+}
+
+impl<V> TranspositionTable<V> {
+    /// Creates a table with room for at least `entries` entries.
+    #[must_use]
+    pub fn new(entries: usize) -> Self {
+        let n_buckets = entries.div_ceil(BUCKET_SIZE).max(1);
+        Self {
+            buckets: std::iter::repeat_with(|| [None, None, None, None])
+                .take(n_buckets)
+                .collect(),
+        }
+    }
+
+    /// Creates a table using roughly `megabytes` megabytes of memory.
+    #[must_use]
+    pub fn with_size_mb(megabytes: usize) -> Self {
+        let entry_size = std::mem::size_of::<Option<Entry<V>>>();
+        Self::new(megabytes * 1024 * 1024 / entry_size)
+    }
+
+    /// The number of entries the table can hold.
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.buckets.len() * BUCKET_SIZE
+    }
+
+    fn bucket_index(&self, key: u64) -> usize {
+        #![allow(clippy::cast_possible_truncation)]
+        // map the key onto the buckets without a division.
+        ((u128::from(key) * self.buckets.len() as u128) >> 64) as usize
+    }
+
+    /// Looks up the entry for `key`, returning the depth it was stored with and its value.
+    #[must_use]
+    pub fn probe(&self, key: u64) -> Option<(u8, &V)> {
+        self.buckets[self.bucket_index(key)]
+            .iter()
+            .flatten()
+            .find(|e| e.key == key)
+            .map(|e| (e.depth, &e.value))
+    }
+
+    /// Stores `value` for `key`, computed to `depth`.
+    ///
+    /// An existing entry for `key` is overwritten unless it was stored with a
+    /// greater depth. Otherwise, the value goes into an empty slot in the key's
+    /// bucket if there is one, or replaces the shallowest entry in the bucket.
+    pub fn store(&mut self, key: u64, depth: u8, value: V) {
+        let index = self.bucket_index(key);
+        let bucket = &mut self.buckets[index];
+        let slot = if let Some(i) = bucket
+            .iter()
+            .position(|e| e.as_ref().is_some_and(|e| e.key == key))
+        {
+            if bucket[i].as_ref().is_some_and(|e| e.depth > depth) {
+                return;
+            }
+            i
+        } else if let Some(i) = bucket.iter().position(Option::is_none) {
+            i
+        } else {
+            bucket
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, e)| e.as_ref().map_or(0, |e| e.depth))
+                .map_or(0, |(i, _)| i)
+        };
+        bucket[slot] = Some(Entry { key, depth, value });
+    }
+
+    /// Removes every entry from the table.
+    pub fn clear(&mut self) {
+        for bucket in &mut self.buckets {
+            *bucket = [None, None, None, None];
+        }
+    }
+}
+
+impl<V: FixedBytes> TranspositionTable<V> {
+    /// Writes the table in the binary format described in the
+    /// [module docs](self).
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from the underlying writer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `V::SIZE` does not fit in a `u16`.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(16);
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&VERSION.to_be_bytes());
+        buf.extend_from_slice(&u16::try_from(V::SIZE).unwrap().to_be_bytes());
+        buf.extend_from_slice(&(self.buckets.len() as u64).to_be_bytes());
+        writer.write_all(&buf)?;
+        let slot_size = 10 + V::SIZE;
+        for bucket in &self.buckets {
+            buf.clear();
+            for slot in bucket {
+                match slot {
+                    Some(entry) => {
+                        buf.push(1);
+                        buf.extend_from_slice(&entry.key.to_be_bytes());
+                        buf.push(entry.depth);
+                        entry.value.write_bytes(&mut buf);
+                    }
+                    None => buf.resize(buf.len() + slot_size, 0),
+                }
+            }
+            writer.write_all(&buf)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a table in the binary format described in the
+    /// [module docs](self).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, or if the data is not a
+    /// transposition table holding values of type `V`.
+    pub fn read_from(mut reader: impl Read) -> io::Result<Self> {
+        let mut header = [0; 16];
+        reader.read_exact(&mut header)?;
+        let (magic, rest) = header.split_at(4);
+        if magic != MAGIC {
+            return Err(invalid_data("not a gomokugen transposition table"));
+        }
+        if u16::read_bytes(&rest[..2]) != VERSION {
+            return Err(invalid_data("unsupported transposition table version"));
+        }
+        if usize::from(u16::read_bytes(&rest[2..4])) != V::SIZE {
+            return Err(invalid_data(
+                "transposition table holds a different value type",
+            ));
+        }
+        let n_buckets = usize::try_from(u64::read_bytes(&rest[4..]))
+            .map_err(|_| invalid_data("transposition table is too large"))?;
+        let slot_size = 10 + V::SIZE;
+        let mut data = vec![0; slot_size * BUCKET_SIZE];
+        let mut buckets = Vec::new();
+        for _ in 0..n_buckets {
+            reader.read_exact(&mut data)?;
+            let mut bucket = [None, None, None, None];
+            for (slot, bytes) in bucket.iter_mut().zip(data.chunks_exact(slot_size)) {
+                *slot = match bytes[0] {
+                    0 => None,
+                    1 => Some(Entry {
+                        key: u64::read_bytes(&bytes[1..9]),
+                        depth: bytes[9],
+                        value: V::read_bytes(&bytes[10..]),
+                    }),
+                    _ => return Err(invalid_data("corrupt transposition table slot")),
+                };
+            }
+            buckets.push(bucket);
+        }
+        if buckets.is_empty() {
+            return Err(invalid_data("transposition table has no buckets"));
+        }
+        Ok(Self { buckets })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TranspositionTable;
+
+    #[test]
+    fn store_and_probe() {
+        let mut tt = TranspositionTable::new(64);
+        assert_eq!(tt.capacity(), 64);
+        tt.store(12345, 3, "a");
+        assert_eq!(tt.probe(12345), Some((3, &"a")));
+        assert_eq!(tt.probe(54321), None);
+        // shallower results don't overwrite deeper ones for the same key.
+        tt.store(12345, 2, "b");
+        assert_eq!(tt.probe(12345), Some((3, &"a")));
+        tt.store(12345, 4, "c");
+        assert_eq!(tt.probe(12345), Some((4, &"c")));
+        tt.clear();
+        assert_eq!(tt.probe(12345), None);
+    }
+
+    #[test]
+    fn full_bucket_evicts_shallowest() {
+        // a single bucket, so every key collides.
+        let mut tt = TranspositionTable::new(1);
+        for (key, depth) in [(1, 5), (2, 1), (3, 7), (4, 3)] {
+            tt.store(key, depth, key);
+        }
+        tt.store(5, 2, 5);
+        assert_eq!(tt.probe(2), None);
+        for key in [1, 3, 4, 5] {
+            assert_eq!(tt.probe(key).map(|(_, &v)| v), Some(key));
+        }
+    }
+
+    #[test]
+    fn write_and_read() {
+        let mut tt = TranspositionTable::new(8);
+        for (key, depth) in [(1, 5), (2, 1), (u64::MAX, 7)] {
+            tt.store(key, depth, key.wrapping_mul(3));
+        }
+        let mut bytes = Vec::new();
+        tt.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 16 + tt.capacity() * 18);
+        let read = TranspositionTable::<u64>::read_from(bytes.as_slice()).unwrap();
+        assert_eq!(read.capacity(), tt.capacity());
+        for key in [1, 2, u64::MAX] {
+            assert_eq!(read.probe(key), tt.probe(key));
+        }
+        assert_eq!(read.probe(3), None);
+        assert!(TranspositionTable::<u32>::read_from(bytes.as_slice()).is_err());
+        assert!(TranspositionTable::<u64>::read_from(&bytes[..40]).is_err());
+    }
+}
@@ -0,0 +1,228 @@
+//! Tuning the evaluation and engine parameters.
+//!
+//! This module fits the evaluation weights to game results ("Texel tuning"),
+//! and [`spsa`] tunes any engine parameters by playing matches.
+//!
+//! Each labelled position's static evaluation is mapped to an expected score
+//! with a logistic curve, and the weights are adjusted to minimise the mean
+//! squared difference between the expected scores and the game results. The
+//! evaluation is linear in the weights, so this is a logistic regression over
+//! the window counts of the positions.
+
+use crate::{
+    board::{Board, Player},
+    eval::{window_counts, EvalWeights},
+    game::Game,
+};
+
+pub mod spsa;
+
+pub use spsa::{Spsa, SpsaParam};
+
+/// A position with the result of the game it came from, from the perspective
+/// of the side to move: 1 for a win, 0.5 for a draw, and 0 for a loss.
+pub type LabelledPosition<const SIDE_LENGTH: usize> = (Board<SIDE_LENGTH>, f64);
+
+/// Settings for [`tune`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TuningConfig {
+    /// The weights to start from.
+    pub initial: EvalWeights,
+    /// The steepness of the curve from evaluations to expected scores: an
+    /// evaluation of `1 / scale` is expected to score about 73%.
+    pub scale: f64,
+    /// The size of each step, in units of evaluation.
+    pub learning_rate: f64,
+    /// The number of passes over the positions.
+    pub epochs: usize,
+}
+
+impl Default for TuningConfig {
+    fn default() -> Self {
+        Self {
+            initial: EvalWeights::default(),
+            scale: 1.0 / 256.0,
+            learning_rate: 2.0,
+            epochs: 200,
+        }
+    }
+}
+
+/// Labels every position in `games` with the result of its game, skipping
+/// unfinished games and the final position of each game.
+#[must_use]
+pub fn labelled_positions<const SIDE_LENGTH: usize>(
+    games: &[Game<SIDE_LENGTH>],
+) -> Vec<LabelledPosition<SIDE_LENGTH>> {
+    let mut out = Vec::new();
+    for game in games {
+        let Some(result) = game.winner() else {
+            continue;
+        };
+        let mut board = *game.start();
+        for &mv in game.moves() {
+            let label = match result {
+                Player::None => 0.5,
+                winner if winner == board.turn() => 1.0,
+                _ => 0.0,
+            };
+            out.push((board, label));
+            board.make_move(mv);
+        }
+    }
+    out
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// The window counts of the positions that tuning can learn from, with their
+/// labels. Finished positions and those with a complete window score the
+/// same whatever the weights, so they are left out.
+fn features<const SIDE_LENGTH: usize>(
+    positions: &[LabelledPosition<SIDE_LENGTH>],
+) -> Vec<([f64; 4], f64)> {
+    positions
+        .iter()
+        .filter(|(board, _)| board.outcome().is_none())
+        .filter_map(|(board, label)| {
+            let [complete, counts @ ..] = window_counts(board);
+            (complete == 0).then(|| (counts.map(f64::from), *label))
+        })
+        .collect()
+}
+
+fn mean_error(features: &[([f64; 4], f64)], weights: [f64; 4], scale: f64) -> f64 {
+    #![allow(clippy::cast_precision_loss)]
+    if features.is_empty() {
+        return 0.0;
+    }
+    let total: f64 = features
+        .iter()
+        .map(|(counts, label)| {
+            let eval: f64 = counts.iter().zip(weights).map(|(c, w)| c * w).sum();
+            (label - sigmoid(scale * eval)).powi(2)
+        })
+        .sum();
+    total / features.len() as f64
+}
+
+/// The mean squared difference between the results of `positions` and the
+/// scores that `weights` predict for them.
+#[must_use]
+pub fn error<const SIDE_LENGTH: usize>(
+    positions: &[LabelledPosition<SIDE_LENGTH>],
+    weights: &EvalWeights,
+    scale: f64,
+) -> f64 {
+    mean_error(&features(positions), weights.missing.map(f64::from), scale)
+}
+
+/// Fits the evaluation weights to `positions` by gradient descent, starting
+/// from [`TuningConfig::initial`].
+///
+/// Each weight takes steps scaled to its own gradient history (the Adam
+/// method), as windows missing many stones are far more common than windows
+/// missing few. The weights are kept positive.
+#[must_use]
+pub fn tune<const SIDE_LENGTH: usize>(
+    positions: &[LabelledPosition<SIDE_LENGTH>],
+    config: &TuningConfig,
+) -> EvalWeights {
+    #![allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_possible_wrap
+    )]
+    const BETA1: f64 = 0.9;
+    const BETA2: f64 = 0.999;
+    let features = features(positions);
+    let mut weights = config.initial.missing.map(f64::from);
+    if features.is_empty() {
+        return config.initial;
+    }
+    let (mut m, mut v) = ([0.0; 4], [0.0; 4]);
+    for epoch in 1..=config.epochs {
+        let mut gradient = [0.0; 4];
+        for (counts, label) in &features {
+            let eval: f64 = counts.iter().zip(weights).map(|(c, w)| c * w).sum();
+            let predicted = sigmoid(config.scale * eval);
+            // the derivative of (label - predicted)^2 with respect to eval.
+            let slope = -2.0 * (label - predicted) * predicted * (1.0 - predicted) * config.scale;
+            for (g, c) in gradient.iter_mut().zip(counts) {
+                *g += slope * c / features.len() as f64;
+            }
+        }
+        for i in 0..4 {
+            m[i] = BETA1.mul_add(m[i], (1.0 - BETA1) * gradient[i]);
+            v[i] = BETA2.mul_add(v[i], (1.0 - BETA2) * gradient[i] * gradient[i]);
+            let m_hat = m[i] / (1.0 - BETA1.powi(epoch as i32));
+            let v_hat = v[i] / (1.0 - BETA2.powi(epoch as i32));
+            weights[i] =
+                (weights[i] - config.learning_rate * m_hat / (v_hat.sqrt() + 1e-12)).max(0.0);
+        }
+    }
+    EvalWeights {
+        missing: weights.map(|w| w.round() as i32),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{error, labelled_positions, tune, TuningConfig};
+    use crate::{
+        board::{Board, Move},
+        engine::{HeuristicEngine, Limits, RandomEngine},
+        eval::EvalWeights,
+        game::Game,
+    };
+
+    #[test]
+    fn labels_positions_by_result() {
+        let moves = ["a1", "b1", "a2", "b2", "a3", "b3", "a4", "b4", "a5"]
+            .map(|mv| mv.parse::<Move<9>>().unwrap());
+        let mut game = Game::new(Board::<9>::new());
+        for mv in moves {
+            game.play(mv);
+        }
+        let positions = labelled_positions(&[game, Game::new(Board::new())]);
+        // X won, and X is to move in the even positions.
+        let labels = positions
+            .iter()
+            .map(|&(_, label)| label)
+            .collect::<Vec<_>>();
+        assert_eq!(labels, [1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn tuning_reduces_the_error() {
+        // a strong engine against a random one, so positions with more
+        // potential lines should be winning.
+        let mut games = Vec::new();
+        for seed in 0..8 {
+            let mut game = Game::new(Board::<9>::new());
+            let mut strong = HeuristicEngine::new();
+            let mut random = RandomEngine::new(seed);
+            if seed % 2 == 0 {
+                game.play_out(&mut strong, &mut random, &Limits::default());
+            } else {
+                game.play_out(&mut random, &mut strong, &Limits::default());
+            }
+            games.push(game);
+        }
+        let positions = labelled_positions(&games);
+        // start far from sensible weights.
+        let config = TuningConfig {
+            initial: EvalWeights {
+                missing: [1, 1, 1, 1],
+            },
+            ..TuningConfig::default()
+        };
+        let tuned = tune(&positions, &config);
+        let before = error(&positions, &config.initial, config.scale);
+        let after = error(&positions, &tuned, config.scale);
+        assert!(after < before, "{after} >= {before}");
+        assert!(tuned.missing.iter().all(|&w| w >= 0));
+    }
+}
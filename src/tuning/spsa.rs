@@ -0,0 +1,174 @@
+//! Tuning engine parameters by playing matches, with simultaneous
+//! perturbation stochastic approximation (SPSA).
+//!
+//! Each iteration nudges every parameter up or down at random, plays a short
+//! match between an engine with the parameters nudged one way and an engine
+//! with them nudged the other way, and moves the parameters towards the
+//! winner. A whole match result only estimates the gradient roughly, but the
+//! estimates average out over many iterations.
+
+use crate::{
+    engine::{Engine, RandomEngine},
+    matches::Match,
+};
+
+/// A parameter to tune.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpsaParam {
+    /// The parameter's name, for reporting.
+    pub name: String,
+    /// The current value.
+    pub value: f64,
+    /// The smallest value allowed.
+    pub min: f64,
+    /// The largest value allowed.
+    pub max: f64,
+    /// How far to nudge the parameter at the start of tuning. This should be
+    /// large enough to make a noticeable difference to the engine's play.
+    pub step: f64,
+}
+
+impl SpsaParam {
+    /// Creates a parameter starting at `value`, kept within `min..=max`, and
+    /// nudged by `step`.
+    #[must_use]
+    pub fn new(name: impl Into<String>, value: f64, min: f64, max: f64, step: f64) -> Self {
+        Self {
+            name: name.into(),
+            value,
+            min,
+            max,
+            step,
+        }
+    }
+
+    const fn clamp(&self, value: f64) -> f64 {
+        value.clamp(self.min, self.max)
+    }
+}
+
+/// Settings for an SPSA tuning run.
+///
+/// The step sizes shrink as tuning goes on, following the usual SPSA
+/// schedules: at iteration `k`, parameters are nudged by
+/// `perturbation / (k + 1)^gamma` steps, and moved by up to
+/// `learning_rate / (k + 1 + stability)^alpha` times as far as the nudge.
+#[derive(Clone, Debug)]
+pub struct Spsa<const SIDE_LENGTH: usize> {
+    /// The number of iterations.
+    pub iterations: usize,
+    /// The match played in each iteration, usually a few game pairs.
+    pub runner: Match<SIDE_LENGTH>,
+    /// The size of the first nudge, in units of each parameter's step.
+    pub perturbation: f64,
+    /// How far the parameters move after each match.
+    pub learning_rate: f64,
+    /// Slows the shrinking of the learning rate over the first iterations.
+    pub stability: f64,
+    /// How quickly the learning rate shrinks.
+    pub alpha: f64,
+    /// How quickly the nudges shrink.
+    pub gamma: f64,
+    /// The seed for choosing which way to nudge each parameter.
+    pub seed: u64,
+}
+
+impl<const SIDE_LENGTH: usize> Default for Spsa<SIDE_LENGTH> {
+    fn default() -> Self {
+        Self {
+            iterations: 100,
+            runner: Match {
+                games: 4,
+                ..Match::default()
+            },
+            perturbation: 1.0,
+            learning_rate: 0.5,
+            stability: 10.0,
+            alpha: 0.602,
+            gamma: 0.101,
+            seed: 0,
+        }
+    }
+}
+
+impl<const SIDE_LENGTH: usize> Spsa<SIDE_LENGTH> {
+    /// Tunes `params` in place, building engines with the parameter values
+    /// in order with `make_engine`.
+    ///
+    /// `on_iteration` is called with the iteration number, counted from
+    /// zero, and the parameters after each iteration.
+    pub fn tune<E: Engine<SIDE_LENGTH>>(
+        &self,
+        params: &mut [SpsaParam],
+        mut make_engine: impl FnMut(&[f64]) -> E,
+        mut on_iteration: impl FnMut(usize, &[SpsaParam]),
+    ) {
+        #![allow(clippy::cast_precision_loss, clippy::suboptimal_flops)]
+        let mut rng = RandomEngine::new(self.seed);
+        for k in 0..self.iterations {
+            let k_f = k as f64;
+            let nudge = self.perturbation / (k_f + 1.0).powf(self.gamma);
+            let rate = self.learning_rate / (k_f + 1.0 + self.stability).powf(self.alpha);
+            let signs = params
+                .iter()
+                .map(|_| if rng.next_in(0, 2) == 0 { -1.0 } else { 1.0 })
+                .collect::<Vec<f64>>();
+            let shifted = |direction: f64| {
+                params
+                    .iter()
+                    .zip(&signs)
+                    .map(|(param, sign)| {
+                        param.clamp(param.value + direction * sign * nudge * param.step)
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let mut plus = make_engine(&shifted(1.0));
+            let mut minus = make_engine(&shifted(-1.0));
+            let score = self.runner.run(&mut plus, &mut minus, |_, _| {});
+            // +1 if the nudge upwards won every game, -1 if it lost them all.
+            let result = score.points().map_or(0.0, |points| 2.0 * points - 1.0);
+            for (param, sign) in params.iter_mut().zip(&signs) {
+                param.value = param.clamp(param.value + rate * result * sign * nudge * param.step);
+            }
+            on_iteration(k, params);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Spsa, SpsaParam};
+    use crate::{
+        engine::{Engine, HeuristicEngine, RandomEngine},
+        matches::Match,
+    };
+
+    #[test]
+    fn moves_towards_stronger_settings() {
+        // the engine only plays well with the parameter above a half.
+        let make_engine = |values: &[f64]| -> Box<dyn Engine<9>> {
+            if values[0] > 0.5 {
+                Box::new(HeuristicEngine::new())
+            } else {
+                Box::new(RandomEngine::new(7))
+            }
+        };
+        let spsa = Spsa::<9> {
+            iterations: 10,
+            runner: Match {
+                games: 2,
+                ..Match::default()
+            },
+            ..Spsa::default()
+        };
+        let mut params = [SpsaParam::new("skill", 0.3, 0.0, 1.0, 0.3)];
+        let mut iterations = 0;
+        spsa.tune(&mut params, make_engine, |k, params| {
+            assert_eq!(k, iterations);
+            assert!((0.0..=1.0).contains(&params[0].value));
+            iterations += 1;
+        });
+        assert_eq!(iterations, 10);
+        assert!(params[0].value > 0.3, "{}", params[0].value);
+    }
+}
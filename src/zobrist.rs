@@ -0,0 +1,138 @@
+//! Zobrist key tables.
+//!
+//! The keys are generated at compile time from fixed seeds, and are part of
+//! the crate's stable interface: books, position containers and table dumps
+//! keyed by [`Board::zobrist_key`](crate::board::Board::zobrist_key) stay
+//! valid across crate versions. Any change to the keys bumps [`VERSION`].
+//!
+//! The key of a stone depends only on its cell index and colour, not on the
+//! number of cells, so raising the largest supported board size leaves the
+//! existing keys alone. The side-to-move key has a seed of its own for the
+//! same reason.
+
+use crate::board::Player;
+
+/// The version of the key tables, bumped whenever any key changes.
+pub const VERSION: u16 = 1;
+
+/// The largest number of cells on any supported board.
+const MAX_CELLS: usize = 20 * 20;
+
+/// The seed for the stone keys: "gomokuge" in ASCII.
+const STONE_SEED: u64 = 0x676F_6D6F_6B75_6765;
+
+/// The seed for the side-to-move key: "sidetomv" in ASCII.
+const SIDE_SEED: u64 = 0x7369_6465_746F_6D76;
+
+/// The seed for the verification keys: "verifkey" in ASCII.
+const CHECK_SEED: u64 = 0x7665_7269_666B_6579;
+
+/// The seed for the side-to-move verification key: "verifstm" in ASCII.
+const CHECK_SIDE_SEED: u64 = 0x7665_7269_6673_746D;
+
+/// One step of the `SplitMix64` generator, usable in const contexts.
+pub const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+const fn generate_keys(seed: u64) -> [[u64; 2]; MAX_CELLS] {
+    let mut keys = [[0; 2]; MAX_CELLS];
+    let mut state = seed;
+    let mut i = 0;
+    while i < MAX_CELLS {
+        let (s, x) = splitmix64(state);
+        let (s, o) = splitmix64(s);
+        keys[i] = [x, o];
+        state = s;
+        i += 1;
+    }
+    keys
+}
+
+const KEYS: [[u64; 2]; MAX_CELLS] = generate_keys(STONE_SEED);
+
+const CHECK_KEYS: [[u64; 2]; MAX_CELLS] = generate_keys(CHECK_SEED);
+
+/// The key for a stone belonging to `player` on the cell with flat index `index`.
+pub const fn stone(index: usize, player: Player) -> u64 {
+    match player {
+        Player::None => 0,
+        Player::X => KEYS[index][0],
+        Player::O => KEYS[index][1],
+    }
+}
+
+/// The key toggled whenever the side to move changes.
+pub const SIDE: u64 = splitmix64(SIDE_SEED).1;
+
+/// The verification key for a stone belonging to `player` on the cell with
+/// flat index `index`, independent of [`stone`].
+pub const fn check_stone(index: usize, player: Player) -> u64 {
+    match player {
+        Player::None => 0,
+        Player::X => CHECK_KEYS[index][0],
+        Player::O => CHECK_KEYS[index][1],
+    }
+}
+
+/// The verification key for the side to move, independent of [`SIDE`].
+pub const CHECK_SIDE: u64 = splitmix64(CHECK_SIDE_SEED).1;
+
+#[cfg(test)]
+mod tests {
+    use crate::board::{Board, Move, Player};
+
+    #[test]
+    fn keys_are_distinct() {
+        let mut keys = super::KEYS
+            .iter()
+            .chain(&super::CHECK_KEYS)
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        keys.extend([super::SIDE, super::CHECK_SIDE]);
+        keys.sort_unstable();
+        keys.dedup();
+        assert_eq!(keys.len(), super::MAX_CELLS * 4 + 2);
+    }
+
+    #[test]
+    fn keys_are_stable() {
+        // changing any of these values invalidates files keyed by Zobrist
+        // keys, and must come with a bump of `VERSION`.
+        assert_eq!(super::VERSION, 1);
+        assert_eq!(super::stone(0, Player::X), 0x0E46_6F67_153A_B05B);
+        assert_eq!(super::stone(0, Player::O), 0xD6AA_F61F_F421_1BC0);
+        assert_eq!(super::stone(224, Player::X), 0xE318_D33B_8DE1_30FC);
+        assert_eq!(super::stone(399, Player::O), 0x4546_F6CE_3EA0_782A);
+        assert_eq!(super::SIDE, 0xCB49_A8F5_4056_8C5F);
+        let board = Board::<15>::new()
+            .apply_moves(&["h8".parse().unwrap()])
+            .unwrap();
+        assert_eq!(board.zobrist_key(), 0x2D71_7CDB_5C37_FAD2);
+        assert_eq!(super::check_stone(0, Player::X), 0x1C08_8E50_98FD_8B21);
+        assert_eq!(super::CHECK_SIDE, 0x2298_9ECF_11D7_A067);
+        assert_eq!(
+            board.zobrist_key128(),
+            0x2D71_7CDB_5C37_FAD2_F45C_1AF5_B552_7989
+        );
+    }
+
+    #[test]
+    fn incremental_key_matches_fen() {
+        let moves = ["h8", "h9", "i9", "g7", "j10"].map(|mv| mv.parse::<Move<15>>().unwrap());
+        let board = Board::<15>::new().apply_moves(&moves).unwrap();
+        let parsed: Board<15> = board.fen().parse().unwrap();
+        assert_eq!(board.zobrist_key(), parsed.zobrist_key());
+        assert_ne!(board.zobrist_key(), Board::<15>::new().zobrist_key());
+        // transposed move orders reach the same key.
+        let mut reordered = moves;
+        reordered.swap(0, 2);
+        let other = Board::<15>::new().apply_moves(&reordered).unwrap();
+        assert_eq!(board.zobrist_key(), other.zobrist_key());
+    }
+}